@@ -11,7 +11,7 @@ impl Game for EditorGame {
         // Editor game logic
     }
 
-    fn render(&mut self, _renderer: &mut Renderer) {
+    fn render(&mut self, _renderer: &mut Renderer, _alpha: f64) {
         // Editor rendering
     }
 }
@@ -1,4 +1,4 @@
-use strata::{Engine, Game, Renderer};
+use strata::{Engine, Game, Renderer, StrataError};
 
 const PRINT_FRAMES: bool = false;
 
@@ -20,13 +20,31 @@ impl Game for ExampleGame {
     fn update(&mut self, dt: f64) {
         self.frame_count += 1;
 
-        if PRINT_FRAMES && self.frame_count % 60 == 0 {
+        if PRINT_FRAMES && self.frame_count.is_multiple_of(60) {
             println!("Frame {}, dt: {:.3}ms", self.frame_count, dt * 1000.0);
         }
     }
 
-    fn render(&mut self, _renderer: &mut Renderer) {
+    fn render(&mut self, renderer: &mut Renderer, _alpha: f64) {
+        renderer.set_clear_color([0.05, 0.04, 0.08, 1.0]);
+
+        let frame = match renderer.begin_frame() {
+            Ok(frame) => frame,
+            Err(StrataError::SwapchainOutOfDate) => return,
+            Err(e) => {
+                eprintln!("Failed to begin frame: {}", e);
+                return;
+            }
+        };
+
         // TODO: will draw a triangle
+
+        match renderer.end_frame(frame) {
+            Ok(())
+            | Err(StrataError::SwapchainOutOfDate)
+            | Err(StrataError::SwapchainSuboptimal) => {}
+            Err(e) => eprintln!("Failed to end frame: {}", e),
+        }
     }
 }
 
@@ -0,0 +1,439 @@
+//! Engine configuration
+
+use std::num::NonZero;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::renderer::{MAX_FRAMES_IN_FLIGHT, PresentMode, RendererConfig};
+use crate::time::{FixedTimestep, Timestep};
+use crate::voxel::BlockRegistry;
+use crate::window::{WindowManager, WindowMode};
+use crate::{
+    Engine, ExecutionMode, JobSystem, Result, Scheduler, StrataError, World,
+};
+
+/// Largest window width or height the builder accepts, matching the
+/// `maxImageDimension2D` guaranteed by most desktop Vulkan drivers
+pub const MAX_WINDOW_DIMENSION: u32 = 16384;
+
+/// Builds an [`Engine`] with custom window, renderer and loop settings.
+///
+/// Every option has a default, and the whole combination is validated when
+/// [`EngineBuilder::build`] is called.
+///
+/// # Example
+/// ```no_run
+/// use strata::{Engine, PresentMode, WindowMode};
+///
+/// let engine = Engine::builder()
+///     .title("My Game")
+///     .size(1280, 720)
+///     .window_mode(WindowMode::BorderlessFullscreen)
+///     .present_mode(PresentMode::Mailbox)
+///     .target_fps(144)
+///     .tick_rate(60.0)
+///     .build()?;
+/// # Ok::<(), strata::StrataError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct EngineBuilder {
+    title: Option<String>,
+    width: u32,
+    height: u32,
+    window_mode: WindowMode,
+    resizable: bool,
+    renderer: RendererConfig,
+    target_fps: Option<u32>,
+    tick_rate: Option<f64>,
+    max_catch_up_steps: Option<u32>,
+    execution_mode: ExecutionMode,
+    worker_threads: usize,
+    blocks: Option<PathBuf>,
+}
+
+impl EngineBuilder {
+    /// Start from the default configuration: an 800x600 resizable window
+    /// titled after the game, vsync, no frame cap and a variable timestep
+    pub fn new() -> Self {
+        Self {
+            title: None,
+            width: 800,
+            height: 600,
+            window_mode: WindowMode::Windowed,
+            resizable: true,
+            renderer: RendererConfig::default(),
+            target_fps: None,
+            tick_rate: None,
+            max_catch_up_steps: None,
+            execution_mode: ExecutionMode::default(),
+            worker_threads: thread::available_parallelism()
+                .map_or(1, NonZero::get)
+                .saturating_sub(1)
+                .max(1),
+            blocks: None,
+        }
+    }
+
+    /// Set the window title. Defaults to [`crate::Game::name`].
+    pub fn title(mut self, title: &str) -> Self {
+        self.title = Some(title.to_string());
+        self
+    }
+
+    /// Set the window size in logical pixels
+    pub fn size(mut self, width: u32, height: u32) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    /// Set how the window occupies the screen
+    pub fn window_mode(mut self, mode: WindowMode) -> Self {
+        self.window_mode = mode;
+        self
+    }
+
+    /// Set whether the user can resize the window
+    pub fn resizable(mut self, resizable: bool) -> Self {
+        self.resizable = resizable;
+        self
+    }
+
+    /// Enable or disable vsync. Shorthand for [`PresentMode::Fifo`] or
+    /// [`PresentMode::Immediate`].
+    pub fn vsync(self, vsync: bool) -> Self {
+        self.present_mode(if vsync {
+            PresentMode::Fifo
+        } else {
+            PresentMode::Immediate
+        })
+    }
+
+    /// Set the preferred swapchain present mode
+    pub fn present_mode(mut self, present_mode: PresentMode) -> Self {
+        self.renderer.present_mode = present_mode;
+        self
+    }
+
+    /// Cap the windowed frame rate. Uncapped by default.
+    pub fn target_fps(mut self, fps: u32) -> Self {
+        self.target_fps = Some(fps);
+        self
+    }
+
+    /// Enable or disable the Vulkan validation layer. Defaults to on in
+    /// debug builds.
+    pub fn validation(mut self, validation: bool) -> Self {
+        self.renderer.validation = validation;
+        self
+    }
+
+    /// Set how many frames the CPU may record ahead of the GPU, from 1 to
+    /// [`MAX_FRAMES_IN_FLIGHT`]. Defaults to 2.
+    pub fn frames_in_flight(mut self, frames: usize) -> Self {
+        self.renderer.frames_in_flight = frames;
+        self
+    }
+
+    /// Set the initial size in bytes of each frame's scratch arena, which
+    /// grows on demand. Defaults to 1 MiB.
+    pub fn frame_arena_capacity(mut self, bytes: usize) -> Self {
+        self.renderer.frame_arena_capacity = bytes;
+        self
+    }
+
+    /// Run the simulation at a fixed tick rate (ticks per second)
+    pub fn tick_rate(mut self, tick_rate: f64) -> Self {
+        self.tick_rate = Some(tick_rate);
+        self
+    }
+
+    /// Limit how many fixed ticks may run in one frame. Requires
+    /// [`EngineBuilder::tick_rate`].
+    pub fn max_catch_up_steps(mut self, steps: u32) -> Self {
+        self.max_catch_up_steps = Some(steps);
+        self
+    }
+
+    /// Set how ECS systems run. Defaults to in parallel on every core;
+    /// [`ExecutionMode::SingleThreaded`] is deterministic and suits tests.
+    pub fn execution_mode(mut self, mode: ExecutionMode) -> Self {
+        self.execution_mode = mode;
+        self
+    }
+
+    /// Set how many background threads run jobs. Defaults to one fewer
+    /// than the number of cores, and at least one.
+    pub fn worker_threads(mut self, threads: usize) -> Self {
+        self.worker_threads = threads;
+        self
+    }
+
+    /// Load block definitions from a TOML file when the engine is built.
+    /// Without one, the registry holds only air.
+    pub fn blocks(mut self, path: impl Into<PathBuf>) -> Self {
+        self.blocks = Some(path.into());
+        self
+    }
+
+    /// Validate the configuration and create the engine
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StrataError::InvalidConfig`] if any option is out of range
+    /// or options conflict, `StrataError::WindowCreation` if the window
+    /// manager cannot be created, and the errors of [`BlockRegistry::load`]
+    /// if block definitions were given and cannot be loaded.
+    pub fn build(self) -> Result<Engine> {
+        self.validate()?;
+
+        let timestep = match self.tick_rate {
+            Some(tick_rate) => {
+                let mut fixed = FixedTimestep::new(tick_rate);
+                if let Some(steps) = self.max_catch_up_steps {
+                    fixed = fixed.with_max_steps(steps);
+                }
+                Timestep::Fixed(fixed)
+            }
+            None => Timestep::Variable,
+        };
+
+        let title = self
+            .title
+            .as_deref()
+            .unwrap_or("Strata Engine");
+        let window_manager =
+            WindowManager::with_config(title, self.width, self.height)?
+                .with_mode(self.window_mode)
+                .with_resizable(self.resizable);
+        let blocks = match &self.blocks {
+            Some(path) => BlockRegistry::load(path)?,
+            None => BlockRegistry::new(),
+        };
+        let jobs = Arc::new(JobSystem::new(self.worker_threads));
+
+        Ok(Engine {
+            window_manager,
+            timestep,
+            renderer_config: self.renderer,
+            frame_limit: self
+                .target_fps
+                .map(|fps| Duration::from_secs_f64(1.0 / fps as f64)),
+            title_from_game: self.title.is_none(),
+            world: World::new(),
+            scheduler: Scheduler::new()
+                .with_mode(self.execution_mode)
+                .with_jobs(jobs.clone()),
+            jobs,
+            blocks: Arc::new(blocks),
+        })
+    }
+
+    fn validate(&self) -> Result<()> {
+        let invalid = |msg: String| Err(StrataError::InvalidConfig(msg));
+
+        if self
+            .title
+            .as_deref()
+            .is_some_and(str::is_empty)
+        {
+            return invalid("window title must not be empty".to_string());
+        }
+        if self.width == 0 || self.height == 0 {
+            return invalid(format!(
+                "window size must be non-zero, got {}x{}",
+                self.width, self.height
+            ));
+        }
+        if self.width > MAX_WINDOW_DIMENSION
+            || self.height > MAX_WINDOW_DIMENSION
+        {
+            return invalid(format!(
+                "window size {}x{} exceeds the maximum of {}",
+                self.width, self.height, MAX_WINDOW_DIMENSION
+            ));
+        }
+        if !(1..=MAX_FRAMES_IN_FLIGHT).contains(&self.renderer.frames_in_flight)
+        {
+            return invalid(format!(
+                "frames in flight must be between 1 and {}, got {}",
+                MAX_FRAMES_IN_FLIGHT, self.renderer.frames_in_flight
+            ));
+        }
+        if self.target_fps == Some(0) {
+            return invalid("target FPS must be greater than zero".to_string());
+        }
+        if let Some(tick_rate) = self.tick_rate
+            && !FixedTimestep::is_valid_tick_rate(tick_rate)
+        {
+            return invalid(format!(
+                "tick rate must be positive and finite with a tick of at \
+                 least 1ns, got {}",
+                tick_rate
+            ));
+        }
+        match (self.tick_rate, self.max_catch_up_steps) {
+            (None, Some(_)) => {
+                return invalid(
+                    "max catch-up steps require a fixed tick rate".to_string(),
+                );
+            }
+            (Some(_), Some(0)) => {
+                return invalid(
+                    "max catch-up steps must be greater than zero".to_string(),
+                );
+            }
+            _ => {}
+        }
+        if self.worker_threads == 0 {
+            return invalid(
+                "the job system needs at least one worker thread".to_string(),
+            );
+        }
+        if matches!(self.execution_mode, ExecutionMode::Parallel { threads: 0 })
+        {
+            return invalid(
+                "parallel execution needs at least one thread".to_string(),
+            );
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for EngineBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expect_invalid(builder: EngineBuilder) {
+        match builder.build() {
+            Err(StrataError::InvalidConfig(_)) => {}
+            Err(e) => panic!("Expected InvalidConfig, got {}", e),
+            Ok(_) => panic!("Expected InvalidConfig, got a valid engine"),
+        }
+    }
+
+    #[test]
+    fn test_default_builder_matches_engine_new() {
+        let engine = EngineBuilder::new()
+            .build()
+            .expect("Default is valid");
+        assert_eq!(engine.timestep(), Timestep::Variable);
+        assert_eq!(engine.window_manager.size(), (800, 600));
+        assert!(engine.title_from_game);
+    }
+
+    #[test]
+    fn test_builder_applies_settings() {
+        let engine = EngineBuilder::new()
+            .title("Configured")
+            .size(1920, 1080)
+            .window_mode(WindowMode::ExclusiveFullscreen)
+            .resizable(false)
+            .vsync(false)
+            .validation(false)
+            .frames_in_flight(3)
+            .frame_arena_capacity(4096)
+            .target_fps(120)
+            .tick_rate(30.0)
+            .max_catch_up_steps(3)
+            .execution_mode(ExecutionMode::SingleThreaded)
+            .worker_threads(3)
+            .build()
+            .expect("Configuration is valid");
+
+        assert_eq!(engine.window_manager.title(), "Configured");
+        assert_eq!(engine.window_manager.size(), (1920, 1080));
+        assert_eq!(
+            engine.window_manager.mode(),
+            WindowMode::ExclusiveFullscreen
+        );
+        assert!(!engine.window_manager.resizable());
+        assert!(!engine.title_from_game);
+        assert_eq!(engine.renderer_config.present_mode, PresentMode::Immediate);
+        assert!(!engine.renderer_config.validation);
+        assert_eq!(engine.renderer_config.frames_in_flight, 3);
+        assert_eq!(
+            engine
+                .renderer_config
+                .frame_arena_capacity,
+            4096
+        );
+        assert_eq!(
+            engine.frame_limit,
+            Some(Duration::from_secs_f64(1.0 / 120.0))
+        );
+        assert_eq!(
+            engine.timestep(),
+            Timestep::Fixed(FixedTimestep::new(30.0).with_max_steps(3))
+        );
+        assert_eq!(engine.scheduler().mode(), ExecutionMode::SingleThreaded);
+        assert_eq!(engine.jobs().workers(), 3);
+    }
+
+    #[test]
+    fn test_builder_rejects_bad_values() {
+        expect_invalid(EngineBuilder::new().title(""));
+        expect_invalid(EngineBuilder::new().size(0, 600));
+        expect_invalid(
+            EngineBuilder::new().size(800, MAX_WINDOW_DIMENSION + 1),
+        );
+        expect_invalid(EngineBuilder::new().target_fps(0));
+        expect_invalid(EngineBuilder::new().frames_in_flight(0));
+        expect_invalid(
+            EngineBuilder::new().frames_in_flight(MAX_FRAMES_IN_FLIGHT + 1),
+        );
+        expect_invalid(EngineBuilder::new().tick_rate(0.0));
+        expect_invalid(EngineBuilder::new().tick_rate(f64::NAN));
+        expect_invalid(EngineBuilder::new().tick_rate(1e10));
+        expect_invalid(EngineBuilder::new().tick_rate(1e-30));
+        expect_invalid(
+            EngineBuilder::new()
+                .tick_rate(60.0)
+                .max_catch_up_steps(0),
+        );
+        expect_invalid(
+            EngineBuilder::new()
+                .execution_mode(ExecutionMode::Parallel { threads: 0 }),
+        );
+        expect_invalid(EngineBuilder::new().worker_threads(0));
+    }
+
+    #[test]
+    fn test_builder_loads_blocks() {
+        let path = std::env::temp_dir()
+            .join(format!("strata-blocks-{}.toml", std::process::id()));
+        std::fs::write(&path, "[[block]]\nname = \"stone\"\n")
+            .expect("Temp dir is writable");
+        let engine = EngineBuilder::new()
+            .blocks(&path)
+            .build();
+        std::fs::remove_file(&path).ok();
+
+        let engine = engine.expect("Block definitions are valid");
+        assert_eq!(engine.blocks().len(), 2);
+        assert!(engine.blocks().id("stone").is_some());
+
+        match EngineBuilder::new()
+            .blocks("/nonexistent/blocks.toml")
+            .build()
+        {
+            Err(StrataError::FileRead { .. }) => {}
+            Err(e) => panic!("Expected FileRead, got {}", e),
+            Ok(_) => panic!("Expected FileRead, got a valid engine"),
+        }
+    }
+
+    #[test]
+    fn test_builder_rejects_catch_up_without_tick_rate() {
+        expect_invalid(EngineBuilder::new().max_catch_up_steps(4));
+    }
+}
@@ -13,6 +13,48 @@ pub enum StrataError {
     #[error("Renderer initialization failed: {0}")]
     RendererInit(String),
 
+    /// The engine was configured with invalid or conflicting options
+    #[error("Invalid engine configuration: {0}")]
+    InvalidConfig(String),
+
+    /// The swapchain no longer matches the surface and has been recreated.
+    /// The frame should be skipped.
+    #[error("Swapchain out of date; frame skipped")]
+    SwapchainOutOfDate,
+
+    /// The frame was presented but the swapchain no longer matches the
+    /// surface exactly and has been recreated
+    #[error("Swapchain suboptimal; recreated after present")]
+    SwapchainSuboptimal,
+
+    /// `begin_frame` was called again before the previous frame was ended
+    #[error("A frame is already in progress")]
+    FrameInProgress,
+
+    /// The operation isn't available in the current configuration
+    #[error("Unsupported operation: {0}")]
+    Unsupported(String),
+
+    /// A data file could not be read
+    #[error("Failed to read {path}: {source}")]
+    FileRead {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+
+    /// Block definitions are malformed or conflict with each other
+    #[error("Invalid block definition at {file}:{line}: {message}")]
+    BlockDefinition {
+        file: String,
+        line: usize,
+        message: String,
+    },
+
+    /// No block with this name is registered
+    #[error("Unknown block: {0}")]
+    UnknownBlock(String),
+
     /// A Vulkan API error occured
     #[error("Vulkan error: {0}")]
     Vulkan(#[from] ash::vk::Result),
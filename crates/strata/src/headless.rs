@@ -0,0 +1,128 @@
+//! Headless game loop for servers, CI and replays
+
+use std::time::Duration;
+
+use crate::time::{FrameTimer, ManualClock, Timestep};
+use crate::{Game, JobSystem, Result, Scheduler, World};
+
+/// How time advances between frames of a headless run
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HeadlessClock {
+    /// Every frame advances a virtual clock by exactly this much and frames
+    /// run back to back as fast as possible. Runs are fully deterministic.
+    Simulated(Duration),
+    /// Frames are paced against the system clock, sleeping so that each
+    /// frame takes at least this long. Used for dedicated servers.
+    Realtime(Duration),
+}
+
+/// Configuration for [`crate::Engine::run_headless`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeadlessConfig {
+    max_frames: Option<u64>,
+    clock: HeadlessClock,
+}
+
+impl HeadlessConfig {
+    /// Run until the game requests exit, with a simulated 60 Hz clock
+    pub fn new() -> Self {
+        Self {
+            max_frames: None,
+            clock: HeadlessClock::Simulated(Duration::from_secs_f64(
+                1.0 / 60.0,
+            )),
+        }
+    }
+
+    /// Stop after `max_frames` frames even if the game hasn't exited
+    pub fn with_max_frames(mut self, max_frames: u64) -> Self {
+        self.max_frames = Some(max_frames);
+        self
+    }
+
+    /// Set how time advances between frames
+    pub fn with_clock(mut self, clock: HeadlessClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Frame limit, if any
+    pub fn max_frames(&self) -> Option<u64> {
+        self.max_frames
+    }
+
+    /// How time advances between frames
+    pub fn clock(&self) -> HeadlessClock {
+        self.clock
+    }
+}
+
+impl Default for HeadlessConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Drive `game` without a window or renderer. Returns the number of frames
+/// that ran.
+pub(crate) fn run<G: Game>(
+    game: &mut G,
+    mut world: World,
+    mut scheduler: Scheduler,
+    jobs: &JobSystem,
+    timestep: Timestep,
+    config: HeadlessConfig,
+) -> Result<u64> {
+    let mut frames = 0;
+    game.add_systems(&mut scheduler);
+    game.setup(&mut world);
+
+    match config.clock {
+        HeadlessClock::Simulated(frame_time) => {
+            let clock = ManualClock::new();
+            let mut timer = FrameTimer::with_clock(timestep, clock.clone());
+
+            while !finished(game, frames, config.max_frames) {
+                clock.advance(frame_time);
+                crate::simulate(
+                    game,
+                    &mut world,
+                    &mut scheduler,
+                    jobs,
+                    timer.tick(),
+                );
+                frames += 1;
+            }
+        }
+        HeadlessClock::Realtime(frame_time) => {
+            let mut timer = FrameTimer::new(timestep);
+            let mut next_frame = std::time::Instant::now();
+
+            while !finished(game, frames, config.max_frames) {
+                crate::simulate(
+                    game,
+                    &mut world,
+                    &mut scheduler,
+                    jobs,
+                    timer.tick(),
+                );
+                frames += 1;
+
+                next_frame += frame_time;
+                let now = std::time::Instant::now();
+                if next_frame > now {
+                    std::thread::sleep(next_frame - now);
+                } else {
+                    // Running behind; don't try to catch up with a burst
+                    next_frame = now;
+                }
+            }
+        }
+    }
+
+    Ok(frames)
+}
+
+fn finished<G: Game>(game: &G, frames: u64, max_frames: Option<u64>) -> bool {
+    game.should_exit() || max_frames.is_some_and(|max| frames >= max)
+}
@@ -3,26 +3,46 @@
 //! This crate provides the core engine functionality for building voxel-based
 //! games with a retro aesthetic.
 
+pub mod builder;
 pub mod error;
+pub mod headless;
 pub mod renderer;
+pub mod time;
+pub mod voxel;
 pub mod window;
 
+pub use builder::EngineBuilder;
 pub use error::{Result, StrataError};
-pub use renderer::Renderer;
-pub use window::WindowManager;
+pub use headless::{HeadlessClock, HeadlessConfig};
+pub use renderer::{
+    DrawCommand, DrawStats, Frame, MaterialHandle, MeshHandle, PipelineDesc,
+    PipelineHandle, PresentMode, Renderer, RendererConfig, SortKey,
+    SortKeyLayout, Transform,
+};
+pub use time::{Clock, FixedTimestep, FrameTime, FrameTimer, Timestep};
+pub use voxel::{BlockId, BlockRegistry, Chunk, ChunkMap, ChunkPos};
+pub use window::{WindowManager, WindowMode};
 
-use std::time::Instant;
+pub use substrate::ecs::{
+    self, Entity, ExecutionMode, Scheduler, Stage, System, World,
+};
+pub use substrate::jobs::{self, Dependency, JobHandle, JobSystem};
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use winit::application::ApplicationHandler;
-use winit::event::WindowEvent;
-use winit::event_loop::{ActiveEventLoop, EventLoop};
+use winit::event::{StartCause, WindowEvent};
+use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
 use winit::raw_window_handle::{HasDisplayHandle, HasWindowHandle};
 use winit::window::WindowId;
 
 /// Trait that games implement to define their logic.
 ///
 /// Implement this trait to create your game, providing update and render logic
-/// that the engine will call each frame.
+/// that the engine will call each frame. Games that need deterministic
+/// simulation can also implement [`Game::fixed_update`] and run the engine
+/// with [`Engine::with_fixed_timestep`].
 ///
 /// # Example
 ///
@@ -37,8 +57,12 @@ use winit::window::WindowId;
 ///     fn update(&mut self, dt: f64) {
 ///         // Game logic here
 ///     }
-///     
-///     fn render(&mut self, renderer: &mut Renderer) {
+///
+///     fn fixed_update(&mut self, dt: f64) {
+///         // Physics and AI here
+///     }
+///
+///     fn render(&mut self, renderer: &mut Renderer, alpha: f64) {
 ///         // Rendering here
 ///     }
 /// }
@@ -50,17 +74,55 @@ pub trait Game {
     /// Called every frame with delta time in seconds
     fn update(&mut self, dt: f64);
 
-    /// Called every frame to render
-    fn render(&mut self, renderer: &mut Renderer);
+    /// Called once per simulation tick with the fixed tick length in seconds.
+    ///
+    /// Only called when the engine runs with a [`Timestep::Fixed`]; may run
+    /// zero or several times per frame, always before [`Game::update`].
+    fn fixed_update(&mut self, _dt: f64) {}
+
+    /// Called every frame to render.
+    ///
+    /// `alpha` is how far the frame lies between the previous and next fixed
+    /// tick, for interpolating simulation state. It is always 1.0 with a
+    /// variable timestep.
+    fn render(&mut self, renderer: &mut Renderer, alpha: f64);
+
+    /// Called once before [`Game::setup`] to register the systems the
+    /// engine runs each frame
+    fn add_systems(&mut self, _scheduler: &mut Scheduler) {}
+
+    /// Called once with the engine's [`World`] before the first frame, to
+    /// spawn the initial entities
+    fn setup(&mut self, _world: &mut World) {}
+
+    /// Called every frame after [`Game::update`] and the [`Stage::Update`]
+    /// systems, with the engine's [`World`].
+    ///
+    /// Queries filtered with [`ecs::Added`] or [`ecs::Changed`] see what
+    /// changed since the end of the previous frame.
+    fn update_world(&mut self, _world: &mut World, _dt: f64) {}
+
+    /// Checked after every frame; return `true` to stop the engine
+    fn should_exit(&self) -> bool {
+        false
+    }
 }
 
 /// The main engine instance that manages the game loop, rendering, and window.
 pub struct Engine {
     window_manager: WindowManager,
+    timestep: Timestep,
+    renderer_config: RendererConfig,
+    frame_limit: Option<Duration>,
+    title_from_game: bool,
+    world: World,
+    scheduler: Scheduler,
+    jobs: Arc<JobSystem>,
+    blocks: Arc<BlockRegistry>,
 }
 
 impl Engine {
-    /// Create a new engine instance
+    /// Create a new engine instance with the default configuration
     ///
     /// # Example
     /// ```no_run
@@ -73,7 +135,87 @@ impl Engine {
     ///
     /// Returns `StrataError::WindowCreation` if the window cannot be created.
     pub fn new() -> Result<Self> {
-        Ok(Self { window_manager: WindowManager::new()? })
+        EngineBuilder::new().build()
+    }
+
+    /// Start configuring an engine
+    pub fn builder() -> EngineBuilder {
+        EngineBuilder::new()
+    }
+
+    /// Run the simulation at a fixed tick rate.
+    ///
+    /// [`Game::fixed_update`] is called as many times per frame as needed to
+    /// keep up with wall-clock time, and [`Game::render`] receives the
+    /// interpolation alpha between ticks.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use strata::{Engine, FixedTimestep};
+    /// let engine = Engine::new()?
+    ///     .with_fixed_timestep(FixedTimestep::new(60.0).with_max_steps(8))?;
+    /// # Ok::<(), strata::StrataError>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StrataError::InvalidConfig`] if `config` runs no ticks per
+    /// frame or clamps every frame to zero time, as the simulation would
+    /// never advance.
+    pub fn with_fixed_timestep(
+        mut self,
+        config: FixedTimestep,
+    ) -> Result<Self> {
+        if config.max_steps() == 0 {
+            return Err(StrataError::InvalidConfig(
+                "max catch-up steps must be greater than zero".to_string(),
+            ));
+        }
+        if config.max_frame_time().is_zero() {
+            return Err(StrataError::InvalidConfig(
+                "max frame time must be greater than zero".to_string(),
+            ));
+        }
+        self.timestep = Timestep::Fixed(config);
+        Ok(self)
+    }
+
+    /// The timestep the engine will run with
+    pub fn timestep(&self) -> Timestep {
+        self.timestep
+    }
+
+    /// The entities and components games are handed each frame
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    /// The world, for spawning the starting entities before the engine
+    /// runs
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    /// The systems run on the world each frame
+    pub fn scheduler(&self) -> &Scheduler {
+        &self.scheduler
+    }
+
+    /// The scheduler, for adding systems before the engine runs
+    pub fn scheduler_mut(&mut self) -> &mut Scheduler {
+        &mut self.scheduler
+    }
+
+    /// The background job system. Clone it into the game to spawn jobs
+    /// from its hooks; main-thread jobs run at the start of each frame.
+    pub fn jobs(&self) -> &Arc<JobSystem> {
+        &self.jobs
+    }
+
+    /// The block types loaded at startup. Clone it into the game before
+    /// running the engine.
+    pub fn blocks(&self) -> &Arc<BlockRegistry> {
+        &self.blocks
     }
 
     /// Run the engine with the given game
@@ -86,22 +228,35 @@ impl Engine {
     /// impl Game for MyGame {
     ///     fn name(&self) -> &str { "Dummy Game" }
     ///     fn update(&mut self, _dt: f64) {}
-    ///     fn render(&mut self, _renderer: &mut Renderer) {}
+    ///     fn render(&mut self, _renderer: &mut Renderer, _alpha: f64) {}
     /// }
     ///
     /// let engine = Engine::new()?;
     /// engine.run(MyGame)?;
     /// # Ok::<(), strata::StrataError>(())
     /// ```
-    pub fn run<G: Game>(self, game: G) -> Result<()> {
+    pub fn run<G: Game>(mut self, mut game: G) -> Result<()> {
         let event_loop = EventLoop::new()
             .map_err(|e| StrataError::WindowCreation(e.to_string()))?;
 
+        if self.title_from_game {
+            self.window_manager
+                .set_title(game.name());
+        }
+
+        game.add_systems(&mut self.scheduler);
+        game.setup(&mut self.world);
         let mut app = EngineApp {
             window_manager: self.window_manager,
             renderer: None,
+            renderer_config: self.renderer_config,
             game,
-            last_frame_time: Instant::now(),
+            world: self.world,
+            scheduler: self.scheduler,
+            jobs: self.jobs,
+            timer: FrameTimer::new(self.timestep),
+            frame_limit: self.frame_limit,
+            next_frame: Instant::now(),
         };
 
         event_loop
@@ -110,17 +265,96 @@ impl Engine {
 
         Ok(())
     }
+
+    /// Run the engine with no window, surface or renderer.
+    ///
+    /// Drives the same [`Game`] hooks as [`Engine::run`] except
+    /// [`Game::render`], until the game requests exit or the configured frame
+    /// limit is reached. Returns the number of frames that ran.
+    ///
+    /// # Example
+    /// ```
+    /// use strata::{Engine, Game, HeadlessConfig, Renderer};
+    ///
+    /// struct MyGame;
+    /// impl Game for MyGame {
+    ///     fn name(&self) -> &str { "Dummy Game" }
+    ///     fn update(&mut self, _dt: f64) {}
+    ///     fn render(&mut self, _renderer: &mut Renderer, _alpha: f64) {}
+    /// }
+    ///
+    /// let engine = Engine::new()?;
+    /// let frames = engine.run_headless(
+    ///     &mut MyGame,
+    ///     HeadlessConfig::new().with_max_frames(10),
+    /// )?;
+    /// assert_eq!(frames, 10);
+    /// # Ok::<(), strata::StrataError>(())
+    /// ```
+    pub fn run_headless<G: Game>(
+        self,
+        game: &mut G,
+        config: HeadlessConfig,
+    ) -> Result<u64> {
+        headless::run(
+            game,
+            self.world,
+            self.scheduler,
+            &self.jobs,
+            self.timestep,
+            config,
+        )
+    }
+}
+
+/// Advance the simulation by one frame: ready main-thread jobs, any fixed
+/// ticks that are due, then the per-frame update and world update, each
+/// followed by its stage's systems
+fn simulate<G: Game>(
+    game: &mut G,
+    world: &mut World,
+    scheduler: &mut Scheduler,
+    jobs: &JobSystem,
+    frame: FrameTime,
+) {
+    jobs.run_main_thread_jobs();
+    scheduler.run_stage(Stage::PreUpdate, world, frame.dt);
+    for _ in 0..frame.fixed_steps {
+        game.fixed_update(frame.fixed_dt);
+        scheduler.run_stage(Stage::FixedUpdate, world, frame.fixed_dt);
+    }
+    game.update(frame.dt);
+    scheduler.run_stage(Stage::Update, world, frame.dt);
+    game.update_world(world, frame.dt);
+    scheduler.run_stage(Stage::PostUpdate, world, frame.dt);
+    scheduler.run_stage(Stage::RenderExtract, world, frame.dt);
+    world.clear_trackers();
 }
 
 /// Internal application handler that manages the game loop
 struct EngineApp<G: Game> {
     window_manager: WindowManager,
     renderer: Option<Renderer>,
+    renderer_config: RendererConfig,
     game: G,
-    last_frame_time: Instant,
+    world: World,
+    scheduler: Scheduler,
+    jobs: Arc<JobSystem>,
+    timer: FrameTimer,
+    frame_limit: Option<Duration>,
+    next_frame: Instant,
 }
 
 impl<G: Game> ApplicationHandler for EngineApp<G> {
+    fn new_events(&mut self, _event_loop: &ActiveEventLoop, cause: StartCause) {
+        // With a frame cap, redraws are scheduled by waking at the deadline
+        if let StartCause::ResumeTimeReached { .. } = cause
+            && let Some(window) = self.window_manager.window()
+        {
+            window.request_redraw();
+        }
+    }
+
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         // Step 1: Create window
         if let Err(e) = self
@@ -158,11 +392,14 @@ impl<G: Game> ApplicationHandler for EngineApp<G> {
             }
         };
 
-        // Step 3: Create Vulkan Instance (uses display handle)
+        // Step 3: Create Vulkan renderer (uses display and window handles)
+        let window_size = window.inner_size();
         let renderer = match Renderer::new(
             display_handle.as_raw(),
             window_handle.as_raw(),
             self.game.name(),
+            (window_size.width, window_size.height),
+            self.renderer_config,
         ) {
             Ok(renderer) => {
                 println!("✓ Vulkan renderer initialized successfully!");
@@ -193,25 +430,52 @@ impl<G: Game> ApplicationHandler for EngineApp<G> {
             WindowEvent::CloseRequested => {
                 event_loop.exit();
             }
+            WindowEvent::Resized(size) => {
+                if let Some(renderer) = &mut self.renderer
+                    && let Err(e) = renderer.resize(size.width, size.height)
+                {
+                    eprintln!("Failed to resize swapchain: {}", e);
+                    event_loop.exit();
+                }
+            }
             WindowEvent::RedrawRequested => {
-                // Calculate deta time
-                let dt = self
-                    .last_frame_time
-                    .elapsed()
-                    .as_secs_f64();
-                self.last_frame_time = Instant::now();
-
-                // Call game update
-                self.game.update(dt);
-
-                // Call game render (once we have a renderer)
-                if let Some(renderer) = &mut self.renderer {
-                    self.game.render(renderer);
+                let frame = self.timer.tick();
+                simulate(
+                    &mut self.game,
+                    &mut self.world,
+                    &mut self.scheduler,
+                    &self.jobs,
+                    frame,
+                );
+
+                // Call game render (once we have a renderer), skipping frames
+                // while there's nothing to draw into
+                if let Some(renderer) = &mut self.renderer
+                    && !renderer.is_minimized()
+                {
+                    self.game.render(renderer, frame.alpha);
+                }
+
+                if self.game.should_exit() {
+                    event_loop.exit();
+                    return;
                 }
 
-                // Request next frame
-                if let Some(window) = self.window_manager.window() {
-                    window.request_redraw();
+                // Request next frame, or sleep until it's due when capped
+                match self.frame_limit {
+                    Some(frame_limit) => {
+                        let now = Instant::now();
+                        self.next_frame =
+                            (self.next_frame + frame_limit).max(now);
+                        event_loop.set_control_flow(ControlFlow::WaitUntil(
+                            self.next_frame,
+                        ));
+                    }
+                    None => {
+                        if let Some(window) = self.window_manager.window() {
+                            window.request_redraw();
+                        }
+                    }
                 }
             }
             _ => {}
@@ -1,15 +1,116 @@
 //! Vulkan renderer
 
+mod device;
+mod draw;
+mod frame;
+mod mesh;
+mod offscreen;
+mod pipeline;
+mod swapchain;
+
 use std::ffi::{CStr, CString};
+use std::rc::Rc;
 
 use ash::{Entry, Instance, ext, khr, vk};
 use winit::raw_window_handle::{RawDisplayHandle, RawWindowHandle};
 
 use crate::{Result, StrataError};
+use device::Device;
+use frame::{FrameSlots, transition_image};
+use mesh::Mesh;
+use offscreen::{OFFSCREEN_FORMAT, OffscreenTarget};
+use pipeline::{Material, Pipeline};
+use substrate::{DrawList, SlotMap};
+use swapchain::Swapchain;
+
+pub use draw::DrawStats;
+pub use frame::Frame;
+pub use mesh::MeshHandle;
+pub use pipeline::{MaterialHandle, PipelineDesc, PipelineHandle};
+pub use substrate::{SortKey, SortKeyLayout, Transform};
+
+/// One mesh drawn with one material, queued with [`Renderer::draw`]
+pub type DrawCommand = substrate::DrawCommand<MeshHandle, MaterialHandle>;
+
+/// Most frames the CPU may record ahead of the GPU
+pub const MAX_FRAMES_IN_FLIGHT: usize = 4;
+
+/// How finished frames are presented to the screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PresentMode {
+    /// Wait for vertical blank; never tears. Always supported.
+    #[default]
+    Fifo,
+    /// Like `Fifo`, but late frames are shown immediately and may tear
+    FifoRelaxed,
+    /// Wait for vertical blank but replace queued frames with newer ones
+    Mailbox,
+    /// Present immediately; may tear
+    Immediate,
+}
+
+impl PresentMode {
+    /// Whether this mode synchronises presentation to vertical blank
+    pub fn is_vsync(&self) -> bool {
+        matches!(self, PresentMode::Fifo | PresentMode::Mailbox)
+    }
+}
+
+/// Options controlling how the renderer is created
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RendererConfig {
+    /// Preferred present mode; falls back to `Fifo` if unsupported
+    pub present_mode: PresentMode,
+    /// Enable the Khronos validation layer and debug messenger when the
+    /// layer is installed
+    pub validation: bool,
+    /// Frames the CPU may record while the GPU is still working on earlier
+    /// ones, from 1 to [`MAX_FRAMES_IN_FLIGHT`]
+    pub frames_in_flight: usize,
+    /// Initial size in bytes of each frame's scratch [`Frame::arena`],
+    /// which grows on demand
+    pub frame_arena_capacity: usize,
+}
+
+impl Default for RendererConfig {
+    /// `Fifo` presentation, two frames in flight with 1 MiB of scratch each,
+    /// and validation in debug builds only
+    fn default() -> Self {
+        Self {
+            present_mode: PresentMode::Fifo,
+            validation: cfg!(debug_assertions),
+            frames_in_flight: 2,
+            frame_arena_capacity: 1 << 20,
+        }
+    }
+}
+
+/// What frames are rendered into
+enum RenderTarget {
+    /// A window surface's swapchain
+    Window(Swapchain),
+    /// A single offscreen image that can be read back to the CPU
+    Offscreen(OffscreenTarget),
+}
 
 /// Manages Vulkan rendering state and draw calls
 pub struct Renderer {
-    _context: VulkanContext,
+    config: RendererConfig,
+    window_size: (u32, u32),
+    swapchain_dirty: bool,
+    clear_color: [f32; 4],
+    draw_list: DrawList<MeshHandle, MaterialHandle>,
+    draw_stats: DrawStats,
+    materials: SlotMap<Material>,
+    // Field order matters: GPU resources, frame resources and the target
+    // must be destroyed before the device, and the device before the
+    // instance
+    meshes: SlotMap<Mesh>,
+    pipelines: SlotMap<Pipeline>,
+    frames: FrameSlots,
+    target: RenderTarget,
+    device: Device,
+    context: VulkanContext,
 }
 
 impl Renderer {
@@ -20,67 +121,923 @@ impl Renderer {
     /// * `display_handle` - The raw display handle provided by the windowing system.
     /// * `window_handle` - The raw window handle used to create the Vulkan surface.
     /// * `app_name` - The application name passed to Vulkan for instance identification.
+    /// * `window_size` - The window's inner size in physical pixels.
+    /// * `config` - Present mode, validation and frame options.
     ///
     /// # Errors
     ///
     /// Returns [`StrataError::RendererInit`] if Vulkan initialization fails
+    /// or no GPU can present to the window.
     pub fn new(
         display_handle: RawDisplayHandle,
         window_handle: RawWindowHandle,
         app_name: &str,
+        window_size: (u32, u32),
+        config: RendererConfig,
     ) -> Result<Self> {
+        let context = VulkanContext::new(
+            Some((display_handle, window_handle)),
+            app_name,
+            config.validation,
+        )?;
+        let surface = context
+            .surface
+            .expect("surface created for window");
+        let device = Device::new(
+            &context.instance,
+            Some((&context.surface_loader, surface)),
+        )?;
+        let swapchain_loader =
+            khr::swapchain::Device::new(&context.instance, &device.handle);
+        let swapchain = Swapchain::new(
+            &device,
+            &swapchain_loader,
+            &context.surface_loader,
+            surface,
+            config.present_mode,
+            window_size,
+            vk::SwapchainKHR::null(),
+        )?;
+
+        Self::with_target(
+            context,
+            device,
+            RenderTarget::Window(swapchain),
+            window_size,
+            config,
+        )
+    }
+
+    /// Create a renderer that draws into an offscreen `width` x `height`
+    /// RGBA8 (sRGB) image instead of a window.
+    ///
+    /// No window, surface or presentation support is needed, so this works
+    /// on headless machines with a software driver such as lavapipe. Use
+    /// [`Renderer::read_pixels`] after [`Renderer::end_frame`] to fetch the
+    /// result, for example for golden-image tests.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StrataError::RendererInit`] if the size is zero, Vulkan
+    /// initialization fails or no GPU supports graphics.
+    pub fn new_offscreen(
+        width: u32,
+        height: u32,
+        app_name: &str,
+        config: RendererConfig,
+    ) -> Result<Self> {
+        if width == 0 || height == 0 {
+            return Err(StrataError::RendererInit(format!(
+                "Offscreen target must be non-zero, got {}x{}",
+                width, height
+            )));
+        }
+
+        let context = VulkanContext::new(None, app_name, config.validation)?;
+        let device = Device::new(&context.instance, None)?;
+        let target =
+            OffscreenTarget::new(&context.instance, &device, width, height)?;
+
+        Self::with_target(
+            context,
+            device,
+            RenderTarget::Offscreen(target),
+            (width, height),
+            config,
+        )
+    }
+
+    fn with_target(
+        context: VulkanContext,
+        device: Device,
+        target: RenderTarget,
+        size: (u32, u32),
+        config: RendererConfig,
+    ) -> Result<Self> {
+        let frames = FrameSlots::new(
+            &device.handle,
+            device.families.graphics,
+            config.frames_in_flight,
+            config.frame_arena_capacity,
+        )?;
+
         Ok(Self {
-            _context: VulkanContext::new(
-                display_handle,
-                window_handle,
-                app_name,
-            )?,
+            config,
+            window_size: size,
+            swapchain_dirty: false,
+            clear_color: [0.0, 0.0, 0.0, 1.0],
+            draw_list: DrawList::new(),
+            draw_stats: DrawStats::default(),
+            materials: SlotMap::new(),
+            meshes: SlotMap::new(),
+            pipelines: SlotMap::new(),
+            frames,
+            target,
+            device,
+            context,
         })
     }
+
+    /// Wait for a free frame slot, acquire an image to render into and
+    /// start recording.
+    ///
+    /// The returned [`Frame`]'s command buffer is recording, and its image
+    /// has been cleared to the clear colour and is in
+    /// `COLOR_ATTACHMENT_OPTIMAL` layout. Pass it to [`Renderer::end_frame`]
+    /// to submit and present.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StrataError::SwapchainOutOfDate`] if the swapchain had to
+    /// be recreated (or can't be while [`Renderer::is_minimized`]); skip
+    /// rendering this frame and try again next frame. Returns
+    /// [`StrataError::FrameInProgress`] if the previous frame is still alive
+    /// and was never ended; a frame that was dropped instead is abandoned
+    /// and its slot reused.
+    pub fn begin_frame(&mut self) -> Result<Frame> {
+        if self
+            .frames
+            .current()
+            .arena
+            .borrow()
+            .is_none()
+        {
+            return Err(StrataError::FrameInProgress);
+        }
+
+        self.release_abandoned_images()?;
+
+        if self.swapchain_dirty {
+            self.recreate_swapchain()?;
+            if self.swapchain_dirty {
+                return Err(StrataError::SwapchainOutOfDate);
+            }
+        }
+
+        let slot_index = self.frames.current;
+        let (in_flight, image_available) = {
+            let slot = &self.frames.slots[slot_index];
+            (slot.in_flight, slot.image_available)
+        };
+
+        // Wait until the GPU is done with this slot's last frame
+        unsafe {
+            self.device
+                .handle
+                .wait_for_fences(&[in_flight], true, u64::MAX)?
+        };
+        self.frames.slots[slot_index]
+            .retired
+            .clear();
+
+        let (image_index, image, image_view, extent) = match &mut self.target {
+            RenderTarget::Window(swapchain) => {
+                let image_index = match swapchain
+                    .acquire_next_image(image_available)
+                {
+                    Ok((index, suboptimal)) => {
+                        // The image is still usable; recreate after
+                        // presenting it
+                        self.swapchain_dirty |= suboptimal;
+                        self.frames.slots[slot_index].acquired = Some(index);
+                        index
+                    }
+                    Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
+                        self.swapchain_dirty = true;
+                        self.recreate_swapchain()?;
+                        return Err(StrataError::SwapchainOutOfDate);
+                    }
+                    Err(e) => return Err(e.into()),
+                };
+
+                // Another slot may still be rendering to this image
+                let image = image_index as usize;
+                let image_fence = swapchain.images_in_flight[image];
+                if image_fence != vk::Fence::null() && image_fence != in_flight
+                {
+                    unsafe {
+                        self.device.handle.wait_for_fences(
+                            &[image_fence],
+                            true,
+                            u64::MAX,
+                        )?
+                    };
+                }
+                swapchain.images_in_flight[image] = in_flight;
+
+                (
+                    image_index,
+                    swapchain.images[image],
+                    swapchain.image_views[image],
+                    swapchain.extent,
+                )
+            }
+            RenderTarget::Offscreen(target) => {
+                (0, target.image, target.image_view, target.extent)
+            }
+        };
+
+        let device = &self.device.handle;
+        let slot = &mut self.frames.slots[slot_index];
+        unsafe {
+            device.reset_command_pool(
+                slot.command_pool,
+                vk::CommandPoolResetFlags::empty(),
+            )?;
+            device.begin_command_buffer(
+                slot.command_buffer,
+                &vk::CommandBufferBeginInfo {
+                    flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+                    ..Default::default()
+                },
+            )?;
+        }
+        record_clear(device, slot.command_buffer, image, self.clear_color);
+
+        // Safe to reuse: the fence wait above means the GPU is done with it
+        let mut arena = slot
+            .arena
+            .borrow_mut()
+            .take()
+            .expect("arena checked above");
+        arena.reset();
+        // Anything queued for an abandoned frame goes with it
+        self.draw_list.clear();
+
+        Ok(Frame {
+            slot: slot_index,
+            image_index,
+            image,
+            image_view,
+            extent,
+            command_buffer: slot.command_buffer,
+            arena: Some(arena),
+            home: Rc::clone(&slot.arena),
+        })
+    }
+
+    /// Finish recording `frame`, submit it and present its image.
+    ///
+    /// For an offscreen renderer nothing is presented; the image is copied
+    /// to a readback buffer for [`Renderer::read_pixels`] instead.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StrataError::SwapchainSuboptimal`] or
+    /// [`StrataError::SwapchainOutOfDate`] if presentation reported that the
+    /// swapchain no longer matches the surface; it has been recreated and
+    /// rendering can continue next frame. Other Vulkan errors are returned
+    /// as [`StrataError::Vulkan`].
+    pub fn end_frame(&mut self, frame: Frame) -> Result<()> {
+        let (slot_index, image_index, image, image_view, extent) = (
+            frame.slot,
+            frame.image_index,
+            frame.image,
+            frame.image_view,
+            frame.extent,
+        );
+        debug_assert_eq!(
+            slot_index, self.frames.current,
+            "frame ended out of order"
+        );
+        // Gives the arena back to the slot
+        drop(frame);
+
+        let device = &self.device.handle;
+        let slot = &self.frames.slots[slot_index];
+
+        self.draw_list.sort();
+        self.draw_stats = draw::record_draws(
+            device,
+            slot.command_buffer,
+            (image_view, extent),
+            &self.draw_list,
+            &self.pipelines,
+            &self.materials,
+            &self.meshes,
+        );
+        self.draw_list.clear();
+
+        let rendered = (
+            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        );
+        let (wait_semaphores, signal_semaphores) = match &self.target {
+            RenderTarget::Window(swapchain) => {
+                transition_image(
+                    device,
+                    slot.command_buffer,
+                    image,
+                    rendered,
+                    (
+                        vk::ImageLayout::PRESENT_SRC_KHR,
+                        vk::AccessFlags::empty(),
+                        vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                    ),
+                );
+                (
+                    vec![slot.image_available],
+                    vec![swapchain.render_finished[image_index as usize]],
+                )
+            }
+            RenderTarget::Offscreen(target) => {
+                transition_image(
+                    device,
+                    slot.command_buffer,
+                    image,
+                    rendered,
+                    (
+                        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                        vk::AccessFlags::TRANSFER_READ,
+                        vk::PipelineStageFlags::TRANSFER,
+                    ),
+                );
+                target.record_readback(slot.command_buffer);
+                (Vec::new(), Vec::new())
+            }
+        };
+
+        unsafe { device.end_command_buffer(slot.command_buffer)? };
+        self.submit(
+            slot_index,
+            &wait_semaphores,
+            vk::PipelineStageFlags::TRANSFER
+                | vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            &signal_semaphores,
+        )?;
+        self.frames.slots[slot_index].acquired = None;
+        self.frames.advance();
+
+        let RenderTarget::Window(swapchain) = &self.target else {
+            return Ok(());
+        };
+        match swapchain.present(self.device.present_queue, image_index) {
+            Ok(false) if !self.swapchain_dirty => Ok(()),
+            Ok(_) => {
+                self.swapchain_dirty = true;
+                self.recreate_swapchain()?;
+                Err(StrataError::SwapchainSuboptimal)
+            }
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
+                self.swapchain_dirty = true;
+                self.recreate_swapchain()?;
+                Err(StrataError::SwapchainOutOfDate)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Submit `slot_index`'s recorded command buffer, signalling its fence
+    /// when the GPU is done.
+    ///
+    /// The fence is only reset once the submit is about to happen, and is
+    /// signalled again if the submit fails, so later waits on it can't hang.
+    fn submit(
+        &mut self,
+        slot_index: usize,
+        wait_semaphores: &[vk::Semaphore],
+        wait_stage: vk::PipelineStageFlags,
+        signal_semaphores: &[vk::Semaphore],
+    ) -> Result<()> {
+        let slot = &self.frames.slots[slot_index];
+        let wait_stages = vec![wait_stage; wait_semaphores.len()];
+        let command_buffers = [slot.command_buffer];
+        let submit_info = vk::SubmitInfo {
+            wait_semaphore_count: wait_semaphores.len() as u32,
+            p_wait_semaphores: wait_semaphores.as_ptr(),
+            p_wait_dst_stage_mask: wait_stages.as_ptr(),
+            command_buffer_count: command_buffers.len() as u32,
+            p_command_buffers: command_buffers.as_ptr(),
+            signal_semaphore_count: signal_semaphores.len() as u32,
+            p_signal_semaphores: signal_semaphores.as_ptr(),
+            ..Default::default()
+        };
+
+        let device = &self.device.handle;
+        let result = unsafe {
+            device.reset_fences(&[slot.in_flight])?;
+            device.queue_submit(
+                self.device.graphics_queue,
+                &[submit_info],
+                slot.in_flight,
+            )
+        };
+        if let Err(e) = result {
+            self.restore_fence(slot_index);
+            return Err(e.into());
+        }
+        self.frames.last_submitted = Some(slot_index);
+        Ok(())
+    }
+
+    /// Signal `slot_index`'s fence after a failed submit left it reset.
+    ///
+    /// An empty submit signals it once earlier work completes; if even that
+    /// fails the fence is replaced with a new, signalled one.
+    fn restore_fence(&mut self, slot_index: usize) {
+        let fence = self.frames.slots[slot_index].in_flight;
+        let signalled = unsafe {
+            self.device.handle.queue_submit(
+                self.device.graphics_queue,
+                &[],
+                fence,
+            )
+        };
+        if signalled.is_ok() {
+            return;
+        }
+
+        let Ok(old) = self.frames.replace_fence(slot_index) else {
+            return;
+        };
+        if let RenderTarget::Window(swapchain) = &mut self.target {
+            for image_fence in &mut swapchain.images_in_flight {
+                if *image_fence == old {
+                    *image_fence = vk::Fence::null();
+                }
+            }
+        }
+    }
+
+    /// Present swapchain images that were acquired for frames that never
+    /// got submitted, because the frame was dropped or an error cut it
+    /// short, so the images and their semaphores can be used again.
+    ///
+    /// Slots whose [`Frame`] is still alive are left alone.
+    fn release_abandoned_images(&mut self) -> Result<()> {
+        for slot_index in 0..self.frames.slots.len() {
+            let slot = &self.frames.slots[slot_index];
+            let Some(image_index) = slot.acquired else {
+                continue;
+            };
+            if slot.arena.borrow().is_none() {
+                continue;
+            }
+            let RenderTarget::Window(swapchain) = &self.target else {
+                continue;
+            };
+
+            let device = &self.device.handle;
+            let command_buffer = slot.command_buffer;
+            unsafe {
+                device.wait_for_fences(&[slot.in_flight], true, u64::MAX)?;
+                device.reset_command_pool(
+                    slot.command_pool,
+                    vk::CommandPoolResetFlags::empty(),
+                )?;
+                device.begin_command_buffer(
+                    command_buffer,
+                    &vk::CommandBufferBeginInfo {
+                        flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+                        ..Default::default()
+                    },
+                )?;
+            }
+            // Contents are discarded; the image only needs a layout the
+            // presentation engine accepts
+            transition_image(
+                device,
+                command_buffer,
+                swapchain.images[image_index as usize],
+                (
+                    vk::ImageLayout::UNDEFINED,
+                    vk::AccessFlags::empty(),
+                    vk::PipelineStageFlags::ALL_COMMANDS,
+                ),
+                (
+                    vk::ImageLayout::PRESENT_SRC_KHR,
+                    vk::AccessFlags::empty(),
+                    vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                ),
+            );
+            unsafe { device.end_command_buffer(command_buffer)? };
+
+            let wait = [slot.image_available];
+            let signal = [swapchain.render_finished[image_index as usize]];
+            self.submit(
+                slot_index,
+                &wait,
+                vk::PipelineStageFlags::ALL_COMMANDS,
+                &signal,
+            )?;
+            self.frames.slots[slot_index].acquired = None;
+
+            let RenderTarget::Window(swapchain) = &self.target else {
+                unreachable!("checked above");
+            };
+            match swapchain.present(self.device.present_queue, image_index) {
+                Ok(suboptimal) => self.swapchain_dirty |= suboptimal,
+                Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
+                    self.swapchain_dirty = true;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Read back the most recently ended frame of an offscreen renderer.
+    ///
+    /// Waits for the GPU to finish, then returns tightly packed RGBA8 rows,
+    /// top row first. The contents are undefined before the first frame has
+    /// been ended.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StrataError::Unsupported`] for a windowed renderer, or a
+    /// Vulkan error if waiting or mapping fails.
+    pub fn read_pixels(&self) -> Result<Vec<u8>> {
+        let RenderTarget::Offscreen(target) = &self.target else {
+            return Err(StrataError::Unsupported(
+                "read_pixels requires an offscreen renderer".to_string(),
+            ));
+        };
+
+        unsafe {
+            self.device
+                .handle
+                .queue_wait_idle(self.device.graphics_queue)?
+        };
+        target.read()
+    }
+
+    /// Upload a mesh of `vertices` and 32-bit triangle-list `indices`.
+    ///
+    /// `V` should be `#[repr(C)]` and match the vertex layout of the
+    /// pipelines it is drawn with. Meshes live until
+    /// [`Renderer::destroy_mesh`] or the renderer is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StrataError::InvalidConfig`] if either slice is empty, or a
+    /// Vulkan error if the buffers cannot be created.
+    pub fn create_mesh<V: Copy>(
+        &mut self,
+        vertices: &[V],
+        indices: &[u32],
+    ) -> Result<MeshHandle> {
+        let mesh =
+            Mesh::new(&self.context.instance, &self.device, vertices, indices)?;
+        let handle = self.meshes.insert(mesh);
+        Ok(MeshHandle(handle))
+    }
+
+    /// Build a graphics pipeline that renders into this renderer's frames.
+    /// Pipelines live until [`Renderer::destroy_pipeline`] or the renderer
+    /// is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns a Vulkan error if the shaders or pipeline cannot be created.
+    pub fn create_pipeline(
+        &mut self,
+        desc: &PipelineDesc,
+    ) -> Result<PipelineHandle> {
+        let pipeline =
+            Pipeline::new(&self.device.handle, desc, self.color_format())?;
+        Ok(PipelineHandle(self.pipelines.insert(pipeline)))
+    }
+
+    /// Create a material that draws with `pipeline`, binding
+    /// `descriptor_set` at set 0 if given.
+    ///
+    /// Materials sharing a pipeline are drawn without rebinding it, so
+    /// prefer one pipeline per shader and a material per set of resources.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pipeline` was not created by this renderer or has been
+    /// destroyed.
+    pub fn create_material(
+        &mut self,
+        pipeline: PipelineHandle,
+        descriptor_set: Option<vk::DescriptorSet>,
+    ) -> MaterialHandle {
+        assert!(
+            self.pipelines.contains(pipeline.0),
+            "unknown pipeline {:?}",
+            pipeline
+        );
+        let handle = self
+            .materials
+            .insert(Material { pipeline, descriptor_set });
+        MaterialHandle(handle)
+    }
+
+    /// Destroy a mesh once the GPU has finished every frame that may draw
+    /// it.
+    ///
+    /// The handle and any copies of it are rejected from then on, even
+    /// after its slot is reused. Draws already queued with it this frame are
+    /// skipped. Returns whether the mesh existed.
+    pub fn destroy_mesh(&mut self, mesh: MeshHandle) -> bool {
+        let slot = mesh.0;
+        if !self.meshes.contains(slot) {
+            return false;
+        }
+        let mesh = self
+            .meshes
+            .remove(slot)
+            .expect("mesh is live");
+        if let Some(retired) = self.frames.retired() {
+            retired.meshes.push(mesh);
+        }
+        true
+    }
+
+    /// Destroy a material. Its handle and any copies are rejected from
+    /// then on, and draws already queued with it this frame are skipped.
+    /// Returns whether the material existed.
+    pub fn destroy_material(&mut self, material: MaterialHandle) -> bool {
+        let slot = material.0;
+        self.materials.contains(slot) && self.materials.remove(slot).is_some()
+    }
+
+    /// Destroy a pipeline once the GPU has finished every frame that may
+    /// use it.
+    ///
+    /// The handle and any copies of it are rejected from then on. Materials
+    /// created with it stay valid, but their draws are skipped until they
+    /// are destroyed. Returns whether the pipeline existed.
+    pub fn destroy_pipeline(&mut self, pipeline: PipelineHandle) -> bool {
+        if !self.pipelines.contains(pipeline.0) {
+            return false;
+        }
+        let pipeline = self
+            .pipelines
+            .remove(pipeline.0)
+            .expect("pipeline is live");
+        if let Some(retired) = self.frames.retired() {
+            retired.pipelines.push(pipeline);
+        }
+        true
+    }
+
+    /// Queue `command` for the frame being recorded.
+    ///
+    /// Commands are sorted by [`DrawCommand::sort_key`] and recorded in
+    /// [`Renderer::end_frame`], after the clear, with consecutive commands
+    /// sharing a mesh and material drawn without rebinding. Build keys with
+    /// [`SortKey`] to order by layer, translucency, material and depth.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the mesh or material was not created by this renderer or
+    /// has been destroyed.
+    pub fn draw(&mut self, command: DrawCommand) {
+        assert!(
+            self.meshes.contains(command.mesh.0),
+            "unknown mesh {:?}",
+            command.mesh
+        );
+        assert!(
+            self.materials
+                .contains(command.material.0),
+            "unknown material {:?}",
+            command.material
+        );
+        self.draw_list.push(command);
+    }
+
+    /// Draw and bind counts from the most recently ended frame
+    pub fn draw_stats(&self) -> DrawStats {
+        self.draw_stats
+    }
+
+    /// Set the colour each frame is cleared to in
+    /// [`Renderer::begin_frame`], as linear RGBA
+    pub fn set_clear_color(&mut self, color: [f32; 4]) {
+        self.clear_color = color;
+    }
+
+    /// The logical device, for recording commands into a [`Frame`]
+    pub fn device(&self) -> &ash::Device {
+        &self.device.handle
+    }
+
+    /// The configuration this renderer was created with
+    pub fn config(&self) -> &RendererConfig {
+        &self.config
+    }
+
+    /// Name of the GPU the renderer is running on
+    pub fn device_name(&self) -> &str {
+        &self.device.name
+    }
+
+    /// Whether frames render into an offscreen image rather than a window
+    pub fn is_offscreen(&self) -> bool {
+        matches!(self.target, RenderTarget::Offscreen(_))
+    }
+
+    /// The present mode in use, which may differ from the configured one if
+    /// the surface didn't support it. `None` when rendering offscreen.
+    pub fn present_mode(&self) -> Option<PresentMode> {
+        match &self.target {
+            RenderTarget::Window(swapchain) => Some(swapchain.present_mode),
+            RenderTarget::Offscreen(_) => None,
+        }
+    }
+
+    /// Format of the images frames render into
+    fn color_format(&self) -> vk::Format {
+        match &self.target {
+            RenderTarget::Window(swapchain) => swapchain.format,
+            RenderTarget::Offscreen(_) => OFFSCREEN_FORMAT,
+        }
+    }
+
+    /// Size of the render target as (width, height) in pixels
+    pub fn extent(&self) -> (u32, u32) {
+        let extent = match &self.target {
+            RenderTarget::Window(swapchain) => swapchain.extent,
+            RenderTarget::Offscreen(target) => target.extent,
+        };
+        (extent.width, extent.height)
+    }
+
+    /// Whether the window has no area, so no frame can be rendered until it
+    /// is resized again. The engine skips [`crate::Game::render`] meanwhile.
+    pub fn is_minimized(&self) -> bool {
+        self.window_size.0 == 0 || self.window_size.1 == 0
+    }
+
+    /// Notify the renderer that the window was resized, or resize an
+    /// offscreen target.
+    ///
+    /// The swapchain is recreated immediately unless the window is minimized
+    /// (zero sized), in which case recreation waits for the next non-zero
+    /// size and [`Renderer::is_minimized`] is true until then.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StrataError::InvalidConfig`] if an offscreen target is
+    /// resized to zero, or a Vulkan error if the target cannot be recreated.
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<()> {
+        if self.is_offscreen() && (width == 0 || height == 0) {
+            return Err(StrataError::InvalidConfig(format!(
+                "Offscreen target must be non-zero, got {}x{}",
+                width, height
+            )));
+        }
+        self.window_size = (width, height);
+        self.swapchain_dirty = true;
+        self.recreate_swapchain()
+    }
+
+    /// Rebuild the render target if it has been marked out of date, for
+    /// example after a resize or `VK_ERROR_OUT_OF_DATE_KHR`. Does nothing
+    /// while the window has no area.
+    pub(crate) fn recreate_swapchain(&mut self) -> Result<()> {
+        let (width, height) = self.window_size;
+        if !self.swapchain_dirty || width == 0 || height == 0 {
+            return Ok(());
+        }
+
+        // Acquired images must go back before their swapchain is replaced
+        self.release_abandoned_images()?;
+        unsafe { self.device.handle.device_wait_idle()? };
+
+        match &mut self.target {
+            RenderTarget::Window(swapchain) => {
+                // The new swapchain is created before the old one is dropped
+                // so the driver can hand over its images
+                *swapchain = Swapchain::new(
+                    &self.device,
+                    &swapchain.loader,
+                    &self.context.surface_loader,
+                    self.context
+                        .surface
+                        .expect("windowed renderer has a surface"),
+                    self.config.present_mode,
+                    self.window_size,
+                    swapchain.handle,
+                )?;
+            }
+            RenderTarget::Offscreen(target) => {
+                *target = OffscreenTarget::new(
+                    &self.context.instance,
+                    &self.device,
+                    width,
+                    height,
+                )?;
+            }
+        }
+        self.swapchain_dirty = false;
+        Ok(())
+    }
+}
+
+/// Record a transition of `image` from undefined contents to a clear colour
+/// in `COLOR_ATTACHMENT_OPTIMAL` layout.
+///
+/// The clear waits for earlier transfers on the queue, since an offscreen
+/// image is shared by every frame slot and the previous frame may still be
+/// copying it out. Swapchain images are acquired with a semaphore that is
+/// waited on at the transfer stage, so this chains with it too.
+fn record_clear(
+    device: &ash::Device,
+    command_buffer: vk::CommandBuffer,
+    image: vk::Image,
+    color: [f32; 4],
+) {
+    transition_image(
+        device,
+        command_buffer,
+        image,
+        (
+            vk::ImageLayout::UNDEFINED,
+            vk::AccessFlags::empty(),
+            vk::PipelineStageFlags::TRANSFER,
+        ),
+        (
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::AccessFlags::TRANSFER_WRITE,
+            vk::PipelineStageFlags::TRANSFER,
+        ),
+    );
+
+    unsafe {
+        device.cmd_clear_color_image(
+            command_buffer,
+            image,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            &vk::ClearColorValue { float32: color },
+            &[frame::COLOR_SUBRESOURCE_RANGE],
+        );
+    }
+
+    transition_image(
+        device,
+        command_buffer,
+        image,
+        (
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::AccessFlags::TRANSFER_WRITE,
+            vk::PipelineStageFlags::TRANSFER,
+        ),
+        (
+            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            vk::AccessFlags::COLOR_ATTACHMENT_READ
+                | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        ),
+    );
+}
+
+impl Drop for Renderer {
+    fn drop(&mut self) {
+        // Nothing may be in flight when the swapchain and device go away
+        unsafe {
+            let _ = self.device.handle.device_wait_idle();
+        }
+    }
 }
 
 /// Contains core Vulkan state information known only by Renderer
 struct VulkanContext {
     debug_messenger: Option<vk::DebugUtilsMessengerEXT>,
     debug_utils_loader: Option<ext::debug_utils::Instance>,
-    surface: vk::SurfaceKHR,
+    surface: Option<vk::SurfaceKHR>,
     surface_loader: khr::surface::Instance,
-    _instance: Instance,
+    instance: Instance,
     _entry: Entry,
 }
 
 impl VulkanContext {
-    /// Create a new VulkanContext and initialize Vulkan.
+    /// Create a new VulkanContext and initialize Vulkan, with a surface for
+    /// `window` if given.
     ///
-    /// In debug builds, automatically enables Vulkan validation layers
-    /// and sets up a debug messenger for error reporting.
+    /// When `validation` is set and the layer is installed, enables the
+    /// Vulkan validation layer and sets up a debug messenger for error
+    /// reporting.
     ///
     /// # Errors
     ///
     /// Returns `StrataError::RendererInit` if Vulkan initialization fails
     pub fn new(
-        display_handle: RawDisplayHandle,
-        window_handle: RawWindowHandle,
+        window: Option<(RawDisplayHandle, RawWindowHandle)>,
         app_name: &str,
+        validation: bool,
     ) -> Result<Self> {
-        let extensions = ash_window::enumerate_required_extensions(
-            display_handle,
-        )
-        .map_err(|e| {
-            StrataError::RendererInit(format!(
-                "Failed to enumerate required Vulkan extensions: {}",
-                e
-            ))
-        })?;
-
-        let mut extension_names_vec: Vec<*const i8>;
-        let extensions_slice = if cfg!(debug_assertions) {
-            extension_names_vec = extensions.to_vec();
-            extension_names_vec.push(vk::EXT_DEBUG_UTILS_NAME.as_ptr());
-            extension_names_vec.as_slice()
-        } else {
-            extensions
+        let mut extension_names_vec: Vec<*const i8> = match window {
+            Some((display_handle, _)) => {
+                ash_window::enumerate_required_extensions(display_handle)
+                    .map_err(|e| {
+                        StrataError::RendererInit(format!(
+                            "Failed to enumerate required Vulkan extensions: {}",
+                            e
+                        ))
+                    })?
+                    .to_vec()
+            }
+            None => Vec::new(),
         };
+        if validation {
+            extension_names_vec.push(vk::EXT_DEBUG_UTILS_NAME.as_ptr());
+        }
+        let extensions_slice = extension_names_vec.as_slice();
 
         let entry = Entry::linked();
 
@@ -110,16 +1067,22 @@ impl VulkanContext {
         let layer_name_cstr: CString;
         let layer_names: [*const i8; 1];
 
-        let (layer_count, layer_names_ptr) =
-            if cfg!(debug_assertions) && has_validation {
-                println!("✓ Enabling Vulkan validation layer");
-                layer_name_cstr = CString::new("VK_LAYER_KHRONOS_validation")
-                    .expect("Layer name must not contain null bytes");
-                layer_names = [layer_name_cstr.as_ptr()];
-                (1, layer_names.as_ptr())
-            } else {
-                (0, std::ptr::null())
-            };
+        if validation && !has_validation {
+            eprintln!(
+                "Vulkan validation requested but VK_LAYER_KHRONOS_validation \
+                 is not installed"
+            );
+        }
+
+        let (layer_count, layer_names_ptr) = if validation && has_validation {
+            println!("✓ Enabling Vulkan validation layer");
+            layer_name_cstr = CString::new("VK_LAYER_KHRONOS_validation")
+                .expect("Layer name must not contain null bytes");
+            layer_names = [layer_name_cstr.as_ptr()];
+            (1, layer_names.as_ptr())
+        } else {
+            (0, std::ptr::null())
+        };
 
         let create_info = vk::InstanceCreateInfo {
             p_application_info: &app_info,
@@ -140,7 +1103,7 @@ impl VulkanContext {
                 })?
         };
 
-        let (debug_utils_loader, debug_messenger) = if cfg!(debug_assertions)
+        let (debug_utils_loader, debug_messenger) = if validation
             && has_validation
         {
             let loader = ext::debug_utils::Instance::new(&entry, &instance);
@@ -166,20 +1129,23 @@ impl VulkanContext {
 
         let surface_loader = khr::surface::Instance::new(&entry, &instance);
 
-        let surface = unsafe {
-            ash_window::create_surface(
-                &entry,
-                &instance,
-                display_handle,
-                window_handle,
-                None,
-            )
-            .map_err(|e| {
-                StrataError::RendererInit(format!(
-                    "Failed to create Vulkan surface: {}",
-                    e
-                ))
-            })?
+        let surface = match window {
+            Some((display_handle, window_handle)) => Some(unsafe {
+                ash_window::create_surface(
+                    &entry,
+                    &instance,
+                    display_handle,
+                    window_handle,
+                    None,
+                )
+                .map_err(|e| {
+                    StrataError::RendererInit(format!(
+                        "Failed to create Vulkan surface: {}",
+                        e
+                    ))
+                })?
+            }),
+            None => None,
         };
 
         Ok(Self {
@@ -187,7 +1153,7 @@ impl VulkanContext {
             debug_utils_loader,
             surface_loader,
             surface,
-            _instance: instance,
+            instance,
             _entry: entry,
         })
     }
@@ -196,14 +1162,18 @@ impl VulkanContext {
 impl Drop for VulkanContext {
     fn drop(&mut self) {
         unsafe {
-            self.surface_loader
-                .destroy_surface(self.surface, None);
+            if let Some(surface) = self.surface {
+                self.surface_loader
+                    .destroy_surface(surface, None);
+            }
 
-            if let Some(messenger) = self.debug_messenger {
-                if let Some(loader) = &self.debug_utils_loader {
-                    loader.destroy_debug_utils_messenger(messenger, None);
-                }
+            if let Some(messenger) = self.debug_messenger
+                && let Some(loader) = &self.debug_utils_loader
+            {
+                loader.destroy_debug_utils_messenger(messenger, None);
             }
+
+            self.instance.destroy_instance(None);
         }
     }
 }
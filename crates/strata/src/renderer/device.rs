@@ -0,0 +1,333 @@
+//! Physical device selection and logical device creation
+
+use std::ffi::{CStr, c_void};
+
+use ash::{Instance, khr, vk};
+
+use crate::{Result, StrataError};
+
+/// Device extensions a GPU must support to present to a surface
+const PRESENT_EXTENSIONS: [&CStr; 1] = [vk::KHR_SWAPCHAIN_NAME];
+
+/// Queue family indices used by the renderer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct QueueFamilies {
+    pub graphics: u32,
+    pub present: u32,
+}
+
+impl QueueFamilies {
+    /// Distinct family indices, for queue creation and sharing mode
+    pub fn unique(&self) -> Vec<u32> {
+        if self.graphics == self.present {
+            vec![self.graphics]
+        } else {
+            vec![self.graphics, self.present]
+        }
+    }
+}
+
+/// Rank a device type. Higher is better: discrete GPUs beat integrated
+/// ones, which beat virtual GPUs and software rasterisers such as lavapipe.
+pub(crate) fn device_type_score(device_type: vk::PhysicalDeviceType) -> u32 {
+    match device_type {
+        vk::PhysicalDeviceType::DISCRETE_GPU => 4,
+        vk::PhysicalDeviceType::INTEGRATED_GPU => 3,
+        vk::PhysicalDeviceType::VIRTUAL_GPU => 2,
+        vk::PhysicalDeviceType::CPU => 1,
+        _ => 0,
+    }
+}
+
+/// Find graphics and present queue families, preferring a single family
+/// that supports both.
+///
+/// `supports_present` is queried with each family index.
+pub(crate) fn select_queue_families(
+    families: &[vk::QueueFamilyProperties],
+    mut supports_present: impl FnMut(u32) -> bool,
+) -> Option<QueueFamilies> {
+    let mut graphics = None;
+    let mut present = None;
+
+    for (index, family) in families.iter().enumerate() {
+        let index = index as u32;
+        let is_graphics = family.queue_count > 0
+            && family
+                .queue_flags
+                .contains(vk::QueueFlags::GRAPHICS);
+        let is_present = family.queue_count > 0 && supports_present(index);
+
+        if is_graphics && is_present {
+            return Some(QueueFamilies { graphics: index, present: index });
+        }
+        if is_graphics && graphics.is_none() {
+            graphics = Some(index);
+        }
+        if is_present && present.is_none() {
+            present = Some(index);
+        }
+    }
+
+    Some(QueueFamilies { graphics: graphics?, present: present? })
+}
+
+/// A physical device that can drive the renderer
+struct Candidate {
+    physical_device: vk::PhysicalDevice,
+    name: String,
+    families: QueueFamilies,
+    score: u32,
+}
+
+/// Logical device, its queues and the physical device it was created from
+pub(crate) struct Device {
+    pub physical_device: vk::PhysicalDevice,
+    pub families: QueueFamilies,
+    pub graphics_queue: vk::Queue,
+    pub present_queue: vk::Queue,
+    pub name: String,
+    pub handle: ash::Device,
+}
+
+impl Device {
+    /// Pick the best GPU and create a logical device with graphics and
+    /// present queues.
+    ///
+    /// With a surface, only GPUs able to present to it are considered.
+    /// Without one (offscreen rendering) any GPU with a graphics queue will
+    /// do, and the present queue is the graphics queue.
+    ///
+    /// # Errors
+    ///
+    /// Returns `StrataError::RendererInit` if no suitable GPU is found or the
+    /// logical device cannot be created.
+    pub fn new(
+        instance: &Instance,
+        surface: Option<(&khr::surface::Instance, vk::SurfaceKHR)>,
+    ) -> Result<Self> {
+        let extensions: &[&CStr] = match surface {
+            Some(_) => &PRESENT_EXTENSIONS,
+            None => &[],
+        };
+
+        let candidate =
+            pick_physical_device(instance, extensions, |pdev, index| {
+                let Some((loader, surface)) = surface else {
+                    return true;
+                };
+                unsafe {
+                    loader
+                        .get_physical_device_surface_support(
+                            pdev, index, surface,
+                        )
+                        .unwrap_or(false)
+                }
+            })?
+            .ok_or_else(|| {
+                StrataError::RendererInit(
+                    "No Vulkan device supports the required graphics and \
+                     presentation features"
+                        .to_string(),
+                )
+            })?;
+
+        Self::from_candidate(instance, candidate, extensions)
+    }
+
+    fn from_candidate(
+        instance: &Instance,
+        candidate: Candidate,
+        extensions: &[&CStr],
+    ) -> Result<Self> {
+        let priorities = [1.0f32];
+        let queue_infos: Vec<vk::DeviceQueueCreateInfo> = candidate
+            .families
+            .unique()
+            .into_iter()
+            .map(|family| vk::DeviceQueueCreateInfo {
+                queue_family_index: family,
+                queue_count: 1,
+                p_queue_priorities: priorities.as_ptr(),
+                ..Default::default()
+            })
+            .collect();
+
+        let extension_names: Vec<*const i8> = extensions
+            .iter()
+            .map(|ext| ext.as_ptr())
+            .collect();
+
+        let features = vk::PhysicalDeviceFeatures::default();
+        // Dynamic rendering lets pipelines draw into the frame image
+        // without render pass and framebuffer objects
+        let vulkan13_features = vk::PhysicalDeviceVulkan13Features {
+            dynamic_rendering: vk::TRUE,
+            ..Default::default()
+        };
+        let create_info = vk::DeviceCreateInfo {
+            p_next: &vulkan13_features as *const _ as *const c_void,
+            queue_create_info_count: queue_infos.len() as u32,
+            p_queue_create_infos: queue_infos.as_ptr(),
+            enabled_extension_count: extension_names.len() as u32,
+            pp_enabled_extension_names: extension_names.as_ptr(),
+            p_enabled_features: &features,
+            ..Default::default()
+        };
+
+        let handle = unsafe {
+            instance
+                .create_device(candidate.physical_device, &create_info, None)
+                .map_err(|e| {
+                    StrataError::RendererInit(format!(
+                        "Failed to create logical device: {}",
+                        e
+                    ))
+                })?
+        };
+
+        let graphics_queue =
+            unsafe { handle.get_device_queue(candidate.families.graphics, 0) };
+        let present_queue =
+            unsafe { handle.get_device_queue(candidate.families.present, 0) };
+
+        println!("✓ Using GPU: {}", candidate.name);
+
+        Ok(Self {
+            physical_device: candidate.physical_device,
+            families: candidate.families,
+            graphics_queue,
+            present_queue,
+            name: candidate.name,
+            handle,
+        })
+    }
+}
+
+impl Drop for Device {
+    fn drop(&mut self) {
+        unsafe {
+            self.handle.destroy_device(None);
+        }
+    }
+}
+
+/// Enumerate physical devices and return the highest scoring one that
+/// supports Vulkan 1.3 and has the required extensions and queue families
+fn pick_physical_device(
+    instance: &Instance,
+    extensions: &[&CStr],
+    mut supports_present: impl FnMut(vk::PhysicalDevice, u32) -> bool,
+) -> Result<Option<Candidate>> {
+    let devices = unsafe { instance.enumerate_physical_devices()? };
+
+    let mut best: Option<Candidate> = None;
+    for physical_device in devices {
+        let properties =
+            unsafe { instance.get_physical_device_properties(physical_device) };
+        let name = properties
+            .device_name_as_c_str()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|_| "Unknown device".to_string());
+
+        if properties.api_version < vk::API_VERSION_1_3
+            || !supports_extensions(instance, physical_device, extensions)?
+        {
+            continue;
+        }
+
+        let queue_families = unsafe {
+            instance
+                .get_physical_device_queue_family_properties(physical_device)
+        };
+        let Some(families) = select_queue_families(&queue_families, |index| {
+            supports_present(physical_device, index)
+        }) else {
+            continue;
+        };
+
+        let score = device_type_score(properties.device_type);
+        if best
+            .as_ref()
+            .is_none_or(|b| score > b.score)
+        {
+            best = Some(Candidate { physical_device, name, families, score });
+        }
+    }
+
+    Ok(best)
+}
+
+fn supports_extensions(
+    instance: &Instance,
+    physical_device: vk::PhysicalDevice,
+    required: &[&CStr],
+) -> Result<bool> {
+    let available = unsafe {
+        instance.enumerate_device_extension_properties(physical_device)?
+    };
+
+    Ok(required.iter().all(|required| {
+        available
+            .iter()
+            .any(|ext| ext.extension_name_as_c_str() == Ok(*required))
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn family(flags: vk::QueueFlags) -> vk::QueueFamilyProperties {
+        vk::QueueFamilyProperties {
+            queue_flags: flags,
+            queue_count: 1,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_device_type_ranking() {
+        let discrete = device_type_score(vk::PhysicalDeviceType::DISCRETE_GPU);
+        let integrated =
+            device_type_score(vk::PhysicalDeviceType::INTEGRATED_GPU);
+        let cpu = device_type_score(vk::PhysicalDeviceType::CPU);
+
+        assert!(discrete > integrated);
+        assert!(integrated > cpu);
+        assert!(cpu > device_type_score(vk::PhysicalDeviceType::OTHER));
+    }
+
+    #[test]
+    fn test_queue_families_prefer_shared_family() {
+        let families = [
+            family(vk::QueueFlags::GRAPHICS),
+            family(vk::QueueFlags::COMPUTE),
+            family(vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE),
+        ];
+
+        let selected = select_queue_families(&families, |i| i != 0);
+
+        assert_eq!(selected, Some(QueueFamilies { graphics: 2, present: 2 }));
+    }
+
+    #[test]
+    fn test_queue_families_split_graphics_and_present() {
+        let families = [
+            family(vk::QueueFlags::GRAPHICS),
+            family(vk::QueueFlags::TRANSFER),
+        ];
+
+        let selected = select_queue_families(&families, |i| i == 1)
+            .expect("Should find both families");
+
+        assert_eq!(selected, QueueFamilies { graphics: 0, present: 1 });
+        assert_eq!(selected.unique(), vec![0, 1]);
+    }
+
+    #[test]
+    fn test_queue_families_none_without_present_support() {
+        let families = [family(vk::QueueFlags::GRAPHICS)];
+        assert_eq!(select_queue_families(&families, |_| false), None);
+    }
+}
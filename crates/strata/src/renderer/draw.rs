@@ -0,0 +1,231 @@
+//! Recording sorted draw lists with minimal rebinding
+
+use ash::vk;
+use substrate::{DrawList, Handle, SlotMap};
+
+use super::mesh::{Mesh, MeshHandle};
+use super::pipeline::{Material, MaterialHandle, Pipeline, PipelineHandle};
+
+/// Counts from recording a frame's draw list
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DrawStats {
+    /// Draw calls issued, one per command
+    pub draws: u32,
+    /// Runs of commands sharing a mesh and material
+    pub batches: u32,
+    pub pipeline_binds: u32,
+    pub descriptor_binds: u32,
+    pub mesh_binds: u32,
+}
+
+/// Tracks what is bound to the command buffer so redundant binds can be
+/// skipped. Each `bind_*` method returns whether a bind is needed.
+#[derive(Debug, Default)]
+pub(crate) struct BindState {
+    pipeline: Option<PipelineHandle>,
+    descriptor_set: Option<vk::DescriptorSet>,
+    mesh: Option<MeshHandle>,
+}
+
+impl BindState {
+    pub fn bind_pipeline(&mut self, pipeline: PipelineHandle) -> bool {
+        if self.pipeline == Some(pipeline) {
+            return false;
+        }
+        self.pipeline = Some(pipeline);
+        // Sets bound for a different layout can't be relied on
+        self.descriptor_set = None;
+        true
+    }
+
+    pub fn bind_descriptor_set(&mut self, set: vk::DescriptorSet) -> bool {
+        if self.descriptor_set == Some(set) {
+            return false;
+        }
+        self.descriptor_set = Some(set);
+        true
+    }
+
+    pub fn bind_mesh(&mut self, mesh: MeshHandle) -> bool {
+        if self.mesh == Some(mesh) {
+            return false;
+        }
+        self.mesh = Some(mesh);
+        true
+    }
+}
+
+/// The value behind `handle`, or `None` once it has been destroyed.
+/// Checked with `contains` first, since `get` debug-asserts on stale handles.
+fn live<T>(map: &SlotMap<T>, handle: Handle<T>) -> Option<&T> {
+    map.contains(handle)
+        .then(|| &map[handle])
+}
+
+/// Record `list`, which should already be sorted, into `command_buffer`
+/// as a dynamic rendering pass that loads and stores `image_view`.
+///
+/// The image must be in `COLOR_ATTACHMENT_OPTIMAL` layout.
+pub(crate) fn record_draws(
+    device: &ash::Device,
+    command_buffer: vk::CommandBuffer,
+    (image_view, extent): (vk::ImageView, vk::Extent2D),
+    list: &DrawList<MeshHandle, MaterialHandle>,
+    pipelines: &SlotMap<Pipeline>,
+    materials: &SlotMap<Material>,
+    meshes: &SlotMap<Mesh>,
+) -> DrawStats {
+    let mut stats = DrawStats::default();
+    if list.is_empty() {
+        return stats;
+    }
+
+    let color_attachment = vk::RenderingAttachmentInfo {
+        image_view,
+        image_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        load_op: vk::AttachmentLoadOp::LOAD,
+        store_op: vk::AttachmentStoreOp::STORE,
+        ..Default::default()
+    };
+    let area = vk::Rect2D { offset: vk::Offset2D::default(), extent };
+    let rendering_info = vk::RenderingInfo {
+        render_area: area,
+        layer_count: 1,
+        color_attachment_count: 1,
+        p_color_attachments: &color_attachment,
+        ..Default::default()
+    };
+    let viewport = vk::Viewport {
+        x: 0.0,
+        y: 0.0,
+        width: extent.width as f32,
+        height: extent.height as f32,
+        min_depth: 0.0,
+        max_depth: 1.0,
+    };
+
+    let mut state = BindState::default();
+    unsafe {
+        device.cmd_begin_rendering(command_buffer, &rendering_info);
+        device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+        device.cmd_set_scissor(command_buffer, 0, &[area]);
+
+        for batch in list.batches() {
+            // Resources destroyed after their draws were queued
+            let Some(material) = live(materials, batch.material.0) else {
+                continue;
+            };
+            let (Some(pipeline), Some(mesh)) = (
+                live(pipelines, material.pipeline.0),
+                live(meshes, batch.mesh.0),
+            ) else {
+                continue;
+            };
+            stats.batches += 1;
+
+            if state.bind_pipeline(material.pipeline) {
+                stats.pipeline_binds += 1;
+                device.cmd_bind_pipeline(
+                    command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    pipeline.handle,
+                );
+            }
+            if let Some(set) = material.descriptor_set
+                && state.bind_descriptor_set(set)
+            {
+                stats.descriptor_binds += 1;
+                device.cmd_bind_descriptor_sets(
+                    command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    pipeline.layout,
+                    0,
+                    &[set],
+                    &[],
+                );
+            }
+            if state.bind_mesh(batch.mesh) {
+                stats.mesh_binds += 1;
+                device.cmd_bind_vertex_buffers(
+                    command_buffer,
+                    0,
+                    &[mesh.vertex_buffer],
+                    &[0],
+                );
+                device.cmd_bind_index_buffer(
+                    command_buffer,
+                    mesh.index_buffer,
+                    0,
+                    vk::IndexType::UINT32,
+                );
+            }
+
+            for command in list.range(batch.range) {
+                stats.draws += 1;
+                let transform = std::slice::from_raw_parts(
+                    command.transform.as_ptr() as *const u8,
+                    size_of_val(&command.transform),
+                );
+                device.cmd_push_constants(
+                    command_buffer,
+                    pipeline.layout,
+                    vk::ShaderStageFlags::VERTEX,
+                    0,
+                    transform,
+                );
+                device.cmd_draw_indexed(
+                    command_buffer,
+                    mesh.index_count,
+                    1,
+                    0,
+                    0,
+                    0,
+                );
+            }
+        }
+
+        device.cmd_end_rendering(command_buffer);
+    }
+
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ash::vk::Handle as _;
+
+    fn pipeline(index: u32) -> PipelineHandle {
+        PipelineHandle(Handle::from_raw_parts(index, 0))
+    }
+
+    fn mesh_handle(index: u32) -> MeshHandle {
+        MeshHandle(Handle::from_raw_parts(index, 0))
+    }
+
+    #[test]
+    fn test_bind_state_skips_redundant_binds() {
+        let mut state = BindState::default();
+        let pipeline = pipeline(0);
+        let mesh = mesh_handle(3);
+
+        assert!(state.bind_pipeline(pipeline));
+        assert!(!state.bind_pipeline(pipeline));
+        assert!(state.bind_mesh(mesh));
+        assert!(!state.bind_mesh(mesh));
+        assert!(state.bind_mesh(mesh_handle(4)));
+    }
+
+    #[test]
+    fn test_bind_state_rebinds_descriptors_after_pipeline_change() {
+        let mut state = BindState::default();
+        let set = vk::DescriptorSet::from_raw(1);
+
+        state.bind_pipeline(pipeline(0));
+        assert!(state.bind_descriptor_set(set));
+        assert!(!state.bind_descriptor_set(set));
+
+        state.bind_pipeline(pipeline(1));
+        assert!(state.bind_descriptor_set(set));
+    }
+}
@@ -0,0 +1,292 @@
+//! Per-frame command recording and synchronisation
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use ash::vk;
+use substrate::arena::Arena;
+
+use super::mesh::Mesh;
+use super::pipeline::Pipeline;
+use crate::Result;
+
+/// A frame being recorded, returned by [`crate::Renderer::begin_frame`].
+///
+/// The command buffer is already in the recording state and the target
+/// image has been cleared and transitioned to
+/// `COLOR_ATTACHMENT_OPTIMAL`. Hand the frame back to
+/// [`crate::Renderer::end_frame`] to submit and present it.
+///
+/// Dropping a frame without ending it abandons it: nothing is submitted,
+/// and the next [`crate::Renderer::begin_frame`] reclaims its slot.
+pub struct Frame {
+    pub(crate) slot: usize,
+    pub(crate) image_index: u32,
+    pub(crate) image: vk::Image,
+    pub(crate) image_view: vk::ImageView,
+    pub(crate) extent: vk::Extent2D,
+    pub(crate) command_buffer: vk::CommandBuffer,
+    /// Always `Some` until the frame is dropped
+    pub(crate) arena: Option<Arena>,
+    /// The slot's arena cell, which gets the arena back on drop
+    pub(crate) home: Rc<RefCell<Option<Arena>>>,
+}
+
+impl Frame {
+    /// Command buffer to record this frame's work into
+    pub fn command_buffer(&self) -> vk::CommandBuffer {
+        self.command_buffer
+    }
+
+    /// Index of the swapchain image being rendered; always 0 offscreen
+    pub fn image_index(&self) -> u32 {
+        self.image_index
+    }
+
+    /// The image being rendered
+    pub fn image(&self) -> vk::Image {
+        self.image
+    }
+
+    /// A colour view of the image being rendered
+    pub fn image_view(&self) -> vk::ImageView {
+        self.image_view
+    }
+
+    /// Size of the image being rendered as (width, height) in pixels
+    pub fn extent(&self) -> (u32, u32) {
+        (self.extent.width, self.extent.height)
+    }
+
+    /// Scratch memory that lives until this frame slot is reused, after the
+    /// GPU has finished with it. Allocations borrow the frame, so they can't
+    /// outlive it.
+    pub fn arena(&self) -> &Arena {
+        self.arena
+            .as_ref()
+            .expect("arena held until drop")
+    }
+}
+
+impl Drop for Frame {
+    fn drop(&mut self) {
+        *self.home.borrow_mut() = self.arena.take();
+    }
+}
+
+/// Resources owned by one of the frames in flight
+pub(crate) struct FrameSlot {
+    pub command_pool: vk::CommandPool,
+    pub command_buffer: vk::CommandBuffer,
+    pub image_available: vk::Semaphore,
+    pub in_flight: vk::Fence,
+    /// Taken by the [`Frame`] while it is being recorded, and put back
+    /// when it is dropped
+    pub arena: Rc<RefCell<Option<Arena>>>,
+    /// Swapchain image acquired for this slot but not yet submitted
+    pub acquired: Option<u32>,
+    /// Destroyed resources that earlier frames may still be using, freed
+    /// once this slot's fence shows the GPU is done with them
+    pub retired: Retired,
+}
+
+/// GPU resources waiting for in-flight frames to finish before they are
+/// dropped
+#[derive(Default)]
+pub(crate) struct Retired {
+    pub meshes: Vec<Mesh>,
+    pub pipelines: Vec<Pipeline>,
+}
+
+impl Retired {
+    pub fn clear(&mut self) {
+        self.meshes.clear();
+        self.pipelines.clear();
+    }
+}
+
+/// Ring of frame slots, one per frame in flight
+pub(crate) struct FrameSlots {
+    pub slots: Vec<FrameSlot>,
+    pub current: usize,
+    /// Slot whose fence signals after everything submitted so far
+    pub last_submitted: Option<usize>,
+    device: ash::Device,
+}
+
+impl FrameSlots {
+    /// Create `count` frame slots whose command buffers are allocated from
+    /// `queue_family`
+    pub fn new(
+        device: &ash::Device,
+        queue_family: u32,
+        count: usize,
+        arena_capacity: usize,
+    ) -> Result<Self> {
+        let mut frames = Self {
+            slots: Vec::with_capacity(count),
+            current: 0,
+            last_submitted: None,
+            device: device.clone(),
+        };
+
+        // Pushed one at a time so Drop cleans up if a later slot fails
+        for _ in 0..count {
+            let slot = unsafe { frames.create_slot(queue_family)? };
+            frames.slots.push(FrameSlot {
+                arena: Rc::new(RefCell::new(Some(Arena::growable(
+                    arena_capacity,
+                )))),
+                ..slot
+            });
+        }
+
+        Ok(frames)
+    }
+
+    unsafe fn create_slot(&self, queue_family: u32) -> Result<FrameSlot> {
+        unsafe {
+            let command_pool = self.device.create_command_pool(
+                &vk::CommandPoolCreateInfo {
+                    flags: vk::CommandPoolCreateFlags::TRANSIENT,
+                    queue_family_index: queue_family,
+                    ..Default::default()
+                },
+                None,
+            )?;
+
+            let command_buffer = self.device.allocate_command_buffers(
+                &vk::CommandBufferAllocateInfo {
+                    command_pool,
+                    level: vk::CommandBufferLevel::PRIMARY,
+                    command_buffer_count: 1,
+                    ..Default::default()
+                },
+            )?[0];
+
+            let image_available = self
+                .device
+                .create_semaphore(&vk::SemaphoreCreateInfo::default(), None)?;
+
+            // Signalled so the first wait on each slot returns immediately
+            let in_flight = self.device.create_fence(
+                &vk::FenceCreateInfo {
+                    flags: vk::FenceCreateFlags::SIGNALED,
+                    ..Default::default()
+                },
+                None,
+            )?;
+
+            Ok(FrameSlot {
+                command_pool,
+                command_buffer,
+                image_available,
+                in_flight,
+                arena: Rc::default(),
+                acquired: None,
+                retired: Retired::default(),
+            })
+        }
+    }
+
+    /// The slot the next frame will use
+    pub fn current(&mut self) -> &mut FrameSlot {
+        &mut self.slots[self.current]
+    }
+
+    /// Swap `slot`'s fence for a new, signalled one and return the old
+    /// handle, which has been destroyed
+    pub fn replace_fence(&mut self, slot: usize) -> Result<vk::Fence> {
+        let fence = unsafe {
+            self.device.create_fence(
+                &vk::FenceCreateInfo {
+                    flags: vk::FenceCreateFlags::SIGNALED,
+                    ..Default::default()
+                },
+                None,
+            )?
+        };
+        let old = std::mem::replace(&mut self.slots[slot].in_flight, fence);
+        unsafe { self.device.destroy_fence(old, None) };
+        Ok(old)
+    }
+
+    /// Where to put resources that must outlive every frame submitted so
+    /// far, or `None` if nothing has been submitted and they can be dropped
+    /// right away
+    pub fn retired(&mut self) -> Option<&mut Retired> {
+        self.last_submitted
+            .map(|slot| &mut self.slots[slot].retired)
+    }
+
+    /// Move on to the next slot in the ring
+    pub fn advance(&mut self) {
+        self.current = (self.current + 1) % self.slots.len();
+    }
+}
+
+impl Drop for FrameSlots {
+    fn drop(&mut self) {
+        unsafe {
+            for slot in &self.slots {
+                self.device
+                    .destroy_fence(slot.in_flight, None);
+                self.device
+                    .destroy_semaphore(slot.image_available, None);
+                self.device
+                    .destroy_command_pool(slot.command_pool, None);
+            }
+        }
+    }
+}
+
+/// Record a layout transition for a single-mip colour image
+pub(crate) fn transition_image(
+    device: &ash::Device,
+    command_buffer: vk::CommandBuffer,
+    image: vk::Image,
+    (old_layout, src_access, src_stage): (
+        vk::ImageLayout,
+        vk::AccessFlags,
+        vk::PipelineStageFlags,
+    ),
+    (new_layout, dst_access, dst_stage): (
+        vk::ImageLayout,
+        vk::AccessFlags,
+        vk::PipelineStageFlags,
+    ),
+) {
+    let barrier = vk::ImageMemoryBarrier {
+        src_access_mask: src_access,
+        dst_access_mask: dst_access,
+        old_layout,
+        new_layout,
+        src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+        dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+        image,
+        subresource_range: COLOR_SUBRESOURCE_RANGE,
+        ..Default::default()
+    };
+
+    unsafe {
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            src_stage,
+            dst_stage,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[barrier],
+        );
+    }
+}
+
+/// The single mip and layer of a colour image
+pub(crate) const COLOR_SUBRESOURCE_RANGE: vk::ImageSubresourceRange =
+    vk::ImageSubresourceRange {
+        aspect_mask: vk::ImageAspectFlags::COLOR,
+        base_mip_level: 0,
+        level_count: 1,
+        base_array_layer: 0,
+        layer_count: 1,
+    };
@@ -0,0 +1,146 @@
+//! GPU vertex and index buffers for meshes
+
+use ash::{Instance, vk};
+use substrate::Handle;
+
+use super::device::Device;
+use super::offscreen::allocate;
+use crate::{Result, StrataError};
+
+/// Identifies a mesh created by [`crate::Renderer::create_mesh`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MeshHandle(pub(crate) Handle<Mesh>);
+
+/// A mesh's vertex and index buffers, in host-visible memory
+pub(crate) struct Mesh {
+    pub vertex_buffer: vk::Buffer,
+    pub index_buffer: vk::Buffer,
+    pub index_count: u32,
+    vertex_memory: vk::DeviceMemory,
+    index_memory: vk::DeviceMemory,
+    device: ash::Device,
+}
+
+impl Mesh {
+    /// Upload `vertices` and 32-bit `indices` into new buffers.
+    ///
+    /// `V` should be `#[repr(C)]` and match the vertex layout of the
+    /// pipelines the mesh is drawn with.
+    ///
+    /// # Errors
+    ///
+    /// Returns `StrataError::InvalidConfig` if either slice is empty, or a
+    /// Vulkan error if allocation fails.
+    pub fn new<V: Copy>(
+        instance: &Instance,
+        device: &Device,
+        vertices: &[V],
+        indices: &[u32],
+    ) -> Result<Self> {
+        if size_of_val(vertices) == 0 || indices.is_empty() {
+            return Err(StrataError::InvalidConfig(
+                "Mesh needs at least one vertex and one index".to_string(),
+            ));
+        }
+
+        let memory_properties = unsafe {
+            instance
+                .get_physical_device_memory_properties(device.physical_device)
+        };
+
+        let mut mesh = Self {
+            vertex_buffer: vk::Buffer::null(),
+            index_buffer: vk::Buffer::null(),
+            index_count: indices.len() as u32,
+            vertex_memory: vk::DeviceMemory::null(),
+            index_memory: vk::DeviceMemory::null(),
+            device: device.handle.clone(),
+        };
+
+        // Each handle is stored as soon as it exists so Drop cleans up if a
+        // later step fails
+        unsafe {
+            upload(
+                &mesh.device,
+                &memory_properties,
+                vertices,
+                vk::BufferUsageFlags::VERTEX_BUFFER,
+                &mut mesh.vertex_buffer,
+                &mut mesh.vertex_memory,
+            )?;
+            upload(
+                &mesh.device,
+                &memory_properties,
+                indices,
+                vk::BufferUsageFlags::INDEX_BUFFER,
+                &mut mesh.index_buffer,
+                &mut mesh.index_memory,
+            )?;
+        }
+
+        Ok(mesh)
+    }
+}
+
+impl Drop for Mesh {
+    fn drop(&mut self) {
+        // Destroying or freeing null handles is a no-op
+        unsafe {
+            self.device
+                .destroy_buffer(self.index_buffer, None);
+            self.device
+                .free_memory(self.index_memory, None);
+            self.device
+                .destroy_buffer(self.vertex_buffer, None);
+            self.device
+                .free_memory(self.vertex_memory, None);
+        }
+    }
+}
+
+/// Create a buffer for `data`, writing the handles into `buffer` and
+/// `memory` as they are created, and copy `data` into it
+unsafe fn upload<T: Copy>(
+    device: &ash::Device,
+    memory_properties: &vk::PhysicalDeviceMemoryProperties,
+    data: &[T],
+    usage: vk::BufferUsageFlags,
+    buffer: &mut vk::Buffer,
+    memory: &mut vk::DeviceMemory,
+) -> Result<()> {
+    let size = size_of_val(data);
+    unsafe {
+        *buffer = device.create_buffer(
+            &vk::BufferCreateInfo {
+                size: size as vk::DeviceSize,
+                usage,
+                sharing_mode: vk::SharingMode::EXCLUSIVE,
+                ..Default::default()
+            },
+            None,
+        )?;
+        let requirements = device.get_buffer_memory_requirements(*buffer);
+        *memory = allocate(
+            device,
+            memory_properties,
+            requirements,
+            vk::MemoryPropertyFlags::HOST_VISIBLE
+                | vk::MemoryPropertyFlags::HOST_COHERENT,
+        )?;
+        device.bind_buffer_memory(*buffer, *memory, 0)?;
+
+        let ptr = device.map_memory(
+            *memory,
+            0,
+            size as vk::DeviceSize,
+            vk::MemoryMapFlags::empty(),
+        )?;
+        std::ptr::copy_nonoverlapping(
+            data.as_ptr() as *const u8,
+            ptr as *mut u8,
+            size,
+        );
+        device.unmap_memory(*memory);
+    }
+    Ok(())
+}
@@ -0,0 +1,348 @@
+//! Offscreen colour target with CPU readback
+
+use ash::{Instance, vk};
+
+use super::device::Device;
+use super::frame::COLOR_SUBRESOURCE_RANGE;
+use crate::{Result, StrataError};
+
+/// Format of offscreen targets. sRGB so readback matches what the same
+/// frame looks like on screen through an sRGB swapchain.
+pub(crate) const OFFSCREEN_FORMAT: vk::Format = vk::Format::R8G8B8A8_SRGB;
+
+/// Find a memory type allowed by `type_bits` that has all of `flags`
+pub(crate) fn find_memory_type(
+    properties: &vk::PhysicalDeviceMemoryProperties,
+    type_bits: u32,
+    flags: vk::MemoryPropertyFlags,
+) -> Option<u32> {
+    (0..properties.memory_type_count).find(|&i| {
+        type_bits & (1 << i) != 0
+            && properties.memory_types[i as usize]
+                .property_flags
+                .contains(flags)
+    })
+}
+
+/// A device-local colour image that frames render into, plus a host-visible
+/// buffer each finished frame is copied into
+pub(crate) struct OffscreenTarget {
+    pub image: vk::Image,
+    pub image_view: vk::ImageView,
+    pub extent: vk::Extent2D,
+    pub readback: vk::Buffer,
+    image_memory: vk::DeviceMemory,
+    readback_memory: vk::DeviceMemory,
+    device: ash::Device,
+}
+
+impl OffscreenTarget {
+    /// Create a `width` x `height` target
+    ///
+    /// # Errors
+    ///
+    /// Returns `StrataError::RendererInit` if no suitable memory type exists
+    /// or a Vulkan error if allocation fails.
+    pub fn new(
+        instance: &Instance,
+        device: &Device,
+        width: u32,
+        height: u32,
+    ) -> Result<Self> {
+        let memory_properties = unsafe {
+            instance
+                .get_physical_device_memory_properties(device.physical_device)
+        };
+        let extent = vk::Extent2D { width, height };
+
+        let mut target = Self {
+            image: vk::Image::null(),
+            image_view: vk::ImageView::null(),
+            extent,
+            readback: vk::Buffer::null(),
+            image_memory: vk::DeviceMemory::null(),
+            readback_memory: vk::DeviceMemory::null(),
+            device: device.handle.clone(),
+        };
+
+        // Each handle is stored as soon as it exists so Drop cleans up if a
+        // later step fails
+        let device = &target.device;
+        unsafe {
+            target.image = device.create_image(
+                &vk::ImageCreateInfo {
+                    image_type: vk::ImageType::TYPE_2D,
+                    format: OFFSCREEN_FORMAT,
+                    extent: vk::Extent3D { width, height, depth: 1 },
+                    mip_levels: 1,
+                    array_layers: 1,
+                    samples: vk::SampleCountFlags::TYPE_1,
+                    tiling: vk::ImageTiling::OPTIMAL,
+                    usage: vk::ImageUsageFlags::COLOR_ATTACHMENT
+                        | vk::ImageUsageFlags::TRANSFER_SRC
+                        | vk::ImageUsageFlags::TRANSFER_DST,
+                    sharing_mode: vk::SharingMode::EXCLUSIVE,
+                    initial_layout: vk::ImageLayout::UNDEFINED,
+                    ..Default::default()
+                },
+                None,
+            )?;
+            let requirements =
+                device.get_image_memory_requirements(target.image);
+            target.image_memory = allocate(
+                device,
+                &memory_properties,
+                requirements,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            )?;
+            device.bind_image_memory(target.image, target.image_memory, 0)?;
+
+            target.image_view = device.create_image_view(
+                &vk::ImageViewCreateInfo {
+                    image: target.image,
+                    view_type: vk::ImageViewType::TYPE_2D,
+                    format: OFFSCREEN_FORMAT,
+                    subresource_range: COLOR_SUBRESOURCE_RANGE,
+                    ..Default::default()
+                },
+                None,
+            )?;
+
+            target.readback = device.create_buffer(
+                &vk::BufferCreateInfo {
+                    size: target.readback_size(),
+                    usage: vk::BufferUsageFlags::TRANSFER_DST,
+                    sharing_mode: vk::SharingMode::EXCLUSIVE,
+                    ..Default::default()
+                },
+                None,
+            )?;
+            let requirements =
+                device.get_buffer_memory_requirements(target.readback);
+            target.readback_memory = allocate(
+                device,
+                &memory_properties,
+                requirements,
+                vk::MemoryPropertyFlags::HOST_VISIBLE
+                    | vk::MemoryPropertyFlags::HOST_COHERENT,
+            )?;
+            device.bind_buffer_memory(
+                target.readback,
+                target.readback_memory,
+                0,
+            )?;
+        }
+
+        Ok(target)
+    }
+
+    /// Bytes in a tightly packed RGBA8 copy of the image
+    pub fn readback_size(&self) -> vk::DeviceSize {
+        self.extent.width as vk::DeviceSize
+            * self.extent.height as vk::DeviceSize
+            * 4
+    }
+
+    /// Record copying the image, in `TRANSFER_SRC_OPTIMAL` layout, into the
+    /// readback buffer and making it visible to the host.
+    ///
+    /// The copy waits for the previous frame's copy into the same buffer.
+    pub fn record_readback(&self, command_buffer: vk::CommandBuffer) {
+        let region = vk::BufferImageCopy {
+            buffer_offset: 0,
+            buffer_row_length: 0,
+            buffer_image_height: 0,
+            image_subresource: vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+            image_offset: vk::Offset3D::default(),
+            image_extent: vk::Extent3D {
+                width: self.extent.width,
+                height: self.extent.height,
+                depth: 1,
+            },
+        };
+        let previous_copy = vk::BufferMemoryBarrier {
+            src_access_mask: vk::AccessFlags::TRANSFER_WRITE,
+            dst_access_mask: vk::AccessFlags::TRANSFER_WRITE,
+            src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+            dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+            buffer: self.readback,
+            offset: 0,
+            size: vk::WHOLE_SIZE,
+            ..Default::default()
+        };
+        let barrier = vk::BufferMemoryBarrier {
+            src_access_mask: vk::AccessFlags::TRANSFER_WRITE,
+            dst_access_mask: vk::AccessFlags::HOST_READ,
+            src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+            dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+            buffer: self.readback,
+            offset: 0,
+            size: vk::WHOLE_SIZE,
+            ..Default::default()
+        };
+
+        unsafe {
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[previous_copy],
+                &[],
+            );
+            self.device.cmd_copy_image_to_buffer(
+                command_buffer,
+                self.image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                self.readback,
+                &[region],
+            );
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::HOST,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[barrier],
+                &[],
+            );
+        }
+    }
+
+    /// Copy the readback buffer to CPU memory. The caller must make sure the
+    /// GPU has finished writing it.
+    pub fn read(&self) -> Result<Vec<u8>> {
+        let size = self.readback_size();
+        unsafe {
+            let ptr = self.device.map_memory(
+                self.readback_memory,
+                0,
+                size,
+                vk::MemoryMapFlags::empty(),
+            )?;
+            let pixels =
+                std::slice::from_raw_parts(ptr as *const u8, size as usize)
+                    .to_vec();
+            self.device
+                .unmap_memory(self.readback_memory);
+            Ok(pixels)
+        }
+    }
+}
+
+impl Drop for OffscreenTarget {
+    fn drop(&mut self) {
+        // Destroying or freeing null handles is a no-op
+        unsafe {
+            self.device
+                .destroy_buffer(self.readback, None);
+            self.device
+                .free_memory(self.readback_memory, None);
+            self.device
+                .destroy_image_view(self.image_view, None);
+            self.device
+                .destroy_image(self.image, None);
+            self.device
+                .free_memory(self.image_memory, None);
+        }
+    }
+}
+
+/// Allocate memory for `requirements` from a type with all of `flags`
+pub(crate) unsafe fn allocate(
+    device: &ash::Device,
+    properties: &vk::PhysicalDeviceMemoryProperties,
+    requirements: vk::MemoryRequirements,
+    flags: vk::MemoryPropertyFlags,
+) -> Result<vk::DeviceMemory> {
+    let memory_type_index =
+        find_memory_type(properties, requirements.memory_type_bits, flags)
+            .ok_or_else(|| {
+                StrataError::RendererInit(format!(
+                    "No memory type with {:?} for allocation",
+                    flags
+                ))
+            })?;
+
+    let memory = unsafe {
+        device.allocate_memory(
+            &vk::MemoryAllocateInfo {
+                allocation_size: requirements.size,
+                memory_type_index,
+                ..Default::default()
+            },
+            None,
+        )?
+    };
+    Ok(memory)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory_properties(
+        types: &[vk::MemoryPropertyFlags],
+    ) -> vk::PhysicalDeviceMemoryProperties {
+        let mut properties = vk::PhysicalDeviceMemoryProperties {
+            memory_type_count: types.len() as u32,
+            ..Default::default()
+        };
+        for (i, &flags) in types.iter().enumerate() {
+            properties.memory_types[i].property_flags = flags;
+        }
+        properties
+    }
+
+    #[test]
+    fn test_find_memory_type_matches_flags() {
+        let properties = memory_properties(&[
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            vk::MemoryPropertyFlags::HOST_VISIBLE
+                | vk::MemoryPropertyFlags::HOST_COHERENT,
+        ]);
+
+        let host = vk::MemoryPropertyFlags::HOST_VISIBLE
+            | vk::MemoryPropertyFlags::HOST_COHERENT;
+        assert_eq!(find_memory_type(&properties, 0b11, host), Some(1));
+        assert_eq!(
+            find_memory_type(
+                &properties,
+                0b11,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL
+            ),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn test_find_memory_type_respects_type_bits() {
+        let properties = memory_properties(&[
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        ]);
+
+        assert_eq!(
+            find_memory_type(
+                &properties,
+                0b10,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL
+            ),
+            Some(1)
+        );
+        assert_eq!(
+            find_memory_type(
+                &properties,
+                0b00,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL
+            ),
+            None
+        );
+    }
+}
@@ -0,0 +1,248 @@
+//! Graphics pipelines and the materials that use them
+
+use std::ffi::c_void;
+
+use ash::vk;
+use substrate::{Handle, SortMaterial};
+
+use crate::Result;
+
+/// Bytes of push constants each draw receives: its model transform
+pub const PUSH_CONSTANT_SIZE: u32 = size_of::<substrate::Transform>() as u32;
+
+/// Identifies a pipeline created by [`crate::Renderer::create_pipeline`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PipelineHandle(pub(crate) Handle<Pipeline>);
+
+/// Shaders and fixed-function state for a graphics pipeline.
+///
+/// Pipelines draw triangle lists into the frame image with dynamic
+/// rendering. The vertex shader receives the draw's column-major model
+/// transform as a `mat4` push constant at offset 0.
+#[derive(Debug, Clone, Copy)]
+pub struct PipelineDesc<'a> {
+    /// SPIR-V for the vertex stage, entry point `main`
+    pub vertex_shader: &'a [u32],
+    /// SPIR-V for the fragment stage, entry point `main`
+    pub fragment_shader: &'a [u32],
+    /// Bytes between consecutive vertices in binding 0
+    pub vertex_stride: u32,
+    /// Attributes read from binding 0
+    pub vertex_attributes: &'a [vk::VertexInputAttributeDescription],
+    /// Layouts of the descriptor sets materials bind, in set order
+    pub descriptor_set_layouts: &'a [vk::DescriptorSetLayout],
+    /// Blend with premultiplied alpha instead of overwriting
+    pub alpha_blend: bool,
+}
+
+/// A pipeline and its layout
+pub(crate) struct Pipeline {
+    pub handle: vk::Pipeline,
+    pub layout: vk::PipelineLayout,
+    device: ash::Device,
+}
+
+impl Pipeline {
+    /// Build a pipeline from `desc` that renders into `color_format` images
+    ///
+    /// # Errors
+    ///
+    /// Returns a Vulkan error if a shader module or the pipeline cannot be
+    /// created.
+    pub fn new(
+        device: &ash::Device,
+        desc: &PipelineDesc,
+        color_format: vk::Format,
+    ) -> Result<Self> {
+        let push_constant_range = vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::VERTEX,
+            offset: 0,
+            size: PUSH_CONSTANT_SIZE,
+        };
+        let layout_info = vk::PipelineLayoutCreateInfo {
+            set_layout_count: desc.descriptor_set_layouts.len() as u32,
+            p_set_layouts: desc.descriptor_set_layouts.as_ptr(),
+            push_constant_range_count: 1,
+            p_push_constant_ranges: &push_constant_range,
+            ..Default::default()
+        };
+
+        let mut pipeline = Self {
+            handle: vk::Pipeline::null(),
+            layout: vk::PipelineLayout::null(),
+            device: device.clone(),
+        };
+        pipeline.layout =
+            unsafe { device.create_pipeline_layout(&layout_info, None)? };
+
+        let vertex = shader_module(device, desc.vertex_shader)?;
+        let fragment = match shader_module(device, desc.fragment_shader) {
+            Ok(module) => module,
+            Err(e) => {
+                unsafe { device.destroy_shader_module(vertex, None) };
+                return Err(e);
+            }
+        };
+
+        let result = pipeline.create(desc, color_format, vertex, fragment);
+        unsafe {
+            device.destroy_shader_module(vertex, None);
+            device.destroy_shader_module(fragment, None);
+        }
+        pipeline.handle = result?;
+
+        Ok(pipeline)
+    }
+
+    fn create(
+        &self,
+        desc: &PipelineDesc,
+        color_format: vk::Format,
+        vertex: vk::ShaderModule,
+        fragment: vk::ShaderModule,
+    ) -> Result<vk::Pipeline> {
+        let stages = [
+            vk::PipelineShaderStageCreateInfo {
+                stage: vk::ShaderStageFlags::VERTEX,
+                module: vertex,
+                p_name: c"main".as_ptr(),
+                ..Default::default()
+            },
+            vk::PipelineShaderStageCreateInfo {
+                stage: vk::ShaderStageFlags::FRAGMENT,
+                module: fragment,
+                p_name: c"main".as_ptr(),
+                ..Default::default()
+            },
+        ];
+
+        let binding = vk::VertexInputBindingDescription {
+            binding: 0,
+            stride: desc.vertex_stride,
+            input_rate: vk::VertexInputRate::VERTEX,
+        };
+        let vertex_input = vk::PipelineVertexInputStateCreateInfo {
+            vertex_binding_description_count: 1,
+            p_vertex_binding_descriptions: &binding,
+            vertex_attribute_description_count: desc.vertex_attributes.len()
+                as u32,
+            p_vertex_attribute_descriptions: desc.vertex_attributes.as_ptr(),
+            ..Default::default()
+        };
+        let input_assembly = vk::PipelineInputAssemblyStateCreateInfo {
+            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            ..Default::default()
+        };
+        // Viewport and scissor are dynamic and set from the frame extent
+        let viewport = vk::PipelineViewportStateCreateInfo {
+            viewport_count: 1,
+            scissor_count: 1,
+            ..Default::default()
+        };
+        let rasterization = vk::PipelineRasterizationStateCreateInfo {
+            polygon_mode: vk::PolygonMode::FILL,
+            cull_mode: vk::CullModeFlags::NONE,
+            front_face: vk::FrontFace::COUNTER_CLOCKWISE,
+            line_width: 1.0,
+            ..Default::default()
+        };
+        let multisample = vk::PipelineMultisampleStateCreateInfo {
+            rasterization_samples: vk::SampleCountFlags::TYPE_1,
+            ..Default::default()
+        };
+        let blend_attachment = vk::PipelineColorBlendAttachmentState {
+            blend_enable: desc.alpha_blend as vk::Bool32,
+            src_color_blend_factor: vk::BlendFactor::ONE,
+            dst_color_blend_factor: vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+            color_blend_op: vk::BlendOp::ADD,
+            src_alpha_blend_factor: vk::BlendFactor::ONE,
+            dst_alpha_blend_factor: vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+            alpha_blend_op: vk::BlendOp::ADD,
+            color_write_mask: vk::ColorComponentFlags::RGBA,
+        };
+        let color_blend = vk::PipelineColorBlendStateCreateInfo {
+            attachment_count: 1,
+            p_attachments: &blend_attachment,
+            ..Default::default()
+        };
+        let dynamic_states =
+            [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+        let dynamic_state = vk::PipelineDynamicStateCreateInfo {
+            dynamic_state_count: dynamic_states.len() as u32,
+            p_dynamic_states: dynamic_states.as_ptr(),
+            ..Default::default()
+        };
+        let rendering = vk::PipelineRenderingCreateInfo {
+            color_attachment_count: 1,
+            p_color_attachment_formats: &color_format,
+            ..Default::default()
+        };
+
+        let create_info = vk::GraphicsPipelineCreateInfo {
+            p_next: &rendering as *const _ as *const c_void,
+            stage_count: stages.len() as u32,
+            p_stages: stages.as_ptr(),
+            p_vertex_input_state: &vertex_input,
+            p_input_assembly_state: &input_assembly,
+            p_viewport_state: &viewport,
+            p_rasterization_state: &rasterization,
+            p_multisample_state: &multisample,
+            p_color_blend_state: &color_blend,
+            p_dynamic_state: &dynamic_state,
+            layout: self.layout,
+            ..Default::default()
+        };
+
+        let pipelines = unsafe {
+            self.device
+                .create_graphics_pipelines(
+                    vk::PipelineCache::null(),
+                    &[create_info],
+                    None,
+                )
+                .map_err(|(_, e)| e)?
+        };
+        Ok(pipelines[0])
+    }
+}
+
+impl Drop for Pipeline {
+    fn drop(&mut self) {
+        // Destroying null handles is a no-op
+        unsafe {
+            self.device
+                .destroy_pipeline(self.handle, None);
+            self.device
+                .destroy_pipeline_layout(self.layout, None);
+        }
+    }
+}
+
+fn shader_module(
+    device: &ash::Device,
+    code: &[u32],
+) -> Result<vk::ShaderModule> {
+    let create_info = vk::ShaderModuleCreateInfo {
+        code_size: size_of_val(code),
+        p_code: code.as_ptr(),
+        ..Default::default()
+    };
+    Ok(unsafe { device.create_shader_module(&create_info, None)? })
+}
+
+/// Identifies a material created by [`crate::Renderer::create_material`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MaterialHandle(pub(crate) Handle<Material>);
+
+impl SortMaterial for MaterialHandle {
+    fn sort_index(&self) -> u32 {
+        self.0.index()
+    }
+}
+
+/// A pipeline plus the descriptor set bound when drawing with it
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Material {
+    pub pipeline: PipelineHandle,
+    pub descriptor_set: Option<vk::DescriptorSet>,
+}
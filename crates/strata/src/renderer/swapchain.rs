@@ -0,0 +1,361 @@
+//! Swapchain creation, negotiation and recreation
+
+use ash::{khr, vk};
+
+use super::PresentMode;
+use super::device::Device;
+use super::frame::COLOR_SUBRESOURCE_RANGE;
+use crate::{Result, StrataError};
+
+/// Pick an sRGB BGRA/RGBA format if the surface offers one, otherwise the
+/// first format reported
+pub(crate) fn choose_surface_format(
+    available: &[vk::SurfaceFormatKHR],
+) -> Option<vk::SurfaceFormatKHR> {
+    const PREFERRED: [vk::Format; 2] =
+        [vk::Format::B8G8R8A8_SRGB, vk::Format::R8G8B8A8_SRGB];
+
+    PREFERRED
+        .iter()
+        .find_map(|&format| {
+            available.iter().copied().find(|f| {
+                f.format == format
+                    && f.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR
+            })
+        })
+        .or_else(|| available.first().copied())
+}
+
+/// Map the requested present mode onto what the surface supports, falling
+/// back to FIFO which every implementation must provide
+pub(crate) fn choose_present_mode(
+    preferred: PresentMode,
+    available: &[vk::PresentModeKHR],
+) -> vk::PresentModeKHR {
+    let wanted = match preferred {
+        PresentMode::Fifo => vk::PresentModeKHR::FIFO,
+        PresentMode::FifoRelaxed => vk::PresentModeKHR::FIFO_RELAXED,
+        PresentMode::Mailbox => vk::PresentModeKHR::MAILBOX,
+        PresentMode::Immediate => vk::PresentModeKHR::IMMEDIATE,
+    };
+
+    if available.contains(&wanted) { wanted } else { vk::PresentModeKHR::FIFO }
+}
+
+/// The [`PresentMode`] matching a negotiated Vulkan present mode
+fn present_mode_from_vk(mode: vk::PresentModeKHR) -> PresentMode {
+    match mode {
+        vk::PresentModeKHR::MAILBOX => PresentMode::Mailbox,
+        vk::PresentModeKHR::IMMEDIATE => PresentMode::Immediate,
+        vk::PresentModeKHR::FIFO_RELAXED => PresentMode::FifoRelaxed,
+        _ => PresentMode::Fifo,
+    }
+}
+
+/// Use the surface's fixed extent if it has one, otherwise clamp the window
+/// size to the supported range
+pub(crate) fn choose_extent(
+    capabilities: &vk::SurfaceCapabilitiesKHR,
+    window_size: (u32, u32),
+) -> vk::Extent2D {
+    if capabilities.current_extent.width != u32::MAX {
+        return capabilities.current_extent;
+    }
+
+    let min = capabilities.min_image_extent;
+    let max = capabilities.max_image_extent;
+    vk::Extent2D {
+        width: window_size
+            .0
+            .clamp(min.width, max.width),
+        height: window_size
+            .1
+            .clamp(min.height, max.height),
+    }
+}
+
+/// Request one more image than the minimum so the driver never blocks us,
+/// within the surface's limit (0 means unlimited)
+pub(crate) fn choose_image_count(
+    capabilities: &vk::SurfaceCapabilitiesKHR,
+) -> u32 {
+    let desired = capabilities.min_image_count + 1;
+    if capabilities.max_image_count > 0 {
+        desired.min(capabilities.max_image_count)
+    } else {
+        desired
+    }
+}
+
+/// A swapchain, the views onto its images and per-image synchronisation
+pub(crate) struct Swapchain {
+    pub handle: vk::SwapchainKHR,
+    pub images: Vec<vk::Image>,
+    pub image_views: Vec<vk::ImageView>,
+    /// Signalled when rendering to the image finishes; presentation waits on
+    /// it. Kept per image because presentation holds it until the image is
+    /// acquired again.
+    pub render_finished: Vec<vk::Semaphore>,
+    /// Fence of the frame currently rendering to each image, if any
+    pub images_in_flight: Vec<vk::Fence>,
+    pub format: vk::Format,
+    pub present_mode: PresentMode,
+    pub extent: vk::Extent2D,
+    pub loader: khr::swapchain::Device,
+    device: ash::Device,
+}
+
+impl Swapchain {
+    /// Create a swapchain for `surface`, retiring `old` if given.
+    ///
+    /// # Errors
+    ///
+    /// Returns `StrataError::RendererInit` if the surface reports no formats
+    /// or a Vulkan error if swapchain creation fails.
+    pub fn new(
+        device: &Device,
+        loader: &khr::swapchain::Device,
+        surface_loader: &khr::surface::Instance,
+        surface: vk::SurfaceKHR,
+        preferred: PresentMode,
+        window_size: (u32, u32),
+        old: vk::SwapchainKHR,
+    ) -> Result<Self> {
+        let pdev = device.physical_device;
+        let (capabilities, formats, present_modes) = unsafe {
+            (
+                surface_loader
+                    .get_physical_device_surface_capabilities(pdev, surface)?,
+                surface_loader
+                    .get_physical_device_surface_formats(pdev, surface)?,
+                surface_loader
+                    .get_physical_device_surface_present_modes(pdev, surface)?,
+            )
+        };
+
+        let format = choose_surface_format(&formats).ok_or_else(|| {
+            StrataError::RendererInit(
+                "Surface reports no supported formats".to_string(),
+            )
+        })?;
+        let present_mode = choose_present_mode(preferred, &present_modes);
+        let extent = choose_extent(&capabilities, window_size);
+
+        let family_indices = device.families.unique();
+        let sharing_mode = if family_indices.len() > 1 {
+            vk::SharingMode::CONCURRENT
+        } else {
+            vk::SharingMode::EXCLUSIVE
+        };
+
+        let create_info = vk::SwapchainCreateInfoKHR {
+            surface,
+            min_image_count: choose_image_count(&capabilities),
+            image_format: format.format,
+            image_color_space: format.color_space,
+            image_extent: extent,
+            image_array_layers: 1,
+            image_usage: vk::ImageUsageFlags::COLOR_ATTACHMENT
+                | vk::ImageUsageFlags::TRANSFER_DST,
+            image_sharing_mode: sharing_mode,
+            queue_family_index_count: family_indices.len() as u32,
+            p_queue_family_indices: family_indices.as_ptr(),
+            pre_transform: capabilities.current_transform,
+            composite_alpha: vk::CompositeAlphaFlagsKHR::OPAQUE,
+            present_mode,
+            clipped: vk::TRUE,
+            old_swapchain: old,
+            ..Default::default()
+        };
+
+        let handle = unsafe { loader.create_swapchain(&create_info, None)? };
+        let images = unsafe { loader.get_swapchain_images(handle)? };
+
+        let mut swapchain = Self {
+            handle,
+            images,
+            image_views: Vec::new(),
+            render_finished: Vec::new(),
+            images_in_flight: Vec::new(),
+            format: format.format,
+            present_mode: present_mode_from_vk(present_mode),
+            extent,
+            loader: loader.clone(),
+            device: device.handle.clone(),
+        };
+
+        // Pushed one at a time so Drop cleans up if a later view fails
+        for i in 0..swapchain.images.len() {
+            let view_info = vk::ImageViewCreateInfo {
+                image: swapchain.images[i],
+                view_type: vk::ImageViewType::TYPE_2D,
+                format: format.format,
+                subresource_range: COLOR_SUBRESOURCE_RANGE,
+                ..Default::default()
+            };
+            let view = unsafe {
+                swapchain
+                    .device
+                    .create_image_view(&view_info, None)?
+            };
+            swapchain.image_views.push(view);
+
+            let semaphore = unsafe {
+                swapchain.device.create_semaphore(
+                    &vk::SemaphoreCreateInfo::default(),
+                    None,
+                )?
+            };
+            swapchain
+                .render_finished
+                .push(semaphore);
+            swapchain
+                .images_in_flight
+                .push(vk::Fence::null());
+        }
+
+        Ok(swapchain)
+    }
+
+    /// Acquire the next image, signalling `semaphore` when it is ready.
+    ///
+    /// Returns the image index and whether the swapchain is suboptimal.
+    pub fn acquire_next_image(
+        &self,
+        semaphore: vk::Semaphore,
+    ) -> ash::prelude::VkResult<(u32, bool)> {
+        unsafe {
+            self.loader.acquire_next_image(
+                self.handle,
+                u64::MAX,
+                semaphore,
+                vk::Fence::null(),
+            )
+        }
+    }
+
+    /// Present `image_index` once its render-finished semaphore signals.
+    ///
+    /// Returns whether the swapchain is suboptimal.
+    pub fn present(
+        &self,
+        queue: vk::Queue,
+        image_index: u32,
+    ) -> ash::prelude::VkResult<bool> {
+        let wait = [self.render_finished[image_index as usize]];
+        let swapchains = [self.handle];
+        let indices = [image_index];
+        let present_info = vk::PresentInfoKHR {
+            wait_semaphore_count: wait.len() as u32,
+            p_wait_semaphores: wait.as_ptr(),
+            swapchain_count: 1,
+            p_swapchains: swapchains.as_ptr(),
+            p_image_indices: indices.as_ptr(),
+            ..Default::default()
+        };
+
+        unsafe {
+            self.loader
+                .queue_present(queue, &present_info)
+        }
+    }
+}
+
+impl Drop for Swapchain {
+    fn drop(&mut self) {
+        unsafe {
+            for &semaphore in &self.render_finished {
+                self.device
+                    .destroy_semaphore(semaphore, None);
+            }
+            for &view in &self.image_views {
+                self.device
+                    .destroy_image_view(view, None);
+            }
+            self.loader
+                .destroy_swapchain(self.handle, None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn surface_format(format: vk::Format) -> vk::SurfaceFormatKHR {
+        vk::SurfaceFormatKHR {
+            format,
+            color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
+        }
+    }
+
+    fn capabilities(
+        current: (u32, u32),
+        min_images: u32,
+        max_images: u32,
+    ) -> vk::SurfaceCapabilitiesKHR {
+        vk::SurfaceCapabilitiesKHR {
+            current_extent: vk::Extent2D {
+                width: current.0,
+                height: current.1,
+            },
+            min_image_extent: vk::Extent2D { width: 1, height: 1 },
+            max_image_extent: vk::Extent2D { width: 4096, height: 4096 },
+            min_image_count: min_images,
+            max_image_count: max_images,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_surface_format_prefers_srgb() {
+        let formats = [
+            surface_format(vk::Format::B8G8R8A8_UNORM),
+            surface_format(vk::Format::B8G8R8A8_SRGB),
+        ];
+        let chosen = choose_surface_format(&formats).unwrap();
+        assert_eq!(chosen.format, vk::Format::B8G8R8A8_SRGB);
+    }
+
+    #[test]
+    fn test_surface_format_falls_back_to_first() {
+        let formats = [surface_format(vk::Format::R16G16B16A16_SFLOAT)];
+        let chosen = choose_surface_format(&formats).unwrap();
+        assert_eq!(chosen.format, vk::Format::R16G16B16A16_SFLOAT);
+        assert!(choose_surface_format(&[]).is_none());
+    }
+
+    #[test]
+    fn test_present_mode_negotiation() {
+        let available = [vk::PresentModeKHR::FIFO, vk::PresentModeKHR::MAILBOX];
+
+        assert_eq!(
+            choose_present_mode(PresentMode::Mailbox, &available),
+            vk::PresentModeKHR::MAILBOX
+        );
+        assert_eq!(
+            choose_present_mode(PresentMode::Immediate, &available),
+            vk::PresentModeKHR::FIFO
+        );
+    }
+
+    #[test]
+    fn test_extent_uses_current_extent_when_fixed() {
+        let caps = capabilities((1024, 768), 2, 3);
+        let extent = choose_extent(&caps, (800, 600));
+        assert_eq!((extent.width, extent.height), (1024, 768));
+    }
+
+    #[test]
+    fn test_extent_clamps_window_size() {
+        let caps = capabilities((u32::MAX, u32::MAX), 2, 3);
+        let extent = choose_extent(&caps, (8000, 600));
+        assert_eq!((extent.width, extent.height), (4096, 600));
+    }
+
+    #[test]
+    fn test_image_count_respects_maximum() {
+        assert_eq!(choose_image_count(&capabilities((1, 1), 2, 0)), 3);
+        assert_eq!(choose_image_count(&capabilities((1, 1), 2, 2)), 2);
+    }
+}
@@ -0,0 +1,344 @@
+//! Frame timing and fixed-timestep simulation
+
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// A monotonic time source that drives the game loop.
+///
+/// The engine uses [`SystemClock`] by default; tests and replays can supply a
+/// [`ManualClock`] to step time deterministically.
+pub trait Clock {
+    /// Time elapsed since the clock was created
+    fn now(&self) -> Duration;
+}
+
+/// Clock backed by the operating system's monotonic timer
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    /// Create a clock starting at zero now
+    pub fn new() -> Self {
+        Self { start: Instant::now() }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// Clock that only moves when told to.
+///
+/// Clones share the same underlying time, so a test can keep one handle and
+/// give another to a [`FrameTimer`].
+#[derive(Clone, Default)]
+pub struct ManualClock {
+    now: Rc<Cell<Duration>>,
+}
+
+impl ManualClock {
+    /// Create a clock starting at zero
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Move the clock forward by `dt`
+    pub fn advance(&self, dt: Duration) {
+        self.now.set(self.now.get() + dt);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        self.now.get()
+    }
+}
+
+/// Configuration for fixed-timestep simulation.
+///
+/// Frame time is accumulated and consumed in ticks of `1 / tick_rate`
+/// seconds. To avoid a spiral of death when the simulation can't keep up,
+/// each frame's delta is clamped to `max_frame_time` and at most
+/// `max_steps` ticks run per frame; any whole ticks left over are dropped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FixedTimestep {
+    tick_rate: f64,
+    max_steps: u32,
+    max_frame_time: Duration,
+}
+
+impl FixedTimestep {
+    /// Create a fixed timestep running `tick_rate` ticks per second.
+    ///
+    /// Defaults to at most 5 catch-up steps per frame and a 250ms frame
+    /// time clamp.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tick_rate` is not a positive, finite number whose tick
+    /// lasts at least a nanosecond; see [`FixedTimestep::is_valid_tick_rate`].
+    pub fn new(tick_rate: f64) -> Self {
+        assert!(
+            Self::is_valid_tick_rate(tick_rate),
+            "tick rate must be positive and finite with a tick of at least 1ns"
+        );
+        Self {
+            tick_rate,
+            max_steps: 5,
+            max_frame_time: Duration::from_millis(250),
+        }
+    }
+
+    /// Whether `tick_rate` is positive and finite, and its tick is at least
+    /// one nanosecond and fits in a [`Duration`]
+    pub fn is_valid_tick_rate(tick_rate: f64) -> bool {
+        tick_rate.is_finite()
+            && tick_rate > 0.0
+            && Duration::try_from_secs_f64(1.0 / tick_rate)
+                .is_ok_and(|tick| !tick.is_zero())
+    }
+
+    /// Set the maximum number of ticks run in a single frame
+    pub fn with_max_steps(mut self, max_steps: u32) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// Set the largest frame delta that will be fed to the accumulator
+    pub fn with_max_frame_time(mut self, max_frame_time: Duration) -> Self {
+        self.max_frame_time = max_frame_time;
+        self
+    }
+
+    /// Ticks per second
+    pub fn tick_rate(&self) -> f64 {
+        self.tick_rate
+    }
+
+    /// Maximum ticks run in a single frame
+    pub fn max_steps(&self) -> u32 {
+        self.max_steps
+    }
+
+    /// Largest frame delta fed to the accumulator
+    pub fn max_frame_time(&self) -> Duration {
+        self.max_frame_time
+    }
+
+    /// Length of a single tick
+    pub fn tick(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.tick_rate)
+    }
+}
+
+/// How the game loop advances simulation time
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Timestep {
+    /// `Game::update` is called once per frame with the wall-clock delta
+    #[default]
+    Variable,
+    /// `Game::fixed_update` is called zero or more times per frame with a
+    /// constant delta, and rendering is given an interpolation alpha
+    Fixed(FixedTimestep),
+}
+
+/// Timing information for a single frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameTime {
+    /// Seconds since the previous frame (clamped in fixed mode)
+    pub dt: f64,
+    /// Number of fixed ticks to run this frame
+    pub fixed_steps: u32,
+    /// Seconds per fixed tick, or 0.0 in variable mode
+    pub fixed_dt: f64,
+    /// How far between the last and next fixed tick this frame lies, in
+    /// `[0, 1)`. Always 1.0 in variable mode.
+    pub alpha: f64,
+}
+
+/// Measures frame deltas and runs the fixed-timestep accumulator
+pub struct FrameTimer {
+    clock: Box<dyn Clock>,
+    timestep: Timestep,
+    last: Duration,
+    accumulator: Duration,
+}
+
+impl FrameTimer {
+    /// Create a frame timer driven by the system clock
+    pub fn new(timestep: Timestep) -> Self {
+        Self::with_clock(timestep, SystemClock::new())
+    }
+
+    /// Create a frame timer driven by the given clock
+    pub fn with_clock(timestep: Timestep, clock: impl Clock + 'static) -> Self {
+        let last = clock.now();
+        Self {
+            clock: Box::new(clock),
+            timestep,
+            last,
+            accumulator: Duration::ZERO,
+        }
+    }
+
+    /// The timestep this timer was configured with
+    pub fn timestep(&self) -> Timestep {
+        self.timestep
+    }
+
+    /// Sample the clock and work out how to advance this frame
+    pub fn tick(&mut self) -> FrameTime {
+        let now = self.clock.now();
+        let elapsed = now.saturating_sub(self.last);
+        self.last = now;
+
+        let fixed = match self.timestep {
+            Timestep::Variable => {
+                return FrameTime {
+                    dt: elapsed.as_secs_f64(),
+                    fixed_steps: 0,
+                    fixed_dt: 0.0,
+                    alpha: 1.0,
+                };
+            }
+            Timestep::Fixed(fixed) => fixed,
+        };
+
+        let elapsed = elapsed.min(fixed.max_frame_time);
+        let tick = fixed.tick();
+        self.accumulator += elapsed;
+
+        let mut steps = 0;
+        while self.accumulator >= tick && steps < fixed.max_steps {
+            self.accumulator -= tick;
+            steps += 1;
+        }
+
+        // Drop ticks we couldn't run rather than carrying them forward
+        if self.accumulator >= tick {
+            let leftover = self.accumulator.as_nanos() % tick.as_nanos();
+            self.accumulator = Duration::from_nanos(leftover as u64);
+        }
+
+        FrameTime {
+            dt: elapsed.as_secs_f64(),
+            fixed_steps: steps,
+            fixed_dt: tick.as_secs_f64(),
+            alpha: self.accumulator.as_secs_f64() / tick.as_secs_f64(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixed_timer(config: FixedTimestep) -> (FrameTimer, ManualClock) {
+        let clock = ManualClock::new();
+        let timer =
+            FrameTimer::with_clock(Timestep::Fixed(config), clock.clone());
+        (timer, clock)
+    }
+
+    #[test]
+    fn test_variable_timestep_reports_elapsed_time() {
+        let clock = ManualClock::new();
+        let mut timer =
+            FrameTimer::with_clock(Timestep::Variable, clock.clone());
+
+        clock.advance(Duration::from_millis(16));
+        let frame = timer.tick();
+
+        assert!((frame.dt - 0.016).abs() < 1e-9);
+        assert_eq!(frame.fixed_steps, 0);
+        assert_eq!(frame.alpha, 1.0);
+    }
+
+    #[test]
+    fn test_fixed_timestep_runs_whole_ticks() {
+        let (mut timer, clock) = fixed_timer(FixedTimestep::new(100.0));
+
+        clock.advance(Duration::from_millis(35));
+        let frame = timer.tick();
+
+        assert_eq!(frame.fixed_steps, 3);
+        assert!((frame.fixed_dt - 0.01).abs() < 1e-9);
+        assert!((frame.alpha - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_fixed_timestep_accumulates_across_frames() {
+        let (mut timer, clock) = fixed_timer(FixedTimestep::new(100.0));
+
+        clock.advance(Duration::from_millis(6));
+        assert_eq!(timer.tick().fixed_steps, 0);
+
+        clock.advance(Duration::from_millis(6));
+        let frame = timer.tick();
+        assert_eq!(frame.fixed_steps, 1);
+        assert!((frame.alpha - 0.2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_fixed_timestep_caps_steps_per_frame() {
+        let config = FixedTimestep::new(100.0)
+            .with_max_steps(4)
+            .with_max_frame_time(Duration::from_secs(1));
+        let (mut timer, clock) = fixed_timer(config);
+
+        clock.advance(Duration::from_millis(105));
+        let frame = timer.tick();
+        assert_eq!(frame.fixed_steps, 4);
+        assert!((frame.alpha - 0.5).abs() < 1e-6);
+
+        // The dropped ticks are not replayed on the next frame
+        let frame = timer.tick();
+        assert_eq!(frame.fixed_steps, 0);
+    }
+
+    #[test]
+    fn test_fixed_timestep_clamps_frame_time() {
+        let config = FixedTimestep::new(100.0)
+            .with_max_steps(100)
+            .with_max_frame_time(Duration::from_millis(50));
+        let (mut timer, clock) = fixed_timer(config);
+
+        clock.advance(Duration::from_secs(10));
+        let frame = timer.tick();
+
+        assert_eq!(frame.fixed_steps, 5);
+        assert!((frame.dt - 0.05).abs() < 1e-9);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_fixed_timestep_rejects_zero_tick_rate() {
+        let _ = FixedTimestep::new(0.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least 1ns")]
+    fn test_fixed_timestep_rejects_sub_nanosecond_tick() {
+        let _ = FixedTimestep::new(1e10);
+    }
+
+    #[test]
+    fn test_tick_rate_validity() {
+        assert!(FixedTimestep::is_valid_tick_rate(60.0));
+        assert!(FixedTimestep::is_valid_tick_rate(1e9));
+        assert!(!FixedTimestep::is_valid_tick_rate(1e10));
+        assert!(!FixedTimestep::is_valid_tick_rate(1e-30));
+        assert!(!FixedTimestep::is_valid_tick_rate(f64::INFINITY));
+        assert!(!FixedTimestep::is_valid_tick_rate(-1.0));
+    }
+}
@@ -0,0 +1,22 @@
+//! Voxel world storage
+
+mod chunk;
+mod chunk_map;
+mod dungeon;
+mod light;
+mod mesher;
+mod noise;
+mod registry;
+mod terrain;
+
+pub use chunk::{BlockId, CHUNK_SIZE, CHUNK_VOLUME, Chunk};
+pub use chunk_map::{ChunkMap, ChunkPos};
+pub use dungeon::{
+    Doorway, Dungeon, DungeonGenerator, PlacedRoom, RoomPrefab, Socket,
+    SpawnPoint,
+};
+pub use light::{Light, LightEngine, MAX_LIGHT};
+pub use mesher::{ChunkMesh, ChunkMesher, ChunkVertex};
+pub use noise::Noise;
+pub use registry::{BlockRegistry, BlockType, CollisionShape, Face};
+pub use terrain::{Biome, NoiseTerrain, TerrainBlocks, TerrainGenerator};
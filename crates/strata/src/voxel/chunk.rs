@@ -0,0 +1,539 @@
+//! Palette-compressed block storage for one chunk
+
+use std::fmt;
+use std::mem;
+
+use super::light::Light;
+
+/// Blocks along each edge of a chunk
+pub const CHUNK_SIZE: usize = 32;
+
+/// Blocks in a chunk
+pub const CHUNK_VOLUME: usize = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
+
+/// Bit widths a chunk steps through as its palette grows. Each divides 64,
+/// so no index straddles two words.
+const BIT_WIDTHS: [u32; 5] = [1, 2, 4, 8, 16];
+
+/// Identifies a kind of block. What each id means is up to the block
+/// registry; [`BlockId::AIR`] is always empty space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct BlockId(u16);
+
+impl BlockId {
+    pub const AIR: BlockId = BlockId(0);
+
+    pub const fn new(raw: u16) -> Self {
+        Self(raw)
+    }
+
+    pub const fn raw(self) -> u16 {
+        self.0
+    }
+
+    pub const fn is_air(self) -> bool {
+        self.0 == 0
+    }
+}
+
+/// A cube of [`CHUNK_SIZE`]³ blocks.
+///
+/// Each block is stored as an index into a palette of the distinct ids in
+/// the chunk, packed at the fewest bits that fit the palette: a chunk of
+/// one kind of block stores no indices at all, and two kinds take one bit
+/// per block. The width grows as new ids are set, and shrinks again on
+/// [`Chunk::compact`].
+///
+/// Each block also has a [`Light`] level, stored only once the levels
+/// differ. New chunks are lit by open sky until a light engine says
+/// otherwise.
+///
+/// Blocks are addressed by local coordinates from 0 to `CHUNK_SIZE - 1`
+/// and laid out x fastest, then z, then y.
+#[derive(Clone)]
+pub struct Chunk {
+    palette: Vec<BlockId>,
+    /// Blocks using each palette entry; entries at zero are free for reuse
+    counts: Vec<u32>,
+    /// Bits per index, 0 when the palette has a single entry
+    bits: u32,
+    data: Vec<u64>,
+    /// Light per block, or `None` while every block has `light_fill`
+    light: Option<Box<[Light]>>,
+    light_fill: Light,
+}
+
+impl Chunk {
+    /// A chunk of nothing but air
+    pub fn new() -> Self {
+        Self::filled(BlockId::AIR)
+    }
+
+    /// A chunk of nothing but `block`
+    pub fn filled(block: BlockId) -> Self {
+        Self {
+            palette: vec![block],
+            counts: vec![CHUNK_VOLUME as u32],
+            bits: 0,
+            data: Vec::new(),
+            light: None,
+            light_fill: Light::SKY,
+        }
+    }
+
+    /// # Panics
+    ///
+    /// Panics if a coordinate is `CHUNK_SIZE` or more.
+    pub fn get(&self, x: usize, y: usize, z: usize) -> BlockId {
+        self.palette[self.slot(index(x, y, z))]
+    }
+
+    /// Set a block, returning the one it replaced.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a coordinate is `CHUNK_SIZE` or more.
+    pub fn set(
+        &mut self,
+        x: usize,
+        y: usize,
+        z: usize,
+        block: BlockId,
+    ) -> BlockId {
+        let index = index(x, y, z);
+        let old_slot = self.slot(index);
+        let old = self.palette[old_slot];
+        if old == block {
+            return old;
+        }
+
+        let slot = self.palette_slot(block);
+        self.write_slot(index, slot);
+        self.counts[old_slot] -= 1;
+        self.counts[slot] += 1;
+        old
+    }
+
+    /// Set every block to `block`, dropping the palette. Light is kept.
+    pub fn fill(&mut self, block: BlockId) {
+        *self = Self {
+            light: self.light.take(),
+            light_fill: self.light_fill,
+            ..Self::filled(block)
+        };
+    }
+
+    /// # Panics
+    ///
+    /// Panics if a coordinate is `CHUNK_SIZE` or more.
+    pub fn light(&self, x: usize, y: usize, z: usize) -> Light {
+        let index = index(x, y, z);
+        self.light
+            .as_ref()
+            .map_or(self.light_fill, |light| light[index])
+    }
+
+    /// Set the light at a block, returning the previous level.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a coordinate is `CHUNK_SIZE` or more.
+    pub fn set_light(
+        &mut self,
+        x: usize,
+        y: usize,
+        z: usize,
+        light: Light,
+    ) -> Light {
+        let index = index(x, y, z);
+        if self.light.is_none() && light == self.light_fill {
+            return light;
+        }
+        let fill = self.light_fill;
+        let levels = self
+            .light
+            .get_or_insert_with(|| vec![fill; CHUNK_VOLUME].into());
+        mem::replace(&mut levels[index], light)
+    }
+
+    /// Set the light at every block
+    pub fn fill_light(&mut self, light: Light) {
+        self.light = None;
+        self.light_fill = light;
+    }
+
+    /// Whether every block is air
+    pub fn is_empty(&self) -> bool {
+        self.count(BlockId::AIR) == CHUNK_VOLUME
+    }
+
+    /// Whether every block is the same
+    pub fn is_uniform(&self) -> bool {
+        self.counts
+            .iter()
+            .filter(|&&count| count > 0)
+            .count()
+            == 1
+    }
+
+    /// Blocks of kind `block`
+    pub fn count(&self, block: BlockId) -> usize {
+        self.palette
+            .iter()
+            .zip(&self.counts)
+            .filter(|&(&id, _)| id == block)
+            .map(|(_, &count)| count as usize)
+            .sum()
+    }
+
+    /// Distinct block ids in use
+    pub fn palette_len(&self) -> usize {
+        self.counts
+            .iter()
+            .filter(|&&count| count > 0)
+            .count()
+    }
+
+    /// Bits stored per block
+    pub fn bits_per_block(&self) -> u32 {
+        self.bits
+    }
+
+    /// Approximate heap and inline bytes used by the chunk
+    pub fn memory_usage(&self) -> usize {
+        mem::size_of::<Self>()
+            + self.palette.capacity() * mem::size_of::<BlockId>()
+            + self.counts.capacity() * mem::size_of::<u32>()
+            + self.data.capacity() * mem::size_of::<u64>()
+            + self
+                .light
+                .as_ref()
+                .map_or(0, |light| light.len() * mem::size_of::<Light>())
+    }
+
+    /// Drop unused palette entries and repack at the fewest bits that fit,
+    /// and drop stored light if every block has the same level
+    pub fn compact(&mut self) {
+        if let Some(light) = &self.light
+            && light
+                .iter()
+                .all(|&level| level == light[0])
+        {
+            self.light_fill = light[0];
+            self.light = None;
+        }
+
+        let live: Vec<usize> = (0..self.palette.len())
+            .filter(|&slot| self.counts[slot] > 0)
+            .collect();
+        if live.len() == self.palette.len() && bits_for(live.len()) == self.bits
+        {
+            return;
+        }
+
+        let mut remap = vec![0; self.palette.len()];
+        for (new, &old) in live.iter().enumerate() {
+            remap[old] = new;
+        }
+        let slots: Vec<usize> = (0..CHUNK_VOLUME)
+            .map(|index| remap[self.slot(index)])
+            .collect();
+
+        self.palette = live
+            .iter()
+            .map(|&slot| self.palette[slot])
+            .collect();
+        self.counts = live
+            .iter()
+            .map(|&slot| self.counts[slot])
+            .collect();
+        self.pack(bits_for(live.len()), slots);
+    }
+
+    /// Every block in layout order
+    pub fn blocks(&self) -> impl Iterator<Item = BlockId> + '_ {
+        (0..CHUNK_VOLUME).map(|index| self.palette[self.slot(index)])
+    }
+
+    /// Every block with its local coordinates `[x, y, z]`
+    pub fn iter(&self) -> impl Iterator<Item = ([usize; 3], BlockId)> + '_ {
+        self.blocks()
+            .enumerate()
+            .map(|(index, block)| (position(index), block))
+    }
+
+    fn slot(&self, index: usize) -> usize {
+        if self.bits == 0 {
+            return 0;
+        }
+        let per_word = 64 / self.bits as usize;
+        let word = self.data[index / per_word];
+        let shift = (index % per_word) as u32 * self.bits;
+        ((word >> shift) & mask(self.bits)) as usize
+    }
+
+    fn write_slot(&mut self, index: usize, slot: usize) {
+        let per_word = 64 / self.bits as usize;
+        let word = &mut self.data[index / per_word];
+        let shift = (index % per_word) as u32 * self.bits;
+        *word =
+            (*word & !(mask(self.bits) << shift)) | ((slot as u64) << shift);
+    }
+
+    /// The palette slot for `block`, adding it and widening the indices if
+    /// needed
+    fn palette_slot(&mut self, block: BlockId) -> usize {
+        if let Some(slot) = self
+            .palette
+            .iter()
+            .position(|&id| id == block)
+        {
+            return slot;
+        }
+        if let Some(slot) = self
+            .counts
+            .iter()
+            .position(|&count| count == 0)
+        {
+            self.palette[slot] = block;
+            return slot;
+        }
+
+        self.palette.push(block);
+        self.counts.push(0);
+        let bits = bits_for(self.palette.len());
+        if bits > self.bits {
+            let slots = (0..CHUNK_VOLUME)
+                .map(|index| self.slot(index))
+                .collect();
+            self.pack(bits, slots);
+        }
+        self.palette.len() - 1
+    }
+
+    /// Store `slots` at `bits` per block
+    fn pack(&mut self, bits: u32, slots: Vec<usize>) {
+        self.bits = bits;
+        self.data = Vec::new();
+        if bits == 0 {
+            return;
+        }
+        self.data = vec![0; CHUNK_VOLUME * bits as usize / 64];
+        for (index, slot) in slots.into_iter().enumerate() {
+            self.write_slot(index, slot);
+        }
+    }
+}
+
+impl Default for Chunk {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Chunks are equal when they hold the same blocks, however they are packed
+impl PartialEq for Chunk {
+    fn eq(&self, other: &Self) -> bool {
+        self.blocks().eq(other.blocks())
+    }
+}
+
+impl Eq for Chunk {}
+
+impl fmt::Debug for Chunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Chunk")
+            .field("palette", &self.palette)
+            .field("bits", &self.bits)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Layout index of local coordinates
+///
+/// # Panics
+///
+/// Panics if a coordinate is `CHUNK_SIZE` or more.
+pub(crate) fn index(x: usize, y: usize, z: usize) -> usize {
+    assert!(
+        x < CHUNK_SIZE && y < CHUNK_SIZE && z < CHUNK_SIZE,
+        "block ({x}, {y}, {z}) is outside the chunk"
+    );
+    (y * CHUNK_SIZE + z) * CHUNK_SIZE + x
+}
+
+/// Local coordinates `[x, y, z]` of a layout index
+pub(crate) fn position(index: usize) -> [usize; 3] {
+    [
+        index % CHUNK_SIZE,
+        index / (CHUNK_SIZE * CHUNK_SIZE),
+        index / CHUNK_SIZE % CHUNK_SIZE,
+    ]
+}
+
+/// Fewest bits from [`BIT_WIDTHS`] that index `entries` palette entries
+fn bits_for(entries: usize) -> u32 {
+    if entries <= 1 {
+        return 0;
+    }
+    BIT_WIDTHS
+        .into_iter()
+        .find(|&bits| entries <= 1 << bits)
+        .expect("palette fits in 16 bits")
+}
+
+fn mask(bits: u32) -> u64 {
+    (1 << bits) - 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(raw: u16) -> BlockId {
+        BlockId::new(raw)
+    }
+
+    #[test]
+    fn test_new_chunk_is_air_with_no_indices() {
+        let chunk = Chunk::new();
+        assert!(chunk.is_empty());
+        assert!(chunk.is_uniform());
+        assert_eq!(chunk.bits_per_block(), 0);
+        assert_eq!(chunk.get(31, 31, 31), BlockId::AIR);
+    }
+
+    #[test]
+    fn test_set_and_get() {
+        let mut chunk = Chunk::new();
+        assert_eq!(chunk.set(1, 2, 3, block(7)), BlockId::AIR);
+        assert_eq!(chunk.set(1, 2, 3, block(8)), block(7));
+
+        assert_eq!(chunk.get(1, 2, 3), block(8));
+        assert_eq!(chunk.get(3, 2, 1), BlockId::AIR);
+        assert_eq!(chunk.count(block(8)), 1);
+        assert!(!chunk.is_empty());
+    }
+
+    #[test]
+    fn test_bit_width_grows_with_palette() {
+        let mut chunk = Chunk::new();
+        let mut widths = Vec::new();
+        for raw in 1..=300u16 {
+            let i = raw as usize;
+            chunk.set(i % 32, i / 32, 0, block(raw));
+            widths.push(chunk.bits_per_block());
+        }
+
+        assert_eq!(widths[0], 1);
+        assert_eq!(widths[2], 2);
+        assert_eq!(widths[14], 4);
+        assert_eq!(widths[254], 8);
+        assert_eq!(widths[299], 16);
+        for raw in 1..=300u16 {
+            let i = raw as usize;
+            assert_eq!(chunk.get(i % 32, i / 32, 0), block(raw));
+        }
+    }
+
+    #[test]
+    fn test_freed_palette_entries_are_reused() {
+        let mut chunk = Chunk::new();
+        chunk.set(0, 0, 0, block(1));
+        chunk.set(0, 0, 0, block(2));
+        chunk.set(1, 0, 0, block(3));
+
+        // Block 1 is gone, so block 3 takes its entry without widening
+        assert_eq!(chunk.bits_per_block(), 2);
+        assert_eq!(chunk.palette_len(), 3);
+        assert_eq!(chunk.get(1, 0, 0), block(3));
+    }
+
+    #[test]
+    fn test_compact_shrinks_bits_and_memory() {
+        let mut chunk = Chunk::new();
+        for raw in 1..=20u16 {
+            chunk.set(raw as usize, 5, 5, block(raw));
+        }
+        let wide = chunk.memory_usage();
+        for raw in 2..=20u16 {
+            chunk.set(raw as usize, 5, 5, BlockId::AIR);
+        }
+        chunk.compact();
+
+        assert_eq!(chunk.bits_per_block(), 1);
+        assert!(chunk.memory_usage() < wide);
+        assert_eq!(chunk.get(1, 5, 5), block(1));
+        assert_eq!(chunk.count(BlockId::AIR), CHUNK_VOLUME - 1);
+
+        chunk.set(1, 5, 5, BlockId::AIR);
+        chunk.compact();
+        assert_eq!(chunk.bits_per_block(), 0);
+        assert_eq!(chunk, Chunk::new());
+    }
+
+    #[test]
+    fn test_iteration_matches_get() {
+        let mut chunk = Chunk::new();
+        chunk.set(3, 4, 5, block(9));
+        chunk.set(31, 0, 17, block(2));
+
+        let solid: Vec<_> = chunk
+            .iter()
+            .filter(|(_, id)| !id.is_air())
+            .collect();
+        assert_eq!(solid, [([31, 0, 17], block(2)), ([3, 4, 5], block(9))]);
+        for index in [0, 1, 999, CHUNK_VOLUME - 1] {
+            let [x, y, z] = position(index);
+            assert_eq!(super::index(x, y, z), index);
+        }
+    }
+
+    #[test]
+    fn test_filled_chunk_memory_is_small() {
+        let stone = Chunk::filled(block(1));
+        let mut noisy = Chunk::new();
+        for (i, (x, y, z)) in (0..CHUNK_SIZE)
+            .flat_map(|y| {
+                (0..CHUNK_SIZE).map(move |x| (x, y, (x * 7 + y) % 32))
+            })
+            .enumerate()
+        {
+            noisy.set(x, y, z, block(i as u16 % 16 + 1));
+        }
+
+        assert!(stone.memory_usage() < 128);
+        // Air plus 16 ids is one too many for 4 bits
+        assert_eq!(noisy.bits_per_block(), 8);
+        assert!(noisy.memory_usage() < CHUNK_VOLUME * 2);
+    }
+
+    #[test]
+    fn test_light_is_stored_once_it_varies() {
+        let mut chunk = Chunk::filled(block(1));
+        let uniform = chunk.memory_usage();
+        assert_eq!(chunk.light(5, 5, 5), Light::SKY);
+        assert_eq!(chunk.set_light(5, 5, 5, Light::SKY), Light::SKY);
+        assert_eq!(chunk.memory_usage(), uniform);
+
+        assert_eq!(chunk.set_light(5, 5, 5, Light::new(7, 0)), Light::SKY);
+        assert_eq!(chunk.light(5, 5, 5), Light::new(7, 0));
+        assert_eq!(chunk.light(5, 5, 6), Light::SKY);
+        assert!(chunk.memory_usage() >= uniform + CHUNK_VOLUME);
+
+        chunk.fill(block(2));
+        assert_eq!(chunk.light(5, 5, 5), Light::new(7, 0));
+        chunk.set_light(5, 5, 5, Light::SKY);
+        chunk.compact();
+        assert_eq!(chunk.memory_usage(), uniform);
+
+        chunk.fill_light(Light::DARK);
+        assert_eq!(chunk.light(0, 31, 0), Light::DARK);
+    }
+
+    #[test]
+    #[should_panic(expected = "outside the chunk")]
+    fn test_out_of_range_panics() {
+        Chunk::new().get(32, 0, 0);
+    }
+}
@@ -0,0 +1,288 @@
+//! Loaded chunks keyed by chunk coordinates
+
+use std::collections::HashMap;
+
+use super::chunk::{BlockId, CHUNK_SIZE, Chunk};
+use super::light::Light;
+use super::registry::Face;
+
+/// Coordinates of a chunk, in chunks. Chunk `(0, 0, 0)` spans world blocks
+/// 0 to `CHUNK_SIZE - 1` on each axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct ChunkPos {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl ChunkPos {
+    pub const fn new(x: i32, y: i32, z: i32) -> Self {
+        Self { x, y, z }
+    }
+
+    /// The chunk holding a world block, and the block's local coordinates
+    /// within it
+    pub fn from_block(block: [i32; 3]) -> (Self, [usize; 3]) {
+        let size = CHUNK_SIZE as i32;
+        let pos = Self::new(
+            block[0].div_euclid(size),
+            block[1].div_euclid(size),
+            block[2].div_euclid(size),
+        );
+        let local = block.map(|v| v.rem_euclid(size) as usize);
+        (pos, local)
+    }
+
+    /// World coordinates of the chunk's lowest corner block
+    pub fn origin(self) -> [i32; 3] {
+        let size = CHUNK_SIZE as i32;
+        [self.x * size, self.y * size, self.z * size]
+    }
+
+    /// The neighbouring chunk `offset` chunks away
+    pub fn offset(self, dx: i32, dy: i32, dz: i32) -> Self {
+        Self::new(self.x + dx, self.y + dy, self.z + dz)
+    }
+
+    /// Distance in chunks along the axis where it is largest
+    pub fn chebyshev_distance(self, other: Self) -> u32 {
+        (self.x.abs_diff(other.x))
+            .max(self.y.abs_diff(other.y))
+            .max(self.z.abs_diff(other.z))
+    }
+}
+
+/// The chunks currently loaded, addressable by chunk or by world block.
+///
+/// Blocks in chunks that are not loaded read as `None`; nothing is
+/// generated on demand.
+#[derive(Debug, Clone, Default)]
+pub struct ChunkMap {
+    chunks: HashMap<ChunkPos, Chunk>,
+}
+
+impl ChunkMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loaded chunks
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    /// Load a chunk, returning the one it replaced
+    pub fn load(&mut self, pos: ChunkPos, chunk: Chunk) -> Option<Chunk> {
+        self.chunks.insert(pos, chunk)
+    }
+
+    /// Unload a chunk, handing it back so it can be saved
+    pub fn unload(&mut self, pos: ChunkPos) -> Option<Chunk> {
+        self.chunks.remove(&pos)
+    }
+
+    /// Unload every chunk more than `radius` chunks from `center` on any
+    /// axis, returning them in no particular order
+    pub fn unload_outside(
+        &mut self,
+        center: ChunkPos,
+        radius: u32,
+    ) -> Vec<(ChunkPos, Chunk)> {
+        let far: Vec<ChunkPos> = self
+            .chunks
+            .keys()
+            .copied()
+            .filter(|pos| pos.chebyshev_distance(center) > radius)
+            .collect();
+        far.into_iter()
+            .filter_map(|pos| {
+                self.chunks
+                    .remove(&pos)
+                    .map(|chunk| (pos, chunk))
+            })
+            .collect()
+    }
+
+    pub fn contains(&self, pos: ChunkPos) -> bool {
+        self.chunks.contains_key(&pos)
+    }
+
+    pub fn get(&self, pos: ChunkPos) -> Option<&Chunk> {
+        self.chunks.get(&pos)
+    }
+
+    pub fn get_mut(&mut self, pos: ChunkPos) -> Option<&mut Chunk> {
+        self.chunks.get_mut(&pos)
+    }
+
+    /// The loaded chunks sharing a face with `pos`, indexed by
+    /// [`Face::index`], as [`super::ChunkMesher::mesh`] takes them
+    pub fn neighbours(&self, pos: ChunkPos) -> [Option<&Chunk>; 6] {
+        Face::ALL.map(|face| {
+            let [dx, dy, dz] = face.normal();
+            self.get(pos.offset(dx, dy, dz))
+        })
+    }
+
+    /// The block at world coordinates, if its chunk is loaded
+    pub fn block(&self, block: [i32; 3]) -> Option<BlockId> {
+        let (pos, [x, y, z]) = ChunkPos::from_block(block);
+        self.get(pos)
+            .map(|chunk| chunk.get(x, y, z))
+    }
+
+    /// Set the block at world coordinates, returning the one it replaced,
+    /// or `None` without changing anything if its chunk is not loaded
+    pub fn set_block(
+        &mut self,
+        block: [i32; 3],
+        id: BlockId,
+    ) -> Option<BlockId> {
+        let (pos, [x, y, z]) = ChunkPos::from_block(block);
+        self.get_mut(pos)
+            .map(|chunk| chunk.set(x, y, z, id))
+    }
+
+    /// The light at world coordinates, if its chunk is loaded
+    pub fn light(&self, block: [i32; 3]) -> Option<Light> {
+        let (pos, [x, y, z]) = ChunkPos::from_block(block);
+        self.get(pos)
+            .map(|chunk| chunk.light(x, y, z))
+    }
+
+    /// Set the light at world coordinates, returning the previous level,
+    /// or `None` without changing anything if its chunk is not loaded
+    pub fn set_light(
+        &mut self,
+        block: [i32; 3],
+        light: Light,
+    ) -> Option<Light> {
+        let (pos, [x, y, z]) = ChunkPos::from_block(block);
+        self.get_mut(pos)
+            .map(|chunk| chunk.set_light(x, y, z, light))
+    }
+
+    /// Every loaded chunk, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (ChunkPos, &Chunk)> {
+        self.chunks
+            .iter()
+            .map(|(&pos, chunk)| (pos, chunk))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (ChunkPos, &mut Chunk)> {
+        self.chunks
+            .iter_mut()
+            .map(|(&pos, chunk)| (pos, chunk))
+    }
+
+    /// Approximate bytes used by all loaded chunks
+    pub fn memory_usage(&self) -> usize {
+        self.chunks
+            .values()
+            .map(Chunk::memory_usage)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_block_handles_negative_coordinates() {
+        assert_eq!(
+            ChunkPos::from_block([0, 31, 32]),
+            (ChunkPos::new(0, 0, 1), [0, 31, 0])
+        );
+        assert_eq!(
+            ChunkPos::from_block([-1, -32, -33]),
+            (ChunkPos::new(-1, -1, -2), [31, 0, 31])
+        );
+        assert_eq!(ChunkPos::new(-1, 2, 0).origin(), [-32, 64, 0]);
+    }
+
+    #[test]
+    fn test_blocks_in_unloaded_chunks_are_none() {
+        let mut map = ChunkMap::new();
+        assert_eq!(map.block([5, 5, 5]), None);
+        assert_eq!(map.set_block([5, 5, 5], BlockId::new(1)), None);
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn test_set_block_across_chunks() {
+        let mut map = ChunkMap::new();
+        map.load(ChunkPos::new(0, 0, 0), Chunk::new());
+        map.load(ChunkPos::new(-1, 0, 0), Chunk::new());
+
+        let stone = BlockId::new(3);
+        assert_eq!(map.set_block([-1, 4, 4], stone), Some(BlockId::AIR));
+        assert_eq!(map.set_block([0, 4, 4], stone), Some(BlockId::AIR));
+
+        assert_eq!(map.block([-1, 4, 4]), Some(stone));
+        assert_eq!(
+            map.get(ChunkPos::new(-1, 0, 0))
+                .map(|chunk| chunk.get(31, 4, 4)),
+            Some(stone)
+        );
+        assert_eq!(map.block([1, 4, 4]), Some(BlockId::AIR));
+    }
+
+    #[test]
+    fn test_neighbours_follow_face_order() {
+        let mut map = ChunkMap::new();
+        let center = ChunkPos::new(0, 0, 0);
+        map.load(center, Chunk::new());
+        map.load(center.offset(0, 1, 0), Chunk::filled(BlockId::new(1)));
+        map.load(center.offset(0, 0, -1), Chunk::filled(BlockId::new(2)));
+
+        let neighbours = map.neighbours(center);
+        let blocks = neighbours.map(|chunk| chunk.map(|c| c.get(0, 0, 0)));
+        assert_eq!(blocks[Face::Top.index()], Some(BlockId::new(1)));
+        assert_eq!(blocks[Face::North.index()], Some(BlockId::new(2)));
+        assert_eq!(blocks.iter().flatten().count(), 2);
+    }
+
+    #[test]
+    fn test_load_and_unload() {
+        let mut map = ChunkMap::new();
+        let pos = ChunkPos::new(2, 0, -3);
+        assert!(map.load(pos, Chunk::new()).is_none());
+        assert!(
+            map.load(pos, Chunk::filled(BlockId::new(1)))
+                .is_some()
+        );
+        assert!(map.contains(pos));
+        assert!(map.memory_usage() > 0);
+
+        let chunk = map
+            .unload(pos)
+            .expect("chunk was loaded");
+        assert_eq!(chunk.get(0, 0, 0), BlockId::new(1));
+        assert!(!map.contains(pos));
+        assert_eq!(map.memory_usage(), 0);
+    }
+
+    #[test]
+    fn test_unload_outside_radius() {
+        let mut map = ChunkMap::new();
+        for x in -3..=3 {
+            for z in -3..=3 {
+                map.load(ChunkPos::new(x, 0, z), Chunk::new());
+            }
+        }
+
+        let unloaded = map.unload_outside(ChunkPos::new(1, 0, 0), 1);
+        assert_eq!(map.len(), 9);
+        assert_eq!(unloaded.len(), 40);
+        assert!(
+            map.iter()
+                .all(|(pos, _)| (0..=2).contains(&pos.x)
+                    && (-1..=1).contains(&pos.z))
+        );
+    }
+}
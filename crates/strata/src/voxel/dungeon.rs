@@ -0,0 +1,827 @@
+//! Dungeons assembled from prefab rooms
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use super::chunk::{BlockId, Chunk};
+use super::chunk_map::{ChunkMap, ChunkPos};
+use super::noise::split_mix;
+use super::registry::Face;
+
+/// Doorways are this many blocks tall
+const DOOR_HEIGHT: usize = 2;
+
+/// A place in a room's wall where a doorway to another room may open
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Socket {
+    /// The wall block at the foot of the doorway
+    pub position: [usize; 3],
+    /// The wall the doorway is in
+    pub face: Face,
+}
+
+/// A hand-built room: a box of blocks with doorway sockets in its side
+/// walls and points where entities spawn.
+///
+/// Two rooms join when a socket of one faces a socket of the other across
+/// their touching walls. The doorway is carved through both walls when the
+/// dungeon is written; sockets left unused stay walled up.
+#[derive(Debug, Clone)]
+pub struct RoomPrefab {
+    name: String,
+    size: [usize; 3],
+    /// Indexed by `(y * depth + z) * width + x`
+    blocks: Vec<BlockId>,
+    sockets: Vec<Socket>,
+    spawns: Vec<([usize; 3], String)>,
+}
+
+impl RoomPrefab {
+    /// A room of `size` blocks, all air
+    ///
+    /// # Panics
+    ///
+    /// Panics if any side of `size` is zero.
+    pub fn new(name: impl Into<String>, size: [usize; 3]) -> Self {
+        assert!(size.iter().all(|&side| side > 0), "Rooms can't be empty");
+        Self {
+            name: name.into(),
+            size,
+            blocks: vec![BlockId::AIR; size.iter().product()],
+            sockets: Vec::new(),
+            spawns: Vec::new(),
+        }
+    }
+
+    /// Enclose the room in `wall`: floor, ceiling and four walls
+    pub fn with_walls(mut self, wall: BlockId) -> Self {
+        let [width, height, depth] = self.size;
+        for y in 0..height {
+            for z in 0..depth {
+                for x in 0..width {
+                    let inside = [(x, width), (y, height), (z, depth)]
+                        .iter()
+                        .all(|&(i, side)| i > 0 && i + 1 < side);
+                    if !inside {
+                        self.set(x, y, z, wall);
+                    }
+                }
+            }
+        }
+        self
+    }
+
+    /// Add a doorway socket at the wall block `position` facing out of
+    /// the room through `face`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `face` is the top or bottom, if `position` is not on that
+    /// wall or is in one of its corner columns, where the doorway would
+    /// open into the adjoining wall, or if the doorway would not stand on
+    /// the floor and fit below the ceiling.
+    pub fn with_socket(mut self, position: [usize; 3], face: Face) -> Self {
+        let [x, y, z] = position;
+        let [width, height, depth] = self.size;
+        let inner_x = 0 < x && x + 1 < width;
+        let inner_z = 0 < z && z + 1 < depth;
+        let on_wall = match face {
+            Face::East => x + 1 == width && inner_z,
+            Face::West => x == 0 && inner_z,
+            Face::South => z + 1 == depth && inner_x,
+            Face::North => z == 0 && inner_x,
+            Face::Top | Face::Bottom => false,
+        };
+        assert!(
+            on_wall && 1 <= y && y + DOOR_HEIGHT < height,
+            "Socket {position:?} is not on the {face:?} wall of {}, clear of \
+             its corners, floor and ceiling",
+            self.name
+        );
+        self.sockets
+            .push(Socket { position, face });
+        self
+    }
+
+    /// Mark where an entity of `kind` should spawn
+    ///
+    /// # Panics
+    ///
+    /// Panics if `position` is outside the room.
+    pub fn with_spawn(
+        mut self,
+        position: [usize; 3],
+        kind: impl Into<String>,
+    ) -> Self {
+        self.index(position);
+        self.spawns
+            .push((position, kind.into()));
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Width, height and depth in blocks
+    pub fn size(&self) -> [usize; 3] {
+        self.size
+    }
+
+    pub fn sockets(&self) -> &[Socket] {
+        &self.sockets
+    }
+
+    /// Each spawn point's position in the room and the kind of entity
+    pub fn spawns(&self) -> impl Iterator<Item = ([usize; 3], &str)> {
+        self.spawns
+            .iter()
+            .map(|(position, kind)| (*position, kind.as_str()))
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the position is outside the room.
+    pub fn get(&self, x: usize, y: usize, z: usize) -> BlockId {
+        self.blocks[self.index([x, y, z])]
+    }
+
+    /// Set a block, returning the one it replaced
+    ///
+    /// # Panics
+    ///
+    /// Panics if the position is outside the room.
+    pub fn set(
+        &mut self,
+        x: usize,
+        y: usize,
+        z: usize,
+        id: BlockId,
+    ) -> BlockId {
+        let index = self.index([x, y, z]);
+        std::mem::replace(&mut self.blocks[index], id)
+    }
+
+    /// Set every block from `min` to `max`, inclusive
+    ///
+    /// # Panics
+    ///
+    /// Panics if the box reaches outside the room.
+    pub fn fill(&mut self, min: [usize; 3], max: [usize; 3], id: BlockId) {
+        for y in min[1]..=max[1] {
+            for z in min[2]..=max[2] {
+                for x in min[0]..=max[0] {
+                    self.set(x, y, z, id);
+                }
+            }
+        }
+    }
+
+    fn index(&self, [x, y, z]: [usize; 3]) -> usize {
+        let [width, height, depth] = self.size;
+        assert!(
+            x < width && y < height && z < depth,
+            "Block {x}, {y}, {z} is outside the room"
+        );
+        (y * depth + z) * width + x
+    }
+}
+
+/// Builds [`Dungeon`]s from a set of [`RoomPrefab`]s.
+///
+/// Starting from one room, each step picks an unused socket at random and
+/// attaches a random prefab with a matching socket, as long as the new
+/// room doesn't overlap the others. Every room is therefore reachable from
+/// the first, as long as each prefab's open space is in one piece. Once
+/// enough rooms are placed, unused sockets that happen to face each other
+/// are opened too, adding loops.
+///
+/// The same seed, prefabs and settings always give the same dungeon.
+///
+/// # Example
+/// ```
+/// use strata::voxel::{BlockId, DungeonGenerator, Face, RoomPrefab};
+///
+/// let stone = BlockId::new(1);
+/// let hall = RoomPrefab::new("hall", [7, 5, 7])
+///     .with_walls(stone)
+///     .with_socket([6, 1, 3], Face::East)
+///     .with_socket([0, 1, 3], Face::West)
+///     .with_spawn([3, 1, 3], "skeleton");
+///
+/// let dungeon = DungeonGenerator::new(vec![hall])
+///     .with_rooms(4)
+///     .generate(99, [0, -20, 0]);
+/// assert_eq!(dungeon.rooms().len(), 4);
+/// assert_eq!(dungeon.spawns().len(), 4);
+/// ```
+#[derive(Debug, Clone)]
+pub struct DungeonGenerator {
+    prefabs: Arc<[RoomPrefab]>,
+    rooms: usize,
+    start: usize,
+}
+
+impl DungeonGenerator {
+    /// # Panics
+    ///
+    /// Panics if `prefabs` is empty, or if a block a doorway would open
+    /// onto inside its room is not air.
+    pub fn new(prefabs: Vec<RoomPrefab>) -> Self {
+        assert!(!prefabs.is_empty(), "A dungeon needs at least one prefab");
+        for prefab in &prefabs {
+            for socket in &prefab.sockets {
+                // Sockets are clear of the corners, so this is in the room
+                let [x, y, z] = sub(
+                    socket.position.map(|i| i as i32),
+                    socket.face.normal(),
+                )
+                .map(|i| i as usize);
+                let clear = (0..DOOR_HEIGHT)
+                    .all(|up| prefab.get(x, y + up, z) == BlockId::AIR);
+                assert!(
+                    clear,
+                    "Socket {:?} of {} is blocked on the inside",
+                    socket.position, prefab.name
+                );
+            }
+        }
+        Self {
+            prefabs: prefabs.into(),
+            rooms: 16,
+            start: 0,
+        }
+    }
+
+    /// How many rooms to place. Fewer are placed if no more fit. Defaults
+    /// to 16.
+    pub fn with_rooms(mut self, rooms: usize) -> Self {
+        self.rooms = rooms;
+        self
+    }
+
+    /// Which prefab the dungeon grows from. Defaults to the first.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no such prefab.
+    pub fn with_start(mut self, prefab: usize) -> Self {
+        assert!(prefab < self.prefabs.len(), "No prefab {prefab}");
+        self.start = prefab;
+        self
+    }
+
+    pub fn prefabs(&self) -> &[RoomPrefab] {
+        &self.prefabs
+    }
+
+    /// Lay out a dungeon whose first room has its lowest corner at `origin`
+    pub fn generate(&self, seed: u64, origin: [i32; 3]) -> Dungeon {
+        let mut rng = Rng(seed);
+        let mut dungeon = Dungeon {
+            prefabs: self.prefabs.clone(),
+            rooms: Vec::new(),
+            doorways: Vec::new(),
+        };
+        if self.rooms == 0 {
+            return dungeon;
+        }
+        dungeon
+            .rooms
+            .push(PlacedRoom { prefab: self.start, origin });
+
+        // Sockets still free to grow from, as (room, socket)
+        let mut open: Vec<(usize, usize)> =
+            (0..self.prefabs[self.start].sockets.len())
+                .map(|socket| (0, socket))
+                .collect();
+        while dungeon.rooms.len() < self.rooms && !open.is_empty() {
+            let (room, socket) = open.remove(rng.below(open.len()));
+            let (door, face) = dungeon.socket(room, socket);
+            let target = add(door, face.normal());
+
+            let mut candidates: Vec<(usize, usize)> = self
+                .prefabs
+                .iter()
+                .enumerate()
+                .flat_map(|(prefab, room)| {
+                    room.sockets
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, other)| other.face == face.opposite())
+                        .map(move |(socket, _)| (prefab, socket))
+                })
+                .collect();
+            rng.shuffle(&mut candidates);
+
+            for (prefab, other) in candidates {
+                let position = self.prefabs[prefab].sockets[other].position;
+                let origin = sub(target, position.map(|i| i as i32));
+                if !dungeon.fits(prefab, origin) {
+                    continue;
+                }
+                let new = dungeon.rooms.len();
+                dungeon
+                    .rooms
+                    .push(PlacedRoom { prefab, origin });
+                dungeon.doorways.push(Doorway {
+                    rooms: [room, new],
+                    cells: [door, target],
+                });
+                open.extend(
+                    (0..self.prefabs[prefab].sockets.len())
+                        .filter(|&socket| socket != other)
+                        .map(|socket| (new, socket)),
+                );
+                break;
+            }
+        }
+
+        dungeon.join_facing_sockets();
+        dungeon
+    }
+}
+
+/// A room of a [`Dungeon`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlacedRoom {
+    /// Index of the room's prefab in the generator
+    pub prefab: usize,
+    /// World coordinates of the room's lowest corner
+    pub origin: [i32; 3],
+}
+
+/// An opening between two rooms
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Doorway {
+    /// Indices of the rooms in [`Dungeon::rooms`]
+    pub rooms: [usize; 2],
+    /// World coordinates of the foot of the doorway in each room's wall
+    pub cells: [[i32; 3]; 2],
+}
+
+/// Where an entity should be spawned in a generated dungeon
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpawnPoint {
+    pub kind: String,
+    /// World coordinates of the block to spawn in
+    pub position: [i32; 3],
+    /// Index of the room in [`Dungeon::rooms`]
+    pub room: usize,
+}
+
+/// A dungeon laid out by [`DungeonGenerator`], ready to write into a
+/// [`ChunkMap`]
+#[derive(Debug, Clone)]
+pub struct Dungeon {
+    prefabs: Arc<[RoomPrefab]>,
+    rooms: Vec<PlacedRoom>,
+    doorways: Vec<Doorway>,
+}
+
+impl Dungeon {
+    /// Every room, the starting room first
+    pub fn rooms(&self) -> &[PlacedRoom] {
+        &self.rooms
+    }
+
+    pub fn doorways(&self) -> &[Doorway] {
+        &self.doorways
+    }
+
+    /// The prefab a room was built from
+    pub fn prefab(&self, room: &PlacedRoom) -> &RoomPrefab {
+        &self.prefabs[room.prefab]
+    }
+
+    /// Every room's spawn points, in world coordinates
+    pub fn spawns(&self) -> Vec<SpawnPoint> {
+        self.rooms
+            .iter()
+            .enumerate()
+            .flat_map(|(index, room)| {
+                self.prefab(room)
+                    .spawns()
+                    .map(move |(position, kind)| SpawnPoint {
+                        kind: kind.to_string(),
+                        position: add(room.origin, position.map(|i| i as i32)),
+                        room: index,
+                    })
+            })
+            .collect()
+    }
+
+    /// The smallest box holding every room, as its lowest corner and the
+    /// corner just past its highest, or `None` if there are no rooms
+    pub fn bounds(&self) -> Option<([i32; 3], [i32; 3])> {
+        if self.rooms.is_empty() {
+            return None;
+        }
+        let mut min = [i32::MAX; 3];
+        let mut max = [i32::MIN; 3];
+        for room in &self.rooms {
+            let end = self.end(room);
+            for axis in 0..3 {
+                min[axis] = min[axis].min(room.origin[axis]);
+                max[axis] = max[axis].max(end[axis]);
+            }
+        }
+        Some((min, max))
+    }
+
+    /// Write every room's blocks, air included, and carve the doorways.
+    /// Chunks that aren't loaded are loaded empty first. Returns the chunks
+    /// written to, in order.
+    pub fn write(&self, map: &mut ChunkMap) -> Vec<ChunkPos> {
+        let mut touched = BTreeSet::new();
+        let mut set = |block: [i32; 3], id: BlockId| {
+            let (pos, _) = ChunkPos::from_block(block);
+            if !map.contains(pos) {
+                map.load(pos, Chunk::new());
+            }
+            map.set_block(block, id);
+            touched.insert(pos);
+        };
+
+        for room in &self.rooms {
+            let prefab = self.prefab(room);
+            let [width, height, depth] = prefab.size;
+            for y in 0..height {
+                for z in 0..depth {
+                    for x in 0..width {
+                        let offset = [x as i32, y as i32, z as i32];
+                        set(add(room.origin, offset), prefab.get(x, y, z));
+                    }
+                }
+            }
+        }
+        for doorway in &self.doorways {
+            for cell in doorway.cells {
+                for up in 0..DOOR_HEIGHT as i32 {
+                    set(add(cell, [0, up, 0]), BlockId::AIR);
+                }
+            }
+        }
+        touched.into_iter().collect()
+    }
+
+    /// World position and facing of a room's socket
+    fn socket(&self, room: usize, socket: usize) -> ([i32; 3], Face) {
+        let room = &self.rooms[room];
+        let socket = self.prefab(room).sockets[socket];
+        let position = socket.position.map(|i| i as i32);
+        (add(room.origin, position), socket.face)
+    }
+
+    /// The corner just past a room's highest block
+    fn end(&self, room: &PlacedRoom) -> [i32; 3] {
+        add(room.origin, self.prefab(room).size.map(|i| i as i32))
+    }
+
+    /// Whether `prefab` at `origin` stays clear of every placed room
+    fn fits(&self, prefab: usize, origin: [i32; 3]) -> bool {
+        let end = add(
+            origin,
+            self.prefabs[prefab]
+                .size
+                .map(|i| i as i32),
+        );
+        self.rooms.iter().all(|room| {
+            let other = self.end(room);
+            (0..3).any(|axis| {
+                end[axis] <= room.origin[axis] || other[axis] <= origin[axis]
+            })
+        })
+    }
+
+    /// Open doorways between unused sockets of neighbouring rooms that
+    /// face each other
+    fn join_facing_sockets(&mut self) {
+        let used: BTreeSet<[i32; 3]> = self
+            .doorways
+            .iter()
+            .flat_map(|doorway| doorway.cells)
+            .collect();
+        let free: Vec<(usize, [i32; 3], Face)> = (0..self.rooms.len())
+            .flat_map(|room| {
+                (0..self
+                    .prefab(&self.rooms[room])
+                    .sockets
+                    .len())
+                    .map(move |socket| (room, socket))
+            })
+            .map(|(room, socket)| {
+                let (cell, face) = self.socket(room, socket);
+                (room, cell, face)
+            })
+            .filter(|(_, cell, _)| !used.contains(cell))
+            .collect();
+
+        for (i, &(room, cell, face)) in free.iter().enumerate() {
+            let target = add(cell, face.normal());
+            let facing = free[i + 1..]
+                .iter()
+                .find(|&&(other, to, back)| {
+                    other != room && to == target && back == face.opposite()
+                });
+            if let Some(&(other, _, _)) = facing {
+                self.doorways.push(Doorway {
+                    rooms: [room, other],
+                    cells: [cell, target],
+                });
+            }
+        }
+    }
+}
+
+/// A SplitMix64 stream
+struct Rng(u64);
+
+impl Rng {
+    /// A number in `0..n`
+    fn below(&mut self, n: usize) -> usize {
+        (split_mix(&mut self.0) % n as u64) as usize
+    }
+
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.below(i + 1));
+        }
+    }
+}
+
+fn add(a: [i32; 3], b: [i32; 3]) -> [i32; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn sub(a: [i32; 3], b: [i32; 3]) -> [i32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashSet, VecDeque};
+
+    use super::*;
+
+    const STONE: BlockId = BlockId::new(1);
+    const PILLAR: BlockId = BlockId::new(2);
+
+    fn prefabs() -> Vec<RoomPrefab> {
+        let mut hall = RoomPrefab::new("hall", [9, 5, 9])
+            .with_walls(STONE)
+            .with_socket([8, 1, 4], Face::East)
+            .with_socket([0, 1, 4], Face::West)
+            .with_socket([4, 1, 8], Face::South)
+            .with_socket([4, 1, 0], Face::North)
+            .with_spawn([2, 1, 2], "skeleton");
+        hall.fill([6, 1, 6], [6, 3, 6], PILLAR);
+        vec![
+            hall,
+            RoomPrefab::new("corridor_x", [11, 4, 3])
+                .with_walls(STONE)
+                .with_socket([10, 1, 1], Face::East)
+                .with_socket([0, 1, 1], Face::West),
+            RoomPrefab::new("corridor_z", [3, 4, 11])
+                .with_walls(STONE)
+                .with_socket([1, 1, 10], Face::South)
+                .with_socket([1, 1, 0], Face::North),
+            RoomPrefab::new("crypt", [7, 4, 7])
+                .with_walls(STONE)
+                .with_socket([3, 1, 0], Face::North)
+                .with_spawn([3, 1, 5], "treasure")
+                .with_spawn([1, 1, 1], "ghost"),
+        ]
+    }
+
+    /// Air blocks reachable from `start` without leaving `bounds`
+    fn reachable(
+        map: &ChunkMap,
+        start: [i32; 3],
+        (min, max): ([i32; 3], [i32; 3]),
+    ) -> HashSet<[i32; 3]> {
+        let mut seen = HashSet::from([start]);
+        let mut queue = VecDeque::from([start]);
+        while let Some(block) = queue.pop_front() {
+            for face in Face::ALL {
+                let next = add(block, face.normal());
+                let inside =
+                    (0..3).all(|i| min[i] <= next[i] && next[i] < max[i]);
+                if inside
+                    && map.block(next) == Some(BlockId::AIR)
+                    && seen.insert(next)
+                {
+                    queue.push_back(next);
+                }
+            }
+        }
+        seen
+    }
+
+    #[test]
+    fn test_walls_enclose_the_room() {
+        let room = RoomPrefab::new("box", [4, 3, 5]).with_walls(STONE);
+        assert_eq!(room.get(0, 1, 2), STONE);
+        assert_eq!(room.get(3, 1, 2), STONE);
+        assert_eq!(room.get(1, 0, 1), STONE);
+        assert_eq!(room.get(1, 2, 1), STONE);
+        assert_eq!(room.get(2, 1, 4), STONE);
+        assert_eq!(room.get(1, 1, 1), BlockId::AIR);
+        assert_eq!(room.get(2, 1, 3), BlockId::AIR);
+    }
+
+    #[test]
+    #[should_panic(expected = "not on the East wall")]
+    fn test_socket_must_be_on_its_wall() {
+        let _ = RoomPrefab::new("box", [5, 4, 5])
+            .with_socket([3, 1, 2], Face::East);
+    }
+
+    #[test]
+    #[should_panic(expected = "not on the East wall")]
+    fn test_socket_cannot_be_in_a_corner() {
+        let _ = RoomPrefab::new("box", [5, 4, 5])
+            .with_socket([4, 1, 0], Face::East);
+    }
+
+    #[test]
+    #[should_panic(expected = "not on the North wall")]
+    fn test_socket_cannot_be_in_the_far_corner() {
+        let _ = RoomPrefab::new("box", [5, 4, 5])
+            .with_socket([4, 1, 0], Face::North);
+    }
+
+    #[test]
+    #[should_panic(expected = "not on the Top wall")]
+    fn test_socket_cannot_be_in_the_ceiling() {
+        let _ =
+            RoomPrefab::new("box", [5, 4, 5]).with_socket([2, 3, 2], Face::Top);
+    }
+
+    #[test]
+    #[should_panic(expected = "not on the West wall")]
+    fn test_socket_cannot_be_in_the_floor() {
+        let _ = RoomPrefab::new("box", [5, 4, 5])
+            .with_socket([0, 0, 2], Face::West);
+    }
+
+    #[test]
+    #[should_panic(expected = "not on the West wall")]
+    fn test_socket_doorway_cannot_reach_the_ceiling() {
+        let _ = RoomPrefab::new("box", [5, 4, 5])
+            .with_socket([0, 2, 2], Face::West);
+    }
+
+    #[test]
+    #[should_panic(expected = "blocked on the inside")]
+    fn test_socket_must_open_onto_air() {
+        let mut room = RoomPrefab::new("box", [5, 4, 5])
+            .with_walls(STONE)
+            .with_socket([0, 1, 2], Face::West);
+        room.set(1, 2, 2, PILLAR);
+        let _ = DungeonGenerator::new(vec![room]);
+    }
+
+    #[test]
+    fn test_empty_dungeon_has_no_bounds() {
+        let dungeon = DungeonGenerator::new(prefabs())
+            .with_rooms(0)
+            .generate(0, [0, 0, 0]);
+        assert!(dungeon.rooms().is_empty());
+        assert_eq!(dungeon.bounds(), None);
+    }
+
+    #[test]
+    fn test_same_seed_same_dungeon() {
+        let generator = DungeonGenerator::new(prefabs()).with_rooms(20);
+        let a = generator.generate(7, [0, 0, 0]);
+        let b = generator.generate(7, [0, 0, 0]);
+        assert_eq!(a.rooms(), b.rooms());
+        assert_eq!(a.doorways(), b.doorways());
+        assert_eq!(a.spawns(), b.spawns());
+
+        let c = generator.generate(8, [0, 0, 0]);
+        assert_ne!(a.rooms(), c.rooms());
+    }
+
+    #[test]
+    fn test_rooms_do_not_overlap() {
+        let dungeon = DungeonGenerator::new(prefabs())
+            .with_rooms(30)
+            .generate(3, [5, -40, 5]);
+        assert_eq!(dungeon.rooms().len(), 30);
+        assert_eq!(dungeon.rooms()[0].origin, [5, -40, 5]);
+
+        let boxes: Vec<_> = dungeon
+            .rooms()
+            .iter()
+            .map(|room| (room.origin, dungeon.end(room)))
+            .collect();
+        for (i, a) in boxes.iter().enumerate() {
+            for b in &boxes[i + 1..] {
+                let apart = (0..3).any(|axis| {
+                    a.1[axis] <= b.0[axis] || b.1[axis] <= a.0[axis]
+                });
+                assert!(apart, "{a:?} overlaps {b:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_every_room_is_reachable() {
+        for seed in 0..8 {
+            let dungeon = DungeonGenerator::new(prefabs())
+                .with_rooms(25)
+                .generate(seed, [-20, 10, 30]);
+            assert!(dungeon.doorways().len() >= dungeon.rooms().len() - 1);
+
+            let mut map = ChunkMap::new();
+            dungeon.write(&mut map);
+            let start = add(dungeon.rooms()[0].origin, [1, 1, 1]);
+            let reached = reachable(&map, start, dungeon.bounds().unwrap());
+            for room in dungeon.rooms() {
+                let inside = add(room.origin, [1, 1, 1]);
+                assert!(reached.contains(&inside), "seed {seed}: {room:?}");
+            }
+            for spawn in dungeon.spawns() {
+                assert!(reached.contains(&spawn.position), "{spawn:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_spawn_points_are_placed_in_the_world() {
+        let dungeon = DungeonGenerator::new(prefabs())
+            .with_rooms(12)
+            .generate(21, [0, 0, 0]);
+        let spawns = dungeon.spawns();
+        let expected: usize = dungeon
+            .rooms()
+            .iter()
+            .map(|room| dungeon.prefab(room).spawns().count())
+            .sum();
+        assert_eq!(spawns.len(), expected);
+
+        for spawn in &spawns {
+            let room = &dungeon.rooms()[spawn.room];
+            let local = sub(spawn.position, room.origin);
+            let kinds: Vec<_> = dungeon
+                .prefab(room)
+                .spawns()
+                .filter(|(position, _)| position.map(|i| i as i32) == local)
+                .map(|(_, kind)| kind)
+                .collect();
+            assert_eq!(kinds, [spawn.kind.as_str()]);
+        }
+    }
+
+    #[test]
+    fn test_write_carves_into_loaded_chunks() {
+        let mut map = ChunkMap::new();
+        map.load(ChunkPos::new(0, 0, 0), Chunk::filled(PILLAR));
+        let dungeon = DungeonGenerator::new(prefabs())
+            .with_rooms(6)
+            .generate(1, [10, 10, 10]);
+        let touched = dungeon.write(&mut map);
+
+        assert!(touched.contains(&ChunkPos::new(0, 0, 0)));
+        assert!(
+            touched
+                .windows(2)
+                .all(|pair| pair[0] < pair[1])
+        );
+        assert_eq!(map.len(), touched.len());
+        assert_eq!(map.block([10, 10, 10]), Some(STONE));
+        assert_eq!(map.block([11, 11, 11]), Some(BlockId::AIR));
+        // Hall pillar
+        assert_eq!(map.block([16, 11, 16]), Some(PILLAR));
+        for doorway in dungeon.doorways() {
+            for cell in doorway.cells {
+                assert_eq!(map.block(cell), Some(BlockId::AIR));
+                assert_eq!(map.block(add(cell, [0, 1, 0])), Some(BlockId::AIR));
+                assert_eq!(map.block(add(cell, [0, 2, 0])), Some(STONE));
+            }
+        }
+    }
+
+    #[test]
+    fn test_dead_ends_stop_growth() {
+        let dungeon = DungeonGenerator::new(prefabs())
+            .with_start(3)
+            .with_rooms(10)
+            .generate(0, [0, 0, 0]);
+        // Growth continues through the crypt's one socket
+        assert!(dungeon.rooms().len() > 1);
+        assert_eq!(
+            dungeon
+                .prefab(&dungeon.rooms()[0])
+                .name(),
+            "crypt"
+        );
+
+        let lone = DungeonGenerator::new(vec![prefabs().remove(3)])
+            .generate(0, [0, 0, 0]);
+        assert_eq!(lone.rooms().len(), 1);
+        assert!(lone.doorways().is_empty());
+    }
+}
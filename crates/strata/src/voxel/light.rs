@@ -0,0 +1,728 @@
+//! Per-block light levels and their propagation
+
+use std::collections::{HashSet, VecDeque};
+
+use super::chunk::{BlockId, CHUNK_SIZE, Chunk};
+use super::chunk_map::{ChunkMap, ChunkPos};
+use super::registry::{BlockRegistry, Face};
+
+/// Brightest light a block can emit or receive
+pub const MAX_LIGHT: u8 = 15;
+
+/// Light reaching a block on two channels from 0 to [`MAX_LIGHT`]: block
+/// light from emitters such as torches, and sky light from above. Packed
+/// into a byte, block light in the low nibble.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Light(u8);
+
+impl Light {
+    /// No light on either channel
+    pub const DARK: Light = Light(0);
+
+    /// Open sky: full sky light and no block light
+    pub const SKY: Light = Light(MAX_LIGHT << 4);
+
+    /// # Panics
+    ///
+    /// Panics in debug builds if either level exceeds [`MAX_LIGHT`].
+    pub const fn new(block: u8, sky: u8) -> Self {
+        debug_assert!(block <= MAX_LIGHT && sky <= MAX_LIGHT);
+        Self(block | sky << 4)
+    }
+
+    pub const fn block(self) -> u8 {
+        self.0 & 0xf
+    }
+
+    pub const fn sky(self) -> u8 {
+        self.0 >> 4
+    }
+
+    pub const fn with_block(self, block: u8) -> Self {
+        Self::new(block, self.sky())
+    }
+
+    pub const fn with_sky(self, sky: u8) -> Self {
+        Self::new(self.block(), sky)
+    }
+
+    /// Both channels packed, as stored in chunks and vertices
+    pub const fn to_bits(self) -> u8 {
+        self.0
+    }
+
+    pub const fn from_bits(bits: u8) -> Self {
+        Self(bits)
+    }
+}
+
+/// One of the two independent light channels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Channel {
+    Block,
+    Sky,
+}
+
+impl Channel {
+    const ALL: [Channel; 2] = [Channel::Block, Channel::Sky];
+
+    fn get(self, light: Light) -> u8 {
+        match self {
+            Channel::Block => light.block(),
+            Channel::Sky => light.sky(),
+        }
+    }
+
+    fn set(self, light: Light, level: u8) -> Light {
+        match self {
+            Channel::Block => light.with_block(level),
+            Channel::Sky => light.with_sky(level),
+        }
+    }
+
+    /// The level light at `level` reaches the next block with when it
+    /// travels towards `face`. Full sky light falls without fading.
+    fn spread(self, level: u8, face: Face) -> u8 {
+        if self == Channel::Sky && face == Face::Bottom && level == MAX_LIGHT {
+            MAX_LIGHT
+        } else {
+            level.saturating_sub(1)
+        }
+    }
+}
+
+/// Keeps the [`Light`] stored in a [`ChunkMap`]'s chunks up to date.
+///
+/// Light floods outwards one block at a time, losing a level per block,
+/// through every block that is not opaque, and crosses into neighbouring
+/// chunks that are loaded. Block light starts at emitters; sky light
+/// starts at full strength in blocks open to the sky and falls straight
+/// down without fading. A column whose chunk above is not loaded counts as
+/// open to the sky.
+///
+/// Light is computed for a whole chunk when it is loaded with
+/// [`LightEngine::light_chunk`], and kept current as blocks change with
+/// [`LightEngine::set_block`], which removes the light that depended on
+/// the old block before refilling from what remains. Chunks should be
+/// unloaded with [`LightEngine::unload_chunk`] so the light they carried
+/// into their neighbours goes with them. All three record which chunks
+/// need remeshing in [`LightEngine::take_dirty`].
+#[derive(Debug, Default)]
+pub struct LightEngine {
+    /// Blocks whose light should spread to their neighbours
+    additions: VecDeque<[i32; 3]>,
+    /// Blocks just darkened, with the level they had
+    removals: VecDeque<([i32; 3], u8)>,
+    dirty: HashSet<ChunkPos>,
+}
+
+impl LightEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compute the light of the chunk at `pos`, which should have just been
+    /// loaded, and carry light across its faces both ways. Does nothing if
+    /// the chunk is not loaded.
+    pub fn light_chunk(
+        &mut self,
+        map: &mut ChunkMap,
+        registry: &BlockRegistry,
+        pos: ChunkPos,
+    ) {
+        let Some(chunk) = map.get_mut(pos) else {
+            return;
+        };
+        chunk.fill_light(Light::DARK);
+        self.dirty.insert(pos);
+        for face in Face::ALL {
+            let [dx, dy, dz] = face.normal();
+            let neighbour = pos.offset(dx, dy, dz);
+            if map.contains(neighbour) {
+                self.dirty.insert(neighbour);
+            }
+        }
+
+        let origin = pos.origin();
+        let size = CHUNK_SIZE as i32;
+        let above = map.get(pos.offset(0, 1, 0));
+        let mut skylit = Vec::new();
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                let open = above.is_none_or(|above| {
+                    above.light(x, 0, z).sky() == MAX_LIGHT
+                });
+                if !open {
+                    continue;
+                }
+                for y in (0..CHUNK_SIZE).rev() {
+                    let block = map.get(pos).unwrap().get(x, y, z);
+                    if registry.is_opaque(block) {
+                        break;
+                    }
+                    skylit.push([x, y, z]);
+                }
+            }
+        }
+
+        let chunk = map.get_mut(pos).unwrap();
+        for &[x, y, z] in &skylit {
+            chunk.set_light(x, y, z, Light::SKY);
+        }
+        let local_to_world =
+            |local: [usize; 3]| add(origin, local.map(|v| v as i32));
+        let emitters: Vec<_> = chunk
+            .iter()
+            .filter(|&(_, block)| registry.light(block) > 0)
+            .collect();
+        for &([x, y, z], block) in &emitters {
+            let light = chunk.light(x, y, z);
+            chunk.set_light(x, y, z, light.with_block(registry.light(block)));
+        }
+
+        for channel in Channel::ALL {
+            match channel {
+                Channel::Block => self.additions.extend(
+                    emitters
+                        .iter()
+                        .map(|&(local, _)| local_to_world(local)),
+                ),
+                Channel::Sky => self.additions.extend(
+                    skylit
+                        .iter()
+                        .map(|&local| local_to_world(local)),
+                ),
+            }
+
+            // Pull light in from the neighbours' facing layers
+            for face in Face::ALL {
+                let normal = face.normal();
+                let axis = normal
+                    .iter()
+                    .position(|&v| v != 0)
+                    .unwrap();
+                let layer = if normal[axis] > 0 { size } else { -1 };
+                for a in 0..size {
+                    for b in 0..size {
+                        let mut block = [0; 3];
+                        block[axis] = layer;
+                        block[(axis + 1) % 3] = a;
+                        block[(axis + 2) % 3] = b;
+                        let block = add(origin, block);
+                        if map
+                            .light(block)
+                            .is_some_and(|light| channel.get(light) > 0)
+                        {
+                            self.additions.push_back(block);
+                        }
+                    }
+                }
+            }
+
+            // Columns below that assumed open sky now have this chunk
+            // above them
+            if channel == Channel::Sky && map.contains(pos.offset(0, -1, 0)) {
+                for x in 0..size {
+                    for z in 0..size {
+                        let top = add(origin, [x, 0, z]);
+                        let below = add(origin, [x, -1, z]);
+                        let light = map.light(below).unwrap();
+                        if map.light(top).unwrap().sky() < MAX_LIGHT
+                            && light.sky() == MAX_LIGHT
+                        {
+                            self.darken(map, channel, below, light);
+                        }
+                    }
+                }
+            }
+
+            self.propagate(map, registry, channel);
+        }
+    }
+
+    /// Unload the chunk at `pos` from `map` and remove the light it carried
+    /// across its faces, refilling the neighbours from what remains.
+    /// Columns below it count as open to the sky again. Returns the chunk,
+    /// or `None` if it was not loaded.
+    pub fn unload_chunk(
+        &mut self,
+        map: &mut ChunkMap,
+        registry: &BlockRegistry,
+        pos: ChunkPos,
+    ) -> Option<Chunk> {
+        let chunk = map.unload(pos)?;
+        self.dirty.remove(&pos);
+
+        let origin = pos.origin();
+        let size = CHUNK_SIZE as i32;
+        for channel in Channel::ALL {
+            // Light in the faces' layers is all that reached the neighbours
+            for face in Face::ALL {
+                let normal = face.normal();
+                let [dx, dy, dz] = normal;
+                let neighbour = pos.offset(dx, dy, dz);
+                if !map.contains(neighbour) {
+                    continue;
+                }
+                self.dirty.insert(neighbour);
+                let axis = normal
+                    .iter()
+                    .position(|&v| v != 0)
+                    .unwrap();
+                let layer = if normal[axis] > 0 { size - 1 } else { 0 };
+                for a in 0..size {
+                    for b in 0..size {
+                        let mut local = [0; 3];
+                        local[axis] = layer;
+                        local[(axis + 1) % 3] = a;
+                        local[(axis + 2) % 3] = b;
+                        let [x, y, z] = local.map(|v| v as usize);
+                        let level = channel.get(chunk.light(x, y, z));
+                        if level > 0 {
+                            self.removals
+                                .push_back((add(origin, local), level));
+                        }
+                    }
+                }
+            }
+            self.remove_light(map, registry, channel);
+
+            if channel == Channel::Sky && map.contains(pos.offset(0, -1, 0)) {
+                for x in 0..size {
+                    for z in 0..size {
+                        let top = add(origin, [x, -1, z]);
+                        let light = map.light(top).unwrap();
+                        if light.sky() < MAX_LIGHT
+                            && !registry.is_opaque(map.block(top).unwrap())
+                        {
+                            map.set_light(top, light.with_sky(MAX_LIGHT));
+                            self.mark(map, top);
+                            self.additions.push_back(top);
+                        }
+                    }
+                }
+            }
+            self.add_light(map, registry, channel);
+        }
+        Some(chunk)
+    }
+
+    /// Set the block at world coordinates and update the light around it,
+    /// returning the block it replaced, or `None` without changing anything
+    /// if its chunk is not loaded
+    pub fn set_block(
+        &mut self,
+        map: &mut ChunkMap,
+        registry: &BlockRegistry,
+        block: [i32; 3],
+        id: BlockId,
+    ) -> Option<BlockId> {
+        let old = map.set_block(block, id)?;
+        self.mark(map, block);
+        if old == id {
+            return Some(old);
+        }
+
+        let opacity_changed = registry.is_opaque(old) != registry.is_opaque(id);
+        for channel in Channel::ALL {
+            // Sky light only cares about opacity, block light also about
+            // emission
+            if !opacity_changed
+                && (channel == Channel::Sky
+                    || registry.light(old) == registry.light(id))
+            {
+                continue;
+            }
+            let light = map.light(block).unwrap();
+            if channel.get(light) > 0 {
+                self.darken(map, channel, block, light);
+            }
+            self.remove_light(map, registry, channel);
+
+            if channel == Channel::Block && registry.light(id) > 0 {
+                let light = map.light(block).unwrap();
+                map.set_light(block, light.with_block(registry.light(id)));
+                self.additions.push_back(block);
+            }
+            if !registry.is_opaque(id) {
+                // Let the neighbours shine back in
+                for face in Face::ALL {
+                    let neighbour = add(block, face.normal());
+                    if map
+                        .light(neighbour)
+                        .is_some_and(|light| channel.get(light) > 0)
+                    {
+                        self.additions.push_back(neighbour);
+                    }
+                }
+                let (pos, _) = ChunkPos::from_block(block);
+                if channel == Channel::Sky
+                    && !map.contains(pos.offset(0, 1, 0))
+                    && (block[1] + 1).rem_euclid(CHUNK_SIZE as i32) == 0
+                {
+                    let light = map.light(block).unwrap();
+                    map.set_light(block, light.with_sky(MAX_LIGHT));
+                    self.additions.push_back(block);
+                }
+            }
+            self.add_light(map, registry, channel);
+        }
+        Some(old)
+    }
+
+    /// Chunks whose blocks or light changed since the last call, or that
+    /// border such a change, in order. Remesh these.
+    pub fn take_dirty(&mut self) -> Vec<ChunkPos> {
+        let mut dirty: Vec<_> = self.dirty.drain().collect();
+        dirty.sort();
+        dirty
+    }
+
+    /// Set `channel` to zero at `block`, queueing the light it had for
+    /// removal
+    fn darken(
+        &mut self,
+        map: &mut ChunkMap,
+        channel: Channel,
+        block: [i32; 3],
+        light: Light,
+    ) {
+        map.set_light(block, channel.set(light, 0));
+        self.removals
+            .push_back((block, channel.get(light)));
+        self.mark(map, block);
+    }
+
+    /// Run removals and then the additions they uncover
+    fn propagate(
+        &mut self,
+        map: &mut ChunkMap,
+        registry: &BlockRegistry,
+        channel: Channel,
+    ) {
+        self.remove_light(map, registry, channel);
+        self.add_light(map, registry, channel);
+    }
+
+    /// Darken every block lit through the queued removals. Blocks lit from
+    /// elsewhere are queued to spread back into the darkened area.
+    fn remove_light(
+        &mut self,
+        map: &mut ChunkMap,
+        registry: &BlockRegistry,
+        channel: Channel,
+    ) {
+        while let Some((block, level)) = self.removals.pop_front() {
+            for face in Face::ALL {
+                let neighbour = add(block, face.normal());
+                let Some(light) = map.light(neighbour) else {
+                    continue;
+                };
+                let neighbour_level = channel.get(light);
+                if neighbour_level == 0 {
+                    continue;
+                }
+                // Dimmer neighbours, and full sky light falling from here,
+                // were lit through this block
+                let lit_from_here = neighbour_level < level
+                    || channel.spread(level, face) == MAX_LIGHT
+                        && neighbour_level == MAX_LIGHT;
+                if lit_from_here {
+                    self.darken(map, channel, neighbour, light);
+                    let emitted = map
+                        .block(neighbour)
+                        .map_or(0, |id| registry.light(id));
+                    if channel == Channel::Block && emitted > 0 {
+                        map.set_light(neighbour, light.with_block(emitted));
+                        self.additions.push_back(neighbour);
+                    }
+                } else {
+                    self.additions.push_back(neighbour);
+                }
+            }
+        }
+    }
+
+    /// Spread light outwards from the queued additions
+    fn add_light(
+        &mut self,
+        map: &mut ChunkMap,
+        registry: &BlockRegistry,
+        channel: Channel,
+    ) {
+        while let Some(block) = self.additions.pop_front() {
+            let Some(light) = map.light(block) else {
+                continue;
+            };
+            let level = channel.get(light);
+            for face in Face::ALL {
+                let next = channel.spread(level, face);
+                if next == 0 {
+                    continue;
+                }
+                let neighbour = add(block, face.normal());
+                let Some(id) = map.block(neighbour) else {
+                    continue;
+                };
+                let light = map.light(neighbour).unwrap();
+                if registry.is_opaque(id) || channel.get(light) >= next {
+                    continue;
+                }
+                map.set_light(neighbour, channel.set(light, next));
+                self.mark(map, neighbour);
+                self.additions.push_back(neighbour);
+            }
+        }
+    }
+
+    /// Mark the chunk holding `block` dirty, and any neighbour whose mesh
+    /// reads it because it lies on their shared face
+    fn mark(&mut self, map: &ChunkMap, block: [i32; 3]) {
+        let (pos, local) = ChunkPos::from_block(block);
+        self.dirty.insert(pos);
+        for axis in 0..3 {
+            let mut offset = [0; 3];
+            if local[axis] == 0 {
+                offset[axis] = -1;
+            } else if local[axis] == CHUNK_SIZE - 1 {
+                offset[axis] = 1;
+            } else {
+                continue;
+            }
+            let neighbour = pos.offset(offset[0], offset[1], offset[2]);
+            if map.contains(neighbour) {
+                self.dirty.insert(neighbour);
+            }
+        }
+    }
+}
+
+fn add(a: [i32; 3], b: [i32; 3]) -> [i32; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel::Chunk;
+
+    const BLOCKS: &str = r#"
+[[block]]
+name = "stone"
+id = 1
+
+[[block]]
+name = "torch"
+id = 2
+solid = false
+light = 14
+
+[[block]]
+name = "glass"
+id = 3
+opaque = false
+"#;
+
+    const STONE: BlockId = BlockId::new(1);
+    const TORCH: BlockId = BlockId::new(2);
+    const GLASS: BlockId = BlockId::new(3);
+
+    /// Chunks of `fill` at each position, loaded and lit in order
+    fn world(
+        chunks: &[(ChunkPos, BlockId)],
+    ) -> (ChunkMap, BlockRegistry, LightEngine) {
+        let registry = BlockRegistry::from_toml(BLOCKS, "blocks.toml")
+            .expect("Valid blocks");
+        let mut map = ChunkMap::new();
+        let mut engine = LightEngine::new();
+        for &(pos, fill) in chunks {
+            map.load(pos, Chunk::filled(fill));
+            engine.light_chunk(&mut map, &registry, pos);
+        }
+        engine.take_dirty();
+        (map, registry, engine)
+    }
+
+    fn block_light(map: &ChunkMap, block: [i32; 3]) -> u8 {
+        map.light(block).unwrap().block()
+    }
+
+    fn sky_light(map: &ChunkMap, block: [i32; 3]) -> u8 {
+        map.light(block).unwrap().sky()
+    }
+
+    #[test]
+    fn test_channels_are_independent() {
+        let light = Light::new(3, 12);
+        assert_eq!((light.block(), light.sky()), (3, 12));
+        assert_eq!(light.with_block(15).sky(), 12);
+        assert_eq!(light.with_sky(0).block(), 3);
+        assert_eq!(Light::from_bits(light.to_bits()), light);
+        assert_eq!(Light::SKY, Light::new(0, MAX_LIGHT));
+    }
+
+    #[test]
+    fn test_open_chunk_is_sky_lit() {
+        let (map, _, _) = world(&[(ChunkPos::new(0, 0, 0), BlockId::AIR)]);
+        assert_eq!(map.light([0, 0, 0]), Some(Light::SKY));
+        assert_eq!(map.light([31, 17, 4]), Some(Light::SKY));
+    }
+
+    #[test]
+    fn test_torch_light_fades_with_distance() {
+        let (mut map, registry, mut engine) =
+            world(&[(ChunkPos::new(0, 0, 0), BlockId::AIR)]);
+        engine.set_block(&mut map, &registry, [16, 16, 16], TORCH);
+
+        assert_eq!(block_light(&map, [16, 16, 16]), 14);
+        assert_eq!(block_light(&map, [19, 16, 16]), 11);
+        assert_eq!(block_light(&map, [14, 18, 16]), 10);
+        assert_eq!(block_light(&map, [16, 16, 30]), 0);
+        // Open air keeps its sky light around the torch
+        assert_eq!(sky_light(&map, [16, 16, 16]), MAX_LIGHT);
+    }
+
+    #[test]
+    fn test_opaque_blocks_stop_light() {
+        let (mut map, registry, mut engine) =
+            world(&[(ChunkPos::new(0, 0, 0), STONE)]);
+        for x in 4..8 {
+            engine.set_block(&mut map, &registry, [x, 4, 4], BlockId::AIR);
+        }
+        engine.set_block(&mut map, &registry, [4, 4, 4], TORCH);
+        engine.set_block(&mut map, &registry, [6, 4, 4], GLASS);
+
+        assert_eq!(block_light(&map, [5, 4, 4]), 13);
+        // Glass lets light through; the stone around the tunnel does not
+        assert_eq!(block_light(&map, [6, 4, 4]), 12);
+        assert_eq!(block_light(&map, [7, 4, 4]), 11);
+        assert_eq!(block_light(&map, [5, 5, 4]), 0);
+        assert_eq!(sky_light(&map, [5, 4, 4]), 0);
+    }
+
+    #[test]
+    fn test_light_crosses_chunk_faces() {
+        let (mut map, registry, mut engine) = world(&[
+            (ChunkPos::new(0, 0, 0), BlockId::AIR),
+            (ChunkPos::new(1, 0, 0), BlockId::AIR),
+        ]);
+        engine.set_block(&mut map, &registry, [30, 5, 5], TORCH);
+
+        assert_eq!(block_light(&map, [33, 5, 5]), 11);
+        assert_eq!(
+            engine.take_dirty(),
+            [ChunkPos::new(0, 0, 0), ChunkPos::new(1, 0, 0)]
+        );
+
+        // A chunk loaded later pulls the light in from its neighbour
+        map.load(ChunkPos::new(0, 0, -1), Chunk::new());
+        engine.light_chunk(&mut map, &registry, ChunkPos::new(0, 0, -1));
+        assert_eq!(block_light(&map, [30, 5, -1]), 8);
+    }
+
+    #[test]
+    fn test_unloading_a_chunk_takes_its_light_with_it() {
+        let a = ChunkPos::new(0, 0, 0);
+        let (mut map, registry, mut engine) =
+            world(&[(a, BlockId::AIR), (ChunkPos::new(1, 0, 0), BlockId::AIR)]);
+        engine.set_block(&mut map, &registry, [29, 5, 5], TORCH);
+        assert_eq!(block_light(&map, [31, 5, 5]), 12);
+        assert_eq!(block_light(&map, [33, 5, 5]), 10);
+
+        let mut chunk = engine
+            .unload_chunk(&mut map, &registry, a)
+            .unwrap();
+        assert_eq!(block_light(&map, [33, 5, 5]), 0);
+        assert_eq!(engine.take_dirty(), [ChunkPos::new(1, 0, 0)]);
+
+        // Reloaded without the torch, nothing is left to pull back in
+        chunk.set(29, 5, 5, BlockId::AIR);
+        map.load(a, chunk);
+        engine.light_chunk(&mut map, &registry, a);
+        assert_eq!(block_light(&map, [31, 5, 5]), 0);
+        assert_eq!(block_light(&map, [33, 5, 5]), 0);
+    }
+
+    #[test]
+    fn test_unloading_a_chunk_above_opens_the_sky() {
+        let (mut map, registry, mut engine) = world(&[
+            (ChunkPos::new(0, 0, 0), BlockId::AIR),
+            (ChunkPos::new(0, 1, 0), STONE),
+        ]);
+        assert_eq!(sky_light(&map, [5, 0, 5]), 0);
+
+        engine.unload_chunk(&mut map, &registry, ChunkPos::new(0, 1, 0));
+        assert_eq!(sky_light(&map, [5, 31, 5]), MAX_LIGHT);
+        assert_eq!(sky_light(&map, [5, 0, 5]), MAX_LIGHT);
+    }
+
+    #[test]
+    fn test_removing_light_keeps_other_sources() {
+        let (mut map, registry, mut engine) =
+            world(&[(ChunkPos::new(0, 0, 0), BlockId::AIR)]);
+        engine.set_block(&mut map, &registry, [8, 8, 8], TORCH);
+        engine.set_block(&mut map, &registry, [14, 8, 8], TORCH);
+        assert_eq!(block_light(&map, [11, 8, 8]), 11);
+
+        engine.set_block(&mut map, &registry, [8, 8, 8], BlockId::AIR);
+        assert_eq!(block_light(&map, [8, 8, 8]), 8);
+        assert_eq!(block_light(&map, [11, 8, 8]), 11);
+        assert_eq!(block_light(&map, [2, 8, 8]), 2);
+
+        engine.set_block(&mut map, &registry, [14, 8, 8], BlockId::AIR);
+        let lit = (0..32)
+            .flat_map(|x| (0..32).map(move |y| [x, y, 8]))
+            .filter(|&block| block_light(&map, block) > 0)
+            .count();
+        assert_eq!(lit, 0);
+    }
+
+    #[test]
+    fn test_sky_light_spreads_under_a_roof() {
+        let (mut map, registry, mut engine) =
+            world(&[(ChunkPos::new(0, 0, 0), BlockId::AIR)]);
+        for x in 10..13 {
+            for z in 10..13 {
+                engine.set_block(&mut map, &registry, [x, 20, z], STONE);
+            }
+        }
+
+        assert_eq!(sky_light(&map, [11, 21, 11]), MAX_LIGHT);
+        assert_eq!(sky_light(&map, [11, 19, 11]), 13);
+        assert_eq!(sky_light(&map, [10, 2, 11]), 14);
+        assert_eq!(sky_light(&map, [11, 2, 11]), 13);
+
+        engine.set_block(&mut map, &registry, [11, 20, 11], BlockId::AIR);
+        assert_eq!(sky_light(&map, [11, 2, 11]), MAX_LIGHT);
+    }
+
+    #[test]
+    fn test_loading_a_chunk_above_shades_below() {
+        let (mut map, registry, mut engine) =
+            world(&[(ChunkPos::new(0, 0, 0), BlockId::AIR)]);
+        map.load(ChunkPos::new(0, 1, 0), Chunk::filled(STONE));
+        engine.light_chunk(&mut map, &registry, ChunkPos::new(0, 1, 0));
+
+        assert_eq!(sky_light(&map, [5, 31, 5]), 0);
+        assert_eq!(sky_light(&map, [5, 0, 5]), 0);
+        assert_eq!(map.light([5, 32, 5]), Some(Light::DARK));
+    }
+
+    #[test]
+    fn test_only_affected_chunks_are_dirty() {
+        let (mut map, registry, mut engine) = world(&[
+            (ChunkPos::new(-1, 0, 0), BlockId::AIR),
+            (ChunkPos::new(0, 0, 0), BlockId::AIR),
+            (ChunkPos::new(1, 0, 0), BlockId::AIR),
+        ]);
+        engine.set_block(&mut map, &registry, [16, 16, 16], TORCH);
+        assert_eq!(engine.take_dirty(), [ChunkPos::new(0, 0, 0)]);
+
+        engine.set_block(&mut map, &registry, [0, 16, 16], STONE);
+        assert_eq!(
+            engine.take_dirty(),
+            [ChunkPos::new(-1, 0, 0), ChunkPos::new(0, 0, 0)]
+        );
+        assert!(engine.take_dirty().is_empty());
+    }
+}
@@ -0,0 +1,717 @@
+//! Greedy meshing of chunks into compact vertices
+
+use ash::vk;
+
+use super::chunk::{BlockId, CHUNK_SIZE, Chunk};
+use super::light::Light;
+use super::registry::{BlockRegistry, Face};
+
+/// Chunk size with a one-block border from each neighbour
+const PADDED: usize = CHUNK_SIZE + 2;
+
+/// Axes along a face's normal and across it as `(normal, u, v)`. On the
+/// sides, v runs up the world y axis so textures stand upright.
+const fn face_axes(face: Face) -> (usize, usize, usize) {
+    match face {
+        Face::East | Face::West => (0, 2, 1),
+        Face::Top | Face::Bottom => (1, 0, 2),
+        Face::South | Face::North => (2, 0, 1),
+    }
+}
+
+/// A chunk mesh vertex packed into 8 bytes.
+///
+/// The first word holds the corner position within the chunk (6 bits per
+/// axis, 0 to `CHUNK_SIZE` inclusive), the [`Face`] index (3 bits), the
+/// ambient occlusion level (2 bits) and the [`Light`] (8 bits, as
+/// [`Light::to_bits`]); the second holds the texture array layer (16 bits)
+/// and the texture coordinate (6 bits per axis), which counts blocks
+/// across the quad so textures tile once per block.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChunkVertex {
+    data: [u32; 2],
+}
+
+impl ChunkVertex {
+    /// Bytes between consecutive vertices, for [`crate::PipelineDesc`]
+    pub const STRIDE: u32 = size_of::<Self>() as u32;
+
+    /// Binding 0 attributes: both words as a `uvec2` at location 0
+    pub const ATTRIBUTES: [vk::VertexInputAttributeDescription; 1] =
+        [vk::VertexInputAttributeDescription {
+            location: 0,
+            binding: 0,
+            format: vk::Format::R32G32_UINT,
+            offset: 0,
+        }];
+
+    /// # Panics
+    ///
+    /// Panics in debug builds if a position or texture coordinate exceeds
+    /// `CHUNK_SIZE`, or `ao` exceeds 3.
+    pub fn new(
+        position: [u8; 3],
+        face: Face,
+        ao: u8,
+        light: Light,
+        uv: [u8; 2],
+        layer: u16,
+    ) -> Self {
+        debug_assert!(
+            position
+                .iter()
+                .chain(&uv)
+                .all(|&v| v as usize <= CHUNK_SIZE),
+            "vertex {position:?} {uv:?} is outside the chunk"
+        );
+        debug_assert!(ao <= 3, "ambient occlusion level {ao} is above 3");
+
+        let [x, y, z] = position.map(u32::from);
+        let [u, v] = uv.map(u32::from);
+        Self {
+            data: [
+                x | y << 6
+                    | z << 12
+                    | (face.index() as u32) << 18
+                    | (ao as u32) << 21
+                    | (light.to_bits() as u32) << 23,
+                layer as u32 | u << 16 | v << 22,
+            ],
+        }
+    }
+
+    pub fn position(self) -> [u8; 3] {
+        [0, 6, 12].map(|shift| (self.data[0] >> shift & 0x3f) as u8)
+    }
+
+    pub fn face(self) -> Face {
+        Face::ALL[(self.data[0] >> 18 & 0x7) as usize]
+    }
+
+    /// Ambient occlusion from 0, fully occluded, to 3, unoccluded
+    pub fn ao(self) -> u8 {
+        (self.data[0] >> 21 & 0x3) as u8
+    }
+
+    /// Light averaged over the blocks in front of the vertex
+    pub fn light(self) -> Light {
+        Light::from_bits((self.data[0] >> 23) as u8)
+    }
+
+    pub fn uv(self) -> [u8; 2] {
+        [16, 22].map(|shift| (self.data[1] >> shift & 0x3f) as u8)
+    }
+
+    /// Texture array layer
+    pub fn layer(self) -> u16 {
+        self.data[1] as u16
+    }
+}
+
+/// Vertices and triangle-list indices for one chunk, ready for
+/// [`crate::Renderer::create_mesh`]. Positions are relative to the chunk's
+/// origin, so draw it with a translation to [`super::ChunkPos::origin`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChunkMesh {
+    vertices: Vec<ChunkVertex>,
+    indices: Vec<u32>,
+}
+
+impl ChunkMesh {
+    pub fn vertices(&self) -> &[ChunkVertex] {
+        &self.vertices
+    }
+
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    /// Quads in the mesh, four vertices each
+    pub fn quad_count(&self) -> usize {
+        self.vertices.len() / 4
+    }
+
+    /// Whether there is nothing to draw. The renderer rejects empty meshes,
+    /// so skip uploading these.
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+}
+
+/// What a block face looks like; neighbouring faces merge when equal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FaceKey {
+    layer: u16,
+    /// Occlusion at the quad's corners in `(u, v)` order (0,0), (1,0),
+    /// (1,1), (0,1)
+    ao: [u8; 4],
+    /// Smoothed light at the same corners
+    light: [Light; 4],
+}
+
+/// Turns chunks into [`ChunkMesh`]es with greedy meshing.
+///
+/// Faces between a solid block and an opaque neighbour, or two blocks of
+/// the same kind, are culled. Each vertex gets classic voxel ambient
+/// occlusion and smooth lighting from the four blocks in front of it that
+/// touch the corner: the one the face looks into, the two beside that, and
+/// the one diagonal to it. Coplanar visible faces with the same texture,
+/// occlusion and light are merged into as few rectangles as possible, and
+/// each is split into triangles along its brighter diagonal so shading
+/// does not depend on the quad's orientation.
+///
+/// Blocks across the chunk's faces come from the neighbour chunks given to
+/// [`ChunkMesher::mesh`]; missing neighbours, and blocks diagonally beyond
+/// an edge of the chunk, count as air. The mesher keeps its scratch buffers
+/// between calls, so reuse one per thread.
+#[derive(Debug, Clone)]
+pub struct ChunkMesher {
+    /// Chunk blocks with a one-block border, indexed by [`padded`]
+    blocks: Vec<BlockId>,
+    opaque: Vec<bool>,
+    light: Vec<Light>,
+    mask: Vec<Option<FaceKey>>,
+}
+
+impl ChunkMesher {
+    pub fn new() -> Self {
+        Self {
+            blocks: vec![BlockId::AIR; PADDED * PADDED * PADDED],
+            opaque: vec![false; PADDED * PADDED * PADDED],
+            light: vec![Light::SKY; PADDED * PADDED * PADDED],
+            mask: vec![None; CHUNK_SIZE * CHUNK_SIZE],
+        }
+    }
+
+    /// Mesh `chunk`, reading the blocks beyond its faces from `neighbours`,
+    /// indexed by [`Face::index`] as from [`super::ChunkMap::neighbours`]
+    pub fn mesh(
+        &mut self,
+        chunk: &Chunk,
+        neighbours: [Option<&Chunk>; 6],
+        registry: &BlockRegistry,
+    ) -> ChunkMesh {
+        let mut mesh = ChunkMesh::default();
+        if chunk.is_empty() {
+            return mesh;
+        }
+
+        self.load(chunk, neighbours, registry);
+        for face in Face::ALL {
+            for slice in 0..CHUNK_SIZE {
+                self.build_mask(face, slice, registry);
+                self.emit_quads(face, slice, &mut mesh);
+            }
+        }
+        mesh
+    }
+
+    /// Copy the chunk and the bordering layer of each neighbour into the
+    /// padded buffers
+    fn load(
+        &mut self,
+        chunk: &Chunk,
+        neighbours: [Option<&Chunk>; 6],
+        registry: &BlockRegistry,
+    ) {
+        self.blocks.fill(BlockId::AIR);
+        self.light.fill(Light::SKY);
+        for ([x, y, z], block) in chunk.iter() {
+            let index = padded([x, y, z].map(|v| v as isize));
+            self.blocks[index] = block;
+            self.light[index] = chunk.light(x, y, z);
+        }
+
+        let last = CHUNK_SIZE as isize - 1;
+        for face in Face::ALL {
+            let Some(neighbour) = neighbours[face.index()] else {
+                continue;
+            };
+            let (d, u, v) = face_axes(face);
+            let normal = face.normal()[d] as isize;
+            // The padding layer, and the neighbour layer that touches us
+            let (outside, inside) =
+                if normal > 0 { (last + 1, 0) } else { (-1, last) };
+            for a in 0..CHUNK_SIZE {
+                for b in 0..CHUNK_SIZE {
+                    let mut local = [0; 3];
+                    local[d] = inside as usize;
+                    local[u] = a;
+                    local[v] = b;
+                    let mut pad = local.map(|v| v as isize);
+                    pad[d] = outside;
+                    let [x, y, z] = local;
+                    self.blocks[padded(pad)] = neighbour.get(x, y, z);
+                    self.light[padded(pad)] = neighbour.light(x, y, z);
+                }
+            }
+        }
+
+        for (opaque, &block) in self.opaque.iter_mut().zip(&self.blocks) {
+            *opaque = registry.is_opaque(block);
+        }
+    }
+
+    /// Fill the mask with the visible `face`s of blocks in layer `slice`
+    /// along the face's normal
+    fn build_mask(
+        &mut self,
+        face: Face,
+        slice: usize,
+        registry: &BlockRegistry,
+    ) {
+        let (d, u, v) = face_axes(face);
+        let normal = face.normal();
+        let (du, dv) = (unit(u), unit(v));
+
+        for b in 0..CHUNK_SIZE {
+            for a in 0..CHUNK_SIZE {
+                let mut pos = [0; 3];
+                pos[d] = slice as isize;
+                pos[u] = a as isize;
+                pos[v] = b as isize;
+                self.mask[b * CHUNK_SIZE + a] = None;
+
+                let block = self.blocks[padded(pos)];
+                let Some(block_type) = registry
+                    .get(block)
+                    .filter(|block| block.is_solid())
+                else {
+                    continue;
+                };
+                let front = add(pos, normal.map(|v| v as isize));
+                let neighbour = self.blocks[padded(front)];
+                if self.opaque[padded(front)] || neighbour == block {
+                    continue;
+                }
+
+                let corners = [(-1, -1), (1, -1), (1, 1), (-1, 1)];
+                let shading = corners.map(|(su, sv)| {
+                    self.shade_vertex(front, scale(du, su), scale(dv, sv))
+                });
+                self.mask[b * CHUNK_SIZE + a] = Some(FaceKey {
+                    layer: block_type.texture(face),
+                    ao: shading.map(|(ao, _)| ao),
+                    light: shading.map(|(_, light)| light),
+                });
+            }
+        }
+    }
+
+    /// Cover the mask with maximal rectangles of equal faces, emitting a
+    /// quad for each
+    fn emit_quads(&mut self, face: Face, slice: usize, mesh: &mut ChunkMesh) {
+        for b in 0..CHUNK_SIZE {
+            let mut a = 0;
+            while a < CHUNK_SIZE {
+                let Some(key) = self.mask[b * CHUNK_SIZE + a] else {
+                    a += 1;
+                    continue;
+                };
+
+                let width = (a..CHUNK_SIZE)
+                    .take_while(|&a| self.mask[b * CHUNK_SIZE + a] == Some(key))
+                    .count();
+                let height = (b..CHUNK_SIZE)
+                    .take_while(|&b| {
+                        self.mask[b * CHUNK_SIZE + a..][..width]
+                            .iter()
+                            .all(|&k| k == Some(key))
+                    })
+                    .count();
+                for row in b..b + height {
+                    self.mask[row * CHUNK_SIZE + a..][..width].fill(None);
+                }
+
+                push_quad(mesh, face, slice, [a, b], [width, height], key);
+                a += width;
+            }
+        }
+    }
+
+    /// Occlusion and light at the vertex of the face looking into `front`
+    /// that lies `to_u` and `to_v` away from its centre
+    fn shade_vertex(
+        &self,
+        front: [isize; 3],
+        to_u: [isize; 3],
+        to_v: [isize; 3],
+    ) -> (u8, Light) {
+        let side_u = add(front, to_u);
+        let side_v = add(front, to_v);
+        let corner = add(side_u, to_v);
+        let opaque_u = self.is_opaque(side_u);
+        let opaque_v = self.is_opaque(side_v);
+        let ao = if opaque_u && opaque_v {
+            0
+        } else {
+            3 - opaque_u as u8 - opaque_v as u8 - self.is_opaque(corner) as u8
+        };
+
+        // Opaque blocks are dark inside and the corner is hidden behind two
+        // solid sides, so neither counts towards the average. The front
+        // block is always open, so there is at least one sample.
+        let hidden = opaque_u && opaque_v;
+        let samples = [
+            Some(front),
+            Some(side_u),
+            Some(side_v),
+            (!hidden).then_some(corner),
+        ];
+        let (mut block, mut sky, mut count) = (0, 0, 0);
+        for pos in samples
+            .into_iter()
+            .flatten()
+            .filter(|&pos| is_known(pos) && !self.opaque[padded(pos)])
+        {
+            let light = self.light[padded(pos)];
+            block += light.block() as usize;
+            sky += light.sky() as usize;
+            count += 1;
+        }
+        let average = |sum: usize| ((sum + count / 2) / count) as u8;
+        (ao, Light::new(average(block), average(sky)))
+    }
+
+    /// Whether the block at padded-range coordinates is opaque. Unknown
+    /// blocks count as air.
+    fn is_opaque(&self, pos: [isize; 3]) -> bool {
+        is_known(pos) && self.opaque[padded(pos)]
+    }
+}
+
+impl Default for ChunkMesher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Append the quad covering `size` blocks from `start` in (u, v) on the
+/// given slice, wound counter-clockwise seen from outside the face
+fn push_quad(
+    mesh: &mut ChunkMesh,
+    face: Face,
+    slice: usize,
+    start: [usize; 2],
+    size: [usize; 2],
+    key: FaceKey,
+) {
+    let (d, u, v) = face_axes(face);
+    let outward = face.normal()[d] > 0;
+    let plane = slice + outward as usize;
+
+    let corners = [(0, 0), (1, 0), (1, 1), (0, 1)];
+    let base = mesh.vertices.len() as u32;
+    for (corner, (cu, cv)) in corners.into_iter().enumerate() {
+        let mut position = [0; 3];
+        position[d] = plane as u8;
+        position[u] = (start[0] + cu * size[0]) as u8;
+        position[v] = (start[1] + cv * size[1]) as u8;
+        let uv = [(cu * size[0]) as u8, (cv * size[1]) as u8];
+        mesh.vertices.push(ChunkVertex::new(
+            position,
+            face,
+            key.ao[corner],
+            key.light[corner],
+            uv,
+            key.layer,
+        ));
+    }
+
+    // Split along the brighter diagonal, so a single dark or bright corner
+    // shades a corner triangle instead of a stripe across the quad
+    let brightness = |a: usize, b: usize| {
+        let light = |corner: usize| {
+            let light = key.light[corner];
+            light.block() + light.sky()
+        };
+        (key.ao[a] + key.ao[b], light(a) + light(b))
+    };
+    let [c0, c1, c2, c3] = if brightness(1, 3) > brightness(0, 2) {
+        [1, 2, 3, 0]
+    } else {
+        [0, 1, 2, 3]
+    };
+
+    // Corners run counter-clockwise around +u × +v; reverse them when the
+    // face points the other way
+    let order = if cross_sign(u, v) == outward {
+        [c0, c1, c2, c0, c2, c3]
+    } else {
+        [c0, c2, c1, c0, c3, c2]
+    };
+    mesh.indices
+        .extend(order.map(|i| base + i));
+}
+
+/// Whether padded-range coordinates are inside the chunk or beyond just
+/// one of its faces. Blocks diagonally beyond an edge are unknown.
+fn is_known(pos: [isize; 3]) -> bool {
+    pos.iter()
+        .filter(|&&v| v < 0 || v >= CHUNK_SIZE as isize)
+        .count()
+        <= 1
+}
+
+/// Whether axis `u` × axis `v` points along +normal
+fn cross_sign(u: usize, v: usize) -> bool {
+    (u + 1) % 3 == v
+}
+
+/// Index into the padded buffers of coordinates from -1 to `CHUNK_SIZE`
+fn padded(pos: [isize; 3]) -> usize {
+    let [x, y, z] = pos.map(|v| (v + 1) as usize);
+    (y * PADDED + z) * PADDED + x
+}
+
+fn unit(axis: usize) -> [isize; 3] {
+    let mut v = [0; 3];
+    v[axis] = 1;
+    v
+}
+
+fn scale(v: [isize; 3], s: isize) -> [isize; 3] {
+    v.map(|c| c * s)
+}
+
+fn add(a: [isize; 3], b: [isize; 3]) -> [isize; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCKS: &str = r#"
+[[block]]
+name = "stone"
+id = 1
+textures = 1
+
+[[block]]
+name = "grass"
+id = 2
+textures = { top = 3, bottom = 1, side = 2 }
+
+[[block]]
+name = "glass"
+id = 3
+opaque = false
+textures = 4
+
+[[block]]
+name = "torch"
+id = 4
+solid = false
+"#;
+
+    const STONE: BlockId = BlockId::new(1);
+    const GRASS: BlockId = BlockId::new(2);
+    const GLASS: BlockId = BlockId::new(3);
+    const TORCH: BlockId = BlockId::new(4);
+
+    fn registry() -> BlockRegistry {
+        BlockRegistry::from_toml(BLOCKS, "blocks.toml").expect("Valid blocks")
+    }
+
+    fn mesh(chunk: &Chunk, neighbours: [Option<&Chunk>; 6]) -> ChunkMesh {
+        ChunkMesher::new().mesh(chunk, neighbours, &registry())
+    }
+
+    fn quads(mesh: &ChunkMesh, face: Face) -> Vec<&[ChunkVertex]> {
+        mesh.vertices()
+            .chunks(4)
+            .filter(|quad| quad[0].face() == face)
+            .collect()
+    }
+
+    #[test]
+    fn test_vertex_packing_round_trips() {
+        let vertex = ChunkVertex::new(
+            [32, 0, 17],
+            Face::North,
+            2,
+            Light::new(15, 9),
+            [5, 32],
+            999,
+        );
+        assert_eq!(vertex.position(), [32, 0, 17]);
+        assert_eq!(vertex.face(), Face::North);
+        assert_eq!(vertex.ao(), 2);
+        assert_eq!(vertex.light(), Light::new(15, 9));
+        assert_eq!(vertex.uv(), [5, 32]);
+        assert_eq!(vertex.layer(), 999);
+        assert_eq!(ChunkVertex::STRIDE, 8);
+    }
+
+    #[test]
+    fn test_empty_and_non_solid_chunks_have_no_quads() {
+        assert!(mesh(&Chunk::new(), [None; 6]).is_empty());
+
+        let mut chunk = Chunk::new();
+        chunk.set(4, 4, 4, TORCH);
+        assert!(mesh(&chunk, [None; 6]).is_empty());
+    }
+
+    #[test]
+    fn test_single_block_has_six_faces() {
+        let mut chunk = Chunk::new();
+        chunk.set(4, 5, 6, GRASS);
+        let mesh = mesh(&chunk, [None; 6]);
+
+        assert_eq!(mesh.quad_count(), 6);
+        assert_eq!(mesh.indices().len(), 36);
+        for face in Face::ALL {
+            let quad = quads(&mesh, face);
+            assert_eq!(quad.len(), 1, "{face:?}");
+            let expected = match face {
+                Face::Top => 3,
+                Face::Bottom => 1,
+                _ => 2,
+            };
+            assert_eq!(quad[0][0].layer(), expected, "{face:?}");
+            assert!(quad[0].iter().all(|v| v.ao() == 3));
+        }
+
+        let top = quads(&mesh, Face::Top)[0];
+        let mut corners: Vec<_> = top
+            .iter()
+            .map(|v| v.position())
+            .collect();
+        corners.sort();
+        assert_eq!(corners, [[4, 6, 6], [4, 6, 7], [5, 6, 6], [5, 6, 7]]);
+    }
+
+    #[test]
+    fn test_quads_wind_counter_clockwise_from_outside() {
+        let mut chunk = Chunk::new();
+        chunk.set(1, 1, 1, STONE);
+        let mesh = mesh(&chunk, [None; 6]);
+
+        for triangle in mesh.indices().chunks(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| {
+                let vertex = mesh.vertices()[triangle[i] as usize];
+                vertex.position().map(i32::from)
+            });
+            let e1 = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+            let e2 = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+            let cross = [
+                e1[1] * e2[2] - e1[2] * e2[1],
+                e1[2] * e2[0] - e1[0] * e2[2],
+                e1[0] * e2[1] - e1[1] * e2[0],
+            ];
+            let normal = mesh.vertices()[triangle[0] as usize]
+                .face()
+                .normal();
+            let dot: i32 = (0..3)
+                .map(|i| cross[i] * normal[i])
+                .sum();
+            assert!(dot > 0, "triangle {triangle:?} faces inward");
+        }
+    }
+
+    #[test]
+    fn test_full_chunk_merges_into_six_quads() {
+        let chunk = Chunk::filled(STONE);
+        let mesh = mesh(&chunk, [None; 6]);
+
+        assert_eq!(mesh.quad_count(), 6);
+        for quad in mesh.vertices().chunks(4) {
+            let uv_max = quad
+                .iter()
+                .map(|v| v.uv())
+                .max()
+                .unwrap();
+            assert_eq!(uv_max, [32, 32]);
+        }
+    }
+
+    #[test]
+    fn test_opaque_neighbours_cull_border_faces() {
+        let chunk = Chunk::filled(STONE);
+        let solid = Chunk::filled(STONE);
+        let mut neighbours = [Some(&solid); 6];
+        assert!(mesh(&chunk, neighbours).is_empty());
+
+        let air = Chunk::new();
+        neighbours[Face::Top.index()] = Some(&air);
+        let mesh = mesh(&chunk, neighbours);
+        assert_eq!(mesh.quad_count(), 1);
+        assert_eq!(quads(&mesh, Face::Top).len(), 1);
+    }
+
+    #[test]
+    fn test_different_textures_do_not_merge() {
+        let mut chunk = Chunk::new();
+        chunk.set(0, 0, 0, STONE);
+        chunk.set(1, 0, 0, GRASS);
+        chunk.set(2, 0, 0, GRASS);
+        let mesh = mesh(&chunk, [None; 6]);
+
+        let top: Vec<_> = quads(&mesh, Face::Top)
+            .iter()
+            .map(|quad| quad[0].layer())
+            .collect();
+        assert_eq!(top.len(), 2);
+        assert!(top.contains(&1) && top.contains(&3));
+        // Stone and grass sides are layers 1 and 2
+        assert_eq!(quads(&mesh, Face::South).len(), 2);
+        // Grass bottoms share stone's layer
+        assert_eq!(quads(&mesh, Face::Bottom).len(), 1);
+    }
+
+    #[test]
+    fn test_transparent_blocks_cull_only_their_own_kind() {
+        let mut chunk = Chunk::new();
+        chunk.set(0, 0, 0, GLASS);
+        chunk.set(1, 0, 0, GLASS);
+        chunk.set(2, 0, 0, STONE);
+        let mesh = mesh(&chunk, [None; 6]);
+
+        // Glass-glass is culled; glass shows against stone, not vice versa
+        let east = quads(&mesh, Face::East);
+        let west = quads(&mesh, Face::West);
+        assert_eq!(east.len(), 1);
+        assert_eq!(east[0][0].layer(), 1);
+        assert_eq!(west.len(), 2);
+    }
+
+    #[test]
+    fn test_ambient_occlusion_splits_quads() {
+        let mut chunk = Chunk::new();
+        for x in 0..3 {
+            for z in 0..3 {
+                chunk.set(x, 0, z, STONE);
+            }
+        }
+        chunk.set(1, 1, 1, STONE);
+        let mesh = mesh(&chunk, [None; 6]);
+
+        // The floor's top is split around the pillar, whose neighbours are
+        // darkened at the corners they share with it
+        let floor: Vec<_> = quads(&mesh, Face::Top)
+            .into_iter()
+            .filter(|quad| quad[0].position()[1] == 1)
+            .collect();
+        assert_eq!(floor.len(), 8);
+        let beside = floor
+            .iter()
+            .find(|quad| {
+                quad.iter()
+                    .all(|v| (1..=2).contains(&v.position()[0]))
+                    && quad
+                        .iter()
+                        .all(|v| v.position()[2] <= 1)
+            })
+            .expect("a quad north of the pillar");
+        let ao: Vec<_> = beside
+            .iter()
+            .map(|v| (v.position(), v.ao()))
+            .collect();
+        for (position, ao) in ao {
+            let touches = position[2] == 1;
+            assert_eq!(ao, if touches { 2 } else { 3 }, "{position:?}");
+        }
+    }
+}
@@ -0,0 +1,246 @@
+//! Seeded gradient noise
+
+use std::f64::consts::{FRAC_1_SQRT_2, SQRT_2};
+
+/// Entries in the permutation table. Noise repeats every this many units.
+const PERIOD: usize = 256;
+
+/// Gradient directions for 2D noise
+const GRADIENTS_2D: [[f64; 2]; 8] = [
+    [1.0, 0.0],
+    [-1.0, 0.0],
+    [0.0, 1.0],
+    [0.0, -1.0],
+    [FRAC_1_SQRT_2, FRAC_1_SQRT_2],
+    [-FRAC_1_SQRT_2, FRAC_1_SQRT_2],
+    [FRAC_1_SQRT_2, -FRAC_1_SQRT_2],
+    [-FRAC_1_SQRT_2, -FRAC_1_SQRT_2],
+];
+
+/// Gradient directions for 3D noise: the midpoints of a cube's edges
+const GRADIENTS_3D: [[f64; 3]; 12] = [
+    [1.0, 1.0, 0.0],
+    [-1.0, 1.0, 0.0],
+    [1.0, -1.0, 0.0],
+    [-1.0, -1.0, 0.0],
+    [1.0, 0.0, 1.0],
+    [-1.0, 0.0, 1.0],
+    [1.0, 0.0, -1.0],
+    [-1.0, 0.0, -1.0],
+    [0.0, 1.0, 1.0],
+    [0.0, -1.0, 1.0],
+    [0.0, 1.0, -1.0],
+    [0.0, -1.0, -1.0],
+];
+
+/// Perlin gradient noise in two and three dimensions.
+///
+/// Values are smooth, roughly in `-1.0..=1.0`, and zero at integer
+/// coordinates. The same seed always gives the same noise on every
+/// platform.
+///
+/// # Example
+/// ```
+/// use strata::voxel::Noise;
+///
+/// let noise = Noise::new(7);
+/// let height = noise.fractal_2d(12.5, -3.25, 4);
+/// assert_eq!(height, Noise::new(7).fractal_2d(12.5, -3.25, 4));
+/// assert!((-1.0..=1.0).contains(&height));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Noise {
+    /// A shuffle of `0..PERIOD`, twice over so lookups need not wrap
+    permutation: Box<[u8; PERIOD * 2]>,
+}
+
+impl Noise {
+    pub fn new(seed: u64) -> Self {
+        let mut table = [0u8; PERIOD];
+        for (i, entry) in table.iter_mut().enumerate() {
+            *entry = i as u8;
+        }
+        // Fisher-Yates
+        let mut state = seed;
+        for i in (1..PERIOD).rev() {
+            let j = (split_mix(&mut state) % (i as u64 + 1)) as usize;
+            table.swap(i, j);
+        }
+
+        let mut permutation = Box::new([0u8; PERIOD * 2]);
+        permutation[..PERIOD].copy_from_slice(&table);
+        permutation[PERIOD..].copy_from_slice(&table);
+        Self { permutation }
+    }
+
+    /// Noise at a point on the plane
+    pub fn sample_2d(&self, x: f64, y: f64) -> f64 {
+        let (xi, xf) = split(x);
+        let (yi, yf) = split(y);
+        let corner = |dx: usize, dy: usize| {
+            let hash = self.hash(&[xi + dx, yi + dy]);
+            let [gx, gy] = GRADIENTS_2D[hash % GRADIENTS_2D.len()];
+            gx * (xf - dx as f64) + gy * (yf - dy as f64)
+        };
+
+        let (u, v) = (fade(xf), fade(yf));
+        let bottom = lerp(corner(0, 0), corner(1, 0), u);
+        let top = lerp(corner(0, 1), corner(1, 1), u);
+        lerp(bottom, top, v) * SQRT_2
+    }
+
+    /// Noise at a point in space
+    pub fn sample_3d(&self, x: f64, y: f64, z: f64) -> f64 {
+        let (xi, xf) = split(x);
+        let (yi, yf) = split(y);
+        let (zi, zf) = split(z);
+        let corner = |dx: usize, dy: usize, dz: usize| {
+            let hash = self.hash(&[xi + dx, yi + dy, zi + dz]);
+            let [gx, gy, gz] = GRADIENTS_3D[hash % GRADIENTS_3D.len()];
+            gx * (xf - dx as f64)
+                + gy * (yf - dy as f64)
+                + gz * (zf - dz as f64)
+        };
+
+        let (u, v, w) = (fade(xf), fade(yf), fade(zf));
+        let near = lerp(
+            lerp(corner(0, 0, 0), corner(1, 0, 0), u),
+            lerp(corner(0, 1, 0), corner(1, 1, 0), u),
+            v,
+        );
+        let far = lerp(
+            lerp(corner(0, 0, 1), corner(1, 0, 1), u),
+            lerp(corner(0, 1, 1), corner(1, 1, 1), u),
+            v,
+        );
+        lerp(near, far, w).clamp(-1.0, 1.0)
+    }
+
+    /// `octaves` layers of 2D noise, each at twice the frequency and half
+    /// the amplitude of the last, scaled back into `-1.0..=1.0`
+    pub fn fractal_2d(&self, x: f64, y: f64, octaves: u32) -> f64 {
+        fractal(octaves, |frequency| {
+            self.sample_2d(x * frequency, y * frequency)
+        })
+    }
+
+    /// `octaves` layers of 3D noise, as [`Noise::fractal_2d`]
+    pub fn fractal_3d(&self, x: f64, y: f64, z: f64, octaves: u32) -> f64 {
+        fractal(octaves, |frequency| {
+            self.sample_3d(x * frequency, y * frequency, z * frequency)
+        })
+    }
+
+    /// Hash lattice coordinates already wrapped into `0..PERIOD`
+    fn hash(&self, coords: &[usize]) -> usize {
+        coords.iter().fold(0, |hash, &coord| {
+            self.permutation[hash + coord % PERIOD] as usize
+        })
+    }
+}
+
+fn fractal(octaves: u32, mut sample: impl FnMut(f64) -> f64) -> f64 {
+    let (mut total, mut range) = (0.0, 0.0);
+    let (mut frequency, mut amplitude) = (1.0, 1.0);
+    for _ in 0..octaves {
+        total += sample(frequency) * amplitude;
+        range += amplitude;
+        frequency *= 2.0;
+        amplitude *= 0.5;
+    }
+    if range > 0.0 { total / range } else { 0.0 }
+}
+
+/// The lattice cell of `t`, wrapped into `0..PERIOD`, and the offset of `t`
+/// within it
+fn split(t: f64) -> (usize, f64) {
+    let floor = t.floor();
+    let cell = (floor as i64).rem_euclid(PERIOD as i64) as usize;
+    (cell, t - floor)
+}
+
+/// Smoothstep with zero first and second derivatives at the ends
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+/// The next value of the SplitMix64 sequence
+pub(crate) fn split_mix(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Points spread over a few hundred units, off the lattice
+    fn points() -> impl Iterator<Item = [f64; 3]> {
+        (0..2000).map(|i| {
+            let i = i as f64;
+            [i * 0.173 - 150.0, i * 0.091 - 40.0, i * 0.317 - 300.0]
+        })
+    }
+
+    #[test]
+    fn test_same_seed_same_noise() {
+        let (a, b) = (Noise::new(42), Noise::new(42));
+        for [x, y, z] in points() {
+            assert_eq!(a.sample_2d(x, y), b.sample_2d(x, y));
+            assert_eq!(a.sample_3d(x, y, z), b.sample_3d(x, y, z));
+        }
+    }
+
+    #[test]
+    fn test_seeds_differ() {
+        let (a, b) = (Noise::new(1), Noise::new(2));
+        let differing = points()
+            .filter(|&[x, y, z]| a.sample_3d(x, y, z) != b.sample_3d(x, y, z))
+            .count();
+        assert!(differing > 1900, "{differing}");
+    }
+
+    #[test]
+    fn test_range_and_zero_at_lattice() {
+        let noise = Noise::new(9);
+        let (mut min, mut max) = (f64::MAX, f64::MIN);
+        for [x, y, z] in points() {
+            for value in [
+                noise.sample_2d(x, y),
+                noise.sample_3d(x, y, z),
+                noise.fractal_2d(x, z, 5),
+                noise.fractal_3d(x, y, z, 3),
+            ] {
+                assert!((-1.0..=1.0).contains(&value), "{value}");
+                min = min.min(value);
+                max = max.max(value);
+            }
+        }
+        // Uses most of the range, on both sides
+        assert!(min < -0.5 && max > 0.5, "{min}..{max}");
+
+        assert_eq!(noise.sample_2d(3.0, -7.0), 0.0);
+        assert_eq!(noise.sample_3d(-2.0, 5.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn test_noise_is_continuous() {
+        let noise = Noise::new(3);
+        for [x, y, z] in points() {
+            let step = 1e-3;
+            let a = noise.sample_3d(x, y, z);
+            let b = noise.sample_3d(x + step, y, z - step);
+            assert!((a - b).abs() < 0.01, "{a} vs {b} at {x},{y},{z}");
+            let a = noise.sample_2d(x, y);
+            let b = noise.sample_2d(x, y + step);
+            assert!((a - b).abs() < 0.01);
+        }
+    }
+}
@@ -0,0 +1,657 @@
+//! Data-driven block definitions
+
+use std::collections::HashMap;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+use serde::Deserialize;
+use toml::Spanned;
+
+use super::chunk::BlockId;
+use super::light::MAX_LIGHT;
+use crate::{Result, StrataError};
+
+/// A side of a block, named after the direction it faces
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Face {
+    /// +x
+    East,
+    /// -x
+    West,
+    /// +y
+    Top,
+    /// -y
+    Bottom,
+    /// +z
+    South,
+    /// -z
+    North,
+}
+
+impl Face {
+    pub const ALL: [Face; 6] = [
+        Face::East,
+        Face::West,
+        Face::Top,
+        Face::Bottom,
+        Face::South,
+        Face::North,
+    ];
+
+    /// Unit vector pointing out of the face
+    pub const fn normal(self) -> [i32; 3] {
+        match self {
+            Face::East => [1, 0, 0],
+            Face::West => [-1, 0, 0],
+            Face::Top => [0, 1, 0],
+            Face::Bottom => [0, -1, 0],
+            Face::South => [0, 0, 1],
+            Face::North => [0, 0, -1],
+        }
+    }
+
+    /// Position in [`Face::ALL`]
+    pub const fn index(self) -> usize {
+        self as usize
+    }
+
+    pub const fn opposite(self) -> Face {
+        match self {
+            Face::East => Face::West,
+            Face::West => Face::East,
+            Face::Top => Face::Bottom,
+            Face::Bottom => Face::Top,
+            Face::South => Face::North,
+            Face::North => Face::South,
+        }
+    }
+}
+
+/// What a block collides with, in block-local units from 0 to 1
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CollisionShape {
+    None,
+    Full,
+    Box { min: [f32; 3], max: [f32; 3] },
+}
+
+/// Properties shared by every block with the same [`BlockId`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockType {
+    name: String,
+    solid: bool,
+    opaque: bool,
+    light: u8,
+    textures: [u16; 6],
+    collision: CollisionShape,
+    sounds: Option<String>,
+}
+
+impl BlockType {
+    fn air() -> Self {
+        Self {
+            name: "air".to_string(),
+            solid: false,
+            opaque: false,
+            light: 0,
+            textures: [0; 6],
+            collision: CollisionShape::None,
+            sounds: None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether the block is meshed at all
+    pub fn is_solid(&self) -> bool {
+        self.solid
+    }
+
+    /// Whether the block hides the faces behind it and stops light
+    pub fn is_opaque(&self) -> bool {
+        self.opaque
+    }
+
+    /// Light emitted, from 0 to [`MAX_LIGHT`]
+    pub fn light(&self) -> u8 {
+        self.light
+    }
+
+    /// Texture array layer of one face
+    pub fn texture(&self, face: Face) -> u16 {
+        self.textures[face.index()]
+    }
+
+    pub fn collision(&self) -> CollisionShape {
+        self.collision
+    }
+
+    /// Name of the sound set played when stepping on or breaking the block
+    pub fn sounds(&self) -> Option<&str> {
+        self.sounds.as_deref()
+    }
+}
+
+/// Every kind of block the game knows about, indexed by [`BlockId`].
+///
+/// Definitions are loaded from TOML, one `[[block]]` table per block:
+///
+/// ```toml
+/// [[block]]
+/// name = "grass"
+/// id = 2                 # optional; otherwise the lowest free id
+/// solid = true           # default true
+/// opaque = true          # defaults to `solid`
+/// light = 0              # 0 to 15
+/// textures = { top = 3, bottom = 1, side = 2 }   # or one layer for all
+/// collision = "full"     # "none", "full" or { box = { min, max } }
+/// sounds = "grass"
+/// ```
+///
+/// Air is always id 0. Blocks with an explicit `id` keep it however the
+/// file is reordered, so ids saved in chunks stay valid.
+#[derive(Debug, Clone)]
+pub struct BlockRegistry {
+    blocks: Vec<Option<BlockType>>,
+    names: HashMap<String, BlockId>,
+}
+
+impl BlockRegistry {
+    /// A registry holding only air
+    pub fn new() -> Self {
+        Self {
+            blocks: vec![Some(BlockType::air())],
+            names: HashMap::from([("air".to_string(), BlockId::AIR)]),
+        }
+    }
+
+    /// Load block definitions from a TOML file
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StrataError::FileRead`] if the file cannot be read, and
+    /// [`StrataError::BlockDefinition`] with the offending line if it is
+    /// malformed or defines conflicting blocks.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path).map_err(|source| {
+            StrataError::FileRead { path: path.display().to_string(), source }
+        })?;
+        Self::from_toml(&source, &path.display().to_string())
+    }
+
+    /// Parse block definitions from TOML. `file` names the source in
+    /// errors.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StrataError::BlockDefinition`] with the offending line if
+    /// the source is malformed or defines conflicting blocks.
+    pub fn from_toml(source: &str, file: &str) -> Result<Self> {
+        let error = |span: Option<Range<usize>>, message: String| {
+            StrataError::BlockDefinition {
+                file: file.to_string(),
+                line: span.map_or(1, |span| line_of(source, span.start)),
+                message,
+            }
+        };
+
+        let defs: BlockFile = toml::from_str(source)
+            .map_err(|e| error(e.span(), e.message().to_string()))?;
+
+        let mut registry = Self::new();
+        let mut pending: Vec<(Range<usize>, BlockType)> = Vec::new();
+        for def in defs.block {
+            let span = def.span();
+            let def = def.into_inner();
+            let name = def.name.get_ref();
+            if name.is_empty() {
+                return Err(error(
+                    Some(def.name.span()),
+                    "block name must not be empty".to_string(),
+                ));
+            }
+            if registry.names.contains_key(name)
+                || pending
+                    .iter()
+                    .any(|(_, block)| block.name == *name)
+            {
+                return Err(error(
+                    Some(def.name.span()),
+                    format!("block `{name}` is defined more than once"),
+                ));
+            }
+
+            let id = def.id.clone();
+            let block = def
+                .into_block()
+                .map_err(|(span, message)| error(Some(span), message))?;
+            match id {
+                Some(id) => {
+                    let raw = *id.get_ref();
+                    if raw == 0 {
+                        return Err(error(
+                            Some(id.span()),
+                            "id 0 is reserved for air".to_string(),
+                        ));
+                    }
+                    if registry
+                        .get(BlockId::new(raw))
+                        .is_some()
+                    {
+                        return Err(error(
+                            Some(id.span()),
+                            format!("id {raw} is used by more than one block"),
+                        ));
+                    }
+                    registry.insert(BlockId::new(raw), block);
+                }
+                None => pending.push((span, block)),
+            }
+        }
+
+        // Ids that were not given are filled in after every explicit one is
+        // known, so adding an explicit id never shifts the others
+        let mut next = 1u16;
+        for (span, block) in pending {
+            while registry
+                .get(BlockId::new(next))
+                .is_some()
+            {
+                next = next.checked_add(1).ok_or_else(|| {
+                    error(
+                        Some(span.clone()),
+                        "ran out of block ids".to_string(),
+                    )
+                })?;
+            }
+            registry.insert(BlockId::new(next), block);
+        }
+
+        Ok(registry)
+    }
+
+    /// Defined block types, including air
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn get(&self, id: BlockId) -> Option<&BlockType> {
+        self.blocks
+            .get(id.raw() as usize)
+            .and_then(Option::as_ref)
+    }
+
+    /// The id of the block called `name`
+    pub fn id(&self, name: &str) -> Option<BlockId> {
+        self.names.get(name).copied()
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<&BlockType> {
+        self.id(name)
+            .and_then(|id| self.get(id))
+    }
+
+    /// Whether `id` is solid. Unknown ids are not.
+    pub fn is_solid(&self, id: BlockId) -> bool {
+        self.get(id)
+            .is_some_and(BlockType::is_solid)
+    }
+
+    /// Whether `id` is opaque. Unknown ids are not.
+    pub fn is_opaque(&self, id: BlockId) -> bool {
+        self.get(id)
+            .is_some_and(BlockType::is_opaque)
+    }
+
+    /// Light emitted by `id`. Unknown ids emit none.
+    pub fn light(&self, id: BlockId) -> u8 {
+        self.get(id).map_or(0, BlockType::light)
+    }
+
+    /// Every block type in id order
+    pub fn iter(&self) -> impl Iterator<Item = (BlockId, &BlockType)> {
+        self.blocks
+            .iter()
+            .enumerate()
+            .filter_map(|(raw, block)| {
+                block
+                    .as_ref()
+                    .map(|block| (BlockId::new(raw as u16), block))
+            })
+    }
+
+    fn insert(&mut self, id: BlockId, block: BlockType) {
+        let index = id.raw() as usize;
+        if self.blocks.len() <= index {
+            self.blocks.resize(index + 1, None);
+        }
+        self.names
+            .insert(block.name.clone(), id);
+        self.blocks[index] = Some(block);
+    }
+}
+
+impl Default for BlockRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 1-based line holding byte `offset`
+fn line_of(source: &str, offset: usize) -> usize {
+    source[..offset.min(source.len())]
+        .matches('\n')
+        .count()
+        + 1
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BlockFile {
+    #[serde(default)]
+    block: Vec<Spanned<BlockDef>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BlockDef {
+    name: Spanned<String>,
+    id: Option<Spanned<u16>>,
+    solid: Option<bool>,
+    opaque: Option<bool>,
+    light: Option<Spanned<u8>>,
+    textures: Option<Spanned<TexturesDef>>,
+    collision: Option<Spanned<CollisionDef>>,
+    sounds: Option<String>,
+}
+
+impl BlockDef {
+    /// Resolve defaults and check ranges, reporting the span at fault
+    fn into_block(
+        self,
+    ) -> std::result::Result<BlockType, (Range<usize>, String)> {
+        let solid = self.solid.unwrap_or(true);
+
+        let light = match self.light {
+            Some(light) if *light.get_ref() > MAX_LIGHT => {
+                return Err((
+                    light.span(),
+                    format!(
+                        "light must be at most {MAX_LIGHT}, got {}",
+                        light.get_ref()
+                    ),
+                ));
+            }
+            Some(light) => light.into_inner(),
+            None => 0,
+        };
+
+        let textures = match self.textures {
+            Some(textures) => {
+                let span = textures.span();
+                textures
+                    .into_inner()
+                    .resolve()
+                    .map_err(|message| (span, message))?
+            }
+            None => [0; 6],
+        };
+
+        let collision = match self.collision {
+            Some(collision) => {
+                let span = collision.span();
+                collision
+                    .into_inner()
+                    .resolve()
+                    .map_err(|message| (span, message))?
+            }
+            None if solid => CollisionShape::Full,
+            None => CollisionShape::None,
+        };
+
+        Ok(BlockType {
+            name: self.name.into_inner(),
+            solid,
+            opaque: self.opaque.unwrap_or(solid),
+            light,
+            textures,
+            collision,
+            sounds: self.sounds,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TexturesDef {
+    All(u16),
+    Faces(FaceTexturesDef),
+}
+
+/// Per-face layers; the most specific key given for a face wins
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FaceTexturesDef {
+    all: Option<u16>,
+    side: Option<u16>,
+    top: Option<u16>,
+    bottom: Option<u16>,
+    east: Option<u16>,
+    west: Option<u16>,
+    south: Option<u16>,
+    north: Option<u16>,
+}
+
+impl TexturesDef {
+    fn resolve(self) -> std::result::Result<[u16; 6], String> {
+        let faces = match self {
+            TexturesDef::All(layer) => return Ok([layer; 6]),
+            TexturesDef::Faces(faces) => faces,
+        };
+
+        let mut textures = [0; 6];
+        for face in Face::ALL {
+            let (own, group) = match face {
+                Face::East => (faces.east, faces.side),
+                Face::West => (faces.west, faces.side),
+                Face::South => (faces.south, faces.side),
+                Face::North => (faces.north, faces.side),
+                Face::Top => (faces.top, None),
+                Face::Bottom => (faces.bottom, None),
+            };
+            textures[face.index()] = own
+                .or(group)
+                .or(faces.all)
+                .ok_or_else(|| format!("no texture for the {face:?} face"))?;
+        }
+        Ok(textures)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase", deny_unknown_fields)]
+enum CollisionDef {
+    None,
+    Full,
+    Box { min: [f32; 3], max: [f32; 3] },
+}
+
+impl CollisionDef {
+    fn resolve(self) -> std::result::Result<CollisionShape, String> {
+        Ok(match self {
+            CollisionDef::None => CollisionShape::None,
+            CollisionDef::Full => CollisionShape::Full,
+            CollisionDef::Box { min, max } => {
+                let in_block = |v: f32| (0.0..=1.0).contains(&v);
+                if !(min.iter().all(|&v| in_block(v))
+                    && max.iter().all(|&v| in_block(v)))
+                {
+                    return Err(
+                        "collision box must lie within 0 and 1".to_string()
+                    );
+                }
+                if (0..3).any(|axis| min[axis] >= max[axis]) {
+                    return Err(
+                        "collision box min must be below max on every axis"
+                            .to_string(),
+                    );
+                }
+                CollisionShape::Box { min, max }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCKS: &str = r#"
+[[block]]
+name = "stone"
+textures = 1
+sounds = "stone"
+
+[[block]]
+name = "grass"
+textures = { top = 3, bottom = 1, side = 2, north = 4 }
+
+[[block]]
+name = "glass"
+id = 1
+opaque = false
+textures = 5
+
+[[block]]
+name = "torch"
+solid = false
+light = 14
+collision = { box = { min = [0.4, 0.0, 0.4], max = [0.6, 0.6, 0.6] } }
+"#;
+
+    fn expect_error(source: &str, line: usize, contains: &str) {
+        match BlockRegistry::from_toml(source, "blocks.toml") {
+            Err(StrataError::BlockDefinition {
+                file,
+                line: found,
+                message,
+            }) => {
+                assert_eq!(file, "blocks.toml");
+                assert_eq!(found, line, "wrong line for: {message}");
+                assert!(
+                    message.contains(contains),
+                    "expected `{contains}` in: {message}"
+                );
+            }
+            Err(e) => panic!("Expected BlockDefinition, got {}", e),
+            Ok(_) => panic!("Expected BlockDefinition, got a registry"),
+        }
+    }
+
+    #[test]
+    fn test_new_registry_has_only_air() {
+        let registry = BlockRegistry::new();
+        assert_eq!(registry.len(), 1);
+        assert_eq!(registry.id("air"), Some(BlockId::AIR));
+        assert!(!registry.is_solid(BlockId::AIR));
+        assert!(!registry.is_opaque(BlockId::new(9)));
+    }
+
+    #[test]
+    fn test_load_assigns_explicit_then_free_ids() {
+        let registry = BlockRegistry::from_toml(BLOCKS, "blocks.toml")
+            .expect("Definitions are valid");
+
+        let ids: Vec<_> = registry
+            .iter()
+            .map(|(id, block)| (id.raw(), block.name()))
+            .collect();
+        assert_eq!(
+            ids,
+            [
+                (0, "air"),
+                (1, "glass"),
+                (2, "stone"),
+                (3, "grass"),
+                (4, "torch")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_load_resolves_properties() {
+        let registry = BlockRegistry::from_toml(BLOCKS, "blocks.toml")
+            .expect("Definitions are valid");
+
+        let stone = registry.by_name("stone").unwrap();
+        assert!(stone.is_solid() && stone.is_opaque());
+        assert_eq!(stone.collision(), CollisionShape::Full);
+        assert_eq!(stone.sounds(), Some("stone"));
+
+        let grass = registry.by_name("grass").unwrap();
+        let layers = Face::ALL.map(|face| grass.texture(face));
+        assert_eq!(layers, [2, 2, 3, 1, 2, 4]);
+
+        let glass = registry.id("glass").unwrap();
+        assert!(registry.is_solid(glass) && !registry.is_opaque(glass));
+
+        let torch = registry.by_name("torch").unwrap();
+        assert!(!torch.is_opaque());
+        assert_eq!(torch.light(), 14);
+        assert_eq!(
+            torch.collision(),
+            CollisionShape::Box {
+                min: [0.4, 0.0, 0.4],
+                max: [0.6, 0.6, 0.6]
+            }
+        );
+    }
+
+    #[test]
+    fn test_errors_report_line() {
+        expect_error("[[block]]\nname = \"a\"\nsolid = 3\n", 3, "bool");
+        expect_error("[[block]]\nname = \"a\"\ncolour = 3\n", 3, "colour");
+        expect_error("[[block]]\nname = \"a\"\nlight = 16\n", 3, "at most 15");
+        expect_error(
+            "[[block]]\nname = \"a\"\n\n[[block]]\nname = \"a\"\n",
+            5,
+            "more than once",
+        );
+        expect_error("[[block]]\nname = \"air\"\n", 2, "more than once");
+        expect_error("[[block]]\nname = \"a\"\nid = 0\n", 3, "reserved");
+        expect_error(
+            "[[block]]\nname = \"a\"\nid = 7\n[[block]]\nname = \"b\"\nid = 7\n",
+            6,
+            "more than one block",
+        );
+        expect_error(
+            "[[block]]\nname = \"a\"\ntextures = { top = 1 }\n",
+            3,
+            "no texture",
+        );
+        expect_error(
+            "[[block]]\nname = \"a\"\n\
+             collision = { box = { min = [0, 0, 0], max = [2, 1, 1] } }\n",
+            3,
+            "within 0 and 1",
+        );
+    }
+
+    #[test]
+    fn test_load_reports_missing_file() {
+        match BlockRegistry::load("/nonexistent/blocks.toml") {
+            Err(StrataError::FileRead { path, .. }) => {
+                assert_eq!(path, "/nonexistent/blocks.toml");
+            }
+            other => panic!("Expected FileRead, got {:?}", other.err()),
+        }
+    }
+}
@@ -0,0 +1,441 @@
+//! Procedural terrain generation
+
+use substrate::jobs::{JobHandle, JobSystem};
+
+use super::chunk::{BlockId, CHUNK_SIZE, Chunk};
+use super::chunk_map::ChunkPos;
+use super::noise::{Noise, split_mix};
+use super::registry::BlockRegistry;
+use crate::{Result, StrataError};
+
+/// Fills chunks with the blocks of a world.
+///
+/// A chunk must depend only on the generator's settings and its position,
+/// so chunks can be generated in any order, on any thread, and unloaded and
+/// regenerated later. To generate in the background, share the generator
+/// in an `Arc` and spawn [`TerrainGenerator::generate`] as a job.
+pub trait TerrainGenerator: Send + Sync {
+    /// The blocks of the chunk at `pos`
+    fn generate(&self, pos: ChunkPos) -> Chunk;
+
+    /// Generate each chunk in `positions` on `jobs`, in parallel, and
+    /// return them in the same order
+    fn generate_all(
+        &self,
+        jobs: &JobSystem,
+        positions: &[ChunkPos],
+    ) -> Vec<Chunk> {
+        jobs.scope(|scope| {
+            let handles: Vec<_> = positions
+                .iter()
+                .map(|&pos| scope.spawn(move || self.generate(pos)))
+                .collect();
+            handles
+                .into_iter()
+                .map(JobHandle::join)
+                .collect()
+        })
+    }
+}
+
+/// The blocks [`NoiseTerrain`] builds with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TerrainBlocks {
+    pub stone: BlockId,
+    pub dirt: BlockId,
+    pub grass: BlockId,
+    pub sand: BlockId,
+    pub snow: BlockId,
+    pub water: BlockId,
+}
+
+impl TerrainBlocks {
+    /// Look up the blocks named `stone`, `dirt`, `grass`, `sand`, `snow`
+    /// and `water`
+    pub fn from_registry(registry: &BlockRegistry) -> Result<Self> {
+        let id = |name: &str| {
+            registry
+                .id(name)
+                .ok_or_else(|| StrataError::UnknownBlock(name.to_string()))
+        };
+        Ok(Self {
+            stone: id("stone")?,
+            dirt: id("dirt")?,
+            grass: id("grass")?,
+            sand: id("sand")?,
+            snow: id("snow")?,
+            water: id("water")?,
+        })
+    }
+}
+
+/// The broad kind of landscape in a column
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Biome {
+    /// Rolling grassland
+    Plains,
+    /// Flat sand
+    Desert,
+    /// High rocky peaks, snow-capped above the snow line
+    Mountains,
+}
+
+/// Blocks of surface material over the stone
+const SOIL_DEPTH: i32 = 3;
+
+/// Caves stay at least this far below the surface
+const CAVE_ROOF: i32 = 4;
+
+/// How far above sea level mountains turn white
+const SNOW_LINE: i32 = 40;
+
+/// The default [`TerrainGenerator`]: seeded gradient noise shapes a
+/// heightmap, blended between biomes, with water up to sea level and
+/// tunnels carved underground.
+///
+/// # Example
+/// ```
+/// use strata::voxel::{
+///     BlockId, ChunkPos, NoiseTerrain, TerrainBlocks, TerrainGenerator,
+/// };
+///
+/// let blocks = TerrainBlocks {
+///     stone: BlockId::new(1),
+///     dirt: BlockId::new(2),
+///     grass: BlockId::new(3),
+///     sand: BlockId::new(4),
+///     snow: BlockId::new(5),
+///     water: BlockId::new(6),
+/// };
+/// let terrain = NoiseTerrain::new(1234, blocks).with_caves(false);
+///
+/// // Deep underground is solid stone
+/// let chunk = terrain.generate(ChunkPos::new(0, -8, 0));
+/// assert!(chunk.is_uniform());
+/// assert_eq!(chunk.get(0, 0, 0), blocks.stone);
+/// ```
+#[derive(Debug, Clone)]
+pub struct NoiseTerrain {
+    seed: u64,
+    blocks: TerrainBlocks,
+    sea_level: i32,
+    caves: bool,
+    height: Noise,
+    ruggedness: Noise,
+    temperature: Noise,
+    /// Tunnels follow where both of these are near zero
+    tunnels: [Noise; 2],
+}
+
+impl NoiseTerrain {
+    pub fn new(seed: u64, blocks: TerrainBlocks) -> Self {
+        // Independent noise for each field, all derived from the one seed
+        let mut state = seed;
+        let mut noise = || Noise::new(split_mix(&mut state));
+        Self {
+            seed,
+            blocks,
+            sea_level: 0,
+            caves: true,
+            height: noise(),
+            ruggedness: noise(),
+            temperature: noise(),
+            tunnels: [noise(), noise()],
+        }
+    }
+
+    /// Fill air below this height with water. Defaults to 0.
+    pub fn with_sea_level(mut self, sea_level: i32) -> Self {
+        self.sea_level = sea_level;
+        self
+    }
+
+    /// Whether to carve caves. Defaults to true.
+    pub fn with_caves(mut self, caves: bool) -> Self {
+        self.caves = caves;
+        self
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn blocks(&self) -> &TerrainBlocks {
+        &self.blocks
+    }
+
+    pub fn sea_level(&self) -> i32 {
+        self.sea_level
+    }
+
+    /// The height of the topmost terrain block in the column at `x, z`,
+    /// ignoring caves and water
+    pub fn height_at(&self, x: i32, z: i32) -> i32 {
+        self.column(x, z).0
+    }
+
+    /// The biome that dominates the column at `x, z`
+    pub fn biome_at(&self, x: i32, z: i32) -> Biome {
+        self.column(x, z).1
+    }
+
+    /// Height and dominant biome of a column. Each biome has its own base
+    /// height and relief, blended by weight so borders are smooth.
+    fn column(&self, x: i32, z: i32) -> (i32, Biome) {
+        let (x, z) = (x as f64, z as f64);
+        let mountains = smoothstep(
+            0.1,
+            0.4,
+            self.ruggedness
+                .fractal_2d(x / 600.0, z / 600.0, 2),
+        );
+        let desert = (1.0 - mountains)
+            * smoothstep(
+                0.05,
+                0.3,
+                self.temperature
+                    .fractal_2d(x / 800.0, z / 800.0, 2),
+            );
+        let plains = 1.0 - mountains - desert;
+
+        let relief = self
+            .height
+            .fractal_2d(x / 160.0, z / 160.0, 5);
+        let height = plains * (3.0 + 16.0 * relief)
+            + desert * (4.0 + 4.0 * relief)
+            + mountains * (24.0 + 48.0 * relief);
+
+        let biome = if mountains >= plains.max(desert) {
+            Biome::Mountains
+        } else if desert > plains {
+            Biome::Desert
+        } else {
+            Biome::Plains
+        };
+        (self.sea_level + height.round() as i32, biome)
+    }
+
+    /// Whether a tunnel runs through `x, y, z`
+    fn is_cave(&self, x: i32, y: i32, z: i32) -> bool {
+        let (x, y, z) = (x as f64 / 40.0, y as f64 / 24.0, z as f64 / 40.0);
+        let [a, b] = &self.tunnels;
+        let a = a.fractal_3d(x, y, z, 2);
+        let b = b.fractal_3d(x, y, z, 2);
+        a * a + b * b < 0.006
+    }
+
+    /// The block at height `y` in a column whose top is at `height`
+    fn layer(&self, y: i32, height: i32, biome: Biome) -> BlockId {
+        let blocks = &self.blocks;
+        if y > height {
+            return if y <= self.sea_level {
+                blocks.water
+            } else {
+                BlockId::AIR
+            };
+        }
+
+        let depth = height - y;
+        match biome {
+            _ if depth >= SOIL_DEPTH => blocks.stone,
+            Biome::Desert => blocks.sand,
+            // Beaches where land meets the sea
+            Biome::Plains if height <= self.sea_level + 1 => blocks.sand,
+            Biome::Plains if depth == 0 => blocks.grass,
+            Biome::Plains => blocks.dirt,
+            Biome::Mountains
+                if depth == 0 && height >= self.sea_level + SNOW_LINE =>
+            {
+                blocks.snow
+            }
+            Biome::Mountains => blocks.stone,
+        }
+    }
+}
+
+impl TerrainGenerator for NoiseTerrain {
+    fn generate(&self, pos: ChunkPos) -> Chunk {
+        let [x0, y0, z0] = pos.origin();
+        let size = CHUNK_SIZE as i32;
+
+        let mut columns = Vec::with_capacity(CHUNK_SIZE * CHUNK_SIZE);
+        for z in 0..size {
+            for x in 0..size {
+                columns.push(self.column(x0 + x, z0 + z));
+            }
+        }
+        let lowest = columns
+            .iter()
+            .map(|&(height, _)| height)
+            .min()
+            .unwrap();
+        if y0 + size - 1 < lowest - SOIL_DEPTH && !self.caves {
+            return Chunk::filled(self.blocks.stone);
+        }
+
+        let mut chunk = Chunk::new();
+        for (i, &(height, biome)) in columns.iter().enumerate() {
+            let (x, z) = (i % CHUNK_SIZE, i / CHUNK_SIZE);
+            for y in 0..CHUNK_SIZE {
+                let world_y = y0 + y as i32;
+                let mut id = self.layer(world_y, height, biome);
+                if id != BlockId::AIR
+                    && self.caves
+                    && world_y <= height - CAVE_ROOF
+                    && self.is_cave(x0 + x as i32, world_y, z0 + z as i32)
+                {
+                    id = BlockId::AIR;
+                }
+                if id != BlockId::AIR {
+                    chunk.set(x, y, z, id);
+                }
+            }
+        }
+        chunk.compact();
+        chunk
+    }
+}
+
+/// 0 below `edge0`, 1 above `edge1`, and a smooth ramp between
+fn smoothstep(edge0: f64, edge1: f64, t: f64) -> f64 {
+    let t = ((t - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCKS: TerrainBlocks = TerrainBlocks {
+        stone: BlockId::new(1),
+        dirt: BlockId::new(2),
+        grass: BlockId::new(3),
+        sand: BlockId::new(4),
+        snow: BlockId::new(5),
+        water: BlockId::new(6),
+    };
+
+    #[test]
+    fn test_blocks_from_registry() {
+        let registry = BlockRegistry::from_toml(
+            r#"
+            block = [
+                { name = "stone" }, { name = "dirt" }, { name = "grass" },
+                { name = "sand" }, { name = "snow" },
+                { name = "water", solid = false },
+            ]
+            "#,
+            "blocks.toml",
+        )
+        .unwrap();
+        let blocks = TerrainBlocks::from_registry(&registry).unwrap();
+        assert_eq!(blocks.stone, BlockId::new(1));
+        assert_eq!(blocks.water, BlockId::new(6));
+
+        let err =
+            TerrainBlocks::from_registry(&BlockRegistry::new()).unwrap_err();
+        assert!(
+            matches!(err, StrataError::UnknownBlock(name) if name == "stone")
+        );
+    }
+
+    #[test]
+    fn test_columns_follow_height() {
+        let terrain = NoiseTerrain::new(5, BLOCKS).with_caves(false);
+        for pos in [ChunkPos::new(0, 0, 0), ChunkPos::new(-3, 0, 7)] {
+            let chunk = terrain.generate(pos);
+            let [x0, y0, z0] = pos.origin();
+            for ([x, y, z], id) in chunk.iter() {
+                let (wx, wy, wz) =
+                    (x0 + x as i32, y0 + y as i32, z0 + z as i32);
+                let height = terrain.height_at(wx, wz);
+                if wy > height {
+                    let expected =
+                        if wy <= 0 { BLOCKS.water } else { BlockId::AIR };
+                    assert_eq!(id, expected, "at {wx},{wy},{wz}");
+                } else if wy < height - SOIL_DEPTH {
+                    assert_eq!(id, BLOCKS.stone, "at {wx},{wy},{wz}");
+                } else {
+                    assert!(!id.is_air(), "at {wx},{wy},{wz}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_biomes_and_heights_vary() {
+        let terrain = NoiseTerrain::new(11, BLOCKS);
+        let mut biomes = Vec::new();
+        let (mut low, mut high) = (i32::MAX, i32::MIN);
+        for x in (-4000..4000).step_by(97) {
+            for z in (-4000..4000).step_by(89) {
+                let biome = terrain.biome_at(x, z);
+                if !biomes.contains(&biome) {
+                    biomes.push(biome);
+                }
+                let height = terrain.height_at(x, z);
+                low = low.min(height);
+                high = high.max(height);
+            }
+        }
+        assert_eq!(biomes.len(), 3, "{biomes:?}");
+        assert!(low < 0 && high > SNOW_LINE, "{low}..{high}");
+    }
+
+    #[test]
+    fn test_caves_stay_underground() {
+        let terrain = NoiseTerrain::new(8, BLOCKS);
+        let solid = NoiseTerrain::new(8, BLOCKS).with_caves(false);
+        let mut carved = 0;
+        for pos in [ChunkPos::new(0, -1, 0), ChunkPos::new(1, -2, 0)] {
+            let chunk = terrain.generate(pos);
+            let reference = solid.generate(pos);
+            let [x0, _, z0] = pos.origin();
+            for ([x, y, z], id) in chunk.iter() {
+                let expected = reference.get(x, y, z);
+                if id != expected {
+                    assert!(id.is_air());
+                    let top = terrain.height_at(x0 + x as i32, z0 + z as i32);
+                    assert!(pos.origin()[1] + (y as i32) <= top - CAVE_ROOF);
+                    carved += 1;
+                }
+            }
+        }
+        assert!(carved > 0);
+    }
+
+    #[test]
+    fn test_sea_level_moves_water() {
+        let terrain = NoiseTerrain::new(5, BLOCKS)
+            .with_caves(false)
+            .with_sea_level(64);
+        assert_eq!(terrain.sea_level(), 64);
+        let plain = NoiseTerrain::new(5, BLOCKS).with_caves(false);
+
+        // Sea level sits at the bottom of this chunk
+        let chunk = terrain.generate(ChunkPos::new(0, 2, 0));
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                let (wx, wz) = (x as i32, z as i32);
+                let height = terrain.height_at(wx, wz);
+                assert_eq!(height, plain.height_at(wx, wz) + 64);
+                assert_eq!(chunk.get(x, 0, z) == BLOCKS.water, height < 64);
+                assert!(chunk.get(x, 1, z) != BLOCKS.water);
+            }
+        }
+    }
+
+    #[test]
+    fn test_generate_all_matches_generate() {
+        let terrain = NoiseTerrain::new(77, BLOCKS);
+        let positions: Vec<_> = (-1..2)
+            .flat_map(|x| (-1..1).map(move |y| ChunkPos::new(x, y, 2)))
+            .collect();
+        let jobs = JobSystem::new(3);
+        let chunks = terrain.generate_all(&jobs, &positions);
+        assert_eq!(chunks.len(), positions.len());
+        for (pos, chunk) in positions.iter().zip(&chunks) {
+            assert_eq!(*chunk, terrain.generate(*pos));
+        }
+    }
+}
@@ -4,14 +4,30 @@ use std::sync::Arc;
 
 use winit::dpi::LogicalSize;
 use winit::event_loop::ActiveEventLoop;
-use winit::window::{Window, WindowAttributes};
+use winit::monitor::VideoModeHandle;
+use winit::window::{Fullscreen, Window, WindowAttributes};
 
 use crate::Result;
 
+/// How the window occupies the screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WindowMode {
+    /// A regular decorated window
+    #[default]
+    Windowed,
+    /// A borderless window covering the current monitor
+    BorderlessFullscreen,
+    /// Exclusive fullscreen using the monitor video mode closest to the
+    /// configured size
+    ExclusiveFullscreen,
+}
+
 pub struct WindowManager {
     title: String,
     width: u32,
     height: u32,
+    mode: WindowMode,
+    resizable: bool,
     window: Option<Arc<Window>>,
 }
 
@@ -27,20 +43,61 @@ impl WindowManager {
             title: title.to_string(),
             width,
             height,
+            mode: WindowMode::Windowed,
+            resizable: true,
             window: None, // Window created later in create_window()
         })
     }
 
+    /// Set how the window occupies the screen
+    pub fn with_mode(mut self, mode: WindowMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Set whether the user can resize the window
+    pub fn with_resizable(mut self, resizable: bool) -> Self {
+        self.resizable = resizable;
+        self
+    }
+
+    /// Change the window title, updating the window if it already exists
+    pub fn set_title(&mut self, title: &str) {
+        self.title = title.to_string();
+        if let Some(window) = &self.window {
+            window.set_title(title);
+        }
+    }
+
     /// Create the actual window (called from event loop's resumed())
     pub fn create_window(
         &mut self,
         event_loop: &ActiveEventLoop,
     ) -> Result<()> {
+        let fullscreen = match self.mode {
+            WindowMode::Windowed => None,
+            WindowMode::BorderlessFullscreen => {
+                Some(Fullscreen::Borderless(None))
+            }
+            WindowMode::ExclusiveFullscreen => {
+                let video_mode = self
+                    .closest_video_mode(event_loop)
+                    .ok_or_else(|| {
+                        crate::StrataError::WindowCreation(
+                            "No fullscreen video modes available".to_string(),
+                        )
+                    })?;
+                Some(Fullscreen::Exclusive(video_mode))
+            }
+        };
+
         let window = event_loop
             .create_window(
                 WindowAttributes::default()
                     .with_title(&self.title)
-                    .with_inner_size(LogicalSize::new(self.width, self.height)),
+                    .with_inner_size(LogicalSize::new(self.width, self.height))
+                    .with_resizable(self.resizable)
+                    .with_fullscreen(fullscreen),
             )
             .map_err(|e| crate::StrataError::WindowCreation(e.to_string()))?;
 
@@ -62,6 +119,36 @@ impl WindowManager {
     pub fn size(&self) -> (u32, u32) {
         (self.width, self.height)
     }
+
+    /// Get how the window occupies the screen
+    pub fn mode(&self) -> WindowMode {
+        self.mode
+    }
+
+    /// Get whether the window is resizable
+    pub fn resizable(&self) -> bool {
+        self.resizable
+    }
+
+    /// Pick the primary monitor's video mode closest to the configured size,
+    /// preferring the highest refresh rate
+    fn closest_video_mode(
+        &self,
+        event_loop: &ActiveEventLoop,
+    ) -> Option<VideoModeHandle> {
+        let monitor = event_loop
+            .primary_monitor()
+            .or_else(|| event_loop.available_monitors().next())?;
+
+        monitor
+            .video_modes()
+            .min_by_key(|mode| {
+                let size = mode.size();
+                let distance = size.width.abs_diff(self.width) as u64
+                    + size.height.abs_diff(self.height) as u64;
+                (distance, std::cmp::Reverse(mode.refresh_rate_millihertz()))
+            })
+    }
 }
 
 #[cfg(test)]
@@ -91,6 +178,25 @@ mod tests {
         assert_eq!(wm.size(), (1920, 1080));
     }
 
+    #[test]
+    fn test_window_mode_config() {
+        let wm = WindowManager::new()
+            .expect("Failed to create WindowManager")
+            .with_mode(WindowMode::BorderlessFullscreen)
+            .with_resizable(false);
+
+        assert_eq!(wm.mode(), WindowMode::BorderlessFullscreen);
+        assert!(!wm.resizable());
+    }
+
+    #[test]
+    fn test_set_title_before_window_created() {
+        let mut wm =
+            WindowManager::new().expect("Failed to create WindowManager");
+        wm.set_title("Renamed");
+        assert_eq!(wm.title(), "Renamed");
+    }
+
     #[test]
     fn test_window_initially_none() {
         let wm = WindowManager::new().expect("Failed to create WindowManager");
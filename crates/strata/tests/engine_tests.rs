@@ -1,10 +1,19 @@
 //! Integration tests for engine setup and initialization
 //!
-//! Note: These tests verify engine creation and configuration,
-//! but cannot test the actual event loop and rendering without
-//! a display. Those are tested manually by running example-game.
+//! Note: The windowed event loop and rendering can't run without a
+//! display, so game loop behaviour is tested through `run_headless`.
+//! Rendering is tested manually by running example-game.
 
-use strata::{Engine, Game, Renderer};
+use std::time::Duration;
+
+use std::sync::{Arc, Mutex};
+
+use strata::ecs::Changed;
+use strata::{
+    Engine, Entity, ExecutionMode, FixedTimestep, Game, HeadlessClock,
+    HeadlessConfig, JobSystem, Renderer, Scheduler, Stage, StrataError, System,
+    World,
+};
 
 #[test]
 fn test_engine_creation() {
@@ -28,7 +37,7 @@ impl Game for DummyGame {
         "Dummy Game"
     }
     fn update(&mut self, _dt: f64) {}
-    fn render(&mut self, _renderer: &mut Renderer) {}
+    fn render(&mut self, _renderer: &mut Renderer, _alpha: f64) {}
 }
 
 #[test]
@@ -41,3 +50,347 @@ fn test_engine_accepts_game() {
     // The fact this compiles proves the Game trait works correctly
     let _ = (engine, game);
 }
+
+/// Records how often each hook ran and exits after `exit_after` updates
+#[derive(Default)]
+struct CountingGame {
+    updates: u32,
+    fixed_updates: u32,
+    total_dt: f64,
+    exit_after: Option<u32>,
+}
+
+impl Game for CountingGame {
+    fn name(&self) -> &str {
+        "Counting Game"
+    }
+    fn update(&mut self, dt: f64) {
+        self.updates += 1;
+        self.total_dt += dt;
+    }
+    fn fixed_update(&mut self, _dt: f64) {
+        self.fixed_updates += 1;
+    }
+    fn render(&mut self, _renderer: &mut Renderer, _alpha: f64) {
+        panic!("render must not be called without a renderer");
+    }
+    fn should_exit(&self) -> bool {
+        self.exit_after
+            .is_some_and(|n| self.updates >= n)
+    }
+}
+
+#[test]
+fn test_headless_runs_requested_frames() {
+    let engine = Engine::new().expect("Failed to create engine");
+    let mut game = CountingGame::default();
+
+    let frames = engine
+        .run_headless(&mut game, HeadlessConfig::new().with_max_frames(30))
+        .expect("Headless run should succeed");
+
+    assert_eq!(frames, 30);
+    assert_eq!(game.updates, 30);
+    assert_eq!(game.fixed_updates, 0);
+    assert!((game.total_dt - 0.5).abs() < 1e-6);
+}
+
+#[test]
+fn test_headless_stops_when_game_exits() {
+    let engine = Engine::new().expect("Failed to create engine");
+    let mut game = CountingGame {
+        exit_after: Some(7),
+        ..Default::default()
+    };
+
+    let frames = engine
+        .run_headless(&mut game, HeadlessConfig::new().with_max_frames(100))
+        .expect("Headless run should succeed");
+
+    assert_eq!(frames, 7);
+    assert_eq!(game.updates, 7);
+}
+
+#[test]
+fn test_fixed_timestep_rejects_configs_that_never_tick() {
+    let engine = Engine::new().expect("Failed to create engine");
+    let result =
+        engine.with_fixed_timestep(FixedTimestep::new(60.0).with_max_steps(0));
+    assert!(matches!(result, Err(StrataError::InvalidConfig(_))));
+
+    let engine = Engine::new().expect("Failed to create engine");
+    let result = engine.with_fixed_timestep(
+        FixedTimestep::new(60.0).with_max_frame_time(Duration::ZERO),
+    );
+    assert!(matches!(result, Err(StrataError::InvalidConfig(_))));
+}
+
+#[test]
+fn test_headless_fixed_timestep_is_deterministic() {
+    let config = HeadlessConfig::new()
+        .with_max_frames(60)
+        .with_clock(HeadlessClock::Simulated(Duration::from_millis(25)));
+
+    let run = || {
+        let engine = Engine::new()
+            .expect("Failed to create engine")
+            .with_fixed_timestep(FixedTimestep::new(100.0))
+            .expect("Valid timestep");
+        let mut game = CountingGame::default();
+        engine
+            .run_headless(&mut game, config)
+            .expect("Headless run");
+        game.fixed_updates
+    };
+
+    // 60 frames of 25ms at 100 Hz is 150 ticks
+    assert_eq!(run(), 150);
+    assert_eq!(run(), run());
+}
+
+#[test]
+fn test_headless_realtime_paces_frames() {
+    let engine = Engine::new().expect("Failed to create engine");
+    let mut game = CountingGame::default();
+    let config = HeadlessConfig::new()
+        .with_max_frames(5)
+        .with_clock(HeadlessClock::Realtime(Duration::from_millis(10)));
+
+    let start = std::time::Instant::now();
+    engine
+        .run_headless(&mut game, config)
+        .expect("Headless run");
+
+    assert!(start.elapsed() >= Duration::from_millis(40));
+    assert_eq!(game.updates, 5);
+}
+
+#[test]
+fn test_builder_tick_rate_drives_fixed_updates() {
+    let engine = Engine::builder()
+        .title("Builder Test")
+        .tick_rate(30.0)
+        .build()
+        .expect("Failed to build engine");
+    let mut game = CountingGame::default();
+    let config = HeadlessConfig::new()
+        .with_max_frames(30)
+        .with_clock(HeadlessClock::Simulated(Duration::from_millis(100)));
+
+    engine
+        .run_headless(&mut game, config)
+        .expect("Headless run");
+
+    // 3 seconds of simulated time at 30 Hz
+    assert_eq!(game.fixed_updates, 90);
+}
+
+/// Moves every entity with a velocity and counts what changed each frame
+#[derive(Default)]
+struct WorldGame {
+    setups: u32,
+    mover: Option<Entity>,
+    changed_per_frame: Vec<usize>,
+    final_position: f64,
+}
+
+#[derive(Debug, PartialEq)]
+struct Position(f64);
+
+struct Velocity(f64);
+
+impl Game for WorldGame {
+    fn name(&self) -> &str {
+        "World Game"
+    }
+    fn update(&mut self, _dt: f64) {}
+    fn render(&mut self, _renderer: &mut Renderer, _alpha: f64) {}
+    fn setup(&mut self, world: &mut World) {
+        self.setups += 1;
+        self.mover = Some(world.spawn((Position(0.0), Velocity(2.0))));
+        world.spawn((Position(5.0),));
+    }
+    fn update_world(&mut self, world: &mut World, dt: f64) {
+        for (mut position, velocity) in
+            world.query::<(&mut Position, &Velocity)>()
+        {
+            position.0 += velocity.0 * dt;
+        }
+        let changed = world
+            .query_filtered::<&Position, Changed<Position>>()
+            .count();
+        self.changed_per_frame.push(changed);
+        self.final_position = world
+            .get::<Position>(self.mover.unwrap())
+            .unwrap()
+            .0;
+    }
+}
+
+#[test]
+fn test_headless_hands_world_to_game() {
+    let mut engine = Engine::new().expect("Failed to create engine");
+    engine
+        .world_mut()
+        .spawn((Position(1.0),));
+    let mut game = WorldGame::default();
+    let config = HeadlessConfig::new()
+        .with_max_frames(3)
+        .with_clock(HeadlessClock::Simulated(Duration::from_millis(500)));
+
+    engine
+        .run_headless(&mut game, config)
+        .expect("Headless run");
+
+    assert_eq!(game.setups, 1);
+    // Spawned entities count as changed until the first frame ends; after
+    // that only the moving one does
+    assert_eq!(game.changed_per_frame, [3, 1, 1]);
+    assert_eq!(game.final_position, 3.0);
+}
+
+/// Registers a system per stage that logs when it runs
+#[derive(Default)]
+struct SystemsGame {
+    log: Arc<Mutex<Vec<(Stage, f64)>>>,
+}
+
+impl Game for SystemsGame {
+    fn name(&self) -> &str {
+        "Systems Game"
+    }
+    fn update(&mut self, _dt: f64) {}
+    fn render(&mut self, _renderer: &mut Renderer, _alpha: f64) {}
+    fn add_systems(&mut self, scheduler: &mut Scheduler) {
+        for stage in Stage::ALL {
+            let log = self.log.clone();
+            let system = System::new("log", move |ctx| {
+                let total: f64 = ctx
+                    .query::<&Position>()
+                    .map(|position| position.0)
+                    .sum();
+                log.lock().unwrap().push((stage, total));
+            })
+            .with_read::<Position>();
+            scheduler.add_system(stage, system);
+        }
+        scheduler.add_system(
+            Stage::FixedUpdate,
+            System::new("move", |ctx| {
+                let dt = ctx.dt();
+                for (mut position, velocity) in
+                    ctx.query::<(&mut Position, &Velocity)>()
+                {
+                    position.0 += velocity.0 * dt;
+                }
+            })
+            .with_query::<(&mut Position, &Velocity), ()>(),
+        );
+    }
+    fn setup(&mut self, world: &mut World) {
+        world.spawn((Position(0.0), Velocity(1.0)));
+    }
+}
+
+#[test]
+fn test_headless_runs_system_stages() {
+    let engine = Engine::builder()
+        .tick_rate(10.0)
+        .execution_mode(ExecutionMode::SingleThreaded)
+        .build()
+        .expect("Failed to build engine");
+    let mut game = SystemsGame::default();
+    let config = HeadlessConfig::new()
+        .with_max_frames(2)
+        .with_clock(HeadlessClock::Simulated(Duration::from_millis(200)));
+
+    engine
+        .run_headless(&mut game, config)
+        .expect("Headless run");
+
+    // Two fixed ticks per frame, each moving 0.1 after the logging system
+    let log = game.log.lock().unwrap();
+    let frame = |total: f64| {
+        [
+            (Stage::PreUpdate, total),
+            (Stage::FixedUpdate, total),
+            (Stage::FixedUpdate, total + 0.1),
+            (Stage::Update, total + 0.2),
+            (Stage::PostUpdate, total + 0.2),
+            (Stage::RenderExtract, total + 0.2),
+        ]
+    };
+    let expected: Vec<_> = frame(0.0)
+        .into_iter()
+        .chain(frame(0.2))
+        .collect();
+    assert_eq!(log.len(), expected.len());
+    for ((stage, total), (expected_stage, expected_total)) in
+        log.iter().zip(expected)
+    {
+        assert_eq!(*stage, expected_stage);
+        assert!((total - expected_total).abs() < 1e-9);
+    }
+}
+
+/// Sums its frame data with scoped jobs and hands results back through
+/// main-thread jobs
+struct JobsGame {
+    jobs: Arc<JobSystem>,
+    frame_data: Vec<u64>,
+    sums: Arc<Mutex<Vec<u64>>>,
+    frames: u64,
+}
+
+impl Game for JobsGame {
+    fn name(&self) -> &str {
+        "Jobs Game"
+    }
+    fn update(&mut self, _dt: f64) {
+        self.frames += 1;
+        self.frame_data = (0..1000)
+            .map(|i| i * self.frames)
+            .collect();
+
+        let data = &self.frame_data;
+        let sum: u64 = self.jobs.scope(|scope| {
+            let parts: Vec<_> = data
+                .chunks(100)
+                .map(|chunk| scope.spawn(move || chunk.iter().sum::<u64>()))
+                .collect();
+            parts
+                .into_iter()
+                .map(|part| part.join())
+                .sum()
+        });
+
+        // Delivered at the start of the next frame
+        let sums = self.sums.clone();
+        self.jobs
+            .spawn_main(move || sums.lock().unwrap().push(sum));
+    }
+    fn render(&mut self, _renderer: &mut Renderer, _alpha: f64) {}
+}
+
+#[test]
+fn test_headless_runs_jobs_without_window() {
+    let engine = Engine::builder()
+        .worker_threads(2)
+        .build()
+        .expect("Failed to build engine");
+    let mut game = JobsGame {
+        jobs: engine.jobs().clone(),
+        frame_data: Vec::new(),
+        sums: Arc::default(),
+        frames: 0,
+    };
+
+    engine
+        .run_headless(&mut game, HeadlessConfig::new().with_max_frames(3))
+        .expect("Headless run");
+
+    // The last frame's result is still queued
+    assert_eq!(*game.sums.lock().unwrap(), [499_500, 999_000]);
+    assert_eq!(game.jobs.run_main_thread_jobs(), 1);
+    assert_eq!(game.sums.lock().unwrap()[2], 1_498_500);
+}
@@ -0,0 +1,261 @@
+//! Golden-image tests for the offscreen renderer
+//!
+//! Note: These need a Vulkan driver, so they are ignored by default. On CI
+//! install a software ICD such as lavapipe (mesa-vulkan-drivers) and run
+//! `cargo test -- --ignored`.
+
+use strata::{
+    DrawCommand, PipelineDesc, PipelineHandle, Renderer, RendererConfig,
+    StrataError,
+};
+
+const WIDTH: u32 = 64;
+const HEIGHT: u32 = 32;
+
+/// Largest per-channel difference tolerated between drivers
+const TOLERANCE: u8 = 2;
+
+/// `void main() {}` as a vertex shader, hand-assembled SPIR-V
+#[rustfmt::skip]
+const EMPTY_VERTEX_SHADER: &[u32] = &[
+    0x0723_0203, 0x0001_0000, 0, 6, 0, // header, id bound 6
+    0x0002_0011, 1, // OpCapability Shader
+    0x0003_000e, 0, 1, // OpMemoryModel Logical GLSL450
+    0x0005_000f, 0, 4, 0x6e69_616d, 0, // OpEntryPoint Vertex %4 "main"
+    0x0002_0013, 2, // %2 = OpTypeVoid
+    0x0003_0021, 3, 2, // %3 = OpTypeFunction %2
+    0x0005_0036, 2, 4, 0, 3, // %4 = OpFunction %2 None %3
+    0x0002_00f8, 5, // %5 = OpLabel
+    0x0001_00fd, // OpReturn
+    0x0001_0038, // OpFunctionEnd
+];
+
+/// `void main() {}` as a fragment shader, hand-assembled SPIR-V
+#[rustfmt::skip]
+const EMPTY_FRAGMENT_SHADER: &[u32] = &[
+    0x0723_0203, 0x0001_0000, 0, 6, 0, // header, id bound 6
+    0x0002_0011, 1, // OpCapability Shader
+    0x0003_000e, 0, 1, // OpMemoryModel Logical GLSL450
+    0x0005_000f, 4, 4, 0x6e69_616d, 0, // OpEntryPoint Fragment %4 "main"
+    0x0003_0010, 4, 7, // OpExecutionMode %4 OriginUpperLeft
+    0x0002_0013, 2, // %2 = OpTypeVoid
+    0x0003_0021, 3, 2, // %3 = OpTypeFunction %2
+    0x0005_0036, 2, 4, 0, 3, // %4 = OpFunction %2 None %3
+    0x0002_00f8, 5, // %5 = OpLabel
+    0x0001_00fd, // OpReturn
+    0x0001_0038, // OpFunctionEnd
+];
+
+fn offscreen_renderer() -> Renderer {
+    let config = RendererConfig { validation: false, ..Default::default() };
+    Renderer::new_offscreen(WIDTH, HEIGHT, "Offscreen Tests", config)
+        .expect("Failed to create offscreen renderer")
+}
+
+/// A pipeline whose shaders do nothing, for tests that only need handles
+fn empty_pipeline(renderer: &mut Renderer) -> PipelineHandle {
+    renderer
+        .create_pipeline(&PipelineDesc {
+            vertex_shader: EMPTY_VERTEX_SHADER,
+            fragment_shader: EMPTY_FRAGMENT_SHADER,
+            vertex_stride: 12,
+            vertex_attributes: &[],
+            descriptor_set_layouts: &[],
+            alpha_blend: false,
+        })
+        .expect("Failed to create pipeline")
+}
+
+/// Render one frame cleared to `color` and read it back
+fn render_clear(renderer: &mut Renderer, color: [f32; 4]) -> Vec<u8> {
+    renderer.set_clear_color(color);
+    let frame = renderer
+        .begin_frame()
+        .expect("Failed to begin frame");
+    renderer
+        .end_frame(frame)
+        .expect("Failed to end frame");
+    renderer
+        .read_pixels()
+        .expect("Failed to read pixels")
+}
+
+fn assert_all_pixels(pixels: &[u8], expected: [u8; 4]) {
+    assert_eq!(pixels.len(), (WIDTH * HEIGHT * 4) as usize);
+    for (i, pixel) in pixels.chunks_exact(4).enumerate() {
+        let matches = pixel
+            .iter()
+            .zip(expected)
+            .all(|(&a, b)| a.abs_diff(b) <= TOLERANCE);
+        assert!(matches, "pixel {} is {:?}, expected {:?}", i, pixel, expected);
+    }
+}
+
+#[test]
+fn test_offscreen_rejects_zero_size() {
+    let result = Renderer::new_offscreen(
+        0,
+        HEIGHT,
+        "Offscreen Tests",
+        Default::default(),
+    );
+    assert!(matches!(result, Err(StrataError::RendererInit(_))));
+}
+
+#[test]
+#[ignore = "requires a Vulkan driver (e.g. lavapipe)"]
+fn test_offscreen_renderer_reports_target() {
+    let renderer = offscreen_renderer();
+
+    assert!(renderer.is_offscreen());
+    assert_eq!(renderer.extent(), (WIDTH, HEIGHT));
+    assert_eq!(renderer.present_mode(), None);
+}
+
+#[test]
+#[ignore = "requires a Vulkan driver (e.g. lavapipe)"]
+fn test_offscreen_clear_golden() {
+    let mut renderer = offscreen_renderer();
+
+    let pixels = render_clear(&mut renderer, [1.0, 0.0, 0.0, 1.0]);
+    assert_all_pixels(&pixels, [255, 0, 0, 255]);
+
+    // The target is sRGB, so linear 0.5 is stored encoded as 188
+    let pixels = render_clear(&mut renderer, [0.5, 0.5, 0.5, 1.0]);
+    assert_all_pixels(&pixels, [188, 188, 188, 255]);
+}
+
+#[test]
+#[ignore = "requires a Vulkan driver (e.g. lavapipe)"]
+fn test_offscreen_frames_in_flight_read_latest() {
+    let mut renderer = offscreen_renderer();
+
+    // More frames than slots, so every slot is reused at least once
+    for i in 0..=renderer.config().frames_in_flight {
+        let value = if i % 2 == 0 { 0.0 } else { 1.0 };
+        render_clear(&mut renderer, [0.0, value, 0.0, 1.0]);
+    }
+    let pixels = render_clear(&mut renderer, [0.0, 0.0, 1.0, 1.0]);
+    assert_all_pixels(&pixels, [0, 0, 255, 255]);
+}
+
+#[test]
+#[ignore = "requires a Vulkan driver (e.g. lavapipe)"]
+fn test_offscreen_dropped_frame_is_abandoned() {
+    let mut renderer = offscreen_renderer();
+
+    let frame = renderer
+        .begin_frame()
+        .expect("Failed to begin frame");
+    assert!(matches!(
+        renderer.begin_frame(),
+        Err(StrataError::FrameInProgress)
+    ));
+    drop(frame);
+
+    let pixels = render_clear(&mut renderer, [1.0, 0.0, 0.0, 1.0]);
+    assert_all_pixels(&pixels, [255, 0, 0, 255]);
+}
+
+#[test]
+#[ignore = "requires a Vulkan driver (e.g. lavapipe)"]
+fn test_offscreen_resize() {
+    let mut renderer = offscreen_renderer();
+
+    renderer
+        .resize(WIDTH / 2, HEIGHT / 2)
+        .expect("Failed to resize");
+    assert_eq!(renderer.extent(), (WIDTH / 2, HEIGHT / 2));
+
+    renderer.set_clear_color([1.0, 1.0, 1.0, 1.0]);
+    let frame = renderer
+        .begin_frame()
+        .expect("Failed to begin frame");
+    assert_eq!(frame.extent(), (WIDTH / 2, HEIGHT / 2));
+    renderer
+        .end_frame(frame)
+        .expect("Failed to end frame");
+    let pixels = renderer
+        .read_pixels()
+        .expect("Failed to read pixels");
+    assert_eq!(pixels.len(), (WIDTH / 2 * HEIGHT / 2 * 4) as usize);
+    assert!(pixels.iter().all(|&c| c == 255));
+}
+
+#[test]
+#[ignore = "requires a Vulkan driver (e.g. lavapipe)"]
+fn test_offscreen_resize_rejects_zero_size() {
+    let mut renderer = offscreen_renderer();
+
+    assert!(matches!(
+        renderer.resize(0, 0),
+        Err(StrataError::InvalidConfig(_))
+    ));
+    assert!(!renderer.is_minimized());
+    assert_eq!(renderer.extent(), (WIDTH, HEIGHT));
+}
+
+#[test]
+#[ignore = "requires a Vulkan driver (e.g. lavapipe)"]
+#[should_panic(expected = "unknown mesh")]
+fn test_destroyed_mesh_handle_is_rejected() {
+    let mut renderer = offscreen_renderer();
+    let pipeline = empty_pipeline(&mut renderer);
+    let material = renderer.create_material(pipeline, None);
+    let vertices = [[0.0f32; 3]; 3];
+    let mesh = renderer
+        .create_mesh(&vertices, &[0, 1, 2])
+        .expect("Failed to create mesh");
+    // Submitted frames may still be using the mesh when it is destroyed
+    render_clear(&mut renderer, [0.0, 0.0, 0.0, 1.0]);
+
+    assert!(renderer.destroy_mesh(mesh));
+    assert!(!renderer.destroy_mesh(mesh));
+    let replacement = renderer
+        .create_mesh(&vertices, &[0, 1, 2])
+        .expect("Failed to create mesh");
+    assert_ne!(replacement, mesh);
+    render_clear(&mut renderer, [0.0, 0.0, 0.0, 1.0]);
+
+    renderer.draw(DrawCommand::new(mesh, material, [[0.0; 4]; 4]));
+}
+
+#[test]
+#[ignore = "requires a Vulkan driver (e.g. lavapipe)"]
+fn test_draws_with_destroyed_material_or_pipeline_are_skipped() {
+    let mut renderer = offscreen_renderer();
+    let mesh = renderer
+        .create_mesh(&[[0.0f32; 3]; 3], &[0, 1, 2])
+        .expect("Failed to create mesh");
+    let pipeline = empty_pipeline(&mut renderer);
+    let first = renderer.create_material(pipeline, None);
+    let second = renderer.create_material(pipeline, None);
+    renderer.set_clear_color([0.0, 0.0, 1.0, 1.0]);
+
+    let frame = renderer
+        .begin_frame()
+        .expect("Failed to begin frame");
+    renderer.draw(DrawCommand::new(mesh, first, [[0.0; 4]; 4]));
+    assert!(renderer.destroy_material(first));
+    assert!(!renderer.destroy_material(first));
+    renderer
+        .end_frame(frame)
+        .expect("Failed to end frame");
+    assert_eq!(renderer.draw_stats().draws, 0);
+
+    let frame = renderer
+        .begin_frame()
+        .expect("Failed to begin frame");
+    renderer.draw(DrawCommand::new(mesh, second, [[0.0; 4]; 4]));
+    assert!(renderer.destroy_pipeline(pipeline));
+    assert!(!renderer.destroy_pipeline(pipeline));
+    renderer
+        .end_frame(frame)
+        .expect("Failed to end frame");
+    assert_eq!(renderer.draw_stats().draws, 0);
+
+    let pixels = renderer
+        .read_pixels()
+        .expect("Failed to read pixels");
+    assert_all_pixels(&pixels, [0, 0, 255, 255]);
+}
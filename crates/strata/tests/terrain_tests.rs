@@ -0,0 +1,109 @@
+//! Snapshot tests for terrain generation
+//!
+//! Generated chunks are hashed and compared with hashes recorded for a few
+//! seeds. A failure means the same seed no longer builds the same world;
+//! if that is intended, replace the table with the one printed.
+
+use strata::JobSystem;
+use strata::voxel::{
+    BlockId, Chunk, ChunkPos, NoiseTerrain, TerrainBlocks, TerrainGenerator,
+};
+
+const BLOCKS: TerrainBlocks = TerrainBlocks {
+    stone: BlockId::new(1),
+    dirt: BlockId::new(2),
+    grass: BlockId::new(3),
+    sand: BlockId::new(4),
+    snow: BlockId::new(5),
+    water: BlockId::new(6),
+};
+
+/// Surface, underground, open sky and far-away chunks
+const POSITIONS: [ChunkPos; 5] = [
+    ChunkPos::new(0, 0, 0),
+    ChunkPos::new(-1, 0, 2),
+    ChunkPos::new(0, -1, 0),
+    ChunkPos::new(3, 1, -2),
+    ChunkPos::new(-250, 0, 400),
+];
+
+/// FNV-1a over each block id in index order, stable across platforms and
+/// Rust versions
+fn chunk_hash(chunk: &Chunk) -> u64 {
+    chunk
+        .blocks()
+        .flat_map(|id| id.raw().to_le_bytes())
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+fn assert_snapshot(seed: u64, expected: [u64; POSITIONS.len()]) {
+    let terrain = NoiseTerrain::new(seed, BLOCKS);
+    let jobs = JobSystem::new(2);
+    let actual: Vec<u64> = terrain
+        .generate_all(&jobs, &POSITIONS)
+        .iter()
+        .map(chunk_hash)
+        .collect();
+    let table: Vec<String> = actual
+        .iter()
+        .map(|hash| format!("{hash:#018x}"))
+        .collect();
+    assert_eq!(actual, expected, "\nseed {seed}: [{}]", table.join(", "));
+}
+
+#[test]
+fn test_snapshot_seed_0() {
+    assert_snapshot(
+        0,
+        [
+            0x1a549c0d76143794,
+            0x028364dd87857a7c,
+            0xd83b17897b393a85,
+            0xeb05052ea5b62325,
+            0xe86c527668619ef5,
+        ],
+    );
+}
+
+#[test]
+fn test_snapshot_seed_1234() {
+    assert_snapshot(
+        1234,
+        [
+            0xfac55ae270331150,
+            0x990041fb01f576ff,
+            0x26aecf681eb657df,
+            0xeb05052ea5b62325,
+            0xd4ec2704d88c9da5,
+        ],
+    );
+}
+
+#[test]
+fn test_snapshot_seed_max() {
+    assert_snapshot(
+        u64::MAX,
+        [
+            0x94a777ea3346f86a,
+            0xd211fbfff1d40f3a,
+            0x24391d15b9dce1bc,
+            0xeb05052ea5b62325,
+            0xb9c2722187626664,
+        ],
+    );
+}
+
+#[test]
+fn test_chunks_are_not_trivial() {
+    let terrain = NoiseTerrain::new(1234, BLOCKS);
+    let surface = terrain.generate(ChunkPos::new(0, 0, 0));
+    assert!(!surface.is_uniform());
+    assert!(surface.blocks().any(|id| id.is_air()));
+    assert!(
+        surface
+            .blocks()
+            .any(|id| id == BLOCKS.stone)
+    );
+}
@@ -0,0 +1,143 @@
+//! Golden tests for chunk meshing
+//!
+//! Each test meshes a small hand-built chunk and compares a text dump of
+//! the quads with the expected output. A quad is printed as its face,
+//! texture layer, triangle corners and then each corner's position,
+//! occlusion and block/sky light.
+
+use std::fmt::Write;
+
+use strata::voxel::{
+    BlockId, BlockRegistry, Chunk, ChunkMesh, ChunkMesher, Face, Light,
+};
+
+const BLOCKS: &str = r#"
+[[block]]
+name = "stone"
+id = 1
+textures = 1
+
+[[block]]
+name = "grass"
+id = 2
+textures = { top = 3, bottom = 1, side = 2 }
+"#;
+
+const STONE: BlockId = BlockId::new(1);
+const GRASS: BlockId = BlockId::new(2);
+
+fn mesh(chunk: &Chunk) -> ChunkMesh {
+    let registry =
+        BlockRegistry::from_toml(BLOCKS, "blocks.toml").expect("Valid blocks");
+    ChunkMesher::new().mesh(chunk, [None; 6], &registry)
+}
+
+/// One line per quad, optionally only those facing `face`
+fn dump(mesh: &ChunkMesh, face: Option<Face>) -> String {
+    let mut out = String::new();
+    for (quad, vertices) in mesh.vertices().chunks(4).enumerate() {
+        if face.is_some_and(|face| vertices[0].face() != face) {
+            continue;
+        }
+        let base = quad as u32 * 4;
+        let triangles: Vec<_> = mesh.indices()[quad * 6..][..6]
+            .iter()
+            .map(|&i| (i - base).to_string())
+            .collect();
+        write!(
+            out,
+            "{:?} L{} [{}]",
+            vertices[0].face(),
+            vertices[0].layer(),
+            triangles.join("")
+        )
+        .unwrap();
+        for vertex in vertices {
+            let [x, y, z] = vertex.position();
+            let light = vertex.light();
+            write!(
+                out,
+                " {x},{y},{z} a{} {}/{}",
+                vertex.ao(),
+                light.block(),
+                light.sky()
+            )
+            .unwrap();
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn assert_golden(actual: &str, expected: &str) {
+    assert_eq!(actual.trim(), expected.trim(), "\n--- actual ---\n{actual}");
+}
+
+#[test]
+fn test_golden_single_block() {
+    let mut chunk = Chunk::new();
+    chunk.set(0, 0, 0, GRASS);
+
+    assert_golden(
+        &dump(&mesh(&chunk), None),
+        "
+East L2 [021032] 1,0,0 a3 0/15 1,0,1 a3 0/15 1,1,1 a3 0/15 1,1,0 a3 0/15
+West L2 [012023] 0,0,0 a3 0/15 0,0,1 a3 0/15 0,1,1 a3 0/15 0,1,0 a3 0/15
+Top L3 [021032] 0,1,0 a3 0/15 1,1,0 a3 0/15 1,1,1 a3 0/15 0,1,1 a3 0/15
+Bottom L1 [012023] 0,0,0 a3 0/15 1,0,0 a3 0/15 1,0,1 a3 0/15 0,0,1 a3 0/15
+South L2 [012023] 0,0,1 a3 0/15 1,0,1 a3 0/15 1,1,1 a3 0/15 0,1,1 a3 0/15
+North L2 [021032] 0,0,0 a3 0/15 1,0,0 a3 0/15 1,1,0 a3 0/15 0,1,0 a3 0/15
+",
+    );
+}
+
+#[test]
+fn test_golden_diagonal_neighbour_flips_quad() {
+    let mut chunk = Chunk::new();
+    chunk.set(0, 0, 0, STONE);
+    chunk.set(1, 1, 1, STONE);
+
+    assert_golden(
+        &dump(&mesh(&chunk), Some(Face::Top)),
+        "
+Top L1 [132103] 0,1,0 a3 0/15 1,1,0 a3 0/15 1,1,1 a2 0/15 0,1,1 a3 0/15
+Top L1 [021032] 1,2,1 a3 0/15 2,2,1 a3 0/15 2,2,2 a3 0/15 1,2,2 a3 0/15
+",
+    );
+}
+
+#[test]
+fn test_golden_step_occludes_without_flip() {
+    let mut chunk = Chunk::new();
+    chunk.set(0, 0, 0, STONE);
+    chunk.set(1, 0, 0, STONE);
+    chunk.set(0, 1, 0, STONE);
+
+    assert_golden(
+        &dump(&mesh(&chunk), Some(Face::Top)),
+        "
+Top L1 [021032] 1,1,0 a2 0/15 2,1,0 a3 0/15 2,1,1 a3 0/15 1,1,1 a2 0/15
+Top L1 [021032] 0,2,0 a3 0/15 1,2,0 a3 0/15 1,2,1 a3 0/15 0,2,1 a3 0/15
+",
+    );
+}
+
+#[test]
+fn test_golden_smooth_torch_light() {
+    let mut chunk = Chunk::new();
+    chunk.fill_light(Light::DARK);
+    for x in 1..4 {
+        chunk.set(x, 0, 1, STONE);
+    }
+    chunk.set_light(1, 1, 1, Light::new(12, 0));
+    chunk.set_light(2, 1, 1, Light::new(6, 0));
+
+    assert_golden(
+        &dump(&mesh(&chunk), Some(Face::Top)),
+        "
+Top L1 [021032] 1,1,1 a3 3/0 2,1,1 a3 5/0 2,1,2 a3 5/0 1,1,2 a3 3/0
+Top L1 [021032] 2,1,1 a3 5/0 3,1,1 a3 2/0 3,1,2 a3 2/0 2,1,2 a3 5/0
+Top L1 [021032] 3,1,1 a3 2/0 4,1,1 a3 0/0 4,1,2 a3 0/0 3,1,2 a3 2/0
+",
+    );
+}
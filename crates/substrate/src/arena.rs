@@ -1,11 +1,60 @@
 //! arena.rs
 
-use std::alloc::Layout;
-use std::ptr::NonNull;
+use std::alloc::{self, Layout};
+use std::cell::{Cell, RefCell};
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::Deref;
+use std::ptr::{self, NonNull};
 
+mod collections;
+mod per_thread;
+
+pub use collections::{
+    AllocError, Allocator, ArenaHashMap, ArenaHashSet, ArenaString, ArenaVec,
+};
+pub use per_thread::{LocalArena, ThreadLocalArenas};
+
+/// Alignment of every block, so common types need no padding at the start
+const BLOCK_ALIGN: usize = 16;
+
+/// Smallest block a growable arena allocates
+const MIN_BLOCK_SIZE: usize = 4096;
+
+/// A bump allocator over one or more blocks of memory.
+///
+/// Allocation takes `&self`, so any number of allocations can be alive at
+/// once; they all borrow the arena and are invalidated together by
+/// [`Arena::reset`], which needs `&mut self`.
+///
+/// [`Arena::new`] makes a single fixed-size block. [`Arena::growable`]
+/// allocates further blocks on demand, each at least twice the size of the
+/// last, up to an optional [`Arena::with_max_capacity`]. Reset keeps only
+/// the largest block, so a steady workload settles into one block.
+///
+/// Destructors of allocated values only run if the arena was created with
+/// [`Arena::with_drop_list`]; otherwise values needing drop are leaked on
+/// reset, which is safe but skips their cleanup. Values that may be dropped
+/// by the arena must be `'static`: the arena outlives any local they could
+/// borrow, so a registered destructor must not touch one.
+///
+/// `&Arena` implements [`Allocator`], so [`ArenaVec`], [`ArenaHashMap`] and
+/// [`ArenaString`] can keep per-frame collections in the arena.
 pub struct Arena {
-    buf: Vec<u8>,
-    offset: usize,
+    blocks: RefCell<Vec<Block>>,
+    /// Start and size of the block being bumped, the last in `blocks`
+    current: Cell<(NonNull<u8>, usize)>,
+    offset: Cell<usize>,
+    /// Bytes used in blocks before the current one
+    retired: Cell<usize>,
+    capacity: Cell<usize>,
+    max_capacity: Option<usize>,
+    high_water_mark: Cell<usize>,
+    drops: Option<RefCell<Vec<DropEntry>>>,
+    /// Bumped by every rewind and reset so older marks are refused
+    epoch: u64,
+    // Raw block pointers and values of any type: neither Send nor Sync
+    _marker: PhantomData<*mut u8>,
 }
 
 #[derive(Debug)]
@@ -13,52 +62,505 @@ pub enum ArenaError {
     OutOfMemory,
 }
 
+/// Usage figures for an [`Arena`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ArenaStats {
+    /// Bytes allocated since the last reset, including alignment padding
+    pub used: usize,
+    /// Bytes in all blocks currently held
+    pub capacity: usize,
+    pub blocks: usize,
+    /// Most bytes ever in use at once, across resets
+    pub high_water_mark: usize,
+}
+
+/// A savepoint returned by [`Arena::mark`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArenaMark {
+    blocks: usize,
+    block: NonNull<u8>,
+    offset: usize,
+    retired: usize,
+    drops: usize,
+    epoch: u64,
+}
+
+/// Rewinds its arena to where it was created when dropped. Returned by
+/// [`Arena::scope`].
+///
+/// Only derefs to `&Arena`, so the arena can't be reset or rewound past
+/// the scope's start while it is open; nest with [`ArenaScope::scope`].
+///
+/// ```compile_fail
+/// # use substrate::arena::Arena;
+/// let mut arena = Arena::new(64);
+/// let mut scope = arena.scope();
+/// scope.reset();
+/// ```
+pub struct ArenaScope<'a> {
+    arena: &'a mut Arena,
+    mark: ArenaMark,
+}
+
+impl Deref for ArenaScope<'_> {
+    type Target = Arena;
+
+    fn deref(&self) -> &Arena {
+        self.arena
+    }
+}
+
+impl ArenaScope<'_> {
+    /// Start a scope nested in this one
+    pub fn scope(&mut self) -> ArenaScope<'_> {
+        self.arena.scope()
+    }
+}
+
+impl Drop for ArenaScope<'_> {
+    fn drop(&mut self) {
+        // Nested scopes bump the epoch as they close, but they can only
+        // rewind to points after this one, so the mark is still good
+        self.arena.rewind_unchecked(self.mark);
+    }
+}
+
+/// One heap allocation the arena bumps through
+struct Block {
+    ptr: NonNull<u8>,
+    size: usize,
+}
+
+impl Block {
+    fn new(size: usize) -> Self {
+        let layout = Self::layout(size);
+        let ptr = unsafe { alloc::alloc(layout) };
+        let ptr = NonNull::new(ptr)
+            .unwrap_or_else(|| alloc::handle_alloc_error(layout));
+        Self { ptr, size }
+    }
+
+    fn layout(size: usize) -> Layout {
+        Layout::from_size_align(size, BLOCK_ALIGN)
+            .expect("arena block size overflows isize")
+    }
+}
+
+impl Drop for Block {
+    fn drop(&mut self) {
+        unsafe { alloc::dealloc(self.ptr.as_ptr(), Self::layout(self.size)) };
+    }
+}
+
+/// A value or slice in the arena waiting to be dropped on reset
+struct DropEntry {
+    ptr: *mut u8,
+    len: usize,
+    drop: unsafe fn(*mut u8, usize),
+}
+
+/// Drop `len` values of `T` starting at `ptr`
+unsafe fn drop_slice<T>(ptr: *mut u8, len: usize) {
+    unsafe {
+        ptr::drop_in_place(ptr::slice_from_raw_parts_mut(ptr as *mut T, len))
+    };
+}
+
 impl Arena {
+    /// A single block of `capacity` bytes that never grows
     pub fn new(capacity: usize) -> Self {
-        let buf = Vec::with_capacity(capacity);
-        Self { buf, offset: 0 }
+        Self::growable(capacity).with_max_capacity(capacity)
+    }
+
+    /// Start with a block of `initial` bytes (none if 0) and add blocks as
+    /// needed, without limit unless [`Arena::with_max_capacity`] is set
+    pub fn growable(initial: usize) -> Self {
+        let arena = Self {
+            blocks: RefCell::new(Vec::new()),
+            current: Cell::new((NonNull::<u128>::dangling().cast(), 0)),
+            offset: Cell::new(0),
+            retired: Cell::new(0),
+            capacity: Cell::new(0),
+            max_capacity: None,
+            high_water_mark: Cell::new(0),
+            drops: None,
+            epoch: 0,
+            _marker: PhantomData,
+        };
+        if initial > 0 {
+            arena.push_block(initial);
+        }
+        arena
+    }
+
+    /// Never hold more than `bytes` across all blocks. Allocations that
+    /// would need more fail with [`ArenaError::OutOfMemory`].
+    pub fn with_max_capacity(mut self, bytes: usize) -> Self {
+        self.max_capacity = Some(bytes);
+        self
+    }
+
+    /// Run the destructors of allocated values on reset and drop, in reverse
+    /// allocation order
+    pub fn with_drop_list(mut self) -> Self {
+        self.drops = Some(RefCell::new(Vec::new()));
+        self
     }
 
     pub fn capacity(&self) -> usize {
-        self.buf.capacity()
+        self.capacity.get()
+    }
+
+    pub fn max_capacity(&self) -> Option<usize> {
+        self.max_capacity
     }
 
     pub fn used(&self) -> usize {
-        self.offset
+        self.retired.get() + self.offset.get()
+    }
+
+    /// Most bytes ever in use at once, across resets
+    pub fn high_water_mark(&self) -> usize {
+        self.high_water_mark.get()
+    }
+
+    pub fn stats(&self) -> ArenaStats {
+        ArenaStats {
+            used: self.used(),
+            capacity: self.capacity(),
+            blocks: self.blocks.borrow().len(),
+            high_water_mark: self.high_water_mark(),
+        }
+    }
+
+    /// Whether destructors run on reset
+    pub fn has_drop_list(&self) -> bool {
+        self.drops.is_some()
     }
 
     pub fn alloc_layout(
-        &mut self,
+        &self,
         layout: Layout,
     ) -> Result<NonNull<u8>, ArenaError> {
-        let base = self.buf.as_ptr() as usize;
-        let current = base + self.offset;
-        let next = (current + layout.align() - 1) & !(layout.align() - 1);
+        let ptr = match self.bump(layout) {
+            Some(ptr) => ptr,
+            None => {
+                self.grow(layout)?;
+                self.bump(layout)
+                    .expect("new block fits the allocation")
+            }
+        };
+        let used = self.used();
+        if used > self.high_water_mark.get() {
+            self.high_water_mark.set(used);
+        }
+        Ok(ptr)
+    }
 
-        let new_offset = next - base + layout.size();
-        if new_offset > self.capacity() {
-            return Err(ArenaError::OutOfMemory);
+    /// Allocate from the current block, if it has room
+    fn bump(&self, layout: Layout) -> Option<NonNull<u8>> {
+        let (block, size) = self.current.get();
+        let base = block.as_ptr() as usize;
+        let current = base + self.offset.get();
+        let next =
+            current.checked_add(layout.align() - 1)? & !(layout.align() - 1);
+
+        let new_offset = (next - base).checked_add(layout.size())?;
+        if new_offset > size {
+            return None;
         }
-        self.offset = new_offset;
+        self.offset.set(new_offset);
+
+        // Derived from the block pointer so it keeps the block's provenance
+        NonNull::new(unsafe { block.as_ptr().add(next - base) })
+    }
+
+    /// Add a block big enough for `layout`, doubling the last block's size
+    fn grow(&self, layout: Layout) -> Result<(), ArenaError> {
+        // Blocks are 16-aligned, so only larger alignments may need padding
+        let needed = layout
+            .size()
+            .checked_add(
+                layout
+                    .align()
+                    .saturating_sub(BLOCK_ALIGN),
+            )
+            .filter(|&needed| needed <= isize::MAX as usize - BLOCK_ALIGN)
+            .ok_or(ArenaError::OutOfMemory)?
+            .max(1);
+        let last = self.current.get().1;
+        let mut size = last
+            .saturating_mul(2)
+            .clamp(MIN_BLOCK_SIZE, isize::MAX as usize - BLOCK_ALIGN)
+            .max(needed);
+
+        if let Some(max) = self.max_capacity {
+            let remaining = max.saturating_sub(self.capacity());
+            if needed > remaining {
+                return Err(ArenaError::OutOfMemory);
+            }
+            size = size.min(remaining);
+        }
+
+        self.retired.set(self.used());
+        self.offset.set(0);
+        self.push_block(size);
+        Ok(())
+    }
 
-        NonNull::new(next as *mut u8).ok_or(ArenaError::OutOfMemory)
+    fn push_block(&self, size: usize) {
+        let block = Block::new(size);
+        self.current.set((block.ptr, size));
+        self.capacity
+            .set(self.capacity() + size);
+        self.blocks.borrow_mut().push(block);
     }
 
+    /// Free every allocation, running destructors if there is a drop list,
+    /// and release all blocks except the largest
     pub fn reset(&mut self) {
-        self.offset = 0;
+        self.run_drops(0);
+
+        let blocks = self.blocks.get_mut();
+        if let Some(largest) = (0..blocks.len()).max_by_key(|&i| blocks[i].size)
+        {
+            let block = blocks.swap_remove(largest);
+            blocks.clear();
+            self.current
+                .set((block.ptr, block.size));
+            self.capacity.set(block.size);
+            blocks.push(block);
+        }
+        self.offset.set(0);
+        self.retired.set(0);
+        self.epoch += 1;
+    }
+
+    /// A savepoint to [`Arena::rewind`] to, valid until the next rewind or
+    /// reset
+    pub fn mark(&self) -> ArenaMark {
+        ArenaMark {
+            blocks: self.blocks.borrow().len(),
+            block: self.current.get().0,
+            offset: self.offset.get(),
+            retired: self.retired.get(),
+            drops: self
+                .drops
+                .as_ref()
+                .map_or(0, |drops| drops.borrow().len()),
+            epoch: self.epoch,
+        }
+    }
+
+    /// Free everything allocated since `mark`, running destructors if there
+    /// is a drop list, and release blocks added since.
+    ///
+    /// Taking `&mut self` means no allocation can still be borrowed, so
+    /// nothing made after the mark can be used once it is rewound:
+    ///
+    /// ```compile_fail
+    /// # use substrate::arena::Arena;
+    /// let mut arena = Arena::new(64);
+    /// let mark = arena.mark();
+    /// let value = arena.alloc(1u32).unwrap();
+    /// arena.rewind(mark);
+    /// *value += 1;
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `mark` came from another arena or was invalidated by an
+    /// earlier rewind or a reset.
+    pub fn rewind(&mut self, mark: ArenaMark) {
+        let blocks = self.blocks.get_mut();
+        let valid = match mark.blocks.checked_sub(1) {
+            None => true,
+            Some(last) => {
+                blocks
+                    .get(last)
+                    .is_some_and(|block| block.ptr == mark.block)
+                    && (blocks.len() > mark.blocks
+                        || self.offset.get() >= mark.offset)
+            }
+        };
+        assert!(
+            valid && mark.epoch == self.epoch,
+            "arena mark is stale or from another arena"
+        );
+        self.rewind_unchecked(mark);
+    }
+
+    fn rewind_unchecked(&mut self, mark: ArenaMark) {
+        self.run_drops(mark.drops);
+
+        let blocks = self.blocks.get_mut();
+        for block in blocks.drain(mark.blocks..) {
+            self.capacity
+                .set(self.capacity.get() - block.size);
+        }
+        let current = blocks
+            .last()
+            .map_or((NonNull::<u128>::dangling().cast(), 0), |block| {
+                (block.ptr, block.size)
+            });
+        self.current.set(current);
+        self.offset.set(mark.offset);
+        self.retired.set(mark.retired);
+        self.epoch += 1;
+    }
+
+    /// Start a scope whose allocations are rewound when the guard drops.
+    ///
+    /// The guard derefs to the arena, so allocations borrow it and can't
+    /// escape the scope; scopes nest through [`ArenaScope::scope`].
+    ///
+    /// ```compile_fail
+    /// # use substrate::arena::Arena;
+    /// let mut arena = Arena::new(64);
+    /// let escaped = {
+    ///     let scope = arena.scope();
+    ///     scope.alloc(1u32).unwrap()
+    /// };
+    /// ```
+    pub fn scope(&mut self) -> ArenaScope<'_> {
+        let mark = self.mark();
+        ArenaScope { arena: self, mark }
+    }
+
+    /// Run and forget the drop entries from index `from` on, newest first
+    fn run_drops(&mut self, from: usize) {
+        let Some(drops) = &mut self.drops else {
+            return;
+        };
+        let entries = drops.get_mut();
+        if from >= entries.len() {
+            return;
+        }
+        // Detached first so a panicking destructor can't cause a double drop
+        let mut pending = if from == 0 {
+            mem::take(entries)
+        } else {
+            entries.split_off(from)
+        };
+        for entry in pending.iter().rev() {
+            unsafe { (entry.drop)(entry.ptr, entry.len) };
+        }
+        if from == 0 {
+            // Keep the list's allocation for the next frame
+            pending.clear();
+            *drops.get_mut() = pending;
+        }
+    }
+
+    /// Remember to drop `len` values of `T` at `ptr`, if this arena runs
+    /// destructors and `T` has one
+    fn register_drop<T: 'static>(&self, ptr: *mut T, len: usize) {
+        if let Some(drops) = &self.drops
+            && mem::needs_drop::<T>()
+        {
+            drops.borrow_mut().push(DropEntry {
+                ptr: ptr as *mut u8,
+                len,
+                drop: drop_slice::<T>,
+            });
+        }
+    }
+
+    fn alloc_uninit<T>(&self) -> Result<*mut T, ArenaError> {
+        Ok(self
+            .alloc_layout(Layout::new::<T>())?
+            .as_ptr() as *mut T)
+    }
+
+    fn alloc_uninit_slice<T>(&self, len: usize) -> Result<*mut T, ArenaError> {
+        let layout =
+            Layout::array::<T>(len).map_err(|_| ArenaError::OutOfMemory)?;
+        Ok(self.alloc_layout(layout)?.as_ptr() as *mut T)
     }
 
-    pub fn alloc<T: Copy>(&mut self, value: T) -> Result<&mut T, ArenaError> {
-        let layout = Layout::new::<T>();
-        let ptr = self.alloc_layout(layout)?.as_ptr() as *mut T;
+    /// Move `value` into the arena.
+    ///
+    /// `T` must be `'static`, since its destructor may run at the next
+    /// reset, long after anything it borrowed is gone:
+    ///
+    /// ```compile_fail
+    /// # use substrate::arena::Arena;
+    /// let mut arena = Arena::new(256).with_drop_list();
+    /// {
+    ///     let name = String::from("local");
+    ///     arena.alloc(vec![name.as_str()]).unwrap();
+    /// }
+    /// arena.reset();
+    /// ```
+    // Each call hands out a fresh, disjoint region of the buffer, so the
+    // returned `&mut` never aliases another allocation
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc<T: 'static>(&self, value: T) -> Result<&mut T, ArenaError> {
+        let ptr = self.alloc_uninit::<T>()?;
         unsafe { ptr.write(value) };
-        return unsafe { ptr.as_mut().ok_or(ArenaError::OutOfMemory) };
+        self.register_drop(ptr, 1);
+        Ok(unsafe { &mut *ptr })
+    }
+
+    /// Allocate space first, then construct the value in place with `f`
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_with<T: 'static>(
+        &self,
+        f: impl FnOnce() -> T,
+    ) -> Result<&mut T, ArenaError> {
+        let ptr = self.alloc_uninit::<T>()?;
+        unsafe { ptr.write(f()) };
+        self.register_drop(ptr, 1);
+        Ok(unsafe { &mut *ptr })
+    }
+
+    /// Clone `src` into the arena
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_slice<T: Clone + 'static>(
+        &self,
+        src: &[T],
+    ) -> Result<&mut [T], ArenaError> {
+        let ptr = self.alloc_uninit_slice::<T>(src.len())?;
+        // If a clone panics, the elements written so far are leaked
+        for (i, value) in src.iter().enumerate() {
+            unsafe { ptr.add(i).write(value.clone()) };
+        }
+        self.register_drop(ptr, src.len());
+        Ok(unsafe { std::slice::from_raw_parts_mut(ptr, src.len()) })
+    }
+
+    /// Copy `src` into the arena
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_slice_copy<T: Copy>(
+        &self,
+        src: &[T],
+    ) -> Result<&mut [T], ArenaError> {
+        let ptr = self.alloc_uninit_slice::<T>(src.len())?;
+        unsafe {
+            ptr::copy_nonoverlapping(src.as_ptr(), ptr, src.len());
+            Ok(std::slice::from_raw_parts_mut(ptr, src.len()))
+        }
+    }
+
+    /// Copy `src` into the arena
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_str(&self, src: &str) -> Result<&mut str, ArenaError> {
+        let bytes = self.alloc_slice_copy(src.as_bytes())?;
+        Ok(unsafe { std::str::from_utf8_unchecked_mut(bytes) })
+    }
+}
+
+impl Drop for Arena {
+    fn drop(&mut self) {
+        // Blocks free themselves afterwards
+        self.run_drops(0);
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::rc::Rc;
 
     #[test]
     fn new_arena_has_correct_capacity_and_zero_offset() {
@@ -69,7 +571,7 @@ mod tests {
 
     #[test]
     fn alloc_layout_returns_ptr_to_advanced_offset_when_enough_capacity() {
-        let mut arena = Arena::new(1024);
+        let arena = Arena::new(1024);
         let layout =
             Layout::from_size_align(8, 8).expect("Should be valid layout.");
         let result = arena.alloc_layout(layout);
@@ -79,7 +581,7 @@ mod tests {
 
     #[test]
     fn alloc_layout_returns_err_outofmemory_when_new_offset_exceeds_capacity() {
-        let mut arena = Arena::new(8);
+        let arena = Arena::new(8);
         let layout =
             Layout::from_size_align(9, 8).expect("Should be valid layout.");
         let result = arena.alloc_layout(layout);
@@ -99,8 +601,396 @@ mod tests {
 
     #[test]
     fn alloc_u64_writes_u64_value() {
-        let mut arena = Arena::new(64);
-        let ptr = arena.alloc::<u64>(42).expect("Should be enough room");
+        let arena = Arena::new(64);
+        let ptr = arena
+            .alloc::<u64>(42)
+            .expect("Should be enough room");
         assert_eq!(*ptr, 42);
     }
+
+    #[test]
+    fn multiple_allocations_coexist() {
+        let arena = Arena::new(256);
+        let a = arena.alloc(1u32).unwrap();
+        let b = arena.alloc(2u64).unwrap();
+        let s = arena.alloc_str("frame").unwrap();
+        *a += 10;
+        *b += 20;
+        assert_eq!((*a, *b, &*s), (11, 22, "frame"));
+    }
+
+    #[test]
+    fn alloc_respects_alignment() {
+        let arena = Arena::new(256);
+        arena.alloc(1u8).unwrap();
+        let value = arena.alloc(7u64).unwrap();
+        assert_eq!(value as *mut u64 as usize % align_of::<u64>(), 0);
+        assert_eq!(arena.used(), 16);
+    }
+
+    #[test]
+    fn alloc_slice_copy_copies_values() {
+        let arena = Arena::new(64);
+        let slice = arena
+            .alloc_slice_copy(&[1u16, 2, 3])
+            .unwrap();
+        slice[1] = 20;
+        assert_eq!(slice, &[1, 20, 3]);
+    }
+
+    #[test]
+    fn alloc_slice_clones_non_copy_values() {
+        let arena = Arena::new(256).with_drop_list();
+        let src = vec![String::from("a"), String::from("bc")];
+        let slice = arena.alloc_slice(&src).unwrap();
+        slice[0].push('!');
+        assert_eq!(slice, &["a!", "bc"]);
+        assert_eq!(src[0], "a");
+    }
+
+    #[test]
+    fn alloc_with_constructs_in_place() {
+        let arena = Arena::new(64);
+        let value = arena.alloc_with(|| [3u8; 32]).unwrap();
+        assert_eq!(value[31], 3);
+        assert_eq!(arena.used(), 32);
+    }
+
+    #[test]
+    fn alloc_fails_without_consuming_space() {
+        let arena = Arena::new(8);
+        assert!(
+            arena
+                .alloc_slice_copy(&[0u64; 2])
+                .is_err()
+        );
+        assert!(
+            arena
+                .alloc_str("too long for this")
+                .is_err()
+        );
+        assert_eq!(arena.used(), 0);
+        assert!(arena.alloc(1u64).is_ok());
+    }
+
+    #[test]
+    fn zero_capacity_arena_allows_zero_sized_allocations() {
+        let arena = Arena::new(0);
+        assert!(arena.alloc(()).is_ok());
+        assert!(arena.alloc_str("").is_ok());
+        assert!(arena.alloc(1u8).is_err());
+    }
+
+    #[test]
+    fn reset_runs_drop_list_in_reverse_order() {
+        let order = Rc::new(RefCell::new(Vec::new()));
+        struct Noisy(u32, Rc<RefCell<Vec<u32>>>);
+        impl Drop for Noisy {
+            fn drop(&mut self) {
+                self.1.borrow_mut().push(self.0);
+            }
+        }
+
+        let mut arena = Arena::new(256).with_drop_list();
+        arena
+            .alloc(Noisy(1, order.clone()))
+            .unwrap();
+        arena
+            .alloc_with(|| Noisy(2, order.clone()))
+            .unwrap();
+        arena.reset();
+        assert_eq!(*order.borrow(), vec![2, 1]);
+
+        arena.reset();
+        assert_eq!(order.borrow().len(), 2, "drops run only once");
+    }
+
+    #[test]
+    fn dropping_arena_runs_drop_list() {
+        let counter = Rc::new(());
+        {
+            let arena = Arena::new(256).with_drop_list();
+            arena
+                .alloc_slice(&[counter.clone(), counter.clone()])
+                .unwrap();
+            assert_eq!(Rc::strong_count(&counter), 3);
+        }
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn without_drop_list_values_are_leaked() {
+        let counter = Rc::new(());
+        let mut arena = Arena::new(64);
+        arena.alloc(counter.clone()).unwrap();
+        arena.reset();
+        assert!(!arena.has_drop_list());
+        assert_eq!(Rc::strong_count(&counter), 2);
+    }
+
+    #[test]
+    fn fixed_arena_never_grows() {
+        let arena = Arena::new(64);
+        assert!(
+            arena
+                .alloc_slice_copy(&[0u8; 65])
+                .is_err()
+        );
+        assert_eq!(arena.stats().blocks, 1);
+        assert_eq!(arena.max_capacity(), Some(64));
+    }
+
+    #[test]
+    fn growable_arena_adds_blocks_and_keeps_earlier_allocations() {
+        let arena = Arena::growable(64);
+        let first = arena
+            .alloc_slice_copy(&[7u8; 48])
+            .unwrap();
+        let second = arena
+            .alloc_slice_copy(&[9u8; 100])
+            .unwrap();
+        let third = arena.alloc(5u64).unwrap();
+
+        assert_eq!(arena.stats().blocks, 2);
+        assert!(first.iter().all(|&b| b == 7));
+        assert!(second.iter().all(|&b| b == 9));
+        assert_eq!(*third, 5);
+        assert_eq!(arena.used(), 48 + 100 + 4 + 8);
+    }
+
+    #[test]
+    fn growth_is_geometric() {
+        let arena = Arena::growable(MIN_BLOCK_SIZE);
+        for _ in 0..4 {
+            arena
+                .alloc_slice_copy(&[0u8; MIN_BLOCK_SIZE])
+                .unwrap();
+        }
+        // 4K, then 8K holds the next two, then 16K
+        let stats = arena.stats();
+        assert_eq!(stats.blocks, 3);
+        assert_eq!(stats.capacity, MIN_BLOCK_SIZE * (1 + 2 + 4));
+    }
+
+    #[test]
+    fn growth_fits_oversized_allocations() {
+        let arena = Arena::growable(16);
+        let big = arena
+            .alloc_slice_copy(&[1u8; 3 * MIN_BLOCK_SIZE])
+            .unwrap();
+        assert_eq!(big.len(), 3 * MIN_BLOCK_SIZE);
+
+        #[repr(align(64))]
+        struct Aligned(u8);
+        let aligned = arena.alloc(Aligned(3)).unwrap();
+        assert_eq!(aligned as *mut Aligned as usize % 64, 0);
+        assert_eq!(aligned.0, 3);
+    }
+
+    #[test]
+    fn max_capacity_limits_growth() {
+        let arena = Arena::growable(1024).with_max_capacity(3000);
+        arena
+            .alloc_slice_copy(&[0u8; 1024])
+            .unwrap();
+        // The second block is clamped to the 1976 bytes left
+        arena
+            .alloc_slice_copy(&[0u8; 1900])
+            .unwrap();
+        assert_eq!(arena.capacity(), 3000);
+        assert!(
+            arena
+                .alloc_slice_copy(&[0u8; 100])
+                .is_err()
+        );
+        assert!(arena.alloc(0u32).is_ok());
+    }
+
+    #[test]
+    fn reset_keeps_only_largest_block() {
+        let frame = |arena: &Arena| {
+            arena
+                .alloc_slice_copy(&[0u8; 1000])
+                .unwrap();
+            arena
+                .alloc_slice_copy(&[0u8; 2 * MIN_BLOCK_SIZE])
+                .unwrap();
+            arena
+                .alloc_slice_copy(&[0u8; 500])
+                .unwrap();
+        };
+
+        // 1000 bytes in the first block, then an exactly filled 8K block,
+        // then a 16K block
+        let mut arena = Arena::growable(1024);
+        frame(&arena);
+        assert_eq!(arena.stats().blocks, 3);
+
+        arena.reset();
+        let stats = arena.stats();
+        assert_eq!(stats.blocks, 1);
+        assert_eq!(stats.capacity, 4 * MIN_BLOCK_SIZE);
+        assert_eq!(stats.used, 0);
+
+        // The same frame now fits without growing
+        frame(&arena);
+        assert_eq!(arena.stats().blocks, 1);
+    }
+
+    #[test]
+    fn high_water_mark_survives_reset() {
+        let mut arena = Arena::growable(0);
+        assert_eq!(arena.stats(), ArenaStats::default());
+
+        arena
+            .alloc_slice_copy(&[0u8; 5000])
+            .unwrap();
+        arena.reset();
+        arena
+            .alloc_slice_copy(&[0u8; 100])
+            .unwrap();
+
+        let stats = arena.stats();
+        assert_eq!(stats.used, 100);
+        assert_eq!(stats.high_water_mark, 5000);
+    }
+
+    #[test]
+    fn reset_runs_drops_across_blocks() {
+        let counter = Rc::new(());
+        let mut arena = Arena::growable(64).with_drop_list();
+        for _ in 0..100 {
+            arena.alloc(counter.clone()).unwrap();
+        }
+        assert!(arena.stats().blocks > 1);
+        assert_eq!(Rc::strong_count(&counter), 101);
+
+        arena.reset();
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn rewind_frees_allocations_after_mark() {
+        let mut arena = Arena::new(256);
+        let kept = *arena.alloc(1u64).unwrap();
+        let mark = arena.mark();
+        arena
+            .alloc_slice_copy(&[0u8; 100])
+            .unwrap();
+        assert_eq!(arena.used(), 108);
+
+        arena.rewind(mark);
+        assert_eq!(arena.used(), 8);
+        assert_eq!(kept, 1);
+        // The rewound space is handed out again
+        arena
+            .alloc_slice_copy(&[0u8; 200])
+            .unwrap();
+    }
+
+    #[test]
+    fn rewind_releases_blocks_added_after_mark() {
+        let mut arena = Arena::growable(64);
+        arena.alloc(1u32).unwrap();
+        let mark = arena.mark();
+        arena
+            .alloc_slice_copy(&[0u8; MIN_BLOCK_SIZE * 3])
+            .unwrap();
+        assert_eq!(arena.stats().blocks, 2);
+
+        arena.rewind(mark);
+        let stats = arena.stats();
+        assert_eq!((stats.blocks, stats.capacity, stats.used), (1, 64, 4));
+        assert_eq!(stats.high_water_mark, 4 + MIN_BLOCK_SIZE * 3);
+    }
+
+    #[test]
+    fn rewind_runs_only_newer_drops() {
+        let outer = Rc::new(());
+        let inner = Rc::new(());
+        let mut arena = Arena::new(256).with_drop_list();
+        arena.alloc(outer.clone()).unwrap();
+        let mark = arena.mark();
+        arena.alloc(inner.clone()).unwrap();
+
+        arena.rewind(mark);
+        assert_eq!(Rc::strong_count(&inner), 1);
+        assert_eq!(Rc::strong_count(&outer), 2);
+
+        arena.reset();
+        assert_eq!(Rc::strong_count(&outer), 1);
+    }
+
+    #[test]
+    fn nested_scopes_unwind_like_a_stack() {
+        let mut arena = Arena::growable(128);
+        let frame_data = arena.alloc_str("frame").unwrap().len();
+
+        {
+            let mut chunk_job = arena.scope();
+            chunk_job
+                .alloc_slice_copy(&[1u32; 8])
+                .unwrap();
+            let job_used = chunk_job.used();
+            {
+                let mesher = chunk_job.scope();
+                mesher
+                    .alloc_slice_copy(&[0u8; 500])
+                    .unwrap();
+                assert!(mesher.used() > job_used);
+            }
+            assert_eq!(chunk_job.used(), job_used);
+        }
+
+        assert_eq!(arena.used(), frame_data);
+        assert_eq!(arena.stats().blocks, 1);
+    }
+
+    #[test]
+    fn mark_on_empty_growable_arena_rewinds_to_nothing() {
+        let mut arena = Arena::growable(0);
+        let mark = arena.mark();
+        arena.alloc(5u32).unwrap();
+        arena.rewind(mark);
+        assert_eq!(arena.stats().blocks, 0);
+        assert_eq!(arena.capacity(), 0);
+        assert_eq!(*arena.alloc(6u32).unwrap(), 6);
+    }
+
+    #[test]
+    #[should_panic(expected = "stale")]
+    fn rewind_rejects_stale_mark() {
+        let mut arena = Arena::new(64);
+        arena.alloc(1u64).unwrap();
+        let late = arena.mark();
+        arena.reset();
+        arena.rewind(late);
+    }
+
+    #[test]
+    #[should_panic(expected = "stale")]
+    fn rewind_rejects_mark_taken_before_an_earlier_rewind() {
+        let mut arena = Arena::new(128).with_drop_list();
+        let m0 = arena.mark();
+        arena.alloc(Box::new(1u64)).unwrap();
+        let m1 = arena.mark();
+        arena.rewind(m0);
+        arena
+            .alloc((7u64, String::from("reused")))
+            .unwrap();
+        // Would leave the tuple's drop entry pointing at reusable memory
+        arena.rewind(m1);
+        arena
+            .alloc_slice_copy(&[0xffu8; 24])
+            .unwrap();
+        arena.reset();
+    }
+
+    #[test]
+    #[should_panic(expected = "another arena")]
+    fn rewind_rejects_foreign_mark() {
+        let mut a = Arena::new(64);
+        let b = Arena::new(64);
+        a.rewind(b.mark());
+    }
 }
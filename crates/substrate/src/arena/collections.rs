@@ -0,0 +1,331 @@
+//! Standard collections backed by an [`Arena`]
+
+use std::alloc::Layout;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::ptr::{self, NonNull};
+
+pub use allocator_api2::alloc::{AllocError, Allocator};
+
+use super::Arena;
+
+/// A `Vec` whose buffer lives in an arena
+pub type ArenaVec<'a, T> = allocator_api2::vec::Vec<T, &'a Arena>;
+
+/// A `HashMap` whose table lives in an arena
+pub type ArenaHashMap<'a, K, V> =
+    hashbrown::HashMap<K, V, hashbrown::DefaultHashBuilder, &'a Arena>;
+
+/// A `HashSet` whose table lives in an arena
+pub type ArenaHashSet<'a, T> =
+    hashbrown::HashSet<T, hashbrown::DefaultHashBuilder, &'a Arena>;
+
+// Memory is only reclaimed by reset or rewind, except that the most recent
+// allocation can be freed, grown or shrunk in place. That keeps a single
+// growing Vec from leaving a trail of abandoned buffers.
+unsafe impl Allocator for &Arena {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let ptr = self
+            .alloc_layout(layout)
+            .map_err(|_| AllocError)?;
+        Ok(NonNull::slice_from_raw_parts(ptr, layout.size()))
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        if let Some(start) = self.last_allocation(ptr, layout.size()) {
+            self.offset.set(start);
+        }
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        let (_, block_size) = self.current.get();
+        if let Some(start) = self.last_allocation(ptr, old_layout.size())
+            && ptr
+                .as_ptr()
+                .addr()
+                .is_multiple_of(new_layout.align())
+            && block_size - start >= new_layout.size()
+        {
+            self.offset
+                .set(start + new_layout.size());
+            let used = self.used();
+            if used > self.high_water_mark.get() {
+                self.high_water_mark.set(used);
+            }
+            return Ok(NonNull::slice_from_raw_parts(ptr, new_layout.size()));
+        }
+
+        let new = self.allocate(new_layout)?;
+        unsafe {
+            ptr::copy_nonoverlapping(
+                ptr.as_ptr(),
+                new.as_ptr() as *mut u8,
+                old_layout.size(),
+            );
+        }
+        Ok(new)
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        if !ptr
+            .as_ptr()
+            .addr()
+            .is_multiple_of(new_layout.align())
+        {
+            let new = self.allocate(new_layout)?;
+            unsafe {
+                ptr::copy_nonoverlapping(
+                    ptr.as_ptr(),
+                    new.as_ptr() as *mut u8,
+                    new_layout.size(),
+                );
+            }
+            return Ok(new);
+        }
+
+        if let Some(start) = self.last_allocation(ptr, old_layout.size()) {
+            self.offset
+                .set(start + new_layout.size());
+        }
+        Ok(NonNull::slice_from_raw_parts(ptr, new_layout.size()))
+    }
+}
+
+impl Arena {
+    /// Offset of `ptr` in the current block if the `size` bytes from it end
+    /// exactly at the bump offset
+    fn last_allocation(&self, ptr: NonNull<u8>, size: usize) -> Option<usize> {
+        let (block, _) = self.current.get();
+        let start =
+            (ptr.as_ptr() as usize).checked_sub(block.as_ptr() as usize)?;
+        (start + size == self.offset.get()).then_some(start)
+    }
+}
+
+/// A growable UTF-8 string whose buffer lives in an arena
+#[derive(Clone)]
+pub struct ArenaString<'a> {
+    bytes: ArenaVec<'a, u8>,
+}
+
+impl<'a> ArenaString<'a> {
+    pub fn new_in(arena: &'a Arena) -> Self {
+        Self { bytes: ArenaVec::new_in(arena) }
+    }
+
+    pub fn with_capacity_in(capacity: usize, arena: &'a Arena) -> Self {
+        Self {
+            bytes: ArenaVec::with_capacity_in(capacity, arena),
+        }
+    }
+
+    pub fn from_str_in(s: &str, arena: &'a Arena) -> Self {
+        let mut string = Self::with_capacity_in(s.len(), arena);
+        string.push_str(s);
+        string
+    }
+
+    pub fn push_str(&mut self, s: &str) {
+        self.bytes
+            .extend_from_slice(s.as_bytes());
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.push_str(c.encode_utf8(&mut [0; 4]));
+    }
+
+    pub fn as_str(&self) -> &str {
+        // Only ever appended to from `str`s
+        unsafe { std::str::from_utf8_unchecked(&self.bytes) }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.bytes.capacity()
+    }
+
+    pub fn clear(&mut self) {
+        self.bytes.clear();
+    }
+}
+
+impl Deref for ArenaString<'_> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl fmt::Write for ArenaString<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push_str(s);
+        Ok(())
+    }
+}
+
+impl fmt::Display for ArenaString<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Debug for ArenaString<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl PartialEq for ArenaString<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for ArenaString<'_> {}
+
+impl PartialEq<str> for ArenaString<'_> {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for ArenaString<'_> {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl Hash for ArenaString<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt::Write;
+
+    #[test]
+    fn vec_grows_in_place_at_the_end_of_the_arena() {
+        let arena = Arena::new(4096);
+        let mut vec = ArenaVec::new_in(&arena);
+        vec.push(0u32);
+        let first = vec.as_ptr();
+        for i in 1..256 {
+            vec.push(i);
+        }
+
+        assert_eq!(vec.as_ptr(), first);
+        assert_eq!(arena.used(), vec.capacity() * 4);
+        assert_eq!(vec.iter().sum::<u32>(), (0..256).sum());
+    }
+
+    #[test]
+    fn dropping_the_last_allocation_gives_it_back() {
+        let arena = Arena::new(256);
+        let kept = ArenaVec::<u8>::with_capacity_in(16, &arena);
+        let temporary = ArenaVec::<u64>::with_capacity_in(8, &arena);
+        assert_eq!(arena.used(), 80);
+
+        drop(temporary);
+        assert_eq!(arena.used(), 16);
+        // Not the last allocation any more, so nothing is reclaimed
+        let _later = ArenaVec::<u8>::with_capacity_in(1, &arena);
+        drop(kept);
+        assert_eq!(arena.used(), 17);
+    }
+
+    #[test]
+    fn interleaved_vecs_copy_when_growing() {
+        let arena = Arena::growable(64);
+        let mut a = ArenaVec::new_in(&arena);
+        let mut b = ArenaVec::new_in(&arena);
+        for i in 0..1000u32 {
+            a.push(i);
+            b.push(i * 2);
+        }
+
+        assert!(a.iter().copied().eq(0..1000));
+        assert!(
+            b.iter()
+                .copied()
+                .eq((0..1000).map(|i| i * 2))
+        );
+        assert!(arena.stats().blocks > 1);
+    }
+
+    #[test]
+    fn hash_map_and_strings_live_in_the_arena() {
+        let arena = Arena::growable(1024);
+        let mut names = ArenaHashMap::new_in(&arena);
+        for i in 0..100 {
+            let mut name = ArenaString::new_in(&arena);
+            write!(name, "entity-{i}").unwrap();
+            names.insert(name, i);
+        }
+
+        let key = ArenaString::from_str_in("entity-42", &arena);
+        assert_eq!(names.get(&key), Some(&42));
+        assert_eq!(names.len(), 100);
+        assert!(arena.used() > 0);
+    }
+
+    #[test]
+    fn fixed_arena_reports_allocation_failure() {
+        let arena = Arena::new(64);
+        let mut vec = ArenaVec::<u8>::new_in(&arena);
+        assert!(vec.try_reserve(64).is_ok());
+        assert!(vec.try_reserve(65).is_err());
+    }
+
+    #[test]
+    fn reset_reclaims_collection_memory() {
+        let mut arena = Arena::growable(256);
+        for frame in 0..3 {
+            {
+                let mut positions = ArenaVec::new_in(&arena);
+                positions.extend((0..500).map(|i| [i as f32; 3]));
+                let mut lookup = ArenaHashMap::new_in(&arena);
+                for i in 0..200u32 {
+                    lookup.insert(i, i + frame);
+                }
+                let label = ArenaString::from_str_in("frame", &arena);
+
+                assert_eq!(positions.len(), 500);
+                assert_eq!(lookup[&7], 7 + frame);
+                assert_eq!(label, "frame");
+                assert!(arena.used() > 500 * 12);
+            }
+
+            arena.reset();
+            let stats = arena.stats();
+            assert_eq!((stats.used, stats.blocks), (0, 1));
+        }
+    }
+
+    #[test]
+    fn rewind_reclaims_collection_memory() {
+        let mut arena = Arena::growable(128);
+        let mark = arena.mark();
+        {
+            let mut scratch = ArenaVec::new_in(&arena);
+            scratch.extend(0..10_000u64);
+            assert!(arena.used() >= 80_000);
+        }
+        arena.rewind(mark);
+        assert_eq!(arena.used(), 0);
+        assert_eq!(arena.capacity(), 128);
+    }
+}
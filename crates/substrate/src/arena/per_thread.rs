@@ -0,0 +1,408 @@
+//! A pool of arenas with one per thread
+
+use std::cell::{RefCell, UnsafeCell};
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use super::{Arena, ArenaStats};
+
+/// Distinguishes pools in each thread's slot table; never reused
+static NEXT_POOL_ID: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    /// `(pool id, slot)` for each pool this thread has claimed a slot in
+    static CLAIMED: RefCell<Vec<(u64, usize)>> =
+        const { RefCell::new(Vec::new()) };
+}
+
+/// One growable [`Arena`] per thread, for scratch memory in jobs.
+///
+/// [`ThreadLocalArenas::get`] hands the calling thread a guard for its own
+/// arena, claiming a free one the first time; the thread keeps it for the
+/// life of the pool, so the pool should be sized for long-lived workers.
+/// Everything takes `&self`, so the pool can be shared between workers.
+///
+/// [`ThreadLocalArenas::reset_all`] and [`ThreadLocalArenas::stats`] are
+/// meant for frame boundaries and panic if a thread is still holding its
+/// arena. Pool arenas have no drop list, so values needing drop are leaked
+/// on reset.
+pub struct ThreadLocalArenas {
+    id: u64,
+    slots: Box<[Slot]>,
+    claimed: AtomicUsize,
+}
+
+/// Marks a slot as held by [`ThreadLocalArenas::reset_all`] or `stats`
+const LOCKED: usize = usize::MAX;
+
+struct Slot {
+    arena: UnsafeCell<Arena>,
+    /// Live guards from the owning thread, or `LOCKED`
+    borrows: AtomicUsize,
+}
+
+// An arena without a drop list owns nothing but its blocks, so it can be
+// reset from any thread, and `borrows` keeps its owner and the resetting
+// thread from using it at the same time.
+unsafe impl Send for ThreadLocalArenas {}
+unsafe impl Sync for ThreadLocalArenas {}
+
+/// The calling thread's arena, borrowed from a [`ThreadLocalArenas`].
+/// Allocations borrow the guard, so they can't outlive it, and it can't be
+/// sent to another thread:
+///
+/// ```compile_fail
+/// # use substrate::arena::ThreadLocalArenas;
+/// let pool = ThreadLocalArenas::new(2, 64);
+/// let arena = pool.get();
+/// std::thread::scope(|scope| {
+///     scope.spawn(move || arena.alloc(1u32).map(|_| ()));
+/// });
+/// ```
+pub struct LocalArena<'a> {
+    arena: &'a Arena,
+    borrows: &'a AtomicUsize,
+}
+
+impl Deref for LocalArena<'_> {
+    type Target = Arena;
+
+    fn deref(&self) -> &Arena {
+        self.arena
+    }
+}
+
+impl Drop for LocalArena<'_> {
+    fn drop(&mut self) {
+        self.borrows
+            .fetch_sub(1, Ordering::Release);
+    }
+}
+
+impl ThreadLocalArenas {
+    /// Room for `threads` threads, each arena starting with `initial` bytes
+    pub fn new(threads: usize, initial: usize) -> Self {
+        Self {
+            id: NEXT_POOL_ID.fetch_add(1, Ordering::Relaxed),
+            slots: (0..threads)
+                .map(|_| Slot {
+                    arena: UnsafeCell::new(Arena::growable(initial)),
+                    borrows: AtomicUsize::new(0),
+                })
+                .collect(),
+            claimed: AtomicUsize::new(0),
+        }
+    }
+
+    /// Number of arenas, claimed or not
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Arenas claimed by a thread so far
+    pub fn claimed(&self) -> usize {
+        self.claimed
+            .load(Ordering::Relaxed)
+            .min(self.slots.len())
+    }
+
+    /// The calling thread's arena
+    ///
+    /// # Panics
+    ///
+    /// Panics if every arena has been claimed by other threads.
+    pub fn get(&self) -> LocalArena<'_> {
+        self.try_get()
+            .expect("more threads than arenas in ThreadLocalArenas")
+    }
+
+    /// The calling thread's arena, or `None` if every arena has been claimed
+    /// by other threads
+    pub fn try_get(&self) -> Option<LocalArena<'_>> {
+        let slot = &self.slots[self.claim()?];
+        let mut borrows = slot.borrows.load(Ordering::Relaxed);
+        loop {
+            if borrows == LOCKED {
+                // Another thread is briefly resetting or reading the arena
+                std::hint::spin_loop();
+                borrows = slot.borrows.load(Ordering::Relaxed);
+                continue;
+            }
+            match slot.borrows.compare_exchange_weak(
+                borrows,
+                borrows + 1,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => break,
+                Err(current) => borrows = current,
+            }
+        }
+        Some(LocalArena {
+            // Only this thread reaches the arena while `borrows` is nonzero,
+            // and the guard is neither Send nor Sync since `&Arena` isn't
+            arena: unsafe { &*slot.arena.get() },
+            borrows: &slot.borrows,
+        })
+    }
+
+    /// This thread's slot, claiming one if needed
+    fn claim(&self) -> Option<usize> {
+        CLAIMED.with_borrow_mut(|claimed| {
+            if let Some(&(_, slot)) = claimed
+                .iter()
+                .find(|(id, _)| *id == self.id)
+            {
+                return Some(slot);
+            }
+            let slot = self
+                .claimed
+                .fetch_add(1, Ordering::Relaxed);
+            if slot >= self.slots.len() {
+                return None;
+            }
+            claimed.push((self.id, slot));
+            Some(slot)
+        })
+    }
+
+    /// Run `f` on every arena with no thread holding it
+    ///
+    /// # Panics
+    ///
+    /// Panics if any arena is held by a [`LocalArena`].
+    fn for_each_locked(&self, mut f: impl FnMut(&mut Arena)) {
+        for slot in &self.slots {
+            if slot
+                .borrows
+                .compare_exchange(
+                    0,
+                    LOCKED,
+                    Ordering::Acquire,
+                    Ordering::Relaxed,
+                )
+                .is_err()
+            {
+                panic!("a thread is still using its arena");
+            }
+            f(unsafe { &mut *slot.arena.get() });
+            slot.borrows.store(0, Ordering::Release);
+        }
+    }
+
+    /// Reset every arena, as at a frame boundary
+    ///
+    /// # Panics
+    ///
+    /// Panics if a thread is still holding its arena.
+    pub fn reset_all(&self) {
+        self.for_each_locked(Arena::reset);
+    }
+
+    /// Stats summed over every arena. The high water mark is the sum of each
+    /// arena's own peak.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a thread is still holding its arena.
+    pub fn stats(&self) -> ArenaStats {
+        let mut total = ArenaStats::default();
+        self.for_each_locked(|arena| {
+            let stats = arena.stats();
+            total.used += stats.used;
+            total.capacity += stats.capacity;
+            total.blocks += stats.blocks;
+            total.high_water_mark += stats.high_water_mark;
+        });
+        total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Barrier, Mutex};
+    use std::thread;
+
+    const THREADS: usize = 8;
+
+    fn arena_addr(pool: &ThreadLocalArenas) -> usize {
+        &*pool.get() as *const Arena as usize
+    }
+
+    #[test]
+    fn pool_is_shareable_between_threads() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<ThreadLocalArenas>();
+    }
+
+    #[test]
+    fn each_thread_keeps_its_own_arena() {
+        let pool = ThreadLocalArenas::new(THREADS, 256);
+        let main = arena_addr(&pool);
+        assert_eq!(arena_addr(&pool), main);
+
+        let mut all: Vec<usize> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..THREADS - 1)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let first = arena_addr(&pool);
+                        assert_eq!(arena_addr(&pool), first);
+                        first
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect()
+        });
+
+        all.push(main);
+        all.sort_unstable();
+        all.dedup();
+        assert_eq!(all.len(), THREADS);
+        assert_eq!(pool.claimed(), THREADS);
+    }
+
+    #[test]
+    fn extra_threads_get_none() {
+        let pool = ThreadLocalArenas::new(1, 64);
+        drop(pool.get());
+        thread::scope(|scope| {
+            let other = scope.spawn(|| pool.try_get().is_none());
+            assert!(other.join().unwrap());
+        });
+        assert!(pool.try_get().is_some());
+    }
+
+    #[test]
+    fn separate_pools_hand_out_separate_arenas() {
+        let a = ThreadLocalArenas::new(1, 64);
+        let b = ThreadLocalArenas::new(1, 64);
+        assert_ne!(arena_addr(&a), arena_addr(&b));
+    }
+
+    #[test]
+    fn nested_guards_share_the_arena() {
+        let pool = ThreadLocalArenas::new(1, 64);
+        let outer = pool.get();
+        let value = outer.alloc(1u32).unwrap();
+        {
+            let inner = pool.get();
+            inner.alloc(2u32).unwrap();
+            assert_eq!(inner.used(), 8);
+        }
+        assert_eq!(*value, 1);
+        drop(outer);
+        pool.reset_all();
+    }
+
+    #[test]
+    #[should_panic(expected = "still using")]
+    fn reset_while_held_panics() {
+        let pool = ThreadLocalArenas::new(2, 64);
+        let _held = pool.get();
+        pool.reset_all();
+    }
+
+    #[test]
+    fn stress_allocations_never_alias_across_threads() {
+        const FRAMES: u32 = 4;
+        let pool = ThreadLocalArenas::new(THREADS, 1024);
+        let barrier = Barrier::new(THREADS + 1);
+        let ranges = Mutex::new(Vec::new());
+
+        thread::scope(|scope| {
+            for worker in 0..THREADS as u32 {
+                let (pool, barrier, ranges) = (&pool, &barrier, &ranges);
+                // Workers live across frames, like a job system's
+                scope.spawn(move || {
+                    for frame in 0..FRAMES {
+                        let arena = pool.get();
+                        let tag = (frame << 16) | worker;
+                        let mut slices = Vec::new();
+                        for i in 0..500 {
+                            let len = 1 + (i * 7 + worker as usize) % 40;
+                            slices.push(
+                                arena
+                                    .alloc_slice_copy(&vec![tag; len])
+                                    .unwrap(),
+                            );
+                            if i % 100 == 0 {
+                                thread::yield_now();
+                            }
+                        }
+                        ranges
+                            .lock()
+                            .unwrap()
+                            .extend(slices.iter().map(|slice| {
+                                let start = slice.as_ptr() as usize;
+                                (start, start + size_of_val(*slice))
+                            }));
+
+                        // Check only once every thread has written
+                        barrier.wait();
+                        for slice in &slices {
+                            assert!(
+                                slice.iter().all(|&v| v == tag),
+                                "another thread wrote into our memory"
+                            );
+                        }
+                        drop(slices);
+                        drop(arena);
+                        barrier.wait();
+                        // The main thread resets between these two waits
+                        barrier.wait();
+                    }
+                });
+            }
+
+            for _ in 0..FRAMES {
+                barrier.wait();
+                barrier.wait();
+                let mut all = std::mem::take(&mut *ranges.lock().unwrap());
+                all.sort_unstable();
+                for pair in all.windows(2) {
+                    assert!(pair[0].1 <= pair[1].0, "allocations overlap");
+                }
+                assert_eq!(
+                    pool.stats().used,
+                    all.iter()
+                        .map(|(start, end)| end - start)
+                        .sum()
+                );
+                pool.reset_all();
+                assert_eq!(pool.stats().used, 0);
+                barrier.wait();
+            }
+        });
+    }
+
+    #[test]
+    fn stats_sum_every_arena() {
+        let pool = ThreadLocalArenas::new(3, 128);
+        pool.get().alloc(1u64).unwrap();
+        thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    pool.get().alloc(2u32).unwrap();
+                })
+                .join()
+                .unwrap();
+        });
+
+        let stats = pool.stats();
+        assert_eq!(stats.used, 12);
+        assert_eq!(stats.capacity, 3 * 128);
+        assert_eq!(stats.blocks, 3);
+
+        pool.reset_all();
+        let stats = pool.stats();
+        assert_eq!((stats.used, stats.high_water_mark), (0, 12));
+    }
+}
@@ -0,0 +1,322 @@
+//! draw.rs
+
+use std::ops::Range;
+
+use crate::sort_key::{SortKey, SortMaterial, radix_sort};
+
+/// Column-major 4x4 model matrix
+pub type Transform = [[f32; 4]; 4];
+
+pub const IDENTITY: Transform = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+/// One mesh drawn with one material.
+///
+/// Generic over the handle types of the renderer that owns the meshes and
+/// materials.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DrawCommand<M, Mat> {
+    pub mesh: M,
+    pub material: Mat,
+    pub transform: Transform,
+    pub sort_key: SortKey,
+}
+
+impl<M, Mat: SortMaterial> DrawCommand<M, Mat> {
+    /// An opaque command in layer 0 at depth 0, keyed by material
+    pub fn new(mesh: M, material: Mat, transform: Transform) -> Self {
+        Self {
+            mesh,
+            material,
+            transform,
+            sort_key: SortKey::opaque(0, material, 0.0, 0),
+        }
+    }
+}
+
+impl<M, Mat> DrawCommand<M, Mat> {
+    pub fn with_sort_key(mut self, sort_key: SortKey) -> Self {
+        self.sort_key = sort_key;
+        self
+    }
+}
+
+/// Consecutive commands in draw order sharing a mesh and material, which
+/// can be drawn without rebinding anything. `range` indexes draw order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Batch<M, Mat> {
+    pub mesh: M,
+    pub material: Mat,
+    pub range: Range<usize>,
+}
+
+/// Draw commands collected over a frame.
+///
+/// Commands stay where they were pushed; sorting only reorders a compact
+/// array of keys and indices, so large commands are never moved. Draw order
+/// is sorted order after [`DrawList::sort`] until the next push, and
+/// submission order otherwise.
+#[derive(Debug)]
+pub struct DrawList<M, Mat> {
+    commands: Vec<DrawCommand<M, Mat>>,
+    /// Key and command index of every command, in draw order
+    keys: Vec<(u64, u32)>,
+    /// Reused by `sort` so sorting doesn't allocate once warmed up
+    scratch: Vec<(u64, u32)>,
+    sorted: bool,
+}
+
+impl<M, Mat> Default for DrawList<M, Mat> {
+    fn default() -> Self {
+        Self {
+            commands: Vec::new(),
+            keys: Vec::new(),
+            scratch: Vec::new(),
+            sorted: false,
+        }
+    }
+}
+
+impl<M, Mat> DrawList<M, Mat> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            commands: Vec::with_capacity(capacity),
+            keys: Vec::with_capacity(capacity),
+            scratch: Vec::with_capacity(capacity),
+            sorted: false,
+        }
+    }
+
+    pub fn push(&mut self, command: DrawCommand<M, Mat>) {
+        let index = self.commands.len() as u32;
+        self.keys
+            .push((command.sort_key.bits(), index));
+        self.commands.push(command);
+        self.sorted = false;
+    }
+
+    /// Remove all commands, keeping the allocation for the next frame
+    pub fn clear(&mut self) {
+        self.commands.clear();
+        self.keys.clear();
+        self.sorted = false;
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Commands in submission order
+    pub fn commands(&self) -> &[DrawCommand<M, Mat>] {
+        &self.commands
+    }
+
+    /// Radix sort by key. Stable, so equal keys keep submission order.
+    ///
+    /// Takes one pass over the list per key byte that differs between
+    /// commands, so cost grows linearly with the number of commands.
+    pub fn sort(&mut self) {
+        radix_sort(&mut self.keys, &mut self.scratch);
+        self.sorted = true;
+    }
+
+    /// Commands in draw order
+    pub fn iter(&self) -> impl Iterator<Item = &DrawCommand<M, Mat>> + '_ {
+        self.range(0..self.len())
+    }
+
+    /// Commands at `range` positions in draw order, such as a
+    /// [`Batch::range`]
+    pub fn range(
+        &self,
+        range: Range<usize>,
+    ) -> impl Iterator<Item = &DrawCommand<M, Mat>> + '_ {
+        range.map(|i| self.get(i))
+    }
+
+    /// The command at position `i` in draw order
+    fn get(&self, i: usize) -> &DrawCommand<M, Mat> {
+        if self.sorted {
+            &self.commands[self.keys[i].1 as usize]
+        } else {
+            &self.commands[i]
+        }
+    }
+}
+
+impl<M: Copy + Eq, Mat: Copy + Eq> DrawList<M, Mat> {
+    /// Split the list, in draw order, into runs sharing a mesh and material
+    pub fn batches(&self) -> Vec<Batch<M, Mat>> {
+        let mut batches: Vec<Batch<M, Mat>> = Vec::new();
+        for (i, command) in self.iter().enumerate() {
+            match batches.last_mut() {
+                Some(batch)
+                    if batch.mesh == command.mesh
+                        && batch.material == command.material =>
+                {
+                    batch.range.end = i + 1;
+                }
+                _ => batches.push(Batch {
+                    mesh: command.mesh,
+                    material: command.material,
+                    range: i..i + 1,
+                }),
+            }
+        }
+        batches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests use bare indices as mesh and material handles
+    type Command = DrawCommand<u32, u32>;
+    type List = DrawList<u32, u32>;
+
+    fn command(mesh: u32, material: u32, key: u64) -> Command {
+        DrawCommand::new(mesh, material, IDENTITY)
+            .with_sort_key(SortKey::from_bits(key))
+    }
+
+    #[test]
+    fn new_command_keys_by_material() {
+        let a = command(0, 2, 0);
+        let b = DrawCommand::new(a.mesh, 1, IDENTITY);
+        let c = DrawCommand::new(a.mesh, 2, IDENTITY);
+        assert!(b.sort_key < c.sort_key);
+    }
+
+    #[test]
+    fn sort_orders_by_key_and_is_stable() {
+        let mut list = List::new();
+        list.push(command(0, 0, 5));
+        list.push(command(1, 0, 1));
+        list.push(command(2, 0, 5));
+        list.push(command(3, 0, 0));
+        list.sort();
+
+        let meshes: Vec<u32> = list.iter().map(|c| c.mesh).collect();
+        assert_eq!(meshes, vec![3, 1, 0, 2]);
+    }
+
+    #[test]
+    fn batches_group_consecutive_mesh_and_material() {
+        let mut list = List::new();
+        list.push(command(0, 0, 0));
+        list.push(command(0, 0, 1));
+        list.push(command(1, 0, 2));
+        list.push(command(1, 1, 3));
+        list.push(command(1, 1, 4));
+
+        let batches = list.batches();
+        let ranges: Vec<Range<usize>> = batches
+            .iter()
+            .map(|b| b.range.clone())
+            .collect();
+        assert_eq!(ranges, vec![0..2, 2..3, 3..5]);
+        assert_eq!(batches[2].material, 1);
+    }
+
+    #[test]
+    fn sorting_minimises_material_changes() {
+        let mut list = List::with_capacity(100);
+        for i in 0..100 {
+            let material = i % 5;
+            let cmd = DrawCommand::new(0, material, IDENTITY);
+            list.push(cmd);
+        }
+        assert_eq!(list.batches().len(), 100);
+
+        list.sort();
+        assert_eq!(list.len(), 100);
+        assert_eq!(list.batches().len(), 5);
+    }
+
+    #[test]
+    fn sort_puts_translucent_after_opaque_back_to_front() {
+        let mesh = 0;
+        let glass = 0;
+        let stone = 1;
+        let mut list = List::new();
+        for depth in [4.0, 9.0, 1.0] {
+            let mut transform = IDENTITY;
+            transform[3][2] = depth;
+            let opaque = DrawCommand::new(mesh, stone, transform)
+                .with_sort_key(SortKey::opaque(0, stone, depth, 0));
+            let translucent = DrawCommand::new(mesh, glass, transform)
+                .with_sort_key(SortKey::translucent(0, glass, depth, 0));
+            list.push(translucent);
+            list.push(opaque);
+        }
+        list.sort();
+
+        let order: Vec<(u32, f32)> = list
+            .iter()
+            .map(|c| (c.material, c.transform[3][2]))
+            .collect();
+        assert_eq!(
+            order,
+            vec![(1, 1.0), (1, 4.0), (1, 9.0), (0, 9.0), (0, 4.0), (0, 1.0)]
+        );
+    }
+
+    #[test]
+    fn sort_100k_commands() {
+        let mut list = List::with_capacity(100_000);
+        for i in 0..100_000u32 {
+            let material = i.wrapping_mul(2_654_435_761) % 512;
+            let depth = (i.wrapping_mul(40_503) % 10_000) as f32 * 0.1;
+            let key = SortKey::opaque(i % 4, material, depth, i % 2048);
+            list.push(
+                DrawCommand::new(i % 64, material, IDENTITY).with_sort_key(key),
+            );
+        }
+        list.sort();
+
+        let keys: Vec<SortKey> = list
+            .iter()
+            .map(|c| c.sort_key)
+            .collect();
+        assert_eq!(keys.len(), 100_000);
+        assert!(keys.is_sorted());
+    }
+
+    #[test]
+    fn push_after_sort_restores_submission_order() {
+        let mut list = List::new();
+        list.push(command(0, 0, 9));
+        list.push(command(1, 0, 1));
+        list.sort();
+        list.push(command(2, 0, 0));
+
+        let meshes: Vec<u32> = list.iter().map(|c| c.mesh).collect();
+        assert_eq!(meshes, vec![0, 1, 2]);
+
+        list.sort();
+        let meshes: Vec<u32> = list.iter().map(|c| c.mesh).collect();
+        assert_eq!(meshes, vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn clear_empties_list() {
+        let mut list = List::new();
+        list.push(command(0, 0, 0));
+        list.clear();
+        assert!(list.is_empty());
+        assert!(list.batches().is_empty());
+    }
+}
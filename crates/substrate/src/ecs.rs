@@ -0,0 +1,632 @@
+//! Archetype-based entity component system.
+//!
+//! Entities with the same set of component types share an [`Archetype`],
+//! which stores each component type in its own column. Queries walk the
+//! archetypes that match and yield components row by row.
+
+mod query;
+mod schedule;
+mod storage;
+
+use std::any::{TypeId, type_name};
+use std::collections::HashMap;
+
+use crate::slot_map::{Handle, SlotMap};
+
+pub use query::{
+    Access, Added, Changed, Mut, QueryData, QueryFilter, QueryIter,
+    ReadOnlyQueryData, Ticks, With, Without, query_access,
+};
+pub use schedule::{ExecutionMode, Scheduler, Stage, System, SystemContext};
+pub use storage::{Archetype, Bundle, Column, ColumnFactory};
+
+/// Data that can be attached to an entity. Implemented for every
+/// thread-safe `'static` type.
+pub trait Component: Send + Sync + 'static {}
+
+impl<T: Send + Sync + 'static> Component for T {}
+
+/// Identifies an entity in a [`World`]. Stays unique after the entity is
+/// despawned, so stale ids never resolve to a newer entity.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Entity(Handle<Location>);
+
+/// Where an entity's components live
+#[derive(Clone, Copy, Debug)]
+struct Location {
+    archetype: u32,
+    row: u32,
+}
+
+/// Entities and their components
+pub struct World {
+    entities: SlotMap<Location>,
+    archetypes: Vec<Archetype>,
+    /// Archetype for each sorted set of component types
+    archetype_index: HashMap<Box<[TypeId]>, usize>,
+    /// How to make an empty column for each component type seen so far
+    factories: HashMap<TypeId, ColumnFactory>,
+    change_tick: u32,
+    last_change_tick: u32,
+}
+
+impl World {
+    pub fn new() -> Self {
+        let mut archetype_index = HashMap::new();
+        archetype_index.insert(Box::default(), 0);
+        Self {
+            entities: SlotMap::new(),
+            archetypes: vec![Archetype::new(Vec::new())],
+            archetype_index,
+            factories: HashMap::new(),
+            change_tick: 1,
+            last_change_tick: 0,
+        }
+    }
+
+    /// Live entities
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    pub fn archetypes(&self) -> &[Archetype] {
+        &self.archetypes
+    }
+
+    /// Create an entity with the components in `bundle`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bundle` has the same component type twice.
+    pub fn spawn<B: Bundle>(&mut self, bundle: B) -> Entity {
+        let types = self.bundle_types::<B>();
+        let archetype = self.archetype_for(types);
+        let entity = Entity(
+            self.entities
+                .insert(Location { archetype: archetype as u32, row: 0 }),
+        );
+        let archetype = &mut self.archetypes[archetype];
+        let row = archetype.push_entity(entity);
+        bundle.write(archetype, row, self.change_tick);
+        self.entities[entity.0].row = row as u32;
+        entity
+    }
+
+    /// Remove an entity and drop its components. Returns `false` if it was
+    /// already gone.
+    pub fn despawn(&mut self, entity: Entity) -> bool {
+        if !self.entities.contains(entity.0) {
+            return false;
+        }
+        let location = self
+            .entities
+            .remove(entity.0)
+            .expect("entity is alive");
+        let moved = self.archetypes[location.archetype as usize]
+            .swap_remove(location.row as usize);
+        if let Some(moved) = moved {
+            self.entities[moved.0].row = location.row;
+        }
+        true
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.entities.contains(entity.0)
+    }
+
+    /// Whether `entity` is alive and has a `T`
+    pub fn has<T: Component>(&self, entity: Entity) -> bool {
+        self.location(entity)
+            .is_some_and(|location| {
+                self.archetypes[location.archetype as usize].contains::<T>()
+            })
+    }
+
+    /// Add the components in `bundle` to `entity`, replacing any it already
+    /// has. Returns `false` if the entity is gone.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bundle` has the same component type twice.
+    pub fn insert<B: Bundle>(&mut self, entity: Entity, bundle: B) -> bool {
+        let Some(location) = self.location(entity) else {
+            return false;
+        };
+        let src = location.archetype as usize;
+        let mut types = self.bundle_types::<B>();
+        types.extend_from_slice(self.archetypes[src].types());
+        types.sort_unstable();
+        types.dedup();
+
+        let dst = self.archetype_for(types);
+        let tick = self.change_tick;
+        if dst == src {
+            bundle.write(
+                &mut self.archetypes[src],
+                location.row as usize,
+                tick,
+            );
+            return true;
+        }
+
+        let row = self.move_entity(location, dst, None);
+        bundle.write(&mut self.archetypes[dst], row, tick);
+        true
+    }
+
+    /// Take `entity`'s `T` component off it. Returns `None` if the entity
+    /// is gone or has no `T`.
+    pub fn remove<T: Component>(&mut self, entity: Entity) -> Option<T> {
+        let location = self.location(entity)?;
+        let src = location.archetype as usize;
+        let type_id = TypeId::of::<T>();
+        if !self.archetypes[src].contains_id(type_id) {
+            return None;
+        }
+
+        let types = self.archetypes[src]
+            .types()
+            .iter()
+            .copied()
+            .filter(|&id| id != type_id)
+            .collect();
+        let dst = self.archetype_for(types);
+        self.move_entity(location, dst, Some(type_id));
+        let value = self.archetypes[src]
+            .column_mut(type_id)
+            .expect("archetype has T")
+            .typed_mut::<T>()
+            .swap_remove(location.row as usize);
+        Some(value)
+    }
+
+    pub fn get<T: Component>(&self, entity: Entity) -> Option<&T> {
+        let location = self.location(entity)?;
+        let column = self.archetypes[location.archetype as usize]
+            .column(TypeId::of::<T>())?;
+        // Mutable borrows of columns need `&mut self` or an unsafe query
+        let column = unsafe { column.typed::<T>() };
+        column.values.get(location.row as usize)
+    }
+
+    /// The entity's `T`, marked changed when written through
+    pub fn get_mut<T: Component>(
+        &mut self,
+        entity: Entity,
+    ) -> Option<Mut<'_, T>> {
+        let location = self.location(entity)?;
+        let tick = self.change_tick;
+        let column = self.archetypes[location.archetype as usize]
+            .column_mut(TypeId::of::<T>())?
+            .typed_mut::<T>();
+        let row = location.row as usize;
+        Some(Mut::new(&mut column.values[row], &mut column.changed[row], tick))
+    }
+
+    /// Iterate the entities that match `Q`, yielding its items.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `Q` borrows a component mutably and also borrows it again.
+    pub fn query<Q: QueryData>(&mut self) -> QueryIter<'_, Q> {
+        self.query_filtered::<Q, ()>()
+    }
+
+    /// Like [`World::query`], skipping entities that fail `F`
+    pub fn query_filtered<Q: QueryData, F: QueryFilter>(
+        &mut self,
+    ) -> QueryIter<'_, Q, F> {
+        let ticks = self.ticks();
+        // `&mut self` rules out any other borrow
+        unsafe { QueryIter::new(&self.archetypes, ticks) }
+    }
+
+    /// Query through a shared borrow, with explicit change detection ticks.
+    ///
+    /// # Safety
+    ///
+    /// Nothing else may access the components `Q` and `F` access while the
+    /// iterator or its items are alive.
+    pub(crate) unsafe fn query_unchecked<Q: QueryData, F: QueryFilter>(
+        &self,
+        ticks: Ticks,
+    ) -> QueryIter<'_, Q, F> {
+        unsafe { QueryIter::new(&self.archetypes, ticks) }
+    }
+
+    /// Start stamping changes with a new tick
+    pub(crate) fn advance_tick(&mut self) {
+        self.change_tick = self.change_tick.wrapping_add(1);
+    }
+
+    /// The tick new changes are stamped with
+    pub fn change_tick(&self) -> u32 {
+        self.change_tick
+    }
+
+    /// What [`Added`] and [`Changed`] compare against in [`World::query`]:
+    /// changes since the last [`World::clear_trackers`]
+    pub fn ticks(&self) -> Ticks {
+        Ticks {
+            last_run: self.last_change_tick,
+            this_run: self.change_tick,
+        }
+    }
+
+    /// Start a new change detection window, so changes made so far stop
+    /// counting as added or changed. Call once per frame.
+    pub fn clear_trackers(&mut self) {
+        self.last_change_tick = self.change_tick;
+        self.change_tick = self.change_tick.wrapping_add(1);
+    }
+
+    fn location(&self, entity: Entity) -> Option<Location> {
+        // `contains` first: a stale handle passed to `get` trips a debug
+        // assertion
+        if !self.entities.contains(entity.0) {
+            return None;
+        }
+        self.entities.get(entity.0).copied()
+    }
+
+    /// `B`'s component types, sorted, registering how to store each one
+    fn bundle_types<B: Bundle>(&mut self) -> Vec<TypeId> {
+        let mut columns = Vec::new();
+        B::columns(&mut columns);
+        let mut types = Vec::with_capacity(columns.len());
+        for (type_id, factory) in columns {
+            self.factories
+                .entry(type_id)
+                .or_insert(factory);
+            types.push(type_id);
+        }
+        types.sort_unstable();
+        let len = types.len();
+        types.dedup();
+        assert_eq!(
+            types.len(),
+            len,
+            "bundle {} has a component type more than once",
+            type_name::<B>()
+        );
+        types
+    }
+
+    /// The archetype for a sorted set of registered types, created if new
+    fn archetype_for(&mut self, types: Vec<TypeId>) -> usize {
+        if let Some(&index) = self
+            .archetype_index
+            .get(types.as_slice())
+        {
+            return index;
+        }
+        let columns = types
+            .iter()
+            .map(|type_id| self.factories[type_id]())
+            .collect();
+        self.archetypes
+            .push(Archetype::new(columns));
+        let index = self.archetypes.len() - 1;
+        self.archetype_index
+            .insert(types.into_boxed_slice(), index);
+        index
+    }
+
+    /// Move an entity's shared components into archetype `dst`, dropping
+    /// the rest except `keep`. Returns its new row.
+    fn move_entity(
+        &mut self,
+        location: Location,
+        dst: usize,
+        keep: Option<TypeId>,
+    ) -> usize {
+        let src = location.archetype as usize;
+        let row = location.row as usize;
+        let (from, to) = if src < dst {
+            let (low, high) = self.archetypes.split_at_mut(dst);
+            (&mut low[src], &mut high[0])
+        } else {
+            let (low, high) = self.archetypes.split_at_mut(src);
+            (&mut high[0], &mut low[dst])
+        };
+
+        let entity = from.entities()[row];
+        let moved = from.move_row(row, to, keep);
+        let new_row = to.len() - 1;
+        if let Some(moved) = moved {
+            self.entities[moved.0].row = location.row;
+        }
+        self.entities[entity.0] = Location {
+            archetype: dst as u32,
+            row: new_row as u32,
+        };
+        new_row
+    }
+}
+
+impl Default for World {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Position(f32, f32);
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Velocity(f32, f32);
+
+    #[derive(Debug, PartialEq)]
+    struct Name(&'static str);
+
+    #[test]
+    fn spawn_get_and_despawn() {
+        let mut world = World::new();
+        let a = world.spawn((Position(1.0, 2.0), Name("a")));
+        let b = world.spawn((Position(3.0, 4.0),));
+
+        assert_eq!(world.len(), 2);
+        assert_eq!(world.get::<Name>(a), Some(&Name("a")));
+        assert_eq!(world.get::<Name>(b), None);
+
+        assert!(world.despawn(a));
+        assert!(!world.despawn(a));
+        assert!(!world.contains(a));
+        assert_eq!(world.get::<Position>(a), None);
+        assert_eq!(world.get::<Position>(b), Some(&Position(3.0, 4.0)));
+    }
+
+    #[test]
+    fn despawn_keeps_moved_entity_reachable() {
+        let mut world = World::new();
+        let entities: Vec<_> = (0..4)
+            .map(|i| world.spawn((i as u32,)))
+            .collect();
+        world.despawn(entities[0]);
+
+        for (i, &entity) in entities.iter().enumerate().skip(1) {
+            assert_eq!(world.get::<u32>(entity), Some(&(i as u32)));
+        }
+    }
+
+    #[test]
+    fn stale_entity_does_not_resolve_to_reused_slot() {
+        let mut world = World::new();
+        let old = world.spawn((1u8,));
+        world.despawn(old);
+        let new = world.spawn((2u8,));
+
+        assert_ne!(old, new);
+        assert_eq!(world.get::<u8>(old), None);
+        assert!(!world.insert(old, (3u8,)));
+        assert_eq!(world.remove::<u8>(old), None);
+    }
+
+    #[test]
+    fn insert_and_remove_move_between_archetypes() {
+        let mut world = World::new();
+        let a = world.spawn((Position(0.0, 0.0),));
+        let b = world.spawn((Position(1.0, 1.0),));
+
+        assert!(world.insert(a, (Velocity(1.0, 0.0), Name("a"))));
+        assert!(world.has::<Velocity>(a));
+        assert_eq!(world.get::<Position>(a), Some(&Position(0.0, 0.0)));
+        assert_eq!(world.get::<Position>(b), Some(&Position(1.0, 1.0)));
+
+        assert_eq!(world.remove::<Velocity>(a), Some(Velocity(1.0, 0.0)));
+        assert_eq!(world.remove::<Velocity>(a), None);
+        assert_eq!(world.get::<Name>(a), Some(&Name("a")));
+        assert_eq!(world.get::<Position>(a), Some(&Position(0.0, 0.0)));
+    }
+
+    #[test]
+    fn insert_replaces_existing_component() {
+        let mut world = World::new();
+        let entity = world.spawn((Name("old"), 1u32));
+        let archetypes = world.archetypes().len();
+        world.insert(entity, (Name("new"),));
+
+        assert_eq!(world.get::<Name>(entity), Some(&Name("new")));
+        assert_eq!(world.archetypes().len(), archetypes);
+    }
+
+    #[test]
+    #[should_panic(expected = "more than once")]
+    fn duplicate_bundle_types_panic() {
+        World::new().spawn((1u32, 2u32));
+    }
+
+    #[test]
+    fn components_are_dropped() {
+        let value = Arc::new(());
+        let mut world = World::new();
+        let a = world.spawn((value.clone(), 0u8));
+        let b = world.spawn((value.clone(), 0u8));
+        world.spawn((value.clone(),));
+
+        world.despawn(a);
+        assert_eq!(Arc::strong_count(&value), 3);
+        world.remove::<u8>(b);
+        assert_eq!(Arc::strong_count(&value), 3);
+        world.insert(b, (Arc::new(()),));
+        assert_eq!(Arc::strong_count(&value), 2);
+        drop(world);
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+
+    #[test]
+    fn query_yields_matching_entities() {
+        let mut world = World::new();
+        let moving = world.spawn((Position(0.0, 0.0), Velocity(1.0, 2.0)));
+        world.spawn((Position(5.0, 5.0),));
+        world.spawn((Velocity(9.0, 9.0), Name("lost")));
+
+        for (mut position, velocity) in
+            world.query::<(&mut Position, &Velocity)>()
+        {
+            position.0 += velocity.0;
+            position.1 += velocity.1;
+        }
+
+        assert_eq!(world.get::<Position>(moving), Some(&Position(1.0, 2.0)));
+        assert_eq!(world.query::<&Position>().count(), 2);
+        assert_eq!(world.query::<Entity>().count(), 3);
+    }
+
+    #[test]
+    fn query_with_and_without_filters() {
+        let mut world = World::new();
+        let named = world.spawn((Position(0.0, 0.0), Name("n")));
+        let plain = world.spawn((Position(0.0, 0.0),));
+
+        let with: Vec<_> = world
+            .query_filtered::<Entity, With<Name>>()
+            .collect();
+        let without: Vec<_> = world
+            .query_filtered::<Entity, (With<Position>, Without<Name>)>()
+            .collect();
+        assert_eq!(with, [named]);
+        assert_eq!(without, [plain]);
+    }
+
+    #[test]
+    fn optional_query_data() {
+        let mut world = World::new();
+        world.spawn((1u32, Name("one")));
+        world.spawn((2u32,));
+
+        let mut names: Vec<_> = world
+            .query::<(&u32, Option<&Name>)>()
+            .map(|(n, name)| (*n, name.map(|name| name.0)))
+            .collect();
+        names.sort();
+        assert_eq!(names, [(1, Some("one")), (2, None)]);
+    }
+
+    #[test]
+    fn added_and_changed_reset_with_trackers() {
+        let mut world = World::new();
+        let a = world.spawn((Position(0.0, 0.0),));
+        let b = world.spawn((Position(0.0, 0.0),));
+
+        assert_eq!(
+            world
+                .query_filtered::<Entity, Added<Position>>()
+                .count(),
+            2
+        );
+        world.clear_trackers();
+        assert_eq!(
+            world
+                .query_filtered::<Entity, Added<Position>>()
+                .count(),
+            0
+        );
+        assert_eq!(
+            world
+                .query_filtered::<Entity, Changed<Position>>()
+                .count(),
+            0
+        );
+
+        world.get_mut::<Position>(a).unwrap().0 = 1.0;
+        // Reading through `Mut` doesn't count as a change
+        let _ = world.get_mut::<Position>(b).unwrap().0;
+        let changed: Vec<_> = world
+            .query_filtered::<Entity, Changed<Position>>()
+            .collect();
+        assert_eq!(changed, [a]);
+        assert_eq!(
+            world
+                .query_filtered::<Entity, Added<Position>>()
+                .count(),
+            0
+        );
+
+        world.clear_trackers();
+        for mut position in world.query::<&mut Position>() {
+            position.1 = 2.0;
+        }
+        assert_eq!(
+            world
+                .query_filtered::<&Position, Changed<Position>>()
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn changed_filter_alongside_mutable_access() {
+        let mut world = World::new();
+        world.spawn((1u32,));
+        world.clear_trackers();
+        let entity = world.spawn((2u32,));
+
+        let seen: Vec<_> = world
+            .query_filtered::<(Entity, &mut u32), Changed<u32>>()
+            .map(|(entity, mut value)| {
+                *value += 10;
+                entity
+            })
+            .collect();
+        assert_eq!(seen, [entity]);
+    }
+
+    #[test]
+    fn inserted_component_counts_as_added() {
+        let mut world = World::new();
+        let entity = world.spawn((Position(0.0, 0.0),));
+        world.clear_trackers();
+        world.insert(entity, (Velocity(0.0, 0.0),));
+
+        assert_eq!(
+            world
+                .query_filtered::<Entity, Added<Velocity>>()
+                .count(),
+            1
+        );
+        assert_eq!(
+            world
+                .query_filtered::<Entity, Added<Position>>()
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "mutably")]
+    fn aliasing_query_panics() {
+        World::new().query::<(&mut u32, &u32)>();
+    }
+
+    #[test]
+    fn access_compatibility() {
+        let read = query_access::<&u32, ()>();
+        let write = query_access::<&mut u32, ()>();
+        let other = query_access::<&mut u8, With<u32>>();
+
+        assert!(read.is_compatible(&read));
+        assert!(!read.is_compatible(&write));
+        assert!(!write.is_compatible(&write));
+        assert!(write.is_compatible(&other));
+    }
+
+    #[test]
+    fn ticks_handle_wraparound() {
+        let ticks = Ticks { last_run: u32::MAX - 1, this_run: 1 };
+        assert!(ticks.is_newer(u32::MAX));
+        assert!(ticks.is_newer(0));
+        assert!(ticks.is_newer(1));
+        assert!(!ticks.is_newer(u32::MAX - 1));
+        assert!(!ticks.is_newer(2));
+    }
+}
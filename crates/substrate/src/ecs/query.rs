@@ -0,0 +1,601 @@
+//! Typed queries over archetypes
+
+use std::any::{TypeId, type_name};
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+
+use super::storage::Archetype;
+use super::{Component, Entity};
+
+/// The window change detection looks at: values added or changed after
+/// `last_run`, up to and including `this_run`, count as new
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ticks {
+    pub last_run: u32,
+    pub this_run: u32,
+}
+
+impl Ticks {
+    /// Whether `tick` falls in the window, allowing for wraparound
+    pub fn is_newer(&self, tick: u32) -> bool {
+        tick.wrapping_sub(self.last_run)
+            .wrapping_sub(1)
+            < self
+                .this_run
+                .wrapping_sub(self.last_run)
+    }
+}
+
+/// The component types a query reads and writes
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Access {
+    reads: Vec<TypeId>,
+    writes: Vec<TypeId>,
+}
+
+impl Access {
+    /// # Panics
+    ///
+    /// Panics if `T` is already written.
+    pub fn add_read<T: Component>(&mut self) {
+        let id = TypeId::of::<T>();
+        assert!(
+            !self.writes.contains(&id),
+            "query reads {} while also accessing it mutably",
+            type_name::<T>()
+        );
+        if !self.reads.contains(&id) {
+            self.reads.push(id);
+        }
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `T` is already read or written.
+    pub fn add_write<T: Component>(&mut self) {
+        let id = TypeId::of::<T>();
+        assert!(
+            !self.writes.contains(&id) && !self.reads.contains(&id),
+            "query accesses {} mutably more than once",
+            type_name::<T>()
+        );
+        self.writes.push(id);
+    }
+
+    /// Add everything `other` accesses, without checking for conflicts
+    pub fn extend(&mut self, other: &Access) {
+        for id in &other.reads {
+            if !self.reads.contains(id) {
+                self.reads.push(*id);
+            }
+        }
+        for id in &other.writes {
+            if !self.writes.contains(id) {
+                self.writes.push(*id);
+            }
+        }
+    }
+
+    pub fn reads(&self) -> &[TypeId] {
+        &self.reads
+    }
+
+    pub fn writes(&self) -> &[TypeId] {
+        &self.writes
+    }
+
+    /// Whether both can run at once: neither writes what the other touches
+    pub fn is_compatible(&self, other: &Access) -> bool {
+        let touches = |access: &Access, id| {
+            access.reads.contains(id) || access.writes.contains(id)
+        };
+        !self
+            .writes
+            .iter()
+            .any(|id| touches(other, id))
+            && !other
+                .writes
+                .iter()
+                .any(|id| touches(self, id))
+    }
+
+    /// Whether everything `other` accesses is allowed by `self`
+    pub fn covers(&self, other: &Access) -> bool {
+        other
+            .writes
+            .iter()
+            .all(|id| self.writes.contains(id))
+            && other
+                .reads
+                .iter()
+                .all(|id| self.reads.contains(id) || self.writes.contains(id))
+    }
+}
+
+/// What a query yields per entity: `&T`, `&mut T` (as [`Mut`]),
+/// [`Entity`], `Option` of those, or tuples of up to eight of them.
+///
+/// # Safety
+///
+/// `add_access` must report every component the fetch reads or writes.
+pub unsafe trait QueryData {
+    type Item<'w>;
+    #[doc(hidden)]
+    type Fetch<'w>;
+
+    #[doc(hidden)]
+    fn add_access(access: &mut Access);
+
+    #[doc(hidden)]
+    fn matches(archetype: &Archetype) -> bool;
+
+    /// # Safety
+    ///
+    /// `archetype` must match, and the accesses reported by `add_access`
+    /// must not conflict with any other live borrow.
+    #[doc(hidden)]
+    unsafe fn fetch<'w>(
+        archetype: &'w Archetype,
+        ticks: Ticks,
+    ) -> Self::Fetch<'w>;
+
+    /// # Safety
+    ///
+    /// `row` must be in bounds and not have been fetched before.
+    #[doc(hidden)]
+    unsafe fn item<'w>(
+        fetch: &mut Self::Fetch<'w>,
+        row: usize,
+    ) -> Self::Item<'w>;
+}
+
+/// Query data that only reads
+///
+/// # Safety
+///
+/// The query data must never write.
+pub unsafe trait ReadOnlyQueryData: QueryData {}
+
+/// Narrows a query without fetching anything: [`With`], [`Without`],
+/// [`Added`], [`Changed`], or tuples of up to eight of them, which must
+/// all pass.
+///
+/// # Safety
+///
+/// `add_access` must report every component the filter reads.
+pub unsafe trait QueryFilter {
+    #[doc(hidden)]
+    type Fetch<'w>;
+
+    #[doc(hidden)]
+    fn add_access(access: &mut Access);
+
+    #[doc(hidden)]
+    fn matches(archetype: &Archetype) -> bool;
+
+    /// # Safety
+    ///
+    /// As for [`QueryData::fetch`].
+    #[doc(hidden)]
+    unsafe fn fetch<'w>(
+        archetype: &'w Archetype,
+        ticks: Ticks,
+    ) -> Self::Fetch<'w>;
+
+    /// # Safety
+    ///
+    /// `row` must be in bounds.
+    #[doc(hidden)]
+    unsafe fn filter(fetch: &Self::Fetch<'_>, row: usize) -> bool;
+}
+
+/// Mutable access to a component that marks it changed when written
+pub struct Mut<'w, T> {
+    value: &'w mut T,
+    changed: &'w mut u32,
+    tick: u32,
+}
+
+impl<'w, T> Mut<'w, T> {
+    pub(crate) fn new(
+        value: &'w mut T,
+        changed: &'w mut u32,
+        tick: u32,
+    ) -> Self {
+        Self { value, changed, tick }
+    }
+}
+
+impl<T> Deref for Mut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<T> DerefMut for Mut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        *self.changed = self.tick;
+        self.value
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Mut<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+unsafe impl<T: Component> QueryData for &T {
+    type Item<'w> = &'w T;
+    type Fetch<'w> = *const T;
+
+    fn add_access(access: &mut Access) {
+        access.add_read::<T>();
+    }
+
+    fn matches(archetype: &Archetype) -> bool {
+        archetype.contains::<T>()
+    }
+
+    unsafe fn fetch(archetype: &Archetype, _: Ticks) -> *const T {
+        let column = archetype
+            .column(TypeId::of::<T>())
+            .expect("archetype matches");
+        unsafe { column.typed::<T>() }
+            .values
+            .as_ptr()
+    }
+
+    unsafe fn item<'w>(fetch: &mut *const T, row: usize) -> &'w T {
+        unsafe { &*fetch.add(row) }
+    }
+}
+
+unsafe impl<T: Component> ReadOnlyQueryData for &T {}
+
+unsafe impl<T: Component> QueryData for &mut T {
+    type Item<'w> = Mut<'w, T>;
+    type Fetch<'w> = (*mut T, *mut u32, u32);
+
+    fn add_access(access: &mut Access) {
+        access.add_write::<T>();
+    }
+
+    fn matches(archetype: &Archetype) -> bool {
+        archetype.contains::<T>()
+    }
+
+    unsafe fn fetch<'w>(
+        archetype: &'w Archetype,
+        ticks: Ticks,
+    ) -> Self::Fetch<'w> {
+        let column = archetype
+            .column(TypeId::of::<T>())
+            .expect("archetype matches");
+        let column = unsafe { column.typed_unchecked_mut::<T>() };
+        (
+            column.values.as_mut_ptr(),
+            column.changed.as_mut_ptr(),
+            ticks.this_run,
+        )
+    }
+
+    unsafe fn item<'w>(
+        &mut (values, changed, tick): &mut Self::Fetch<'w>,
+        row: usize,
+    ) -> Mut<'w, T> {
+        unsafe { Mut::new(&mut *values.add(row), &mut *changed.add(row), tick) }
+    }
+}
+
+unsafe impl QueryData for Entity {
+    type Item<'w> = Entity;
+    type Fetch<'w> = *const Entity;
+
+    fn add_access(_: &mut Access) {}
+
+    fn matches(_: &Archetype) -> bool {
+        true
+    }
+
+    unsafe fn fetch(archetype: &Archetype, _: Ticks) -> *const Entity {
+        archetype.entities().as_ptr()
+    }
+
+    unsafe fn item<'w>(
+        fetch: &mut *const Entity,
+        row: usize,
+    ) -> Self::Item<'w> {
+        unsafe { *fetch.add(row) }
+    }
+}
+
+unsafe impl ReadOnlyQueryData for Entity {}
+
+unsafe impl<Q: QueryData> QueryData for Option<Q> {
+    type Item<'w> = Option<Q::Item<'w>>;
+    type Fetch<'w> = Option<Q::Fetch<'w>>;
+
+    fn add_access(access: &mut Access) {
+        Q::add_access(access);
+    }
+
+    fn matches(_: &Archetype) -> bool {
+        true
+    }
+
+    unsafe fn fetch<'w>(
+        archetype: &'w Archetype,
+        ticks: Ticks,
+    ) -> Self::Fetch<'w> {
+        Q::matches(archetype).then(|| unsafe { Q::fetch(archetype, ticks) })
+    }
+
+    unsafe fn item<'w>(
+        fetch: &mut Self::Fetch<'w>,
+        row: usize,
+    ) -> Self::Item<'w> {
+        fetch
+            .as_mut()
+            .map(|fetch| unsafe { Q::item(fetch, row) })
+    }
+}
+
+unsafe impl<Q: ReadOnlyQueryData> ReadOnlyQueryData for Option<Q> {}
+
+/// Only entities that have a `T`
+pub struct With<T>(PhantomData<fn() -> T>);
+
+/// Only entities that don't have a `T`
+pub struct Without<T>(PhantomData<fn() -> T>);
+
+/// Only entities whose `T` was added since the system last ran, or for
+/// [`World::query_filtered`](super::World::query_filtered) since the last
+/// [`World::clear_trackers`](super::World::clear_trackers)
+pub struct Added<T>(PhantomData<fn() -> T>);
+
+/// Only entities whose `T` was added or mutably accessed since the system
+/// last ran, or for [`World::query_filtered`](super::World::query_filtered)
+/// since the last [`World::clear_trackers`](super::World::clear_trackers)
+pub struct Changed<T>(PhantomData<fn() -> T>);
+
+unsafe impl<T: Component> QueryFilter for With<T> {
+    type Fetch<'w> = ();
+
+    fn add_access(_: &mut Access) {}
+
+    fn matches(archetype: &Archetype) -> bool {
+        archetype.contains::<T>()
+    }
+
+    unsafe fn fetch(_: &Archetype, _: Ticks) {}
+
+    unsafe fn filter(_: &(), _: usize) -> bool {
+        true
+    }
+}
+
+unsafe impl<T: Component> QueryFilter for Without<T> {
+    type Fetch<'w> = ();
+
+    fn add_access(_: &mut Access) {}
+
+    fn matches(archetype: &Archetype) -> bool {
+        !archetype.contains::<T>()
+    }
+
+    unsafe fn fetch(_: &Archetype, _: Ticks) {}
+
+    unsafe fn filter(_: &(), _: usize) -> bool {
+        true
+    }
+}
+
+macro_rules! impl_tick_filter {
+    ($filter:ident, $ticks:ident) => {
+        unsafe impl<T: Component> QueryFilter for $filter<T> {
+            type Fetch<'w> = (*const u32, Ticks);
+
+            fn add_access(access: &mut Access) {
+                access.add_read::<T>();
+            }
+
+            fn matches(archetype: &Archetype) -> bool {
+                archetype.contains::<T>()
+            }
+
+            unsafe fn fetch(
+                archetype: &Archetype,
+                ticks: Ticks,
+            ) -> (*const u32, Ticks) {
+                let column = archetype
+                    .column(TypeId::of::<T>())
+                    .expect("archetype matches");
+                // Read through a raw pointer, as the same query may also
+                // write `T`
+                let column = unsafe { column.typed::<T>() };
+                (column.$ticks.as_ptr(), ticks)
+            }
+
+            unsafe fn filter(
+                &(stamps, ticks): &(*const u32, Ticks),
+                row: usize,
+            ) -> bool {
+                ticks.is_newer(unsafe { *stamps.add(row) })
+            }
+        }
+    };
+}
+
+impl_tick_filter!(Added, added);
+impl_tick_filter!(Changed, changed);
+
+macro_rules! impl_tuples {
+    ($($name:ident),*) => {
+        #[allow(
+            non_snake_case,
+            unused_variables,
+            unused_unsafe,
+            clippy::unused_unit
+        )]
+        unsafe impl<$($name: QueryData),*> QueryData for ($($name,)*) {
+            type Item<'w> = ($($name::Item<'w>,)*);
+            type Fetch<'w> = ($($name::Fetch<'w>,)*);
+
+            fn add_access(access: &mut Access) {
+                $($name::add_access(access);)*
+            }
+
+            fn matches(archetype: &Archetype) -> bool {
+                true $(&& $name::matches(archetype))*
+            }
+
+            unsafe fn fetch<'w>(
+                archetype: &'w Archetype,
+                ticks: Ticks,
+            ) -> Self::Fetch<'w> {
+                unsafe { ($($name::fetch(archetype, ticks),)*) }
+            }
+
+            unsafe fn item<'w>(
+                fetch: &mut Self::Fetch<'w>,
+                row: usize,
+            ) -> Self::Item<'w> {
+                let ($($name,)*) = fetch;
+                unsafe { ($($name::item($name, row),)*) }
+            }
+        }
+
+        unsafe impl<$($name: ReadOnlyQueryData),*> ReadOnlyQueryData
+            for ($($name,)*)
+        {
+        }
+
+        #[allow(
+            non_snake_case,
+            unused_variables,
+            unused_unsafe,
+            clippy::unused_unit
+        )]
+        unsafe impl<$($name: QueryFilter),*> QueryFilter for ($($name,)*) {
+            type Fetch<'w> = ($($name::Fetch<'w>,)*);
+
+            fn add_access(access: &mut Access) {
+                $($name::add_access(access);)*
+            }
+
+            fn matches(archetype: &Archetype) -> bool {
+                true $(&& $name::matches(archetype))*
+            }
+
+            unsafe fn fetch<'w>(
+                archetype: &'w Archetype,
+                ticks: Ticks,
+            ) -> Self::Fetch<'w> {
+                unsafe { ($($name::fetch(archetype, ticks),)*) }
+            }
+
+            unsafe fn filter(fetch: &Self::Fetch<'_>, row: usize) -> bool {
+                let ($($name,)*) = fetch;
+                true $(&& unsafe { $name::filter($name, row) })*
+            }
+        }
+    };
+}
+
+impl_tuples!();
+impl_tuples!(A);
+impl_tuples!(A, B);
+impl_tuples!(A, B, C);
+impl_tuples!(A, B, C, D);
+impl_tuples!(A, B, C, D, E);
+impl_tuples!(A, B, C, D, E, F);
+impl_tuples!(A, B, C, D, E, F, G);
+impl_tuples!(A, B, C, D, E, F, G, H);
+
+/// The combined access of query data `Q` and filter `F`
+///
+/// # Panics
+///
+/// Panics if `Q` accesses a component mutably more than once, or both
+/// reads and writes it.
+pub fn query_access<Q: QueryData, F: QueryFilter>() -> Access {
+    let mut access = Access::default();
+    Q::add_access(&mut access);
+    // Filters only read ticks through raw pointers, so they may overlap
+    let mut filter = Access::default();
+    F::add_access(&mut filter);
+    access.extend(&filter);
+    access
+}
+
+/// Iterates the entities matching `Q` and `F`, yielding `Q`'s items
+pub struct QueryIter<'w, Q: QueryData, F: QueryFilter = ()> {
+    archetypes: std::slice::Iter<'w, Archetype>,
+    ticks: Ticks,
+    current: Option<(Q::Fetch<'w>, F::Fetch<'w>)>,
+    row: usize,
+    len: usize,
+}
+
+impl<'w, Q: QueryData, F: QueryFilter> QueryIter<'w, Q, F> {
+    /// # Safety
+    ///
+    /// Nothing else may access what `Q` and `F` access while the iterator
+    /// or its items are alive.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `Q`'s own accesses conflict.
+    pub(crate) unsafe fn new(
+        archetypes: &'w [Archetype],
+        ticks: Ticks,
+    ) -> Self {
+        query_access::<Q, F>();
+        Self {
+            archetypes: archetypes.iter(),
+            ticks,
+            current: None,
+            row: 0,
+            len: 0,
+        }
+    }
+}
+
+impl<'w, Q: QueryData, F: QueryFilter> Iterator for QueryIter<'w, Q, F> {
+    type Item = Q::Item<'w>;
+
+    fn next(&mut self) -> Option<Q::Item<'w>> {
+        loop {
+            if let Some((data, filter)) = &mut self.current {
+                while self.row < self.len {
+                    let row = self.row;
+                    self.row += 1;
+                    if unsafe { F::filter(filter, row) } {
+                        return Some(unsafe { Q::item(data, row) });
+                    }
+                }
+            }
+
+            self.current = None;
+            let archetype = self.archetypes.next()?;
+            if archetype.is_empty()
+                || !Q::matches(archetype)
+                || !F::matches(archetype)
+            {
+                continue;
+            }
+            self.current = Some(unsafe {
+                (
+                    Q::fetch(archetype, self.ticks),
+                    F::fetch(archetype, self.ticks),
+                )
+            });
+            self.row = 0;
+            self.len = archetype.len();
+        }
+    }
+}
@@ -0,0 +1,633 @@
+//! Systems and the stages they run in
+
+use std::any::type_name;
+use std::fmt;
+use std::num::NonZero;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use super::query::{Access, QueryData, QueryFilter, QueryIter, Ticks};
+use super::{Component, Entity, World, query_access};
+use crate::jobs::JobSystem;
+
+/// When in the frame a system runs. Each frame runs the stages in this
+/// order, with [`Stage::FixedUpdate`] running once per fixed tick.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Stage {
+    PreUpdate,
+    FixedUpdate,
+    Update,
+    PostUpdate,
+    /// Copies what the renderer needs out of the world
+    RenderExtract,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 5] = [
+        Stage::PreUpdate,
+        Stage::FixedUpdate,
+        Stage::Update,
+        Stage::PostUpdate,
+        Stage::RenderExtract,
+    ];
+}
+
+/// How a [`Scheduler`] runs the systems in a stage
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutionMode {
+    /// One at a time on the calling thread, in the order they were added
+    SingleThreaded,
+    /// Systems whose accesses don't conflict run at once as jobs, up to
+    /// `threads` at a time including the calling thread
+    Parallel { threads: usize },
+}
+
+impl ExecutionMode {
+    /// Parallel on as many threads as the machine has cores
+    pub fn parallel() -> Self {
+        ExecutionMode::Parallel {
+            threads: thread::available_parallelism().map_or(1, NonZero::get),
+        }
+    }
+}
+
+impl Default for ExecutionMode {
+    fn default() -> Self {
+        Self::parallel()
+    }
+}
+
+type RunFn = Box<dyn FnMut(&mut SystemContext<'_>) + Send>;
+
+/// A named function over the [`World`], with the components it reads and
+/// writes declared up front so the [`Scheduler`] can tell which systems
+/// may run at once.
+///
+/// # Example
+/// ```
+/// use substrate::ecs::{System, Without};
+///
+/// struct Position(f32);
+/// struct Velocity(f32);
+/// struct Frozen;
+///
+/// let movement = System::new("movement", |ctx| {
+///     let dt = ctx.dt() as f32;
+///     for (mut position, velocity) in ctx
+///         .query_filtered::<(&mut Position, &Velocity), Without<Frozen>>()
+///     {
+///         position.0 += velocity.0 * dt;
+///     }
+/// })
+/// .with_query::<(&mut Position, &Velocity), Without<Frozen>>();
+/// ```
+pub struct System {
+    name: String,
+    access: Access,
+    run: RunFn,
+    /// Tick of the previous run, for change detection
+    last_run: u32,
+}
+
+impl System {
+    /// A system that accesses nothing until declared with the `with_*`
+    /// methods
+    pub fn new(
+        name: &str,
+        run: impl FnMut(&mut SystemContext<'_>) + Send + 'static,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            access: Access::default(),
+            run: Box::new(run),
+            last_run: 0,
+        }
+    }
+
+    /// Declare that the system reads `T`
+    pub fn with_read<T: Component>(self) -> Self {
+        self.with_query::<&T, ()>()
+    }
+
+    /// Declare that the system writes `T`
+    pub fn with_write<T: Component>(self) -> Self {
+        self.with_query::<&mut T, ()>()
+    }
+
+    /// Declare everything query data `Q` with filter `F` accesses
+    ///
+    /// # Panics
+    ///
+    /// Panics if the query's own accesses conflict.
+    pub fn with_query<Q: QueryData, F: QueryFilter>(mut self) -> Self {
+        self.access
+            .extend(&query_access::<Q, F>());
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn access(&self) -> &Access {
+        &self.access
+    }
+
+    fn run(&mut self, world: &World, this_run: u32, dt: f64) {
+        let mut context = SystemContext {
+            world,
+            name: &self.name,
+            access: &self.access,
+            ticks: Ticks { last_run: self.last_run, this_run },
+            dt,
+        };
+        (self.run)(&mut context);
+        self.last_run = this_run;
+    }
+}
+
+impl fmt::Debug for System {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("System")
+            .field("name", &self.name)
+            .field("access", &self.access)
+            .finish_non_exhaustive()
+    }
+}
+
+/// What a running system can reach: queries over the components it
+/// declared, and the stage's time step.
+///
+/// [`Added`](super::Added) and [`Changed`](super::Changed) filters see
+/// what changed since the system last ran.
+pub struct SystemContext<'w> {
+    world: &'w World,
+    name: &'w str,
+    access: &'w Access,
+    ticks: Ticks,
+    dt: f64,
+}
+
+impl SystemContext<'_> {
+    /// Seconds covered by this run: the frame time, or the fixed tick
+    /// length in [`Stage::FixedUpdate`]
+    pub fn dt(&self) -> f64 {
+        self.dt
+    }
+
+    /// Name of the running system
+    pub fn name(&self) -> &str {
+        self.name
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the system didn't declare what `Q` accesses.
+    pub fn query<Q: QueryData>(&mut self) -> QueryIter<'_, Q> {
+        self.query_filtered::<Q, ()>()
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the system didn't declare what `Q` and `F` access.
+    pub fn query_filtered<Q: QueryData, F: QueryFilter>(
+        &mut self,
+    ) -> QueryIter<'_, Q, F> {
+        self.check_access(&query_access::<Q, F>(), type_name::<(Q, F)>());
+        // The scheduler never runs systems with conflicting declared
+        // accesses at once, and `&mut self` keeps this system's own
+        // queries from overlapping
+        unsafe { self.world.query_unchecked(self.ticks) }
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the system didn't declare that it reads or writes `T`.
+    pub fn get<T: Component>(&self, entity: Entity) -> Option<&T> {
+        self.check_access(&query_access::<&T, ()>(), type_name::<T>());
+        self.world.get(entity)
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.world.contains(entity)
+    }
+
+    fn check_access(&self, access: &Access, what: &str) {
+        assert!(
+            self.access.covers(access),
+            "system {} accesses {what} without declaring it",
+            self.name
+        );
+    }
+}
+
+/// Runs systems stage by stage.
+///
+/// Within a stage, systems run in the order they were added, except that
+/// consecutive systems whose declared accesses don't conflict form a batch
+/// that may run in parallel. Systems that conflict always run in the order
+/// they were added, so both execution modes give the same results.
+///
+/// Parallel batches run on the [`JobSystem`] given to
+/// [`Scheduler::with_jobs`], or on one the scheduler creates when first
+/// needed.
+#[derive(Debug, Default)]
+pub struct Scheduler {
+    stages: [Vec<System>; Stage::ALL.len()],
+    /// Per stage, ranges of systems that may run at once
+    batches: [Vec<Range<usize>>; Stage::ALL.len()],
+    mode: ExecutionMode,
+    jobs: Option<Arc<JobSystem>>,
+}
+
+impl Scheduler {
+    /// A scheduler with no systems that runs batches in parallel
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set how systems are run
+    pub fn with_mode(mut self, mode: ExecutionMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Run parallel batches on `jobs`
+    pub fn with_jobs(mut self, jobs: Arc<JobSystem>) -> Self {
+        self.jobs = Some(jobs);
+        self
+    }
+
+    pub fn mode(&self) -> ExecutionMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: ExecutionMode) {
+        self.mode = mode;
+    }
+
+    /// Add a system to the end of `stage`
+    pub fn add_system(&mut self, stage: Stage, system: System) -> &mut Self {
+        let systems = &mut self.stages[stage as usize];
+        systems.push(system);
+        self.batches[stage as usize] = batch(systems);
+        self
+    }
+
+    /// The systems in `stage`, in the order they were added
+    pub fn systems(&self, stage: Stage) -> &[System] {
+        &self.stages[stage as usize]
+    }
+
+    /// The groups of systems in `stage` that may run at once, in order
+    pub fn batches(&self, stage: Stage) -> impl Iterator<Item = &[System]> {
+        let systems = &self.stages[stage as usize];
+        self.batches[stage as usize]
+            .iter()
+            .map(|range| &systems[range.clone()])
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stages.iter().all(Vec::is_empty)
+    }
+
+    /// Run every system in `stage` once
+    pub fn run_stage(&mut self, stage: Stage, world: &mut World, dt: f64) {
+        let systems = &mut self.stages[stage as usize];
+        for range in &self.batches[stage as usize] {
+            let this_run = world.change_tick();
+            let batch = &mut systems[range.clone()];
+            match self.mode {
+                ExecutionMode::Parallel { threads }
+                    if threads > 1 && batch.len() > 1 =>
+                {
+                    let jobs = self.jobs.get_or_insert_with(|| {
+                        Arc::new(JobSystem::new(threads - 1))
+                    });
+                    run_parallel(batch, world, this_run, dt, threads, jobs);
+                }
+                _ => {
+                    for system in batch {
+                        system.run(world, this_run, dt);
+                    }
+                }
+            }
+            // Later batches, and changes made outside the scheduler, get a
+            // newer tick, so they count as new to these systems
+            world.advance_tick();
+        }
+    }
+}
+
+/// Split systems into runs of consecutive systems that don't conflict
+fn batch(systems: &[System]) -> Vec<Range<usize>> {
+    let mut batches = Vec::new();
+    let mut start = 0;
+    for (index, system) in systems.iter().enumerate() {
+        let conflicts = systems[start..index]
+            .iter()
+            .any(|other| {
+                !other
+                    .access
+                    .is_compatible(&system.access)
+            });
+        if conflicts {
+            batches.push(start..index);
+            start = index;
+        }
+    }
+    if start < systems.len() {
+        batches.push(start..systems.len());
+    }
+    batches
+}
+
+/// Run a batch on the calling thread and up to `threads - 1` scoped jobs,
+/// each taking the next system not yet started
+fn run_parallel(
+    batch: &mut [System],
+    world: &World,
+    this_run: u32,
+    dt: f64,
+    threads: usize,
+    jobs: &JobSystem,
+) {
+    let queue = Mutex::new(batch.iter_mut());
+    let work = || {
+        loop {
+            let Some(system) = queue.lock().unwrap().next() else {
+                break;
+            };
+            system.run(world, this_run, dt);
+        }
+    };
+    jobs.scope(|scope| {
+        for _ in 1..threads.min(queue.lock().unwrap().len()) {
+            scope.spawn(work);
+        }
+        work();
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::{Changed, With};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Barrier};
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Position(f32);
+
+    struct Velocity(f32);
+
+    struct Health(u32);
+
+    fn names<'a>(
+        batches: impl Iterator<Item = &'a [System]>,
+    ) -> Vec<Vec<&'a str>> {
+        batches
+            .map(|batch| batch.iter().map(System::name).collect())
+            .collect()
+    }
+
+    fn noop(name: &str) -> System {
+        System::new(name, |_| {})
+    }
+
+    #[test]
+    fn batches_split_at_conflicts() {
+        let mut scheduler = Scheduler::new();
+        scheduler
+            .add_system(Stage::Update, noop("read a").with_read::<Position>())
+            .add_system(Stage::Update, noop("read b").with_read::<Position>())
+            .add_system(Stage::Update, noop("write c").with_write::<Health>())
+            .add_system(Stage::Update, noop("write a").with_write::<Position>())
+            .add_system(Stage::Update, noop("read c").with_read::<Health>());
+
+        assert_eq!(
+            names(scheduler.batches(Stage::Update)),
+            [vec!["read a", "read b", "write c"], vec!["write a", "read c"],]
+        );
+        assert_eq!(
+            scheduler
+                .batches(Stage::PostUpdate)
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn filters_count_as_reads() {
+        let mut scheduler = Scheduler::new();
+        scheduler
+            .add_system(Stage::Update, noop("write").with_write::<Position>())
+            .add_system(
+                Stage::Update,
+                noop("changed").with_query::<Entity, Changed<Position>>(),
+            )
+            .add_system(
+                Stage::Update,
+                noop("with").with_query::<Entity, With<Position>>(),
+            );
+
+        assert_eq!(
+            names(scheduler.batches(Stage::Update)),
+            [vec!["write"], vec!["changed", "with"]]
+        );
+    }
+
+    /// Adds, doubles, then reads positions; only the order of conflicting
+    /// systems decides the result
+    fn arithmetic(mode: ExecutionMode) -> Vec<f32> {
+        let mut world = World::new();
+        for i in 0..100 {
+            world.spawn((Position(i as f32), Velocity(1.0), Health(i)));
+        }
+        let total = Arc::new(Mutex::new(0.0));
+        let sum = total.clone();
+
+        let mut scheduler = Scheduler::new().with_mode(mode);
+        scheduler
+            .add_system(
+                Stage::Update,
+                System::new("move", |ctx| {
+                    for (mut position, velocity) in
+                        ctx.query::<(&mut Position, &Velocity)>()
+                    {
+                        position.0 += velocity.0;
+                    }
+                })
+                .with_query::<(&mut Position, &Velocity), ()>(),
+            )
+            .add_system(
+                Stage::Update,
+                System::new("heal", |ctx| {
+                    for mut health in ctx.query::<&mut Health>() {
+                        health.0 += 1;
+                    }
+                })
+                .with_write::<Health>(),
+            )
+            .add_system(
+                Stage::Update,
+                System::new("double", |ctx| {
+                    for mut position in ctx.query::<&mut Position>() {
+                        position.0 *= 2.0;
+                    }
+                })
+                .with_write::<Position>(),
+            )
+            .add_system(
+                Stage::PostUpdate,
+                System::new("sum", move |ctx| {
+                    *sum.lock().unwrap() += ctx
+                        .query::<&Position>()
+                        .map(|position| position.0)
+                        .sum::<f32>();
+                })
+                .with_read::<Position>(),
+            );
+
+        for _ in 0..3 {
+            scheduler.run_stage(Stage::Update, &mut world, 1.0);
+            scheduler.run_stage(Stage::PostUpdate, &mut world, 1.0);
+        }
+        let mut positions: Vec<_> = world
+            .query::<&Position>()
+            .map(|position| position.0)
+            .collect();
+        positions.push(*total.lock().unwrap());
+        positions
+    }
+
+    #[test]
+    fn parallel_matches_single_threaded() {
+        let single = arithmetic(ExecutionMode::SingleThreaded);
+        assert_eq!(single[0], (((0.0 + 1.0) * 2.0 + 1.0) * 2.0 + 1.0) * 2.0);
+        assert_eq!(single, arithmetic(ExecutionMode::Parallel { threads: 4 }));
+    }
+
+    /// Runs two systems that each wait for the other, so running them one
+    /// after the other would deadlock
+    fn run_at_once(mut scheduler: Scheduler) {
+        let barrier = Arc::new(Barrier::new(2));
+        let ran = Arc::new(AtomicUsize::new(0));
+        for name in ["a", "b"] {
+            let barrier = barrier.clone();
+            let ran = ran.clone();
+            let system = System::new(name, move |ctx| {
+                ctx.query::<&Position>().count();
+                barrier.wait();
+                ran.fetch_add(1, Ordering::Relaxed);
+            })
+            .with_read::<Position>();
+            scheduler.add_system(Stage::Update, system);
+        }
+
+        scheduler.run_stage(Stage::Update, &mut World::new(), 0.0);
+        assert_eq!(ran.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn compatible_systems_run_at_once() {
+        let parallel = ExecutionMode::Parallel { threads: 2 };
+        run_at_once(Scheduler::new().with_mode(parallel));
+        run_at_once(
+            Scheduler::new()
+                .with_mode(parallel)
+                .with_jobs(Arc::new(JobSystem::new(1))),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "without declaring it")]
+    fn undeclared_access_panics() {
+        let mut world = World::new();
+        world.spawn((Position(0.0),));
+        let mut scheduler =
+            Scheduler::new().with_mode(ExecutionMode::SingleThreaded);
+        scheduler.add_system(
+            Stage::Update,
+            System::new("sneaky", |ctx| {
+                ctx.query::<&mut Position>().count();
+            })
+            .with_read::<Position>(),
+        );
+        scheduler.run_stage(Stage::Update, &mut world, 0.0);
+    }
+
+    #[test]
+    fn change_detection_is_per_system() {
+        let mut world = World::new();
+        let entity = world.spawn((Position(0.0),));
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = seen.clone();
+
+        let mut scheduler =
+            Scheduler::new().with_mode(ExecutionMode::SingleThreaded);
+        scheduler.add_system(
+            Stage::Update,
+            System::new("watch", move |ctx| {
+                let changed = ctx
+                    .query_filtered::<Entity, Changed<Position>>()
+                    .count();
+                log.lock().unwrap().push(changed);
+            })
+            .with_query::<Entity, Changed<Position>>(),
+        );
+
+        scheduler.run_stage(Stage::Update, &mut world, 0.0);
+        scheduler.run_stage(Stage::Update, &mut world, 0.0);
+        world
+            .get_mut::<Position>(entity)
+            .unwrap()
+            .0 = 1.0;
+        scheduler.run_stage(Stage::Update, &mut world, 0.0);
+        world.clear_trackers();
+        scheduler.run_stage(Stage::Update, &mut world, 0.0);
+
+        assert_eq!(*seen.lock().unwrap(), [1, 0, 1, 0]);
+    }
+
+    #[test]
+    fn later_batches_see_earlier_changes() {
+        let mut world = World::new();
+        world.spawn((Position(0.0),));
+        world.clear_trackers();
+        let seen = Arc::new(AtomicUsize::new(0));
+        let count = seen.clone();
+
+        let mut scheduler = Scheduler::new();
+        scheduler
+            .add_system(
+                Stage::Update,
+                System::new("write", |ctx| {
+                    for mut position in ctx.query::<&mut Position>() {
+                        position.0 += 1.0;
+                    }
+                })
+                .with_write::<Position>(),
+            )
+            .add_system(
+                Stage::Update,
+                System::new("watch", move |ctx| {
+                    let changed = ctx
+                        .query_filtered::<Entity, Changed<Position>>()
+                        .count();
+                    count.fetch_add(changed, Ordering::Relaxed);
+                })
+                .with_query::<Entity, Changed<Position>>(),
+            );
+        scheduler.run_stage(Stage::Update, &mut world, 0.0);
+
+        assert_eq!(seen.load(Ordering::Relaxed), 1);
+        assert_eq!(
+            world
+                .query_filtered::<Entity, Changed<Position>>()
+                .count(),
+            1
+        );
+    }
+}
@@ -0,0 +1,307 @@
+//! Archetype tables and their type-erased component columns
+
+use std::any::{Any, TypeId};
+use std::cell::UnsafeCell;
+
+use super::{Component, Entity};
+
+/// A column of one component type, with the tick each value was added and
+/// last changed
+pub(crate) struct TypedColumn<T> {
+    pub values: Vec<T>,
+    pub added: Vec<u32>,
+    pub changed: Vec<u32>,
+}
+
+impl<T: Component> TypedColumn<T> {
+    fn push(&mut self, value: T, tick: u32) {
+        self.values.push(value);
+        self.added.push(tick);
+        self.changed.push(tick);
+    }
+
+    fn set(&mut self, row: usize, value: T, tick: u32) {
+        self.values[row] = value;
+        self.changed[row] = tick;
+    }
+
+    pub fn swap_remove(&mut self, row: usize) -> T {
+        self.added.swap_remove(row);
+        self.changed.swap_remove(row);
+        self.values.swap_remove(row)
+    }
+}
+
+/// Operations on a column whose component type is only known at runtime
+trait ColumnStorage: Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    /// Drop the value at `row`, moving the last value into its place
+    fn swap_remove(&mut self, row: usize);
+    /// Move the value at `row` onto the end of `dst`, a column of the same
+    /// type, keeping its ticks
+    fn move_row(&mut self, row: usize, dst: &mut dyn ColumnStorage);
+}
+
+impl<T: Component> ColumnStorage for TypedColumn<T> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn swap_remove(&mut self, row: usize) {
+        TypedColumn::swap_remove(self, row);
+    }
+
+    fn move_row(&mut self, row: usize, dst: &mut dyn ColumnStorage) {
+        let dst = dst
+            .as_any_mut()
+            .downcast_mut::<Self>()
+            .expect("column types match");
+        dst.added
+            .push(self.added.swap_remove(row));
+        dst.changed
+            .push(self.changed.swap_remove(row));
+        dst.values
+            .push(self.values.swap_remove(row));
+    }
+}
+
+/// One component type's values for every entity in an archetype.
+///
+/// The storage sits in an `UnsafeCell` so queries can borrow different
+/// columns of the same archetype mutably at once.
+pub struct Column {
+    type_id: TypeId,
+    storage: UnsafeCell<Box<dyn ColumnStorage>>,
+}
+
+// Shared access only reaches the storage through the unsafe accessors,
+// whose callers guarantee borrows don't overlap
+unsafe impl Sync for Column {}
+
+impl Column {
+    pub(crate) fn new<T: Component>() -> Self {
+        Self {
+            type_id: TypeId::of::<T>(),
+            storage: UnsafeCell::new(Box::new(TypedColumn::<T> {
+                values: Vec::new(),
+                added: Vec::new(),
+                changed: Vec::new(),
+            })),
+        }
+    }
+
+    fn storage_mut(&mut self) -> &mut dyn ColumnStorage {
+        self.storage.get_mut().as_mut()
+    }
+
+    pub(crate) fn typed_mut<T: Component>(&mut self) -> &mut TypedColumn<T> {
+        self.storage_mut()
+            .as_any_mut()
+            .downcast_mut()
+            .expect("column holds T")
+    }
+
+    /// # Safety
+    ///
+    /// No mutable borrow of this column may be alive.
+    pub(crate) unsafe fn typed<T: Component>(&self) -> &TypedColumn<T> {
+        unsafe { &*self.storage.get() }
+            .as_any()
+            .downcast_ref()
+            .expect("column holds T")
+    }
+
+    /// # Safety
+    ///
+    /// No other borrow of this column may be alive.
+    #[allow(clippy::mut_from_ref)]
+    pub(crate) unsafe fn typed_unchecked_mut<T: Component>(
+        &self,
+    ) -> &mut TypedColumn<T> {
+        unsafe { &mut *self.storage.get() }
+            .as_any_mut()
+            .downcast_mut()
+            .expect("column holds T")
+    }
+}
+
+/// Creates an empty column for one component type
+pub type ColumnFactory = fn() -> Column;
+
+/// The entities that have exactly one set of component types, with a
+/// column per type.
+///
+/// Entities move between archetypes as components are inserted and
+/// removed.
+pub struct Archetype {
+    /// Sorted
+    types: Box<[TypeId]>,
+    columns: Box<[Column]>,
+    entities: Vec<Entity>,
+}
+
+impl Archetype {
+    /// `columns` must be sorted by type
+    pub(crate) fn new(columns: Vec<Column>) -> Self {
+        Self {
+            types: columns
+                .iter()
+                .map(|column| column.type_id)
+                .collect(),
+            columns: columns.into_boxed_slice(),
+            entities: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    /// Whether entities here have a `T` component
+    pub fn contains<T: Component>(&self) -> bool {
+        self.contains_id(TypeId::of::<T>())
+    }
+
+    pub(crate) fn contains_id(&self, type_id: TypeId) -> bool {
+        self.types
+            .binary_search(&type_id)
+            .is_ok()
+    }
+
+    pub(crate) fn types(&self) -> &[TypeId] {
+        &self.types
+    }
+
+    pub(crate) fn column(&self, type_id: TypeId) -> Option<&Column> {
+        let index = self
+            .types
+            .binary_search(&type_id)
+            .ok()?;
+        Some(&self.columns[index])
+    }
+
+    pub(crate) fn column_mut(
+        &mut self,
+        type_id: TypeId,
+    ) -> Option<&mut Column> {
+        let index = self
+            .types
+            .binary_search(&type_id)
+            .ok()?;
+        Some(&mut self.columns[index])
+    }
+
+    /// Add an entity whose components will be pushed onto the columns
+    /// afterwards. Returns its row.
+    pub(crate) fn push_entity(&mut self, entity: Entity) -> usize {
+        self.entities.push(entity);
+        self.entities.len() - 1
+    }
+
+    /// Drop the components at `row`. Returns the entity moved into `row`,
+    /// if any.
+    pub(crate) fn swap_remove(&mut self, row: usize) -> Option<Entity> {
+        for column in self.columns.iter_mut() {
+            column.storage_mut().swap_remove(row);
+        }
+        self.remove_entity(row)
+    }
+
+    /// Move the components at `row` that `dst` also has onto the end of
+    /// `dst`, dropping the rest except `keep`, which stays behind for the
+    /// caller to take. Returns the entity moved into `row`, if any.
+    pub(crate) fn move_row(
+        &mut self,
+        row: usize,
+        dst: &mut Archetype,
+        keep: Option<TypeId>,
+    ) -> Option<Entity> {
+        let entity = self.entities[row];
+        for column in self.columns.iter_mut() {
+            if let Some(target) = dst.column_mut(column.type_id) {
+                column
+                    .storage_mut()
+                    .move_row(row, target.storage_mut());
+            } else if Some(column.type_id) != keep {
+                column.storage_mut().swap_remove(row);
+            }
+        }
+        dst.entities.push(entity);
+        self.remove_entity(row)
+    }
+
+    fn remove_entity(&mut self, row: usize) -> Option<Entity> {
+        self.entities.swap_remove(row);
+        self.entities.get(row).copied()
+    }
+}
+
+/// A set of components that can be added to an entity together: tuples of
+/// up to eight distinct component types
+pub trait Bundle: Send + Sync + 'static {
+    #[doc(hidden)]
+    fn columns(out: &mut Vec<(TypeId, ColumnFactory)>);
+
+    /// Write each component into row `row` of `archetype`, pushing onto
+    /// columns that don't reach it yet and overwriting otherwise
+    #[doc(hidden)]
+    fn write(self, archetype: &mut Archetype, row: usize, tick: u32);
+}
+
+/// Write `value` as row `row` of its column in `archetype`
+pub(crate) fn write_component<T: Component>(
+    archetype: &mut Archetype,
+    row: usize,
+    value: T,
+    tick: u32,
+) {
+    let column = archetype
+        .column_mut(TypeId::of::<T>())
+        .expect("archetype has every bundle component")
+        .typed_mut::<T>();
+    if column.values.len() > row {
+        column.set(row, value, tick);
+    } else {
+        column.push(value, tick);
+    }
+}
+
+macro_rules! impl_bundle {
+    ($($name:ident),*) => {
+        impl<$($name: Component),*> Bundle for ($($name,)*) {
+            #[allow(unused_variables)]
+            fn columns(out: &mut Vec<(TypeId, ColumnFactory)>) {
+                $(out.push((TypeId::of::<$name>(), Column::new::<$name>));)*
+            }
+
+            #[allow(non_snake_case, unused_variables)]
+            fn write(self, archetype: &mut Archetype, row: usize, tick: u32) {
+                let ($($name,)*) = self;
+                $(write_component(archetype, row, $name, tick);)*
+            }
+        }
+    };
+}
+
+impl_bundle!();
+impl_bundle!(A);
+impl_bundle!(A, B);
+impl_bundle!(A, B, C);
+impl_bundle!(A, B, C, D);
+impl_bundle!(A, B, C, D, E);
+impl_bundle!(A, B, C, D, E, F);
+impl_bundle!(A, B, C, D, E, F, G);
+impl_bundle!(A, B, C, D, E, F, G, H);
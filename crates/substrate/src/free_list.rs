@@ -0,0 +1,432 @@
+//! free_list.rs
+
+use std::alloc::{self, Layout};
+use std::cell::{Cell, RefCell};
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+
+use allocator_api2::alloc::{AllocError, Allocator};
+
+/// Smallest size class; a free cell must hold a pointer
+const MIN_CLASS: usize = 16;
+
+/// Bytes carved into cells at a time; also the largest size class
+const PAGE_SIZE: usize = 64 * 1024;
+
+/// Alignment of every page, and so the largest alignment cells can have
+const PAGE_ALIGN: usize = 4096;
+
+/// An allocator that rounds requests up to power-of-two size classes and
+/// keeps a free list per class, so allocating and freeing are O(1).
+///
+/// Classes run from 16 bytes to 64 KiB. Each class carves its cells from
+/// 64 KiB pages that are kept until the allocator drops; larger requests
+/// go straight to the global allocator. Unlike [`crate::arena::Arena`],
+/// individual allocations can be freed, at the cost of rounding waste.
+///
+/// `&FreeListAllocator` implements [`Allocator`].
+pub struct FreeListAllocator {
+    classes: [SizeClass; CLASSES],
+    pages: RefCell<Vec<NonNull<u8>>>,
+    large_count: Cell<usize>,
+    large_bytes: Cell<usize>,
+    requested_bytes: Cell<usize>,
+    // Raw page pointers: neither Send nor Sync
+    _marker: PhantomData<*mut u8>,
+}
+
+const CLASSES: usize =
+    (PAGE_SIZE.trailing_zeros() - MIN_CLASS.trailing_zeros()) as usize + 1;
+
+#[derive(Default)]
+struct SizeClass {
+    free: Cell<Option<NonNull<FreeCell>>>,
+    free_count: Cell<usize>,
+    live: Cell<usize>,
+}
+
+/// Written into free cells to link them
+struct FreeCell {
+    next: Option<NonNull<FreeCell>>,
+}
+
+/// Usage figures for a [`FreeListAllocator`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FreeListStats {
+    /// Allocations not yet freed
+    pub live: usize,
+    /// Bytes asked for by live allocations
+    pub requested_bytes: usize,
+    /// Bytes of the cells and large allocations backing live allocations
+    pub allocated_bytes: usize,
+    /// Bytes in free cells
+    pub free_bytes: usize,
+    /// Bytes held from the global allocator
+    pub reserved_bytes: usize,
+}
+
+impl FreeListStats {
+    /// Fraction of reserved memory not holding requested bytes, from size
+    /// class rounding and free cells
+    pub fn fragmentation(&self) -> f32 {
+        if self.reserved_bytes == 0 {
+            0.0
+        } else {
+            1.0 - self.requested_bytes as f32 / self.reserved_bytes as f32
+        }
+    }
+}
+
+impl FreeListAllocator {
+    pub fn new() -> Self {
+        Self {
+            classes: Default::default(),
+            pages: RefCell::new(Vec::new()),
+            large_count: Cell::new(0),
+            large_bytes: Cell::new(0),
+            requested_bytes: Cell::new(0),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Cell size allocations of `layout` get, or `None` if they bypass the
+    /// size classes
+    pub fn class_size(layout: Layout) -> Option<usize> {
+        Self::class_index(layout).map(|class| MIN_CLASS << class)
+    }
+
+    fn class_index(layout: Layout) -> Option<usize> {
+        let size = layout
+            .size()
+            .max(layout.align())
+            .max(MIN_CLASS)
+            .checked_next_power_of_two()?;
+        if size > PAGE_SIZE || layout.align() > PAGE_ALIGN {
+            return None;
+        }
+        Some((size.trailing_zeros() - MIN_CLASS.trailing_zeros()) as usize)
+    }
+
+    /// Allocate `layout`, reusing a freed cell of its class if there is one
+    ///
+    /// # Errors
+    ///
+    /// Returns `AllocError` if the global allocator fails.
+    pub fn allocate_layout(
+        &self,
+        layout: Layout,
+    ) -> Result<NonNull<u8>, AllocError> {
+        let ptr = match Self::class_index(layout) {
+            Some(index) => {
+                let class = &self.classes[index];
+                if class.free.get().is_none() {
+                    self.add_page(index)?;
+                }
+                let cell = class
+                    .free
+                    .get()
+                    .expect("a new page has free cells");
+                class
+                    .free
+                    .set(unsafe { cell.as_ref().next });
+                class
+                    .free_count
+                    .set(class.free_count.get() - 1);
+                class.live.set(class.live.get() + 1);
+                cell.cast()
+            }
+            None => {
+                let ptr = NonNull::new(unsafe {
+                    alloc::alloc(Self::large_layout(layout))
+                })
+                .ok_or(AllocError)?;
+                self.large_count
+                    .set(self.large_count.get() + 1);
+                self.large_bytes
+                    .set(self.large_bytes.get() + layout.size());
+                ptr
+            }
+        };
+        self.requested_bytes
+            .set(self.requested_bytes.get() + layout.size());
+        Ok(ptr)
+    }
+
+    /// Return an allocation to its class's free list
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have come from this allocator with the same `layout` and
+    /// not have been freed since.
+    pub unsafe fn deallocate_layout(&self, ptr: NonNull<u8>, layout: Layout) {
+        match Self::class_index(layout) {
+            Some(index) => {
+                let class = &self.classes[index];
+                let cell = ptr.cast::<FreeCell>();
+                unsafe { cell.write(FreeCell { next: class.free.get() }) };
+                class.free.set(Some(cell));
+                class
+                    .free_count
+                    .set(class.free_count.get() + 1);
+                class.live.set(class.live.get() - 1);
+            }
+            None => {
+                unsafe {
+                    alloc::dealloc(ptr.as_ptr(), Self::large_layout(layout))
+                };
+                self.large_count
+                    .set(self.large_count.get() - 1);
+                self.large_bytes
+                    .set(self.large_bytes.get() - layout.size());
+            }
+        }
+        self.requested_bytes
+            .set(self.requested_bytes.get() - layout.size());
+    }
+
+    /// Carve a new page into cells for class `index`
+    fn add_page(&self, index: usize) -> Result<(), AllocError> {
+        let page = NonNull::new(unsafe { alloc::alloc(Self::page_layout()) })
+            .ok_or(AllocError)?;
+        self.pages.borrow_mut().push(page);
+
+        let size = MIN_CLASS << index;
+        let class = &self.classes[index];
+        // Linked back to front so cells are handed out in address order
+        let mut next = class.free.get();
+        for offset in (0..PAGE_SIZE).step_by(size).rev() {
+            let cell = unsafe { page.add(offset) }.cast::<FreeCell>();
+            unsafe { cell.write(FreeCell { next }) };
+            next = Some(cell);
+        }
+        class.free.set(next);
+        class
+            .free_count
+            .set(class.free_count.get() + PAGE_SIZE / size);
+        Ok(())
+    }
+
+    /// The global allocator can't take zero-sized layouts
+    fn large_layout(layout: Layout) -> Layout {
+        Layout::from_size_align(layout.size().max(1), layout.align())
+            .expect("valid layout")
+    }
+
+    fn page_layout() -> Layout {
+        Layout::from_size_align(PAGE_SIZE, PAGE_ALIGN).expect("valid layout")
+    }
+
+    pub fn stats(&self) -> FreeListStats {
+        let mut stats = FreeListStats {
+            live: self.large_count.get(),
+            requested_bytes: self.requested_bytes.get(),
+            allocated_bytes: self.large_bytes.get(),
+            free_bytes: 0,
+            reserved_bytes: self.pages.borrow().len() * PAGE_SIZE
+                + self.large_bytes.get(),
+        };
+        for (index, class) in self.classes.iter().enumerate() {
+            let size = MIN_CLASS << index;
+            stats.live += class.live.get();
+            stats.allocated_bytes += class.live.get() * size;
+            stats.free_bytes += class.free_count.get() * size;
+        }
+        stats
+    }
+}
+
+impl Default for FreeListAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for FreeListAllocator {
+    fn drop(&mut self) {
+        // Large allocations still live are leaked
+        for page in self.pages.get_mut().drain(..) {
+            unsafe { alloc::dealloc(page.as_ptr(), Self::page_layout()) };
+        }
+    }
+}
+
+unsafe impl Allocator for &FreeListAllocator {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        if layout.size() == 0 {
+            let dangling = NonNull::new(layout.align() as *mut u8)
+                .expect("alignment is nonzero");
+            return Ok(NonNull::slice_from_raw_parts(dangling, 0));
+        }
+        let ptr = self.allocate_layout(layout)?;
+        Ok(NonNull::slice_from_raw_parts(ptr, layout.size()))
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        if layout.size() != 0 {
+            unsafe { self.deallocate_layout(ptr, layout) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use allocator_api2::vec::Vec as AllocVec;
+
+    fn layout(size: usize, align: usize) -> Layout {
+        Layout::from_size_align(size, align).unwrap()
+    }
+
+    #[test]
+    fn requests_round_up_to_size_classes() {
+        assert_eq!(FreeListAllocator::class_size(layout(1, 1)), Some(16));
+        assert_eq!(FreeListAllocator::class_size(layout(17, 1)), Some(32));
+        assert_eq!(FreeListAllocator::class_size(layout(8, 64)), Some(64));
+        assert_eq!(
+            FreeListAllocator::class_size(layout(PAGE_SIZE, 8)),
+            Some(PAGE_SIZE)
+        );
+        assert_eq!(
+            FreeListAllocator::class_size(layout(PAGE_SIZE + 1, 8)),
+            None
+        );
+        assert_eq!(FreeListAllocator::class_size(layout(16, 8192)), None);
+    }
+
+    #[test]
+    fn freed_cell_is_reused_first() {
+        let allocator = FreeListAllocator::new();
+        let a = allocator
+            .allocate_layout(layout(24, 8))
+            .unwrap();
+        let b = allocator
+            .allocate_layout(layout(24, 8))
+            .unwrap();
+        unsafe { allocator.deallocate_layout(a, layout(24, 8)) };
+
+        let c = allocator
+            .allocate_layout(layout(30, 4))
+            .unwrap();
+        assert_eq!(c, a);
+        assert_ne!(c, b);
+    }
+
+    #[test]
+    fn allocations_are_aligned_and_disjoint() {
+        let allocator = FreeListAllocator::new();
+        let mut ranges = Vec::new();
+        for (size, align) in [(3, 1), (40, 8), (100, 64), (5000, 4096)] {
+            for _ in 0..20 {
+                let ptr = allocator
+                    .allocate_layout(layout(size, align))
+                    .unwrap();
+                assert!(
+                    ptr.as_ptr()
+                        .addr()
+                        .is_multiple_of(align)
+                );
+                let start = ptr.as_ptr().addr();
+                ranges.push((start, start + size));
+            }
+        }
+
+        ranges.sort_unstable();
+        for pair in ranges.windows(2) {
+            assert!(pair[0].1 <= pair[1].0);
+        }
+    }
+
+    #[test]
+    fn large_allocations_bypass_classes() {
+        let allocator = FreeListAllocator::new();
+        let big = layout(PAGE_SIZE * 2, 16);
+        let ptr = allocator.allocate_layout(big).unwrap();
+        let stats = allocator.stats();
+        assert_eq!(stats.live, 1);
+        assert_eq!(stats.reserved_bytes, PAGE_SIZE * 2);
+
+        unsafe { allocator.deallocate_layout(ptr, big) };
+        assert_eq!(allocator.stats(), FreeListStats::default());
+    }
+
+    #[test]
+    fn stats_report_rounding_and_free_cells() {
+        let allocator = FreeListAllocator::new();
+        let small = layout(48, 8);
+        let ptrs: Vec<_> = (0..10)
+            .map(|_| {
+                allocator
+                    .allocate_layout(small)
+                    .unwrap()
+            })
+            .collect();
+        for &ptr in &ptrs[..5] {
+            unsafe { allocator.deallocate_layout(ptr, small) };
+        }
+
+        let stats = allocator.stats();
+        assert_eq!(stats.live, 5);
+        assert_eq!(stats.requested_bytes, 5 * 48);
+        assert_eq!(stats.allocated_bytes, 5 * 64);
+        assert_eq!(stats.free_bytes, PAGE_SIZE - 5 * 64);
+        assert_eq!(stats.reserved_bytes, PAGE_SIZE);
+        assert_eq!(
+            stats.allocated_bytes + stats.free_bytes,
+            stats.reserved_bytes
+        );
+        assert!(stats.fragmentation() > 0.99);
+    }
+
+    #[test]
+    fn backs_collections() {
+        let allocator = FreeListAllocator::new();
+        {
+            let mut values = AllocVec::new_in(&allocator);
+            values.extend(0..10_000u32);
+            assert_eq!(values.iter().sum::<u32>(), (0..10_000).sum());
+            assert_eq!(allocator.stats().live, 1);
+        }
+        assert_eq!(allocator.stats().live, 0);
+        assert_eq!(allocator.stats().requested_bytes, 0);
+    }
+
+    #[test]
+    fn stress_random_alloc_free_keeps_contents() {
+        let allocator = FreeListAllocator::new();
+        let mut live: Vec<(NonNull<u8>, Layout, u8)> = Vec::new();
+        let mut state = 0x9E37_79B9_7F4A_7C15u64;
+        for step in 0..20_000u32 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            if live.len() > 200 || (state.is_multiple_of(3) && !live.is_empty()) {
+                let (ptr, layout, tag) =
+                    live.swap_remove(state as usize % live.len());
+                let bytes = unsafe {
+                    std::slice::from_raw_parts(ptr.as_ptr(), layout.size())
+                };
+                assert!(bytes.iter().all(|&b| b == tag), "cell was clobbered");
+                unsafe { allocator.deallocate_layout(ptr, layout) };
+            } else {
+                let layout = layout(1 + (state >> 8) as usize % 3000, 8);
+                let ptr = allocator
+                    .allocate_layout(layout)
+                    .unwrap();
+                let tag = step as u8;
+                unsafe {
+                    ptr.as_ptr()
+                        .write_bytes(tag, layout.size())
+                };
+                live.push((ptr, layout, tag));
+            }
+        }
+
+        assert_eq!(allocator.stats().live, live.len());
+        for (ptr, layout, _) in live {
+            unsafe { allocator.deallocate_layout(ptr, layout) };
+        }
+        let stats = allocator.stats();
+        assert_eq!((stats.live, stats.allocated_bytes), (0, 0));
+        assert_eq!(stats.free_bytes, stats.reserved_bytes);
+    }
+}
@@ -0,0 +1,906 @@
+//! Work-stealing job system
+
+use std::any::Any;
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::thread::{self, JoinHandle, ThreadId};
+use std::time::Duration;
+
+/// Source of ids telling pools apart in [`WORKER`]
+static NEXT_POOL_ID: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    /// Pool id and worker index, if this thread is a pool worker
+    static WORKER: Cell<Option<(u64, usize)>> =
+        const { Cell::new(None) };
+}
+
+/// How long an idle thread sleeps before looking for work again, in case it
+/// missed a wakeup
+const IDLE_WAIT: Duration = Duration::from_millis(1);
+
+type Task = Box<dyn FnOnce() + Send + 'static>;
+type Outcome<T> = Arc<Mutex<Option<thread::Result<T>>>>;
+
+/// Runs jobs on a pool of worker threads.
+///
+/// Each worker has its own deque: it pushes and pops jobs it spawns at the
+/// back, and idle workers steal from the front of the others. Jobs spawned
+/// from outside the pool go through a shared queue. Threads that wait on a
+/// job run other jobs in the meantime, so jobs can wait on jobs without
+/// deadlocking.
+///
+/// Jobs spawned with [`JobSystem::spawn_main`] only run on the thread that
+/// created the system, when it calls [`JobSystem::run_main_thread_jobs`]
+/// or waits on a job. Only the main thread may wait on a job that is, or
+/// depends on, an unfinished main-thread job; any other thread panics
+/// rather than stall until the main thread gets to it.
+///
+/// # Example
+/// ```
+/// use substrate::jobs::JobSystem;
+///
+/// let jobs = JobSystem::new(2);
+/// let a = jobs.spawn(|| 20);
+/// let b = jobs.spawn(|| 22);
+/// assert_eq!(a.join() + b.join(), 42);
+///
+/// // Scoped jobs may borrow, and all finish before `scope` returns
+/// let mut data = vec![1, 2, 3, 4];
+/// jobs.scope(|scope| {
+///     for chunk in data.chunks_mut(2) {
+///         scope.spawn(move || chunk.iter_mut().for_each(|x| *x *= 10));
+///     }
+/// });
+/// assert_eq!(data, [10, 20, 30, 40]);
+/// ```
+pub struct JobSystem {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+struct Shared {
+    id: u64,
+    /// One per worker
+    local: Box<[Mutex<VecDeque<Arc<Node>>>]>,
+    /// Jobs spawned from outside the pool
+    injector: Mutex<VecDeque<Arc<Node>>>,
+    main: Mutex<VecDeque<Arc<Node>>>,
+    main_thread: ThreadId,
+    /// Jobs queued for workers and not yet taken
+    queued: AtomicUsize,
+    sleep: Mutex<()>,
+    wake: Condvar,
+    shutdown: AtomicBool,
+}
+
+/// A job and the bookkeeping for what it waits on and what waits on it
+struct Node {
+    task: Mutex<Option<Task>>,
+    /// Unfinished dependencies, plus one while the job is being submitted
+    blockers: AtomicUsize,
+    /// Jobs waiting on this one; `None` once it has finished
+    dependents: Mutex<Option<Vec<Arc<Node>>>>,
+    done: AtomicBool,
+    panicked: AtomicBool,
+    main_thread: bool,
+    /// Main-thread jobs this one waited on, directly or not, that were
+    /// unfinished when it was spawned
+    main_ancestors: Vec<Weak<Node>>,
+}
+
+impl Node {
+    /// Whether finishing this job still needs the main thread to run a job
+    fn needs_main_thread(&self) -> bool {
+        !self.done.load(Ordering::Acquire)
+            && (self.main_thread
+                || self
+                    .main_ancestors
+                    .iter()
+                    .any(|ancestor| {
+                        ancestor
+                            .upgrade()
+                            .is_some_and(|ancestor| {
+                                !ancestor.done.load(Ordering::Acquire)
+                            })
+                    }))
+    }
+}
+
+/// A job that other jobs can be made to wait for
+#[derive(Clone)]
+pub struct Dependency(Arc<Node>);
+
+/// Refers to a spawned job and, once it finishes, its result
+pub struct JobHandle<T> {
+    node: Arc<Node>,
+    outcome: Outcome<T>,
+    shared: Arc<Shared>,
+    joined: Arc<AtomicBool>,
+}
+
+/// Spawns jobs that may borrow from outside the scope. Created by
+/// [`JobSystem::scope`].
+pub struct Scope<'scope, 'env: 'scope> {
+    jobs: &'scope JobSystem,
+    spawned: Mutex<Vec<(Arc<Node>, Arc<AtomicBool>)>>,
+    _marker: PhantomData<&'scope mut &'env ()>,
+}
+
+impl JobSystem {
+    /// A system with `workers` background threads. The creating thread is
+    /// its main thread.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `workers` is zero or a thread can't be spawned.
+    pub fn new(workers: usize) -> Self {
+        assert!(workers > 0, "a job system needs at least one worker");
+        let shared = Arc::new(Shared {
+            id: NEXT_POOL_ID.fetch_add(1, Ordering::Relaxed),
+            local: (0..workers)
+                .map(|_| Mutex::new(VecDeque::new()))
+                .collect(),
+            injector: Mutex::new(VecDeque::new()),
+            main: Mutex::new(VecDeque::new()),
+            main_thread: thread::current().id(),
+            queued: AtomicUsize::new(0),
+            sleep: Mutex::new(()),
+            wake: Condvar::new(),
+            shutdown: AtomicBool::new(false),
+        });
+        let workers = (0..workers)
+            .map(|index| {
+                let shared = shared.clone();
+                thread::Builder::new()
+                    .name(format!("job-worker-{index}"))
+                    .spawn(move || shared.work(index))
+                    .expect("failed to spawn job worker")
+            })
+            .collect();
+        Self { shared, workers }
+    }
+
+    /// Background worker threads
+    pub fn workers(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Whether this is the thread main-thread jobs run on
+    pub fn is_main_thread(&self) -> bool {
+        thread::current().id() == self.shared.main_thread
+    }
+
+    /// Run `job` on any worker
+    pub fn spawn<T, F>(&self, job: F) -> JobHandle<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        self.spawn_after(&[], job)
+    }
+
+    /// Run `job` on any worker once every job in `dependencies` has
+    /// finished, whether or not they panicked
+    pub fn spawn_after<T, F>(
+        &self,
+        dependencies: &[Dependency],
+        job: F,
+    ) -> JobHandle<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        // 'static, so nothing to erase
+        unsafe { self.submit(dependencies, false, job) }
+    }
+
+    /// Run `job` on the main thread, the next time it runs main-thread jobs
+    /// or waits on a job
+    pub fn spawn_main<T, F>(&self, job: F) -> JobHandle<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        self.spawn_main_after(&[], job)
+    }
+
+    /// Like [`JobSystem::spawn_main`], once every job in `dependencies` has
+    /// finished
+    pub fn spawn_main_after<T, F>(
+        &self,
+        dependencies: &[Dependency],
+        job: F,
+    ) -> JobHandle<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        unsafe { self.submit(dependencies, true, job) }
+    }
+
+    /// Run the main-thread jobs that are ready, including any that become
+    /// ready meanwhile. Returns how many ran.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from a thread other than the main thread.
+    pub fn run_main_thread_jobs(&self) -> usize {
+        assert!(
+            self.is_main_thread(),
+            "main-thread jobs must run on the thread that created the job \
+             system"
+        );
+        let mut ran = 0;
+        while let Some(node) = self.shared.pop_main() {
+            self.shared.execute(&node);
+            ran += 1;
+        }
+        ran
+    }
+
+    /// Spawn jobs that borrow from the caller, such as this frame's data.
+    /// Every job spawned in the scope has finished when this returns.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `f` does, or if a scoped job panicked and its handle
+    /// wasn't joined.
+    pub fn scope<'env, R>(
+        &self,
+        f: impl for<'scope> FnOnce(&'scope Scope<'scope, 'env>) -> R,
+    ) -> R {
+        let scope = Scope {
+            jobs: self,
+            spawned: Mutex::new(Vec::new()),
+            _marker: PhantomData,
+        };
+        let result = panic::catch_unwind(AssertUnwindSafe(|| f(&scope)));
+
+        // Jobs may spawn more jobs into the scope while we wait
+        let mut finished = Vec::new();
+        loop {
+            let spawned = mem::take(&mut *scope.spawned.lock().unwrap());
+            if spawned.is_empty() {
+                break;
+            }
+            for (node, _) in &spawned {
+                self.shared.wait_for(node);
+            }
+            finished.extend(spawned);
+        }
+
+        match result {
+            Err(payload) => panic::resume_unwind(payload),
+            Ok(result) => {
+                let unjoined_panic = finished.iter().any(|(node, joined)| {
+                    node.panicked.load(Ordering::Acquire)
+                        && !joined.load(Ordering::Acquire)
+                });
+                assert!(!unjoined_panic, "a scoped job panicked");
+                result
+            }
+        }
+    }
+
+    /// # Safety
+    ///
+    /// Anything `job` borrows must outlive its run.
+    unsafe fn submit<'a, T, F>(
+        &self,
+        dependencies: &[Dependency],
+        main_thread: bool,
+        job: F,
+    ) -> JobHandle<T>
+    where
+        T: Send + 'a,
+        F: FnOnce() -> T + Send + 'a,
+    {
+        let mut main_ancestors = Vec::new();
+        for Dependency(dependency) in dependencies {
+            if dependency.done.load(Ordering::Acquire) {
+                continue;
+            }
+            if dependency.main_thread {
+                main_ancestors.push(Arc::downgrade(dependency));
+            }
+            main_ancestors.extend(
+                dependency
+                    .main_ancestors
+                    .iter()
+                    .filter(|ancestor| {
+                        ancestor
+                            .upgrade()
+                            .is_some_and(|ancestor| {
+                                !ancestor.done.load(Ordering::Acquire)
+                            })
+                    })
+                    .cloned(),
+            );
+        }
+
+        let outcome: Outcome<T> = Arc::new(Mutex::new(None));
+        let node = Arc::new(Node {
+            task: Mutex::new(None),
+            blockers: AtomicUsize::new(1),
+            dependents: Mutex::new(Some(Vec::new())),
+            done: AtomicBool::new(false),
+            panicked: AtomicBool::new(false),
+            main_thread,
+            main_ancestors,
+        });
+
+        let task: Box<dyn FnOnce() + Send + 'a> = {
+            let outcome = outcome.clone();
+            let node = Arc::downgrade(&node);
+            Box::new(move || {
+                let result = panic::catch_unwind(AssertUnwindSafe(job));
+                if result.is_err()
+                    && let Some(node) = node.upgrade()
+                {
+                    node.panicked
+                        .store(true, Ordering::Release);
+                }
+                *outcome.lock().unwrap() = Some(result);
+            })
+        };
+        // The caller guarantees the borrows outlive the run
+        let task: Task = unsafe { mem::transmute(task) };
+        *node.task.lock().unwrap() = Some(task);
+
+        for Dependency(dependency) in dependencies {
+            let mut dependents = dependency.dependents.lock().unwrap();
+            if let Some(dependents) = dependents.as_mut() {
+                node.blockers
+                    .fetch_add(1, Ordering::AcqRel);
+                dependents.push(node.clone());
+            }
+        }
+        if node
+            .blockers
+            .fetch_sub(1, Ordering::AcqRel)
+            == 1
+        {
+            self.shared.schedule(node.clone());
+        }
+
+        JobHandle {
+            node,
+            outcome,
+            shared: self.shared.clone(),
+            joined: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+impl Drop for JobSystem {
+    /// Stops the workers once they finish their current jobs. Jobs still
+    /// queued run only if something joins them.
+    fn drop(&mut self) {
+        self.shared
+            .shutdown
+            .store(true, Ordering::Release);
+        self.shared.notify_all();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl fmt::Debug for JobSystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JobSystem")
+            .field("workers", &self.workers.len())
+            .field(
+                "queued",
+                &self
+                    .shared
+                    .queued
+                    .load(Ordering::Relaxed),
+            )
+            .finish_non_exhaustive()
+    }
+}
+
+impl Shared {
+    fn work(&self, index: usize) {
+        WORKER.with(|worker| worker.set(Some((self.id, index))));
+        while !self.shutdown.load(Ordering::Acquire) {
+            match self.find_work(Some(index)) {
+                Some(node) => self.execute(&node),
+                None => self.idle(|| {
+                    self.shutdown.load(Ordering::Acquire)
+                        || self.queued.load(Ordering::Acquire) > 0
+                }),
+            }
+        }
+    }
+
+    /// This thread's worker index, if it's one of ours
+    fn worker_index(&self) -> Option<usize> {
+        WORKER
+            .with(Cell::get)
+            .and_then(|(id, index)| (id == self.id).then_some(index))
+    }
+
+    fn schedule(&self, node: Arc<Node>) {
+        if node.main_thread {
+            self.main
+                .lock()
+                .unwrap()
+                .push_back(node);
+        } else {
+            // Count the job before it can be taken, so the taker's decrement
+            // never runs first and wraps the count
+            self.queued
+                .fetch_add(1, Ordering::AcqRel);
+            match self.worker_index() {
+                Some(index) => self.local[index]
+                    .lock()
+                    .unwrap()
+                    .push_back(node),
+                None => self
+                    .injector
+                    .lock()
+                    .unwrap()
+                    .push_back(node),
+            }
+        }
+        self.notify_all();
+    }
+
+    /// Own jobs newest first, then shared ones, then the oldest of other
+    /// workers'
+    fn find_work(&self, worker: Option<usize>) -> Option<Arc<Node>> {
+        let node = worker
+            .and_then(|index| {
+                self.local[index]
+                    .lock()
+                    .unwrap()
+                    .pop_back()
+            })
+            .or_else(|| {
+                self.injector
+                    .lock()
+                    .unwrap()
+                    .pop_front()
+            })
+            .or_else(|| {
+                let start = worker.map_or(0, |index| index + 1);
+                (0..self.local.len())
+                    .map(|offset| (start + offset) % self.local.len())
+                    .filter(|&victim| Some(victim) != worker)
+                    .find_map(|victim| {
+                        self.local[victim]
+                            .lock()
+                            .unwrap()
+                            .pop_front()
+                    })
+            })?;
+        self.queued
+            .fetch_sub(1, Ordering::AcqRel);
+        Some(node)
+    }
+
+    fn pop_main(&self) -> Option<Arc<Node>> {
+        self.main.lock().unwrap().pop_front()
+    }
+
+    fn execute(&self, node: &Node) {
+        let task = node
+            .task
+            .lock()
+            .unwrap()
+            .take()
+            .expect("job runs once");
+        task();
+
+        node.done.store(true, Ordering::Release);
+        let dependents = node
+            .dependents
+            .lock()
+            .unwrap()
+            .take()
+            .unwrap_or_default();
+        for dependent in dependents {
+            if dependent
+                .blockers
+                .fetch_sub(1, Ordering::AcqRel)
+                == 1
+            {
+                self.schedule(dependent);
+            }
+        }
+        self.notify_all();
+    }
+
+    /// Run other jobs until `node` has finished
+    ///
+    /// # Panics
+    ///
+    /// Panics if this isn't the main thread and `node` still needs a
+    /// main-thread job to run.
+    fn wait_for(&self, node: &Node) {
+        let worker = self.worker_index();
+        let main = thread::current().id() == self.main_thread;
+        assert!(
+            main || !node.needs_main_thread(),
+            "only the main thread can wait on a job that depends on an \
+             unfinished main-thread job"
+        );
+        while !node.done.load(Ordering::Acquire) {
+            let work = main
+                .then(|| self.pop_main())
+                .flatten()
+                .or_else(|| self.find_work(worker));
+            match work {
+                Some(work) => self.execute(&work),
+                None => self.idle(|| {
+                    node.done.load(Ordering::Acquire)
+                        || self.queued.load(Ordering::Acquire) > 0
+                }),
+            }
+        }
+    }
+
+    /// Sleep until woken or briefly, unless `ready` already holds
+    fn idle(&self, ready: impl Fn() -> bool) {
+        let guard = self.sleep.lock().unwrap();
+        if !ready() {
+            let _ = self.wake.wait_timeout(guard, IDLE_WAIT);
+        }
+    }
+
+    fn notify_all(&self) {
+        let _guard = self.sleep.lock().unwrap();
+        self.wake.notify_all();
+    }
+}
+
+impl<T> JobHandle<T> {
+    /// Something other jobs can be made to wait on
+    pub fn dependency(&self) -> Dependency {
+        Dependency(self.node.clone())
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.node.done.load(Ordering::Acquire)
+    }
+
+    /// Wait for the job and take its result, running other jobs meanwhile.
+    ///
+    /// # Panics
+    ///
+    /// Resumes the job's panic if it panicked. Panics if called off the main
+    /// thread while the job is, or depends on, an unfinished main-thread
+    /// job.
+    pub fn join(self) -> T {
+        self.shared.wait_for(&self.node);
+        self.joined
+            .store(true, Ordering::Release);
+        let outcome = self
+            .outcome
+            .lock()
+            .unwrap()
+            .take()
+            .expect("finished job has an outcome");
+        outcome.unwrap_or_else(|payload: Box<dyn Any + Send>| {
+            panic::resume_unwind(payload)
+        })
+    }
+}
+
+impl<T> fmt::Debug for JobHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JobHandle")
+            .field("finished", &self.is_finished())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> From<&JobHandle<T>> for Dependency {
+    fn from(handle: &JobHandle<T>) -> Self {
+        handle.dependency()
+    }
+}
+
+impl fmt::Debug for Dependency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Dependency")
+            .field("finished", &self.0.done.load(Ordering::Acquire))
+            .finish()
+    }
+}
+
+impl<'scope> Scope<'scope, '_> {
+    /// Run `job` on any worker before the scope ends
+    pub fn spawn<T, F>(&'scope self, job: F) -> JobHandle<T>
+    where
+        T: Send + 'scope,
+        F: FnOnce() -> T + Send + 'scope,
+    {
+        self.spawn_after(&[], job)
+    }
+
+    /// Run `job` on any worker once `dependencies` have finished, before
+    /// the scope ends
+    pub fn spawn_after<T, F>(
+        &'scope self,
+        dependencies: &[Dependency],
+        job: F,
+    ) -> JobHandle<T>
+    where
+        T: Send + 'scope,
+        F: FnOnce() -> T + Send + 'scope,
+    {
+        // `JobSystem::scope` waits for every job spawned here before the
+        // borrows end
+        let handle = unsafe {
+            self.jobs
+                .submit(dependencies, false, job)
+        };
+        self.spawned
+            .lock()
+            .unwrap()
+            .push((handle.node.clone(), handle.joined.clone()));
+        handle
+    }
+
+    /// The job system the scope spawns on
+    pub fn jobs(&self) -> &'scope JobSystem {
+        self.jobs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicU32;
+
+    #[test]
+    fn spawn_and_join_return_results() {
+        let jobs = JobSystem::new(2);
+        let handles: Vec<_> = (0..100u64)
+            .map(|i| jobs.spawn(move || i * i))
+            .collect();
+
+        let sum: u64 = handles
+            .into_iter()
+            .map(JobHandle::join)
+            .sum();
+        assert_eq!(sum, (0..100).map(|i| i * i).sum());
+    }
+
+    #[test]
+    fn dependencies_run_first() {
+        let jobs = JobSystem::new(3);
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let push = |name: &'static str| {
+            let log = log.clone();
+            move || log.lock().unwrap().push(name)
+        };
+
+        let a = jobs.spawn(push("a"));
+        let b = jobs.spawn_after(&[a.dependency()], push("b"));
+        let c = jobs.spawn_after(&[a.dependency()], push("c"));
+        let d = jobs.spawn_after(&[(&b).into(), (&c).into()], push("d"));
+        d.join();
+
+        let log = log.lock().unwrap();
+        assert_eq!(log.len(), 4);
+        assert_eq!(log[0], "a");
+        assert_eq!(log[3], "d");
+    }
+
+    #[test]
+    fn depending_on_finished_job_runs_immediately() {
+        let jobs = JobSystem::new(1);
+        let first = jobs.spawn(|| 1);
+        let dependency = first.dependency();
+        assert_eq!(first.join(), 1);
+
+        assert_eq!(
+            jobs.spawn_after(&[dependency], || 2)
+                .join(),
+            2
+        );
+    }
+
+    #[test]
+    fn scoped_jobs_borrow_frame_data() {
+        let jobs = JobSystem::new(2);
+        let mut data: Vec<u32> = (0..1000).collect();
+        let offset = 5;
+
+        let total = jobs.scope(|scope| {
+            let sums: Vec<_> = data
+                .chunks_mut(100)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        chunk
+                            .iter_mut()
+                            .for_each(|x| *x += offset);
+                        chunk.iter().sum::<u32>()
+                    })
+                })
+                .collect();
+            sums.into_iter()
+                .map(JobHandle::join)
+                .sum::<u32>()
+        });
+
+        assert_eq!(data[999], 1004);
+        assert_eq!(total, data.iter().sum());
+    }
+
+    #[test]
+    fn scope_waits_for_unjoined_and_nested_jobs() {
+        let jobs = JobSystem::new(2);
+        let count = AtomicU32::new(0);
+        jobs.scope(|scope| {
+            for _ in 0..10 {
+                scope.spawn(|| {
+                    scope.spawn(|| count.fetch_add(1, Ordering::Relaxed));
+                    count.fetch_add(1, Ordering::Relaxed);
+                });
+            }
+        });
+        assert_eq!(count.load(Ordering::Relaxed), 20);
+    }
+
+    fn fib(jobs: &JobSystem, n: u32) -> u32 {
+        if n < 2 {
+            return n;
+        }
+        jobs.scope(|scope| {
+            let a = scope.spawn(|| fib(scope.jobs(), n - 1));
+            let b = fib(jobs, n - 2);
+            a.join() + b
+        })
+    }
+
+    #[test]
+    fn jobs_waiting_on_jobs_do_not_deadlock() {
+        // More nested waits than workers
+        let jobs = JobSystem::new(1);
+        assert_eq!(fib(&jobs, 10), 55);
+    }
+
+    #[test]
+    fn main_thread_jobs_run_on_main_thread() {
+        let jobs = JobSystem::new(2);
+        let main = thread::current().id();
+        let handle = jobs.spawn_main(move || thread::current().id() == main);
+        let after = jobs.spawn_after(&[handle.dependency()], || 7);
+
+        assert!(!handle.is_finished());
+        assert_eq!(jobs.run_main_thread_jobs(), 1);
+        assert!(handle.join());
+        assert_eq!(after.join(), 7);
+        assert_eq!(jobs.run_main_thread_jobs(), 0);
+    }
+
+    #[test]
+    fn main_thread_job_after_worker_job() {
+        let jobs = JobSystem::new(2);
+        let decoded = jobs.spawn(|| vec![1u8, 2, 3]);
+        let uploaded =
+            jobs.spawn_main_after(&[decoded.dependency()], || "uploaded");
+
+        // Joining on the main thread runs it
+        assert_eq!(uploaded.join(), "uploaded");
+        assert_eq!(decoded.join(), [1, 2, 3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "must run on the thread that created")]
+    fn main_thread_jobs_refuse_other_threads() {
+        let jobs = Arc::new(JobSystem::new(1));
+        let result = {
+            let jobs = jobs.clone();
+            thread::spawn(move || jobs.run_main_thread_jobs()).join()
+        };
+        if let Err(payload) = result {
+            panic::resume_unwind(payload);
+        }
+    }
+
+    /// Wait on the main thread for `handle` without running main-thread
+    /// jobs, then join it
+    fn join_without_main_jobs<T>(handle: JobHandle<T>) -> T {
+        while !handle.is_finished() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        handle.join()
+    }
+
+    #[test]
+    #[should_panic(expected = "only the main thread can wait")]
+    fn workers_refuse_to_wait_on_main_thread_jobs() {
+        let jobs = JobSystem::new(1);
+        let main = jobs.spawn_main(|| 1);
+        let waiter = jobs.spawn(move || main.join());
+        join_without_main_jobs(waiter);
+    }
+
+    #[test]
+    #[should_panic(expected = "only the main thread can wait")]
+    fn workers_refuse_to_wait_on_jobs_after_main_thread_jobs() {
+        let jobs = JobSystem::new(1);
+        let main = jobs.spawn_main(|| 1);
+        let after = jobs.spawn_after(&[main.dependency()], || 2);
+        let waiter = jobs.spawn(move || after.join());
+        join_without_main_jobs(waiter);
+    }
+
+    #[test]
+    fn workers_wait_on_jobs_after_finished_main_thread_jobs() {
+        let jobs = JobSystem::new(1);
+        let main = jobs.spawn_main(|| 1);
+        let after = jobs.spawn_after(&[main.dependency()], || 2);
+        assert_eq!(jobs.run_main_thread_jobs(), 1);
+
+        let waiter = jobs.spawn(move || after.join());
+        assert_eq!(join_without_main_jobs(waiter), 2);
+        assert_eq!(main.join(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "boom")]
+    fn join_resumes_job_panic() {
+        let jobs = JobSystem::new(1);
+        jobs.spawn(|| panic!("boom")).join();
+    }
+
+    #[test]
+    fn dependents_run_after_panicking_job() {
+        let jobs = JobSystem::new(1);
+        let failing = jobs.spawn(|| panic!("expected"));
+        let after = jobs.spawn_after(&[failing.dependency()], || 1);
+        assert_eq!(after.join(), 1);
+        assert!(
+            panic::catch_unwind(AssertUnwindSafe(|| failing.join())).is_err()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "a scoped job panicked")]
+    fn scope_reports_unjoined_panic() {
+        JobSystem::new(1).scope(|scope| {
+            scope.spawn(|| panic!("expected"));
+        });
+    }
+
+    #[test]
+    fn work_spreads_across_workers() {
+        let jobs = JobSystem::new(3);
+        let threads = Mutex::new(Vec::new());
+        jobs.scope(|scope| {
+            for _ in 0..64 {
+                scope.spawn(|| {
+                    thread::sleep(Duration::from_millis(1));
+                    let id = thread::current().id();
+                    let mut threads = threads.lock().unwrap();
+                    if !threads.contains(&id) {
+                        threads.push(id);
+                    }
+                });
+            }
+        });
+        assert!(threads.lock().unwrap().len() > 1);
+    }
+
+    #[test]
+    fn handles_outlive_the_system() {
+        let jobs = JobSystem::new(1);
+        let handle = jobs.spawn(|| 3);
+        drop(jobs);
+        assert_eq!(handle.join(), 3);
+    }
+}
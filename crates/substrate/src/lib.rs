@@ -1,26 +1,13 @@
 pub mod arena;
-
-pub struct DrawCommand {
-    _mesh_id: u64,
-    material_id: u64,
-}
-
-pub fn build_frame_draw_list() -> Vec<DrawCommand> {
-    let mut v: Vec<DrawCommand> = Vec::new();
-    for i in 0..100 {
-        v.push(DrawCommand { _mesh_id: i, material_id: (100 - i) % 5 });
-    }
-    v.sort_by_key(|dc| dc.material_id);
-    v
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn it_works() {
-        let result = build_frame_draw_list();
-        assert_eq!(100, result.len());
-    }
-}
+pub mod draw;
+pub mod ecs;
+pub mod free_list;
+pub mod jobs;
+pub mod pool;
+pub mod slot_map;
+pub mod sort_key;
+
+pub use draw::{Batch, DrawCommand, DrawList, Transform};
+pub use ecs::{Entity, World};
+pub use slot_map::{Handle, SlotMap};
+pub use sort_key::{SortKey, SortKeyLayout, SortMaterial};
@@ -0,0 +1,369 @@
+//! pool.rs
+
+use std::mem;
+
+use crate::slot_map::Handle;
+
+/// Fixed-size slots for values of one type, with O(1) insert and remove.
+///
+/// Values are reached through [`PoolHandle`]s that carry the generation of
+/// their slot. Removing a value bumps the generation, so handles to it stop
+/// resolving even after the slot is reused. Freed slots are reused most
+/// recently freed first.
+///
+/// [`Pool::new`] never grows past its capacity; [`Pool::growable`] adds
+/// slots as needed.
+pub struct Pool<T> {
+    slots: Vec<Slot<T>>,
+    /// Most recently freed slot
+    free_head: Option<u32>,
+    free: usize,
+    live: usize,
+    peak_live: usize,
+    max_slots: Option<usize>,
+}
+
+struct Slot<T> {
+    generation: u32,
+    entry: Entry<T>,
+}
+
+enum Entry<T> {
+    Occupied(T),
+    Vacant {
+        next_free: Option<u32>,
+    },
+    /// Generation exhausted, never reused
+    Retired,
+}
+
+/// Refers to a value in a [`Pool`]: the same generational [`Handle`] a
+/// [`crate::SlotMap`] hands out
+pub type PoolHandle<T> = Handle<T>;
+
+/// Occupancy figures for a [`Pool`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PoolStats {
+    pub live: usize,
+    pub capacity: usize,
+    /// Free slots available for reuse
+    pub free: usize,
+    /// Free slots below the highest live slot
+    pub holes: usize,
+    /// Most values ever live at once
+    pub peak_live: usize,
+}
+
+impl PoolStats {
+    /// Fraction of the slots up to the highest live one that are free, from
+    /// 0 when live values are packed at the start
+    pub fn fragmentation(&self) -> f32 {
+        let span = self.live + self.holes;
+        if span == 0 { 0.0 } else { self.holes as f32 / span as f32 }
+    }
+}
+
+impl<T> Pool<T> {
+    /// A pool of `capacity` slots that never grows
+    pub fn new(capacity: usize) -> Self {
+        let mut pool = Self::growable(capacity);
+        pool.max_slots = Some(capacity);
+        pool
+    }
+
+    /// A pool with room for `initial` values that grows as needed
+    pub fn growable(initial: usize) -> Self {
+        Self {
+            slots: Vec::with_capacity(initial),
+            free_head: None,
+            free: 0,
+            live: 0,
+            peak_live: 0,
+            max_slots: None,
+        }
+    }
+
+    /// Values currently in the pool
+    pub fn len(&self) -> usize {
+        self.live
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.live == 0
+    }
+
+    /// Slots the pool can hold without reallocating
+    pub fn capacity(&self) -> usize {
+        self.max_slots
+            .unwrap_or(self.slots.capacity())
+    }
+
+    /// Store `value`, reusing the most recently freed slot if there is one.
+    ///
+    /// # Errors
+    ///
+    /// Gives `value` back if the pool is fixed-size and full.
+    pub fn insert(&mut self, value: T) -> Result<PoolHandle<T>, T> {
+        let index = match self.free_head {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                let Entry::Vacant { next_free } = slot.entry else {
+                    unreachable!("free list points at a used slot");
+                };
+                self.free_head = next_free;
+                self.free -= 1;
+                slot.entry = Entry::Occupied(value);
+                index
+            }
+            None => {
+                let full = self
+                    .max_slots
+                    .is_some_and(|max| self.slots.len() >= max);
+                if full || self.slots.len() >= u32::MAX as usize {
+                    return Err(value);
+                }
+                self.slots.push(Slot {
+                    generation: 0,
+                    entry: Entry::Occupied(value),
+                });
+                (self.slots.len() - 1) as u32
+            }
+        };
+
+        self.live += 1;
+        self.peak_live = self.peak_live.max(self.live);
+        Ok(Handle::from_raw_parts(index, self.slots[index as usize].generation))
+    }
+
+    /// Take the value out, freeing its slot. Returns `None` if the handle
+    /// is stale.
+    pub fn remove(&mut self, handle: PoolHandle<T>) -> Option<T> {
+        let slot = self
+            .slots
+            .get_mut(handle.index() as usize)?;
+        if slot.generation != handle.generation()
+            || !matches!(slot.entry, Entry::Occupied(_))
+        {
+            return None;
+        }
+
+        self.live -= 1;
+        let entry = if slot.generation == u32::MAX {
+            Entry::Retired
+        } else {
+            slot.generation += 1;
+            self.free += 1;
+            let next_free = self.free_head.replace(handle.index());
+            Entry::Vacant { next_free }
+        };
+        match mem::replace(&mut slot.entry, entry) {
+            Entry::Occupied(value) => Some(value),
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn contains(&self, handle: PoolHandle<T>) -> bool {
+        self.get(handle).is_some()
+    }
+
+    pub fn get(&self, handle: PoolHandle<T>) -> Option<&T> {
+        match self
+            .slots
+            .get(handle.index() as usize)?
+        {
+            Slot {
+                generation,
+                entry: Entry::Occupied(value),
+            } if *generation == handle.generation() => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, handle: PoolHandle<T>) -> Option<&mut T> {
+        match self
+            .slots
+            .get_mut(handle.index() as usize)?
+        {
+            Slot {
+                generation,
+                entry: Entry::Occupied(value),
+            } if *generation == handle.generation() => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Live values and their handles, in slot order
+    pub fn iter(&self) -> impl Iterator<Item = (PoolHandle<T>, &T)> {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(index, slot)| match &slot.entry {
+                Entry::Occupied(value) => Some((
+                    Handle::from_raw_parts(index as u32, slot.generation),
+                    value,
+                )),
+                _ => None,
+            })
+    }
+
+    /// Remove every value. Outstanding handles stop resolving.
+    pub fn clear(&mut self) {
+        for index in 0..self.slots.len() {
+            let handle = Handle::from_raw_parts(
+                index as u32,
+                self.slots[index].generation,
+            );
+            self.remove(handle);
+        }
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        let span = self
+            .slots
+            .iter()
+            .rposition(|slot| matches!(slot.entry, Entry::Occupied(_)))
+            .map_or(0, |last| last + 1);
+        PoolStats {
+            live: self.live,
+            capacity: self.capacity(),
+            free: self.free,
+            holes: span - self.live,
+            peak_live: self.peak_live,
+        }
+    }
+}
+
+impl<T> Default for Pool<T> {
+    fn default() -> Self {
+        Self::growable(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    #[test]
+    fn insert_get_and_remove() {
+        let mut pool = Pool::new(4);
+        let a = pool.insert("a").unwrap();
+        let b = pool.insert("b").unwrap();
+
+        assert_eq!(pool.get(a), Some(&"a"));
+        *pool.get_mut(b).unwrap() = "bee";
+        assert_eq!(pool.remove(b), Some("bee"));
+        assert_eq!(pool.get(b), None);
+        assert_eq!(pool.len(), 1);
+    }
+
+    #[test]
+    fn stale_handle_misses_reused_slot() {
+        let mut pool = Pool::new(1);
+        let old = pool.insert(1).unwrap();
+        pool.remove(old);
+        let new = pool.insert(2).unwrap();
+
+        assert_ne!(old, new);
+        assert_eq!(pool.get(old), None);
+        assert_eq!(pool.remove(old), None);
+        assert_eq!(pool.get(new), Some(&2));
+    }
+
+    #[test]
+    fn freed_slots_are_reused_last_in_first_out() {
+        let mut pool = Pool::growable(0);
+        let handles: Vec<_> = (0..4)
+            .map(|i| pool.insert(i).unwrap())
+            .collect();
+        pool.remove(handles[1]);
+        pool.remove(handles[3]);
+
+        assert_eq!(pool.insert(10).unwrap().index(), 3);
+        assert_eq!(pool.insert(11).unwrap().index(), 1);
+        assert_eq!(pool.insert(12).unwrap().index(), 4);
+    }
+
+    #[test]
+    fn fixed_pool_gives_value_back_when_full() {
+        let mut pool = Pool::new(2);
+        pool.insert(1).unwrap();
+        let second = pool.insert(2).unwrap();
+
+        assert_eq!(pool.insert(3), Err(3));
+        pool.remove(second);
+        assert!(pool.insert(3).is_ok());
+        assert_eq!(pool.capacity(), 2);
+    }
+
+    #[test]
+    fn exhausted_generation_retires_slot() {
+        let mut pool = Pool::growable(1);
+        let handle = pool.insert(()).unwrap();
+        pool.slots[0].generation = u32::MAX;
+        let last = Handle::from_raw_parts(handle.index(), u32::MAX);
+
+        assert_eq!(pool.remove(last), Some(()));
+        assert_eq!(pool.insert(()).unwrap().index(), 1);
+        assert_eq!(pool.stats().free, 0);
+    }
+
+    #[test]
+    fn stats_track_live_holes_and_peak() {
+        let mut pool = Pool::new(8);
+        let handles: Vec<_> = (0..6)
+            .map(|i| pool.insert(i).unwrap())
+            .collect();
+        for &handle in &handles[..3] {
+            pool.remove(handle);
+        }
+
+        let stats = pool.stats();
+        assert_eq!(
+            stats,
+            PoolStats {
+                live: 3,
+                capacity: 8,
+                free: 3,
+                holes: 3,
+                peak_live: 6,
+            }
+        );
+        assert_eq!(stats.fragmentation(), 0.5);
+
+        pool.remove(handles[5]);
+        pool.remove(handles[4]);
+        let stats = pool.stats();
+        assert_eq!((stats.live, stats.holes), (1, 3));
+        pool.clear();
+        assert_eq!(pool.stats().fragmentation(), 0.0);
+    }
+
+    #[test]
+    fn iter_visits_live_values() {
+        let mut pool = Pool::growable(4);
+        let a = pool.insert('a').unwrap();
+        let b = pool.insert('b').unwrap();
+        pool.insert('c').unwrap();
+        pool.remove(b);
+
+        let seen: Vec<_> = pool.iter().collect();
+        assert_eq!(seen.len(), 2);
+        assert_eq!(seen[0], (a, &'a'));
+    }
+
+    #[test]
+    fn clear_and_drop_release_values() {
+        let value = Rc::new(());
+        let mut pool = Pool::growable(2);
+        let handle = pool.insert(value.clone()).unwrap();
+        pool.clear();
+
+        assert_eq!(Rc::strong_count(&value), 1);
+        assert!(!pool.contains(handle));
+
+        pool.insert(value.clone()).unwrap();
+        drop(pool);
+        assert_eq!(Rc::strong_count(&value), 1);
+    }
+}
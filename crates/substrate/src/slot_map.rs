@@ -0,0 +1,438 @@
+//! slot_map.rs
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::ops::{Index, IndexMut};
+
+/// A typed reference to a value in a [`SlotMap`]: a slot index plus the
+/// generation the slot had when the value was inserted.
+///
+/// Handles are `Copy` and don't borrow the map. Once their value is
+/// removed they stop resolving, even if the slot is reused.
+pub struct Handle<T> {
+    index: u32,
+    generation: u32,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Handle<T> {
+    /// Rebuild a handle from [`Handle::index`] and [`Handle::generation`],
+    /// e.g. after serialization
+    pub const fn from_raw_parts(index: u32, generation: u32) -> Self {
+        Self { index, generation, _marker: PhantomData }
+    }
+
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        (self.index, self.generation) == (other.index, other.generation)
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> PartialOrd for Handle<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Handle<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.index, self.generation).cmp(&(other.index, other.generation))
+    }
+}
+
+impl<T> Hash for Handle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.index, self.generation).hash(state);
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Handle({}v{})", self.index, self.generation)
+    }
+}
+
+/// Values of one type addressed by generational [`Handle`]s.
+///
+/// Values are stored densely, so iteration is a walk over a contiguous
+/// slice; removal swaps the last value into the hole. Freed slots are
+/// reused with a bumped generation, and a slot whose generation runs out
+/// is retired.
+///
+/// Looking up a handle whose value has been removed returns `None`, but in
+/// debug builds it panics instead, to catch use-after-free early. Use
+/// [`SlotMap::contains`] to check a handle that may legitimately be stale.
+pub struct SlotMap<T> {
+    slots: Vec<Slot>,
+    values: Vec<T>,
+    /// Slot of each value, parallel to `values`
+    value_slots: Vec<u32>,
+    /// Most recently freed slot
+    free_head: Option<u32>,
+}
+
+struct Slot {
+    generation: u32,
+    state: SlotState,
+}
+
+enum SlotState {
+    /// Index into `values`
+    Occupied(u32),
+    Vacant {
+        next_free: Option<u32>,
+    },
+    /// Generation exhausted, never reused
+    Retired,
+}
+
+impl<T> SlotMap<T> {
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            slots: Vec::with_capacity(capacity),
+            values: Vec::with_capacity(capacity),
+            value_slots: Vec::with_capacity(capacity),
+            free_head: None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the map already holds `u32::MAX` slots.
+    pub fn insert(&mut self, value: T) -> Handle<T> {
+        let dense = self.values.len() as u32;
+        let index = match self.free_head {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                let SlotState::Vacant { next_free } = slot.state else {
+                    unreachable!("free list points at a used slot");
+                };
+                self.free_head = next_free;
+                slot.state = SlotState::Occupied(dense);
+                index
+            }
+            None => {
+                assert!(
+                    self.slots.len() < u32::MAX as usize,
+                    "slot map is full"
+                );
+                self.slots.push(Slot {
+                    generation: 0,
+                    state: SlotState::Occupied(dense),
+                });
+                (self.slots.len() - 1) as u32
+            }
+        };
+
+        self.values.push(value);
+        self.value_slots.push(index);
+        Handle::from_raw_parts(index, self.slots[index as usize].generation)
+    }
+
+    /// Take the value out, or `None` if it was already removed
+    ///
+    /// # Panics
+    ///
+    /// In debug builds, panics if the handle is stale.
+    pub fn remove(&mut self, handle: Handle<T>) -> Option<T> {
+        let dense = self.dense_index(handle)?;
+        let slot = &mut self.slots[handle.index as usize];
+        slot.state = if slot.generation == u32::MAX {
+            SlotState::Retired
+        } else {
+            slot.generation += 1;
+            SlotState::Vacant {
+                next_free: self.free_head.replace(handle.index),
+            }
+        };
+
+        let value = self.values.swap_remove(dense);
+        self.value_slots.swap_remove(dense);
+        // Point the value moved into the hole at its new position
+        if let Some(&moved) = self.value_slots.get(dense) {
+            self.slots[moved as usize].state =
+                SlotState::Occupied(dense as u32);
+        }
+        Some(value)
+    }
+
+    /// Whether the handle's value is still in the map. Never panics.
+    pub fn contains(&self, handle: Handle<T>) -> bool {
+        self.lookup(handle).is_some()
+    }
+
+    /// # Panics
+    ///
+    /// In debug builds, panics if the handle is stale.
+    pub fn get(&self, handle: Handle<T>) -> Option<&T> {
+        let dense = self.dense_index(handle)?;
+        Some(&self.values[dense])
+    }
+
+    /// # Panics
+    ///
+    /// In debug builds, panics if the handle is stale.
+    pub fn get_mut(&mut self, handle: Handle<T>) -> Option<&mut T> {
+        let dense = self.dense_index(handle)?;
+        Some(&mut self.values[dense])
+    }
+
+    /// Values in storage order, which changes as values are removed
+    pub fn values(&self) -> &[T] {
+        &self.values
+    }
+
+    pub fn values_mut(&mut self) -> &mut [T] {
+        &mut self.values
+    }
+
+    /// Handles and values in storage order
+    pub fn iter(&self) -> impl Iterator<Item = (Handle<T>, &T)> {
+        self.value_slots
+            .iter()
+            .zip(&self.values)
+            .map(|(&index, value)| (self.handle_at(index), value))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Handle<T>, &mut T)> {
+        let slots = &self.slots;
+        self.value_slots
+            .iter()
+            .zip(&mut self.values)
+            .map(|(&index, value)| {
+                let generation = slots[index as usize].generation;
+                (Handle::from_raw_parts(index, generation), value)
+            })
+    }
+
+    pub fn handles(&self) -> impl Iterator<Item = Handle<T>> {
+        self.value_slots
+            .iter()
+            .map(|&index| self.handle_at(index))
+    }
+
+    /// Remove every value. Outstanding handles stop resolving.
+    pub fn clear(&mut self) {
+        while let Some(&index) = self.value_slots.last() {
+            self.remove(self.handle_at(index));
+        }
+    }
+
+    fn handle_at(&self, index: u32) -> Handle<T> {
+        Handle::from_raw_parts(index, self.slots[index as usize].generation)
+    }
+
+    fn lookup(&self, handle: Handle<T>) -> Option<usize> {
+        match self.slots.get(handle.index as usize)? {
+            Slot {
+                generation,
+                state: SlotState::Occupied(dense),
+            } if *generation == handle.generation => Some(*dense as usize),
+            _ => None,
+        }
+    }
+
+    fn dense_index(&self, handle: Handle<T>) -> Option<usize> {
+        let dense = self.lookup(handle);
+        debug_assert!(
+            dense.is_some(),
+            "stale {handle:?}: used after its value was removed, or with another map"
+        );
+        dense
+    }
+}
+
+impl<T> Default for SlotMap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Index<Handle<T>> for SlotMap<T> {
+    type Output = T;
+
+    fn index(&self, handle: Handle<T>) -> &T {
+        self.get(handle)
+            .unwrap_or_else(|| panic!("{handle:?} is stale"))
+    }
+}
+
+impl<T> IndexMut<Handle<T>> for SlotMap<T> {
+    fn index_mut(&mut self, handle: Handle<T>) -> &mut T {
+        self.get_mut(handle)
+            .unwrap_or_else(|| panic!("{handle:?} is stale"))
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for SlotMap<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.iter())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_get_and_remove() {
+        let mut map = SlotMap::new();
+        let a = map.insert("a");
+        let b = map.insert("b");
+
+        assert_eq!(map[a], "a");
+        map[b] = "bee";
+        assert_eq!(map.remove(b), Some("bee"));
+        assert!(!map.contains(b));
+        assert_eq!(map.len(), 1);
+    }
+
+    #[test]
+    fn handles_are_copy_and_typed() {
+        fn assert_copy<T: Copy>() {}
+        // No bounds on the value type
+        assert_copy::<Handle<String>>();
+
+        let mut map = SlotMap::new();
+        let handle = map.insert(String::from("mesh"));
+        let copy = handle;
+        assert_eq!(map.get(handle), map.get(copy));
+    }
+
+    #[test]
+    fn reused_slot_gets_new_generation() {
+        let mut map = SlotMap::new();
+        let old = map.insert(1);
+        map.remove(old);
+        let new = map.insert(2);
+
+        assert_eq!(new.index(), old.index());
+        assert_eq!(new.generation(), old.generation() + 1);
+        assert!(!map.contains(old));
+        assert_eq!(map[new], 2);
+    }
+
+    #[test]
+    fn removal_keeps_values_dense() {
+        let mut map = SlotMap::new();
+        let handles: Vec<_> = (0..5).map(|i| map.insert(i)).collect();
+        map.remove(handles[1]);
+        map.remove(handles[3]);
+
+        let mut values = map.values().to_vec();
+        values.sort_unstable();
+        assert_eq!(values, [0, 2, 4]);
+        // The value moved into each hole is still reachable
+        for &i in &[0, 2, 4] {
+            assert_eq!(map[handles[i]], i);
+        }
+        for (handle, &value) in map.iter() {
+            assert_eq!(handles[value], handle);
+        }
+    }
+
+    #[test]
+    fn iter_mut_and_values_mut_update_in_place() {
+        let mut map = SlotMap::new();
+        let a = map.insert(1);
+        let b = map.insert(2);
+        for (_, value) in map.iter_mut() {
+            *value *= 10;
+        }
+        map.values_mut()[0] += 1;
+
+        assert_eq!((map[a], map[b]), (11, 20));
+        assert_eq!(map.handles().collect::<Vec<_>>(), [a, b]);
+    }
+
+    #[test]
+    fn clear_invalidates_every_handle() {
+        let mut map = SlotMap::new();
+        let handles: Vec<_> = (0..3).map(|i| map.insert(i)).collect();
+        map.clear();
+
+        assert!(map.is_empty());
+        assert!(
+            handles
+                .iter()
+                .all(|&h| !map.contains(h))
+        );
+        let reused = map.insert(9);
+        assert!(!handles.contains(&reused));
+    }
+
+    #[test]
+    fn exhausted_generation_retires_slot() {
+        let mut map = SlotMap::new();
+        let handle = map.insert(());
+        map.slots[0].generation = u32::MAX;
+        let last = Handle::from_raw_parts(handle.index(), u32::MAX);
+
+        assert_eq!(map.remove(last), Some(()));
+        assert_eq!(map.insert(()).index(), 1);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "used after its value was removed")]
+    fn stale_handle_panics_in_debug() {
+        let mut map = SlotMap::new();
+        let handle = map.insert(1);
+        map.remove(handle);
+        map.get(handle);
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn stale_handle_misses_in_release() {
+        let mut map = SlotMap::new();
+        let handle = map.insert(1);
+        map.remove(handle);
+        assert_eq!(map.get(handle), None);
+        assert_eq!(map.remove(handle), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "stale")]
+    fn indexing_with_stale_handle_panics() {
+        let mut map = SlotMap::new();
+        let handle = map.insert(1);
+        map.remove(handle);
+        let _ = map[handle];
+    }
+}
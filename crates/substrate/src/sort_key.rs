@@ -0,0 +1,423 @@
+//! sort_key.rs
+
+use crate::slot_map::Handle;
+
+/// A packed 64-bit draw sort key. Sorting keys ascending gives draw order.
+///
+/// Keys are built with a [`SortKeyLayout`]. From the most significant bit
+/// down, opaque keys hold:
+///
+/// `layer | 0 | material | depth | sequence`
+///
+/// so opaque geometry groups by material to minimise rebinds and is drawn
+/// front to back within a material. Translucent keys hold:
+///
+/// `layer | 1 | inverted depth | material | sequence`
+///
+/// so translucent geometry is drawn after opaque geometry in the same layer,
+/// back to front, as blending requires.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct SortKey(u64);
+
+impl SortKey {
+    pub fn from_bits(bits: u64) -> Self {
+        Self(bits)
+    }
+
+    pub fn bits(&self) -> u64 {
+        self.0
+    }
+
+    /// Opaque key in the default layout
+    pub fn opaque(
+        layer: u32,
+        material: impl SortMaterial,
+        depth: f32,
+        sequence: u32,
+    ) -> Self {
+        SortKeyLayout::DEFAULT.opaque(layer, material, depth, sequence)
+    }
+
+    /// Translucent key in the default layout
+    pub fn translucent(
+        layer: u32,
+        material: impl SortMaterial,
+        depth: f32,
+        sequence: u32,
+    ) -> Self {
+        SortKeyLayout::DEFAULT.translucent(layer, material, depth, sequence)
+    }
+}
+
+/// A material reference draws can be keyed by, such as a renderer's
+/// material handle
+pub trait SortMaterial: Copy {
+    /// Value for the key's material field, of which only the low bits are
+    /// kept
+    fn sort_index(&self) -> u32;
+}
+
+impl SortMaterial for u32 {
+    fn sort_index(&self) -> u32 {
+        *self
+    }
+}
+
+impl<T> SortMaterial for Handle<T> {
+    fn sort_index(&self) -> u32 {
+        self.index()
+    }
+}
+
+/// Bit widths of the fields in a [`SortKey`]. Translucency always takes one
+/// bit; the other widths must add up to the remaining 63.
+///
+/// Field values wider than their width keep only their low bits, so a
+/// sequence number wraps around, and depth keeps only its most significant
+/// bits, so nearby depths may compare equal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SortKeyLayout {
+    layer_bits: u32,
+    material_bits: u32,
+    depth_bits: u32,
+    sequence_bits: u32,
+}
+
+impl SortKeyLayout {
+    /// 8 layer bits, 20 material bits, 24 depth bits and 11 sequence bits
+    pub const DEFAULT: Self = Self::new(8, 20, 24, 11);
+
+    /// # Panics
+    ///
+    /// Panics if the widths don't add up to 63, the layer doesn't have at
+    /// least one bit or depth is wider than 32 bits.
+    pub const fn new(
+        layer_bits: u32,
+        material_bits: u32,
+        depth_bits: u32,
+        sequence_bits: u32,
+    ) -> Self {
+        // Checked one by one first so the sum below can't overflow
+        assert!(
+            layer_bits <= 63
+                && material_bits <= 63
+                && depth_bits <= 63
+                && sequence_bits <= 63,
+            "sort key fields must use 63 bits"
+        );
+        assert!(layer_bits >= 1, "layer needs at least one bit");
+        assert!(
+            layer_bits + material_bits + depth_bits + sequence_bits == 63,
+            "sort key fields must use 63 bits"
+        );
+        assert!(depth_bits <= 32, "depth is at most 32 bits");
+        Self {
+            layer_bits,
+            material_bits,
+            depth_bits,
+            sequence_bits,
+        }
+    }
+
+    pub fn layer_bits(&self) -> u32 {
+        self.layer_bits
+    }
+
+    pub fn material_bits(&self) -> u32 {
+        self.material_bits
+    }
+
+    pub fn depth_bits(&self) -> u32 {
+        self.depth_bits
+    }
+
+    pub fn sequence_bits(&self) -> u32 {
+        self.sequence_bits
+    }
+
+    /// Key for opaque geometry: by layer, material, then front to back
+    pub fn opaque(
+        &self,
+        layer: u32,
+        material: impl SortMaterial,
+        depth: f32,
+        sequence: u32,
+    ) -> SortKey {
+        let mut packer = Packer::default();
+        packer.push(layer as u64, self.layer_bits);
+        packer.push(0, 1);
+        packer.push(material.sort_index() as u64, self.material_bits);
+        packer.push(self.quantize_depth(depth), self.depth_bits);
+        packer.push(sequence as u64, self.sequence_bits);
+        SortKey(packer.bits)
+    }
+
+    /// Key for translucent geometry: by layer, after opaque geometry, back
+    /// to front, then material
+    pub fn translucent(
+        &self,
+        layer: u32,
+        material: impl SortMaterial,
+        depth: f32,
+        sequence: u32,
+    ) -> SortKey {
+        let depth_mask = mask(self.depth_bits);
+        let mut packer = Packer::default();
+        packer.push(layer as u64, self.layer_bits);
+        packer.push(1, 1);
+        packer.push(!self.quantize_depth(depth) & depth_mask, self.depth_bits);
+        packer.push(material.sort_index() as u64, self.material_bits);
+        packer.push(sequence as u64, self.sequence_bits);
+        SortKey(packer.bits)
+    }
+
+    pub fn layer(&self, key: SortKey) -> u32 {
+        (key.0 >> (64 - self.layer_bits)) as u32
+    }
+
+    pub fn is_translucent(&self, key: SortKey) -> bool {
+        key.0 & (1 << (63 - self.layer_bits)) != 0
+    }
+
+    /// The material's index; keys don't hold handle generations
+    pub fn material_index(&self, key: SortKey) -> u32 {
+        let shift = if self.is_translucent(key) {
+            self.sequence_bits
+        } else {
+            self.depth_bits + self.sequence_bits
+        };
+        ((key.0 >> shift) & mask(self.material_bits)) as u32
+    }
+
+    pub fn sequence(&self, key: SortKey) -> u32 {
+        (key.0 & mask(self.sequence_bits)) as u32
+    }
+
+    /// The top `depth_bits` of the depth's order-preserving bit pattern
+    fn quantize_depth(&self, depth: f32) -> u64 {
+        if self.depth_bits == 0 {
+            return 0;
+        }
+        (ordered_depth(depth) >> (32 - self.depth_bits)) as u64
+    }
+}
+
+impl Default for SortKeyLayout {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Map a float onto a u32 with the same ordering, negatives included
+fn ordered_depth(depth: f32) -> u32 {
+    let bits = depth.to_bits();
+    if bits & 0x8000_0000 != 0 { !bits } else { bits | 0x8000_0000 }
+}
+
+fn mask(bits: u32) -> u64 {
+    if bits >= 64 { u64::MAX } else { (1 << bits) - 1 }
+}
+
+/// Appends fields from the most significant end
+#[derive(Default)]
+struct Packer {
+    bits: u64,
+}
+
+impl Packer {
+    /// Append the low `width` bits of `value`
+    fn push(&mut self, value: u64, width: u32) {
+        if width == 0 {
+            return;
+        }
+        self.bits = self
+            .bits
+            .checked_shl(width)
+            .unwrap_or(0)
+            | (value & mask(width));
+    }
+}
+
+/// Stable LSD radix sort of `(key, index)` pairs by key, eight bits at a
+/// time. `scratch` is resized to match and can be reused between calls to
+/// avoid allocating. Passes where every key has the same byte are skipped,
+/// so keys that only use a few bits sort quickly.
+pub fn radix_sort(keys: &mut Vec<(u64, u32)>, scratch: &mut Vec<(u64, u32)>) {
+    const RADIX: usize = 256;
+
+    let mut counts = [[0usize; RADIX]; 8];
+    for &(key, _) in keys.iter() {
+        for (pass, count) in counts.iter_mut().enumerate() {
+            count[((key >> (pass * 8)) & 0xFF) as usize] += 1;
+        }
+    }
+
+    scratch.clear();
+    scratch.resize(keys.len(), (0, 0));
+    for (pass, count) in counts.iter().enumerate() {
+        // All keys share this byte, so the pass wouldn't move anything
+        if count.contains(&keys.len()) {
+            continue;
+        }
+
+        let mut offsets = [0usize; RADIX];
+        let mut total = 0;
+        for (offset, &n) in offsets.iter_mut().zip(count) {
+            *offset = total;
+            total += n;
+        }
+
+        for &item in keys.iter() {
+            let digit = ((item.0 >> (pass * 8)) & 0xFF) as usize;
+            scratch[offsets[digit]] = item;
+            offsets[digit] += 1;
+        }
+        std::mem::swap(keys, scratch);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn material(index: u32) -> Handle<()> {
+        Handle::from_raw_parts(index, 0)
+    }
+
+    #[test]
+    fn opaque_orders_by_layer_then_material_then_depth() {
+        assert!(
+            SortKey::opaque(0, material(9), 100.0, 0)
+                < SortKey::opaque(1, material(0), 0.0, 0)
+        );
+        assert!(
+            SortKey::opaque(0, material(1), 100.0, 0)
+                < SortKey::opaque(0, material(2), 0.0, 0)
+        );
+        assert!(
+            SortKey::opaque(0, material(1), 1.0, 0)
+                < SortKey::opaque(0, material(1), 2.0, 0)
+        );
+    }
+
+    #[test]
+    fn translucent_draws_after_opaque_back_to_front() {
+        let opaque = SortKey::opaque(0, material(100), 1000.0, 0);
+        let near = SortKey::translucent(0, material(0), 1.0, 0);
+        let far = SortKey::translucent(0, material(5), 50.0, 0);
+
+        assert!(opaque < far);
+        assert!(far < near);
+        assert!(near < SortKey::opaque(1, material(0), 0.0, 0));
+    }
+
+    #[test]
+    fn depth_ordering_includes_negatives() {
+        let depths = [-10.0, -0.5, 0.0, 0.25, 8.0, 1.0e6];
+
+        for pair in depths.windows(2) {
+            assert!(
+                SortKey::opaque(0, material(3), pair[0], 0)
+                    < SortKey::opaque(0, material(3), pair[1], 0),
+                "{} should sort before {}",
+                pair[0],
+                pair[1]
+            );
+        }
+    }
+
+    #[test]
+    fn sequence_breaks_ties() {
+        let a = SortKey::opaque(2, material(7), 3.0, 1);
+        let b = SortKey::opaque(2, material(7), 3.0, 2);
+        assert!(a < b);
+    }
+
+    #[test]
+    fn fields_decode_from_key() {
+        let layout = SortKeyLayout::DEFAULT;
+        let opaque = layout.opaque(5, material(1234), 2.0, 42);
+        let translucent = layout.translucent(5, material(1234), 2.0, 42);
+
+        for key in [opaque, translucent] {
+            assert_eq!(layout.layer(key), 5);
+            assert_eq!(layout.material_index(key), 1234);
+            assert_eq!(layout.sequence(key), 42);
+        }
+        assert!(!layout.is_translucent(opaque));
+        assert!(layout.is_translucent(translucent));
+    }
+
+    #[test]
+    fn custom_layout_places_fields() {
+        let layout = SortKeyLayout::new(4, 12, 32, 15);
+        let key = layout.opaque(0xA, material(0xBCD), 0.0, 0x1234);
+
+        assert_eq!(key.bits() >> 60, 0xA);
+        assert_eq!((key.bits() >> 47) & 0xFFF, 0xBCD);
+        assert_eq!(key.bits() & 0x7FFF, 0x1234);
+        assert_eq!(layout.material_index(key), 0xBCD);
+    }
+
+    #[test]
+    #[should_panic(expected = "63 bits")]
+    fn layout_rejects_wrong_width() {
+        SortKeyLayout::new(8, 20, 24, 12);
+    }
+
+    #[test]
+    #[should_panic(expected = "layer needs at least one bit")]
+    fn layout_rejects_zero_layer_bits() {
+        SortKeyLayout::new(0, 20, 32, 11);
+    }
+
+    #[test]
+    #[should_panic(expected = "63 bits")]
+    fn layout_rejects_overflowing_widths() {
+        SortKeyLayout::new(u32::MAX, 20, 24, 20);
+    }
+
+    #[test]
+    fn oversized_fields_keep_their_low_bits() {
+        let layout = SortKeyLayout::DEFAULT;
+        let key = layout.opaque(256 + 3, material(1), 1.0, 2048 + 7);
+
+        assert_eq!(layout.layer(key), 3);
+        assert!(!layout.is_translucent(key));
+        assert_eq!(layout.material_index(key), 1);
+        assert_eq!(layout.sequence(key), 7);
+    }
+
+    #[test]
+    fn radix_sort_matches_stable_sort() {
+        // xorshift so the test needs no dependencies
+        let mut state = 0x2545_F491_4F6C_DD1Du64;
+        let mut keys: Vec<(u64, u32)> = (0..10_000)
+            .map(|i| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                // Few distinct keys, so stability is exercised
+                (((state % 64) << 40) | (state % 3), i)
+            })
+            .collect();
+        let mut expected = keys.clone();
+        expected.sort_by_key(|&(key, _)| key);
+
+        let mut scratch = Vec::new();
+        radix_sort(&mut keys, &mut scratch);
+        assert_eq!(keys, expected);
+    }
+
+    #[test]
+    fn radix_sort_handles_empty_and_uniform() {
+        let mut scratch = Vec::new();
+        let mut empty = Vec::new();
+        radix_sort(&mut empty, &mut scratch);
+        assert!(empty.is_empty());
+
+        let mut uniform = vec![(7, 0), (7, 1), (7, 2)];
+        radix_sort(&mut uniform, &mut scratch);
+        assert_eq!(uniform, vec![(7, 0), (7, 1), (7, 2)]);
+    }
+}